@@ -0,0 +1,868 @@
+//! Indicator expressions
+//!
+//! A small language combining prices and indicators into numeric or boolean series, e.g.
+//!
+//! ```text
+//! ema(close, 12) - ema(close, 26) > 0 and rsi(14) < 70
+//! ```
+//!
+//! Expressions know the columns `open`, `high`, `low`, `close`, `volume` and `adjclose`, numbers,
+//! `+ - * /`, the comparisons `< <= > >= == !=`, `crosses_above` and `crosses_below`, and
+//! `and`, `or` and `not`. The indicators of `FUNCTIONS` are named like the columns of the
+//! `TechnicalIndicators` DataFrames and calculated the same way, their parameters are numbers.
+//! The indicators of a single series take it as optional first argument, the column of the
+//! `TechnicalIndicators` method if it is left out: `rsi(14)` is `rsi(close, 14)`, `max` and
+//! `min` default to `high` and `low`, `roc` to `adjclose`. The `vwap` starts again with every
+//! session of the exchange, `XFRA` unless evaluated for another one.
+//!
+//! An expression is checked completely when it is parsed, evaluating it only fails if the
+//! DataFrame lacks a column. Periods span at most `MAX_PERIOD` bars and expressions nest at
+//! most `MAX_DEPTH` levels deep. The result has a value for every bar, a condition is `true` where
+//! it holds:
+//!
+//! ```rust,no_run
+//! use finalytics::analytics::expressions::Expression;
+//! use finalytics::prelude::KLINE;
+//!
+//! let signal: Expression = "ema(close, 12) crosses_above ema(close, 26)".parse()?;
+//! let ohlcv = KLINE::from_csv("AAPL", "../examples/datasets/aapl.csv")?.to_dataframe()?;
+//! let series = signal.evaluate(&ohlcv)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use polars::prelude::*;
+use std::fmt;
+use ta::indicators::*;
+use ta::{DataItem, Next};
+
+use super::indicators;
+use super::technicals;
+use crate::data::sql::TimeSeriesData;
+use crate::data::ticker::TickerData;
+use crate::models::ticker::Ticker;
+
+/// An error in an expression at `column`, counted in characters from 1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.column)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for crate::Error {
+    fn from(error: ParseError) -> Self {
+        crate::Error::analytics(error)
+    }
+}
+
+/// What an indicator is calculated over
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Input {
+    /// one series, `close` if it is left out
+    Series,
+    /// the high, low, close and volume of the bars
+    Bars,
+}
+
+/// An indicator of the language with the names of its parameters
+#[derive(Debug)]
+pub struct Function {
+    pub name: &'static str,
+    input: Input,
+    pub params: &'static [&'static str],
+}
+
+const fn series(name: &'static str, params: &'static [&'static str]) -> Function {
+    Function { name, input: Input::Series, params }
+}
+
+const fn bars(name: &'static str, params: &'static [&'static str]) -> Function {
+    Function { name, input: Input::Bars, params }
+}
+
+const MACD: &[&str] = &["fast_period", "slow_period", "signal_period"];
+
+/// the indicators of the language
+pub const FUNCTIONS: &[Function] = &[
+    series("sma", &["period"]),
+    series("ema", &["period"]),
+    series("rsi", &["period"]),
+    series("roc", &["period"]),
+    series("sd", &["period"]),
+    series("mad", &["period"]),
+    series("max", &["period"]),
+    series("min", &["period"]),
+    series("fs", &["period"]),
+    series("bb", &["period", "std_dev"]),
+    series("bb_upper", &["period", "std_dev"]),
+    series("bb_lower", &["period", "std_dev"]),
+    series("macd", MACD),
+    series("macd_signal", MACD),
+    series("macd_divergence", MACD),
+    series("lag", &["bars"]),
+    series("abs", &[]),
+    bars("atr", &["period"]),
+    bars("adx", &["period"]),
+    bars("plus_di", &["period"]),
+    bars("minus_di", &["period"]),
+    bars("cci", &["period"]),
+    bars("wr", &["period"]),
+    bars("cmf", &["period"]),
+    bars("mfi", &["period"]),
+    bars("obv", &[]),
+    bars("vwap", &[]),
+    bars("psar", &["step", "max_step"]),
+    bars("supertrend", &["period", "multiplier"]),
+    bars("kc", &["period", "atr_period", "multiplier"]),
+    bars("kc_upper", &["period", "atr_period", "multiplier"]),
+    bars("kc_lower", &["period", "atr_period", "multiplier"]),
+    bars("dc", &["period"]),
+    bars("dc_upper", &["period"]),
+    bars("dc_lower", &["period"]),
+];
+
+/// parameters that are no number of bars
+const FRACTIONAL_PARAMS: &[&str] = &["std_dev", "step", "max_step", "multiplier"];
+
+/// the most bars a period may span, far beyond any chart and small enough to add up the lookback
+pub const MAX_PERIOD: usize = 10_000;
+
+/// the most levels of parentheses, calls and `not` or `-` an expression may nest
+pub const MAX_DEPTH: usize = 32;
+
+const COLUMNS: &[&str] = &["open", "high", "low", "close", "volume", "adjclose"];
+
+const KEYWORDS: &[&str] = &["and", "or", "not", "crosses_above", "crosses_below"];
+
+impl Function {
+    /// how the indicator is called, e.g. `rsi([series, ]period)`
+    fn signature(&self) -> String {
+        let series = match (self.input, self.params.is_empty()) {
+            (Input::Series, true) => "[series]",
+            (Input::Series, false) => "[series, ]",
+            (Input::Bars, _) => "",
+        };
+        format!("{}({}{})", self.name, series, self.params.join(", "))
+    }
+
+    /// the series of an indicator called without one, the default column of its `TechnicalIndicators` method
+    fn default_column(&self) -> technicals::Column {
+        match self.name {
+            "roc" => technicals::Column::AdjClose,
+            "max" => technicals::Column::High,
+            "min" => technicals::Column::Low,
+            _ => technicals::Column::Close,
+        }
+    }
+
+    /// bars the indicator needs before its values are meaningful
+    fn warmup(&self, params: &[f64]) -> usize {
+        let whole = |i: usize| params.get(i).map_or(0, |p| *p as usize);
+        if self.params == MACD {
+            whole(1) + whole(2)
+        } else {
+            (0..params.len()).filter(|i| !FRACTIONAL_PARAMS.contains(&self.params[*i])).map(whole).max().unwrap_or(0)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    CrossesAbove,
+    CrossesBelow,
+}
+
+impl Comparison {
+    fn holds(&self, a: f64, b: f64) -> bool {
+        match self {
+            Comparison::Less | Comparison::CrossesBelow => a < b,
+            Comparison::LessEqual => a <= b,
+            Comparison::Greater | Comparison::CrossesAbove => a > b,
+            Comparison::GreaterEqual => a >= b,
+            Comparison::Equal => a == b,
+            Comparison::NotEqual => a != b,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Number(f64),
+    Column(technicals::Column),
+    Negate(Box<Expr>),
+    Arithmetic(Operator, Box<Expr>, Box<Expr>),
+    Compare(Comparison, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Call { function: &'static Function, series: Option<Box<Expr>>, params: Vec<f64> },
+}
+
+impl Expr {
+    fn is_condition(&self) -> bool {
+        matches!(self, Expr::Compare(..) | Expr::And(..) | Expr::Or(..) | Expr::Not(..))
+    }
+
+    fn lookback(&self) -> usize {
+        match self {
+            Expr::Number(_) | Expr::Column(_) => 1,
+            Expr::Negate(e) | Expr::Not(e) => e.lookback(),
+            Expr::Arithmetic(_, a, b) | Expr::And(a, b) | Expr::Or(a, b) => a.lookback().max(b.lookback()),
+            Expr::Compare(comparison, a, b) => {
+                let crossing = matches!(comparison, Comparison::CrossesAbove | Comparison::CrossesBelow);
+                a.lookback().max(b.lookback()) + usize::from(crossing)
+            }
+            Expr::Call { function, series, params } => series.as_ref().map_or(1, |s| s.lookback()) + function.warmup(params),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(&'static str),
+    End,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(n) => format!("number {}", n),
+            Token::Name(name) => format!("`{}`", name),
+            Token::Symbol(s) => format!("`{}`", s),
+            Token::End => "the end of the expression".to_string(),
+        }
+    }
+}
+
+const SYMBOLS: &[&str] = &["<=", ">=", "==", "!=", "(", ")", ",", "+", "-", "*", "/", "<", ">"];
+
+/// the tokens of the source with their columns
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let number = text.parse().map_err(|_| ParseError { column, message: format!("invalid number `{}`", text) })?;
+            tokens.push((Token::Number(number), column));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            tokens.push((Token::Name(name.to_lowercase()), column));
+        } else if let Some(symbol) = SYMBOLS.iter().copied().find(|s| s.chars().enumerate().all(|(k, c)| chars.get(i + k) == Some(&c))) {
+            i += symbol.len();
+            tokens.push((Token::Symbol(symbol), column));
+        } else {
+            let hint = match c {
+                '=' => ", compare with `==`",
+                '&' => ", combine conditions with `and`",
+                '|' => ", combine conditions with `or`",
+                '!' => ", negate conditions with `not`",
+                _ => "",
+            };
+            return Err(ParseError { column, message: format!("unexpected character `{}`{}", c, hint) });
+        }
+    }
+    tokens.push((Token::End, chars.len() + 1));
+    Ok(tokens)
+}
+
+/// recursive descent over the tokens, every method returns the expression with its column
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    /// levels of nesting around the current token
+    depth: usize,
+}
+
+type Parsed = Result<(Expr, usize), ParseError>;
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].0
+    }
+
+    fn column(&self) -> usize {
+        self.tokens[self.position].1
+    }
+
+    fn advance(&mut self) -> (Token, usize) {
+        let token = self.tokens[self.position].clone();
+        if self.position + 1 < self.tokens.len() {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        ParseError { column: self.column(), message }
+    }
+
+    /// one level deeper, an error at `column` beyond `MAX_DEPTH`
+    fn descend(&mut self, column: usize) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(ParseError { column, message: format!("the expression nests deeper than {} levels", MAX_DEPTH) });
+        }
+        Ok(())
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Token::Symbol(s) if *s == symbol);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(self.peek(), Token::Name(name) if name == keyword);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), ParseError> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`, found {}", symbol, self.peek().describe())))
+        }
+    }
+
+    fn or(&mut self) -> Parsed {
+        let (mut left, column) = self.and()?;
+        while self.eat_keyword("or") {
+            let right = self.and()?;
+            left = Expr::Or(Box::new(condition(left, column, "or")?), Box::new(condition(right.0, right.1, "or")?));
+        }
+        Ok((left, column))
+    }
+
+    fn and(&mut self) -> Parsed {
+        let (mut left, column) = self.not()?;
+        while self.eat_keyword("and") {
+            let right = self.not()?;
+            left = Expr::And(Box::new(condition(left, column, "and")?), Box::new(condition(right.0, right.1, "and")?));
+        }
+        Ok((left, column))
+    }
+
+    fn not(&mut self) -> Parsed {
+        let column = self.column();
+        if self.eat_keyword("not") {
+            self.descend(column)?;
+            let (operand, operand_column) = self.not()?;
+            self.depth -= 1;
+            return Ok((Expr::Not(Box::new(condition(operand, operand_column, "not")?)), column));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Parsed {
+        let (left, column) = self.additive()?;
+        let comparison = match self.peek() {
+            Token::Symbol("<") => Comparison::Less,
+            Token::Symbol("<=") => Comparison::LessEqual,
+            Token::Symbol(">") => Comparison::Greater,
+            Token::Symbol(">=") => Comparison::GreaterEqual,
+            Token::Symbol("==") => Comparison::Equal,
+            Token::Symbol("!=") => Comparison::NotEqual,
+            Token::Name(name) if name == "crosses_above" => Comparison::CrossesAbove,
+            Token::Name(name) if name == "crosses_below" => Comparison::CrossesBelow,
+            _ => return Ok((left, column)),
+        };
+        let operator = self.advance().0.describe();
+        let right = self.additive()?;
+        let left = number(left, column, &operator)?;
+        let right = number(right.0, right.1, &operator)?;
+        Ok((Expr::Compare(comparison, Box::new(left), Box::new(right)), column))
+    }
+
+    fn additive(&mut self) -> Parsed {
+        let (mut left, column) = self.term()?;
+        loop {
+            let operator = match self.peek() {
+                Token::Symbol("+") => Operator::Add,
+                Token::Symbol("-") => Operator::Subtract,
+                _ => return Ok((left, column)),
+            };
+            let symbol = self.advance().0.describe();
+            let right = self.term()?;
+            left = Expr::Arithmetic(operator, Box::new(number(left, column, &symbol)?), Box::new(number(right.0, right.1, &symbol)?));
+        }
+    }
+
+    fn term(&mut self) -> Parsed {
+        let (mut left, column) = self.unary()?;
+        loop {
+            let operator = match self.peek() {
+                Token::Symbol("*") => Operator::Multiply,
+                Token::Symbol("/") => Operator::Divide,
+                _ => return Ok((left, column)),
+            };
+            let symbol = self.advance().0.describe();
+            let right = self.unary()?;
+            left = Expr::Arithmetic(operator, Box::new(number(left, column, &symbol)?), Box::new(number(right.0, right.1, &symbol)?));
+        }
+    }
+
+    fn unary(&mut self) -> Parsed {
+        let column = self.column();
+        if self.eat_symbol("-") {
+            self.descend(column)?;
+            let (operand, operand_column) = self.unary()?;
+            self.depth -= 1;
+            return Ok((Expr::Negate(Box::new(number(operand, operand_column, "`-`")?)), column));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Parsed {
+        let (token, column) = self.advance();
+        match token {
+            Token::Number(n) => Ok((Expr::Number(n), column)),
+            Token::Symbol("(") => {
+                self.descend(column)?;
+                let (expr, _) = self.or()?;
+                self.expect_symbol(")")?;
+                self.depth -= 1;
+                Ok((expr, column))
+            }
+            Token::Name(name) if KEYWORDS.contains(&name.as_str()) => Err(ParseError {
+                column,
+                message: format!("expected a number, column or indicator, found `{}`", name),
+            }),
+            Token::Name(name) => {
+                if self.eat_symbol("(") {
+                    self.descend(column)?;
+                    let call = self.call(&name, column)?;
+                    self.depth -= 1;
+                    return Ok(call);
+                }
+                if let Some(function) = FUNCTIONS.iter().find(|f| f.name == name) {
+                    return Err(ParseError { column, message: format!("`{}` needs its parameters: {}", name, function.signature()) });
+                }
+                if !COLUMNS.contains(&name.as_str()) {
+                    return Err(ParseError { column, message: format!("unknown column `{}`, one of {}", name, COLUMNS.join(", ")) });
+                }
+                Ok((Expr::Column(technicals::Column::from_str(&name)), column))
+            }
+            token => Err(ParseError { column, message: format!("expected a number, column or indicator, found {}", token.describe()) }),
+        }
+    }
+
+    /// the arguments of an indicator after its `(`
+    fn call(&mut self, name: &str, column: usize) -> Parsed {
+        let Some(function) = FUNCTIONS.iter().find(|f| f.name == name) else {
+            let names: Vec<&str> = FUNCTIONS.iter().map(|f| f.name).collect();
+            return Err(ParseError { column, message: format!("unknown indicator `{}`, one of {}", name, names.join(", ")) });
+        };
+        let mut args = Vec::new();
+        if !self.eat_symbol(")") {
+            loop {
+                args.push(self.or()?);
+                if self.eat_symbol(")") {
+                    break;
+                }
+                self.expect_symbol(",")?;
+            }
+        }
+        let with_series = function.input == Input::Series && args.len() == function.params.len() + 1;
+        if args.len() != function.params.len() && !with_series {
+            return Err(ParseError {
+                column,
+                message: format!("`{}` takes {}, found {} arguments", name, function.signature(), args.len()),
+            });
+        }
+        let series = if with_series {
+            let (series, series_column) = args.remove(0);
+            Some(Box::new(number(series, series_column, &format!("`{}`", name))?))
+        } else {
+            None
+        };
+        let mut params = Vec::with_capacity(args.len());
+        for ((arg, arg_column), param) in args.into_iter().zip(function.params) {
+            let value = match arg {
+                Expr::Number(value) => value,
+                _ => {
+                    return Err(ParseError { column: arg_column, message: format!("the {} of `{}` must be a number", param, name) });
+                }
+            };
+            if FRACTIONAL_PARAMS.contains(param) {
+                if !(value > 0.0 && value.is_finite()) {
+                    return Err(ParseError { column: arg_column, message: format!("the {} of `{}` must be positive", param, name) });
+                }
+            } else if value < 1.0 || value.fract() != 0.0 || value > MAX_PERIOD as f64 {
+                return Err(ParseError {
+                    column: arg_column,
+                    message: format!("the {} of `{}` must be a whole number from 1 to {}", param, name, MAX_PERIOD),
+                });
+            }
+            params.push(value);
+        }
+        Ok((Expr::Call { function, series, params }, column))
+    }
+}
+
+/// `expr` if it is a condition, an error for the operand of `operator` otherwise
+fn condition(expr: Expr, column: usize, operator: &str) -> Result<Expr, ParseError> {
+    if expr.is_condition() {
+        Ok(expr)
+    } else {
+        Err(ParseError { column, message: format!("`{}` needs conditions like `rsi(14) < 70`, found a number", operator) })
+    }
+}
+
+/// `expr` if it is a number, an error for the operand of `operator` otherwise
+fn number(expr: Expr, column: usize, operator: &str) -> Result<Expr, ParseError> {
+    if expr.is_condition() {
+        Err(ParseError { column, message: format!("{} needs numbers, found a condition", operator) })
+    } else {
+        Ok(expr)
+    }
+}
+
+/// The values of an expression for every bar
+#[derive(Clone, Debug, PartialEq)]
+pub enum Values {
+    Numbers(Vec<f64>),
+    /// whether the condition holds
+    Conditions(Vec<bool>),
+}
+
+/// the columns of the bars an expression is evaluated over
+pub(crate) struct Bars {
+    /// seconds since 1.1.1970
+    pub(crate) timestamp: Vec<i64>,
+    pub(crate) open: Vec<f64>,
+    pub(crate) high: Vec<f64>,
+    pub(crate) low: Vec<f64>,
+    pub(crate) close: Vec<f64>,
+    pub(crate) volume: Vec<f64>,
+    pub(crate) adjclose: Vec<f64>,
+    /// MIC of the exchange whose sessions anchor the `vwap`
+    pub(crate) exchange: String,
+}
+
+impl Bars {
+    pub(crate) fn from_dataframe(ohlcv: &DataFrame) -> crate::error::Result<Self> {
+        let column = |name: &str| -> crate::error::Result<Vec<f64>> {
+            Ok(ohlcv.column(name)?.cast(&DataType::Float64)?.f64()?.into_iter().map(|v| v.unwrap_or(f64::NAN)).collect())
+        };
+        let close = column("close")?;
+        // the KLINE DataFrames have datetimes, the DataFrames of the database milliseconds
+        let timestamp = match ohlcv.column("timestamp") {
+            Ok(timestamp) => {
+                let divisor = match timestamp.dtype() {
+                    DataType::Datetime(TimeUnit::Nanoseconds, _) => 1_000_000_000,
+                    DataType::Datetime(TimeUnit::Microseconds, _) => 1_000_000,
+                    _ => 1000,
+                };
+                timestamp.cast(&DataType::Int64)?.i64()?.into_iter().map(|t| t.unwrap_or_default() / divisor).collect()
+            }
+            Err(_) => vec![0; close.len()],
+        };
+        Ok(Bars {
+            timestamp,
+            open: column("open")?,
+            high: column("high")?,
+            low: column("low")?,
+            volume: column("volume")?,
+            adjclose: column("adjclose").unwrap_or_else(|_| close.clone()),
+            close,
+            exchange: crate::calendar::DEFAULT_EXCHANGE.to_string(),
+        })
+    }
+
+    pub(crate) fn from_series(bars: &[TimeSeriesData]) -> Self {
+        Bars {
+            timestamp: bars.iter().map(|b| b.datetime).collect(),
+            open: bars.iter().map(|b| b.open).collect(),
+            high: bars.iter().map(|b| b.high).collect(),
+            low: bars.iter().map(|b| b.low).collect(),
+            close: bars.iter().map(|b| b.close).collect(),
+            volume: bars.iter().map(|b| b.volume).collect(),
+            adjclose: bars.iter().map(|b| b.close).collect(),
+            exchange: crate::calendar::DEFAULT_EXCHANGE.to_string(),
+        }
+    }
+
+    fn column(&self, column: technicals::Column) -> &[f64] {
+        match column {
+            technicals::Column::Open => &self.open,
+            technicals::Column::High => &self.high,
+            technicals::Column::Low => &self.low,
+            technicals::Column::Close => &self.close,
+            technicals::Column::Volume => &self.volume,
+            technicals::Column::AdjClose => &self.adjclose,
+        }
+    }
+
+    /// the bars for the `ta` indicators using all columns, `None` for invalid bars
+    fn items(&self) -> Vec<Option<DataItem>> {
+        (0..self.close.len())
+            .map(|i| {
+                DataItem::builder()
+                    .open(self.open[i])
+                    .high(self.high[i])
+                    .low(self.low[i])
+                    .close(self.close[i])
+                    .volume(self.volume[i])
+                    .build()
+                    .ok()
+            })
+            .collect()
+    }
+}
+
+fn apply<T: Next<f64, Output = f64>>(mut indicator: T, values: &[f64]) -> Vec<f64> {
+    values.iter().map(|v| indicator.next(*v)).collect()
+}
+
+/// a `ta` indicator over the valid bars, `NaN` for the others
+fn apply_items<T: for<'a> Next<&'a DataItem, Output = f64>>(mut indicator: T, items: &[Option<DataItem>]) -> Vec<f64> {
+    items.iter().map(|item| item.as_ref().map_or(f64::NAN, |item| indicator.next(item))).collect()
+}
+
+fn numbers(expr: &Expr, bars: &Bars) -> crate::error::Result<Vec<f64>> {
+    let n = bars.close.len();
+    Ok(match expr {
+        Expr::Number(value) => vec![*value; n],
+        Expr::Column(column) => bars.column(*column).to_vec(),
+        Expr::Negate(e) => numbers(e, bars)?.into_iter().map(|v| -v).collect(),
+        Expr::Arithmetic(operator, a, b) => {
+            let (a, b) = (numbers(a, bars)?, numbers(b, bars)?);
+            a.iter()
+                .zip(&b)
+                .map(|(a, b)| match operator {
+                    Operator::Add => a + b,
+                    Operator::Subtract => a - b,
+                    Operator::Multiply => a * b,
+                    Operator::Divide if *b == 0.0 => f64::NAN,
+                    Operator::Divide => a / b,
+                })
+                .collect()
+        }
+        Expr::Call { function, series, params } => call(function, series.as_deref(), params, bars)?,
+        _ => return Err(crate::Error::analytics("a condition is no number")),
+    })
+}
+
+fn conditions(expr: &Expr, bars: &Bars) -> crate::error::Result<Vec<bool>> {
+    Ok(match expr {
+        Expr::Compare(comparison, a, b) => {
+            let (a, b) = (numbers(a, bars)?, numbers(b, bars)?);
+            (0..a.len())
+                .map(|i| {
+                    let holds = comparison.holds(a[i], b[i]);
+                    match comparison {
+                        Comparison::CrossesAbove | Comparison::CrossesBelow => {
+                            holds && i > 0 && !comparison.holds(a[i - 1], b[i - 1]) && !a[i - 1].is_nan() && !b[i - 1].is_nan()
+                        }
+                        _ => holds,
+                    }
+                })
+                .collect()
+        }
+        Expr::And(a, b) => conditions(a, bars)?.into_iter().zip(conditions(b, bars)?).map(|(a, b)| a && b).collect(),
+        Expr::Or(a, b) => conditions(a, bars)?.into_iter().zip(conditions(b, bars)?).map(|(a, b)| a || b).collect(),
+        Expr::Not(e) => conditions(e, bars)?.into_iter().map(|v| !v).collect(),
+        _ => return Err(crate::Error::analytics("a number is no condition")),
+    })
+}
+
+/// the values of an indicator, the parameters were checked by the parser
+fn call(function: &Function, series: Option<&Expr>, params: &[f64], bars: &Bars) -> crate::error::Result<Vec<f64>> {
+    let error = |e: ta::errors::TaError| crate::Error::analytics(format!("{}: {:?}", function.name, e));
+    let period = |i: usize| params[i] as usize;
+    let values = match (function.input, series) {
+        (Input::Series, Some(series)) => numbers(series, bars)?,
+        (Input::Series, None) => bars.column(function.default_column()).to_vec(),
+        (Input::Bars, _) => Vec::new(),
+    };
+    let (high, low, close, volume) = (&bars.high[..], &bars.low[..], &bars.close[..], &bars.volume[..]);
+    Ok(match function.name {
+        "sma" => apply(SimpleMovingAverage::new(period(0)).map_err(error)?, &values),
+        "ema" => apply(ExponentialMovingAverage::new(period(0)).map_err(error)?, &values),
+        "rsi" => apply(RelativeStrengthIndex::new(period(0)).map_err(error)?, &values),
+        "roc" => apply(RateOfChange::new(period(0)).map_err(error)?, &values),
+        "sd" => apply(StandardDeviation::new(period(0)).map_err(error)?, &values),
+        "mad" => apply(MeanAbsoluteDeviation::new(period(0)).map_err(error)?, &values),
+        "max" => apply(Maximum::new(period(0)).map_err(error)?, &values),
+        "min" => apply(Minimum::new(period(0)).map_err(error)?, &values),
+        "fs" => apply(FastStochastic::new(period(0)).map_err(error)?, &values),
+        "bb" | "bb_upper" | "bb_lower" => {
+            let mut bb = BollingerBands::new(period(0), params[1]).map_err(error)?;
+            values
+                .iter()
+                .map(|v| {
+                    let band = bb.next(*v);
+                    match function.name {
+                        "bb_upper" => band.upper,
+                        "bb_lower" => band.lower,
+                        _ => band.average,
+                    }
+                })
+                .collect()
+        }
+        "macd" | "macd_signal" | "macd_divergence" => {
+            let mut macd = MovingAverageConvergenceDivergence::new(period(0), period(1), period(2)).map_err(error)?;
+            values
+                .iter()
+                .map(|v| {
+                    let output = macd.next(*v);
+                    match function.name {
+                        "macd_signal" => output.signal,
+                        "macd_divergence" => output.histogram,
+                        _ => output.macd,
+                    }
+                })
+                .collect()
+        }
+        "lag" => (0..values.len()).map(|i| i.checked_sub(period(0)).map_or(f64::NAN, |j| values[j])).collect(),
+        "abs" => values.iter().map(|v| v.abs()).collect(),
+        "atr" => indicators::average_true_range(high, low, close, period(0)),
+        "adx" => indicators::adx(high, low, close, period(0)).adx,
+        "plus_di" => indicators::adx(high, low, close, period(0)).plus_di,
+        "minus_di" => indicators::adx(high, low, close, period(0)).minus_di,
+        "cci" => indicators::cci(high, low, close, period(0)),
+        "wr" => indicators::williams_r(high, low, close, period(0)),
+        "cmf" => indicators::cmf(high, low, close, volume, period(0)),
+        "mfi" => apply_items(MoneyFlowIndex::new(period(0)).map_err(error)?, &bars.items()),
+        "obv" => apply_items(OnBalanceVolume::new(), &bars.items()),
+        "vwap" => {
+            let sessions = crate::calendar::calendar(&bars.exchange).session_dates(&bars.timestamp);
+            indicators::vwap(high, low, close, volume, &sessions)
+        }
+        "psar" => indicators::psar(high, low, params[0], params[1]),
+        "supertrend" => indicators::supertrend(high, low, close, period(0), params[1]).supertrend,
+        "kc" => indicators::keltner(high, low, close, period(0), period(1), params[2]).middle,
+        "kc_upper" => indicators::keltner(high, low, close, period(0), period(1), params[2]).upper,
+        "kc_lower" => indicators::keltner(high, low, close, period(0), period(1), params[2]).lower,
+        "dc" => indicators::donchian(high, low, period(0)).middle,
+        "dc_upper" => indicators::donchian(high, low, period(0)).upper,
+        "dc_lower" => indicators::donchian(high, low, period(0)).lower,
+        name => return Err(crate::Error::analytics(format!("unknown indicator {}", name))),
+    })
+}
+
+/// A parsed indicator expression
+#[derive(Clone, Debug)]
+pub struct Expression {
+    source: String,
+    expr: Expr,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser { tokens: tokenize(source)?, position: 0, depth: 0 };
+        if parser.peek() == &Token::End {
+            return Err(parser.error("empty expression".to_string()));
+        }
+        let (expr, _) = parser.or()?;
+        if parser.peek() != &Token::End {
+            return Err(parser.error(format!("expected an operator, found {}", parser.peek().describe())));
+        }
+        Ok(Expression { source: source.trim().to_string(), expr })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// whether the expression is a condition, evaluating to booleans
+    pub fn is_condition(&self) -> bool {
+        self.expr.is_condition()
+    }
+
+    /// bars needed before the values are meaningful
+    pub fn lookback(&self) -> usize {
+        self.expr.lookback()
+    }
+
+    /// the values for every bar, ordered by time
+    pub fn evaluate_bars(&self, bars: &[TimeSeriesData]) -> crate::error::Result<Values> {
+        self.values(&Bars::from_series(bars))
+    }
+
+    /// the values for every row of an OHLCV DataFrame of a `Ticker` or `KLINE`, a series of
+    /// booleans for a condition, of numbers otherwise, named by the expression
+    pub fn evaluate(&self, ohlcv: &DataFrame) -> crate::error::Result<Series> {
+        self.evaluate_at(ohlcv, crate::calendar::DEFAULT_EXCHANGE)
+    }
+
+    /// the values for every row of an OHLCV DataFrame of bars traded at `exchange`, a MIC
+    pub fn evaluate_at(&self, ohlcv: &DataFrame, exchange: &str) -> crate::error::Result<Series> {
+        let name = PlSmallStr::from(self.source.as_str());
+        let bars = Bars { exchange: exchange.to_string(), ..Bars::from_dataframe(ohlcv)? };
+        Ok(match self.values(&bars)? {
+            Values::Numbers(values) => Series::new(name, values),
+            Values::Conditions(values) => Series::new(name, values),
+        })
+    }
+
+    /// the values over the daily bars of the ticker
+    pub async fn evaluate_ticker(&self, ticker: &Ticker) -> Result<Series, Box<dyn std::error::Error>> {
+        let ohlcv = ticker.get_chart_daily().await?;
+        Ok(self.evaluate_at(&ohlcv, &ticker.exchange())?)
+    }
+
+    fn values(&self, bars: &Bars) -> crate::error::Result<Values> {
+        if self.expr.is_condition() {
+            Ok(Values::Conditions(conditions(&self.expr, bars)?))
+        } else {
+            Ok(Values::Numbers(numbers(&self.expr, bars)?))
+        }
+    }
+}
+
+impl std::str::FromStr for Expression {
+    type Err = ParseError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Expression::parse(source)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
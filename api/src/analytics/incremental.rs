@@ -0,0 +1,395 @@
+//! Technical indicators updated one bar at a time
+//!
+//! The methods of `TechnicalIndicators` recompute an indicator over a whole DataFrame. The
+//! indicators here keep the state of the same `ta` indicators instead: they are seeded from the
+//! stored history once and then follow the live bars, a bar at a time. Fed with the rows of a
+//! DataFrame they produce the same values as the `*_df` methods, column names included.
+//! The state is serializable, `data::sql::indicator_states` keeps it in the database so the
+//! daemon continues where it stopped after a restart.
+//!
+//! ```json
+//! {"indicator": "macd", "fast_period": 12, "slow_period": 26, "signal_period": 9}
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use ta::indicators::*;
+use ta::{DataItem, Next};
+
+use super::technicals::Column;
+use crate::data::sql::TimeSeriesData;
+
+/// An indicator with its parameters, named like the methods of `TechnicalIndicators`
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "indicator", rename_all = "snake_case")]
+pub enum Indicator {
+    Sma { period: usize },
+    Ema { period: usize },
+    Rsi { period: usize },
+    Macd { fast_period: usize, slow_period: usize, signal_period: usize },
+    Ppo { fast_period: usize, slow_period: usize, signal_period: usize },
+    Mfi { period: usize },
+    Bb { period: usize, std_dev: f64 },
+    Fs { period: usize },
+    Ss { stochastic_period: usize, ema_period: usize },
+    Sd { period: usize },
+    Mad { period: usize },
+    Max { period: usize },
+    Min { period: usize },
+    Atr { period: usize },
+    Roc { period: usize },
+    Obv,
+}
+
+impl Indicator {
+    /// names of the values, the columns the `*_df` method adds
+    pub fn columns(&self) -> Vec<String> {
+        match self {
+            Indicator::Sma { period } => vec![format!("sma-{period}")],
+            Indicator::Ema { period } => vec![format!("ema-{period}")],
+            Indicator::Rsi { period } => vec![format!("rsi-{period}")],
+            Indicator::Macd { fast_period, slow_period, signal_period } => {
+                let p = format!("({fast_period},{slow_period},{signal_period})");
+                vec![format!("macd-{p}"), format!("macd_signal-{p}"), format!("macd_divergence-{p}")]
+            }
+            Indicator::Ppo { fast_period, slow_period, signal_period } => {
+                let p = format!("({fast_period},{slow_period},{signal_period})");
+                vec![format!("ppo-{p}"), format!("ppo_signal-{p}"), format!("ppo_divergence-{p}")]
+            }
+            Indicator::Mfi { period } => vec![format!("mfi-{period}")],
+            Indicator::Bb { period, std_dev } => {
+                let p = format!("({period},{std_dev})");
+                vec![format!("bb-{p}"), format!("bb_upper-{p}"), format!("bb_lower-{p}")]
+            }
+            Indicator::Fs { period } => vec![format!("fs-{period}")],
+            Indicator::Ss { stochastic_period, ema_period } => vec![format!("ss-({stochastic_period},{ema_period})")],
+            Indicator::Sd { period } => vec![format!("sd-{period}")],
+            Indicator::Mad { period } => vec![format!("mad-{period}")],
+            Indicator::Max { period } => vec![format!("max-{period}")],
+            Indicator::Min { period } => vec![format!("min-{period}")],
+            Indicator::Atr { period } => vec![format!("atr-{period}")],
+            Indicator::Roc { period } => vec![format!("roc-{period}")],
+            Indicator::Obv => vec!["obv".to_string()],
+        }
+    }
+
+    /// bars needed until the values are meaningful
+    pub fn warmup(&self) -> usize {
+        match self {
+            Indicator::Rsi { period } | Indicator::Roc { period } | Indicator::Mfi { period } | Indicator::Atr { period } => period + 1,
+            Indicator::Macd { slow_period, signal_period, .. } | Indicator::Ppo { slow_period, signal_period, .. } => {
+                slow_period + signal_period + 1
+            }
+            Indicator::Ss { stochastic_period, ema_period } => stochastic_period + ema_period,
+            Indicator::Obv => 1,
+            Indicator::Sma { period }
+            | Indicator::Ema { period }
+            | Indicator::Bb { period, .. }
+            | Indicator::Fs { period }
+            | Indicator::Sd { period }
+            | Indicator::Mad { period }
+            | Indicator::Max { period }
+            | Indicator::Min { period } => *period,
+        }
+    }
+
+    /// the column of the bars the `*_df` method uses by default
+    pub fn default_column(&self) -> Column {
+        match self {
+            Indicator::Roc { .. } => Column::AdjClose,
+            Indicator::Max { .. } => Column::High,
+            Indicator::Min { .. } => Column::Low,
+            _ => Column::Close,
+        }
+    }
+
+    fn state(&self) -> Result<State, ta::errors::TaError> {
+        Ok(match *self {
+            Indicator::Sma { period } => State::Sma(SimpleMovingAverage::new(period)?),
+            Indicator::Ema { period } => State::Ema(ExponentialMovingAverage::new(period)?),
+            Indicator::Rsi { period } => State::Rsi(RelativeStrengthIndex::new(period)?),
+            Indicator::Macd { fast_period, slow_period, signal_period } => {
+                State::Macd(MovingAverageConvergenceDivergence::new(fast_period, slow_period, signal_period)?)
+            }
+            Indicator::Ppo { fast_period, slow_period, signal_period } => {
+                State::Ppo(PercentagePriceOscillator::new(fast_period, slow_period, signal_period)?)
+            }
+            Indicator::Mfi { period } => State::Mfi(MoneyFlowIndex::new(period)?),
+            Indicator::Bb { period, std_dev } => State::Bb(BollingerBands::new(period, std_dev)?),
+            Indicator::Fs { period } => State::Fs(FastStochastic::new(period)?),
+            Indicator::Ss { stochastic_period, ema_period } => State::Ss(SlowStochastic::new(stochastic_period, ema_period)?),
+            Indicator::Sd { period } => State::Sd(StandardDeviation::new(period)?),
+            Indicator::Mad { period } => State::Mad(MeanAbsoluteDeviation::new(period)?),
+            Indicator::Max { period } => State::Max(Maximum::new(period)?),
+            Indicator::Min { period } => State::Min(Minimum::new(period)?),
+            Indicator::Atr { period } => State::Atr(AverageTrueRange::new(period)?),
+            Indicator::Roc { period } => State::Roc(RateOfChange::new(period)?),
+            Indicator::Obv => State::Obv(OnBalanceVolume::new()),
+        })
+    }
+}
+
+/// the `ta` indicator behind an `Indicator`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum State {
+    Sma(SimpleMovingAverage),
+    Ema(ExponentialMovingAverage),
+    Rsi(RelativeStrengthIndex),
+    Macd(MovingAverageConvergenceDivergence),
+    Ppo(PercentagePriceOscillator),
+    Mfi(MoneyFlowIndex),
+    Bb(BollingerBands),
+    Fs(FastStochastic),
+    Ss(SlowStochastic),
+    Sd(StandardDeviation),
+    Mad(MeanAbsoluteDeviation),
+    Max(Maximum),
+    Min(Minimum),
+    Atr(AverageTrueRange),
+    Roc(RateOfChange),
+    Obv(OnBalanceVolume),
+}
+
+impl State {
+    /// the values after one more bar, `value` is the bar's value of the indicator's column.
+    /// `None` if the bar is not a valid OHLCV bar for the indicators using all of them
+    fn next(&mut self, value: f64, bar: &TimeSeriesData) -> Option<Vec<f64>> {
+        let item = || {
+            DataItem::builder()
+                .open(bar.open)
+                .high(bar.high)
+                .low(bar.low)
+                .close(bar.close)
+                .volume(bar.volume)
+                .build()
+                .ok()
+        };
+        Some(match self {
+            State::Sma(s) => vec![s.next(value)],
+            State::Ema(s) => vec![s.next(value)],
+            State::Rsi(s) => vec![s.next(value)],
+            State::Macd(s) => {
+                let o = s.next(value);
+                vec![o.macd, o.signal, o.histogram]
+            }
+            State::Ppo(s) => {
+                let o = s.next(value);
+                vec![o.ppo, o.signal, o.histogram]
+            }
+            State::Mfi(s) => vec![s.next(&item()?)],
+            State::Bb(s) => {
+                let o = s.next(value);
+                vec![o.average, o.upper, o.lower]
+            }
+            State::Fs(s) => vec![s.next(value)],
+            State::Ss(s) => vec![s.next(value)],
+            State::Sd(s) => vec![s.next(value)],
+            State::Mad(s) => vec![s.next(value)],
+            State::Max(s) => vec![s.next(value)],
+            State::Min(s) => vec![s.next(value)],
+            State::Atr(s) => vec![s.next(&item()?)],
+            State::Roc(s) => vec![s.next(value)],
+            State::Obv(s) => vec![s.next(&item()?)],
+        })
+    }
+}
+
+/// the value of a column of a bar, the bars of the database have no adjusted close
+fn column_value(bar: &TimeSeriesData, column: Column) -> f64 {
+    match column {
+        Column::Open => bar.open,
+        Column::High => bar.high,
+        Column::Low => bar.low,
+        Column::Volume => bar.volume,
+        Column::Close | Column::AdjClose => bar.close,
+    }
+}
+
+/// An indicator that is updated one bar at a time
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IncrementalIndicator {
+    pub indicator: Indicator,
+    /// the column of the bars the indicator is calculated over
+    pub column: Column,
+    state: State,
+    /// bars seen so far
+    bars: usize,
+    /// time of the newest bar seen
+    last: Option<i64>,
+    values: Vec<f64>,
+}
+
+impl IncrementalIndicator {
+    /// a new indicator over `column`, the default column of the `*_df` method if `None`
+    pub fn new(indicator: Indicator, column: Option<Column>) -> crate::error::Result<Self> {
+        let state = indicator.state().map_err(|e| crate::Error::analytics(format!("{:?}: {:?}", indicator, e)))?;
+        Ok(IncrementalIndicator {
+            column: column.unwrap_or_else(|| indicator.default_column()),
+            indicator,
+            state,
+            bars: 0,
+            last: None,
+            values: Vec::new(),
+        })
+    }
+
+    /// a new indicator seeded with the bars of the history, ordered by time
+    pub fn seeded(indicator: Indicator, column: Option<Column>, bars: &[TimeSeriesData]) -> crate::error::Result<Self> {
+        let mut incremental = Self::new(indicator, column)?;
+        for bar in bars {
+            incremental.update(bar);
+        }
+        Ok(incremental)
+    }
+
+    /// add a bar, returns the new values. Bars not newer than the last one are ignored
+    pub fn update(&mut self, bar: &TimeSeriesData) -> Option<&[f64]> {
+        if self.last.is_some_and(|last| bar.datetime <= last) {
+            return None;
+        }
+        self.update_value(column_value(bar, self.column), bar)
+    }
+
+    /// add a bar with the value of the column given, for the columns a bar has not
+    fn update_value(&mut self, value: f64, bar: &TimeSeriesData) -> Option<&[f64]> {
+        let values = self.state.next(value, bar)?;
+        self.values = values;
+        self.bars += 1;
+        self.last = Some(bar.datetime);
+        Some(&self.values)
+    }
+
+    /// the values if `bar` was added, without adding it. Used for the newest bar while the
+    /// provider still updates it
+    pub fn peek(&self, bar: &TimeSeriesData) -> Option<Vec<f64>> {
+        self.state.clone().next(column_value(bar, self.column), bar)
+    }
+
+    /// the values after the last bar, empty before the first one
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// the values after the last bar keyed by their column names
+    pub fn named_values(&self) -> Vec<(String, f64)> {
+        self.indicator.columns().into_iter().zip(self.values.iter().copied()).collect()
+    }
+
+    /// whether enough bars were seen for meaningful values
+    pub fn is_ready(&self) -> bool {
+        self.bars >= self.indicator.warmup()
+    }
+
+    /// time of the newest bar seen
+    pub fn last(&self) -> Option<i64> {
+        self.last
+    }
+
+    /// the values after every row of an OHLCV DataFrame as the `*_df` methods calculate them,
+    /// one vector per column of `Indicator::columns`. Rows that are no valid bar keep the
+    /// previous values for the indicators using all of open, high, low, close and volume
+    pub fn over_dataframe(&mut self, ohlcv: &polars::prelude::DataFrame) -> crate::error::Result<Vec<Vec<f64>>> {
+        let column = |name: &str| -> crate::error::Result<Vec<f64>> {
+            Ok(ohlcv.column(name)?.f64()?.into_iter().map(|v| v.unwrap_or(f64::NAN)).collect())
+        };
+        // the timestamps are milliseconds, as i64 or as datetime column
+        let timestamps = ohlcv.column("timestamp")?.cast(&polars::prelude::DataType::Int64)?;
+        let timestamps: Vec<i64> = timestamps.i64()?.into_iter().map(|v| v.unwrap_or_default()).collect();
+        let (open, high, low, close, volume) = (column("open")?, column("high")?, column("low")?, column("close")?, column("volume")?);
+        let values = column(self.column.as_str())?;
+        let mut rows = vec![Vec::with_capacity(values.len()); self.indicator.columns().len()];
+        for i in 0..values.len() {
+            let bar = TimeSeriesData {
+                datetime: timestamps[i] / 1000,
+                open: open[i],
+                high: high[i],
+                low: low[i],
+                close: close[i],
+                volume: volume[i],
+            };
+            if self.update_value(values[i], &bar).is_none() {
+                continue;
+            }
+            for (row, value) in rows.iter_mut().zip(self.values.iter()) {
+                row.push(*value);
+            }
+        }
+        Ok(rows)
+    }
+}
+
+/// The indicators of a symbol that are updated together
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct IndicatorSet {
+    pub indicators: Vec<IncrementalIndicator>,
+}
+
+impl IndicatorSet {
+    pub fn new(indicators: &[Indicator]) -> crate::error::Result<Self> {
+        let indicators = indicators
+            .iter()
+            .map(|i| IncrementalIndicator::new(i.clone(), None))
+            .collect::<crate::error::Result<Vec<_>>>()?;
+        Ok(IndicatorSet { indicators })
+    }
+
+    /// the indicators shown on the live charts, see `technicals::live_indicators`
+    pub fn live_chart() -> Self {
+        Self::new(&[
+            Indicator::Sma { period: 20 },
+            Indicator::Sma { period: 50 },
+            Indicator::Sma { period: 200 },
+            Indicator::Ema { period: 20 },
+            Indicator::Bb { period: 20, std_dev: 2.0 },
+            Indicator::Rsi { period: 14 },
+            Indicator::Macd { fast_period: 12, slow_period: 26, signal_period: 9 },
+        ])
+        .unwrap_or_default()
+    }
+
+    /// the indicators stored with the rows of `live_data`, see `LiveIndicators`
+    pub fn live_data() -> Self {
+        Self::new(&[
+            Indicator::Sma { period: 10 },
+            Indicator::Ema { period: 20 },
+            Indicator::Rsi { period: 14 },
+            Indicator::Fs { period: 14 },
+            Indicator::Macd { fast_period: 12, slow_period: 26, signal_period: 9 },
+        ])
+        .unwrap_or_default()
+    }
+
+    /// time of the newest bar seen
+    pub fn last(&self) -> Option<i64> {
+        self.indicators.iter().filter_map(|i| i.last()).max()
+    }
+
+    /// add the bars newer than the last one, ordered by time
+    pub fn update(&mut self, bars: &[TimeSeriesData]) {
+        for bar in bars {
+            for indicator in self.indicators.iter_mut() {
+                indicator.update(bar);
+            }
+        }
+    }
+
+    /// the values after the last bar by column name, all of them or only the indicators with
+    /// enough bars
+    pub fn values(&self, ready_only: bool) -> BTreeMap<String, f64> {
+        self.indicators
+            .iter()
+            .filter(|i| !ready_only || i.is_ready())
+            .flat_map(|i| i.named_values())
+            .collect()
+    }
+
+    /// the values by column name if `bar` was added, see `IncrementalIndicator::peek`
+    pub fn peek(&self, bar: &TimeSeriesData) -> BTreeMap<String, f64> {
+        self.indicators
+            .iter()
+            .filter_map(|i| Some(i.indicator.columns().into_iter().zip(i.peek(bar)?)))
+            .flatten()
+            .collect()
+    }
+}
@@ -0,0 +1,318 @@
+//! Indicators the `ta` crate does not provide
+//!
+//! The functions work on the columns of the bars ordered by time and return one value per bar,
+//! the methods of `TechnicalIndicators` turn them into DataFrame columns. Like the `ta`
+//! indicators they start with the first bar and use the bars available until the window of
+//! the period is filled. Values that can not exist, e.g. the Ichimoku spans before the first
+//! displaced value, are `NaN`.
+
+use ta::indicators::ExponentialMovingAverage;
+use ta::Next;
+
+/// the values of a window of `period` values ending at every index, shorter at the start
+fn windows(values: &[f64], period: usize) -> impl Iterator<Item = &[f64]> {
+    let period = period.max(1);
+    (0..values.len()).map(move |i| &values[(i + 1).saturating_sub(period)..=i])
+}
+
+fn rolling_max(values: &[f64], period: usize) -> Vec<f64> {
+    windows(values, period).map(|w| w.iter().copied().fold(f64::NEG_INFINITY, f64::max)).collect()
+}
+
+fn rolling_min(values: &[f64], period: usize) -> Vec<f64> {
+    windows(values, period).map(|w| w.iter().copied().fold(f64::INFINITY, f64::min)).collect()
+}
+
+fn rolling_sum(values: &[f64], period: usize) -> Vec<f64> {
+    windows(values, period).map(|w| w.iter().sum()).collect()
+}
+
+/// the average of high, low and close
+pub fn typical_price(high: &[f64], low: &[f64], close: &[f64]) -> Vec<f64> {
+    high.iter().zip(low).zip(close).map(|((h, l), c)| (h + l + c) / 3.0).collect()
+}
+
+/// the true range, the high minus the low of the first bar
+pub fn true_range(high: &[f64], low: &[f64], close: &[f64]) -> Vec<f64> {
+    (0..close.len())
+        .map(|i| {
+            let range = high[i] - low[i];
+            match i.checked_sub(1).map(|p| close[p]) {
+                Some(previous) => range.max((high[i] - previous).abs()).max((low[i] - previous).abs()),
+                None => range,
+            }
+        })
+        .collect()
+}
+
+/// the exponential moving average of the true range, the `AverageTrueRange` of `ta`
+pub fn average_true_range(high: &[f64], low: &[f64], close: &[f64], period: usize) -> Vec<f64> {
+    let mut ema = ExponentialMovingAverage::new(period.max(1)).unwrap();
+    true_range(high, low, close).into_iter().map(|tr| ema.next(tr)).collect()
+}
+
+/// Wilder's smoothing, the average of the values seen until `period` values are reached
+fn wilder(values: &[f64], period: usize) -> Vec<f64> {
+    let period = period.max(1);
+    let mut average = 0.0;
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            average += (value - average) / (i + 1).min(period) as f64;
+            average
+        })
+        .collect()
+}
+
+/// The lines of the Ichimoku cloud
+pub struct Ichimoku {
+    /// tenkan-sen, the middle of the range of the conversion period
+    pub conversion: Vec<f64>,
+    /// kijun-sen, the middle of the range of the base period
+    pub base: Vec<f64>,
+    /// senkou span A, the middle of conversion and base line displaced by the base period
+    pub span_a: Vec<f64>,
+    /// senkou span B, the middle of the range of the span B period displaced by the base period
+    pub span_b: Vec<f64>,
+    /// chikou span as known at every bar, the close of the base period before. Charts draw the
+    /// close the other way round, at the bar the base period back, which is not known at that bar
+    pub lagging: Vec<f64>,
+}
+
+/// the Ichimoku cloud, usually over 9, 26 and 52 bars. The part of the cloud ahead of the
+/// last bar is left out as there are no rows for it
+pub fn ichimoku(
+    high: &[f64],
+    low: &[f64],
+    close: &[f64],
+    conversion_period: usize,
+    base_period: usize,
+    span_b_period: usize,
+) -> Ichimoku {
+    let middle = |period: usize| -> Vec<f64> {
+        rolling_max(high, period).iter().zip(rolling_min(low, period)).map(|(h, l)| (h + l) / 2.0).collect()
+    };
+    let conversion = middle(conversion_period);
+    let base = middle(base_period);
+    let span_a: Vec<f64> = conversion.iter().zip(&base).map(|(c, b)| (c + b) / 2.0).collect();
+    let span_b = middle(span_b_period);
+    let n = close.len();
+    let displaced = |values: &[f64]| -> Vec<f64> {
+        (0..n).map(|i| i.checked_sub(base_period).map_or(f64::NAN, |p| values[p])).collect()
+    };
+    Ichimoku {
+        span_a: displaced(&span_a),
+        span_b: displaced(&span_b),
+        lagging: displaced(close),
+        conversion,
+        base,
+    }
+}
+
+/// The average directional index with the directional indicators
+pub struct Adx {
+    pub adx: Vec<f64>,
+    /// +DI, the strength of the up moves
+    pub plus_di: Vec<f64>,
+    /// -DI, the strength of the down moves
+    pub minus_di: Vec<f64>,
+}
+
+/// ADX and DMI with Wilder's smoothing, usually over 14 bars
+pub fn adx(high: &[f64], low: &[f64], close: &[f64], period: usize) -> Adx {
+    let n = close.len();
+    let mut plus_dm = vec![0.0; n];
+    let mut minus_dm = vec![0.0; n];
+    for i in 1..n {
+        let up = high[i] - high[i - 1];
+        let down = low[i - 1] - low[i];
+        if up > down && up > 0.0 {
+            plus_dm[i] = up;
+        }
+        if down > up && down > 0.0 {
+            minus_dm[i] = down;
+        }
+    }
+    let tr = wilder(&true_range(high, low, close), period);
+    let di = |dm: &[f64]| -> Vec<f64> {
+        wilder(dm, period).iter().zip(&tr).map(|(dm, tr)| if *tr > 0.0 { 100.0 * dm / tr } else { 0.0 }).collect()
+    };
+    let plus_di = di(&plus_dm);
+    let minus_di = di(&minus_dm);
+    let dx: Vec<f64> = plus_di
+        .iter()
+        .zip(&minus_di)
+        .map(|(p, m)| if p + m > 0.0 { 100.0 * (p - m).abs() / (p + m) } else { 0.0 })
+        .collect();
+    Adx { adx: wilder(&dx, period), plus_di, minus_di }
+}
+
+/// the parabolic stop and reverse, usually with a step of 0.02 up to 0.2
+pub fn psar(high: &[f64], low: &[f64], step: f64, max_step: f64) -> Vec<f64> {
+    let n = high.len();
+    let mut values = Vec::with_capacity(n);
+    if n == 0 {
+        return values;
+    }
+    let mut up = n < 2 || high[1] + low[1] >= high[0] + low[0];
+    let mut sar = if up { low[0] } else { high[0] };
+    let mut extreme = if up { high[0] } else { low[0] };
+    let mut factor = step;
+    values.push(sar);
+    for i in 1..n {
+        sar += factor * (extreme - sar);
+        // the SAR may not move into the range of the two previous bars
+        let (previous_low, previous_high) = (&low[i.saturating_sub(2)..i], &high[i.saturating_sub(2)..i]);
+        if up {
+            sar = previous_low.iter().copied().fold(sar, f64::min);
+            if low[i] < sar {
+                up = false;
+                sar = extreme;
+                extreme = low[i];
+                factor = step;
+            } else if high[i] > extreme {
+                extreme = high[i];
+                factor = (factor + step).min(max_step);
+            }
+        } else {
+            sar = previous_high.iter().copied().fold(sar, f64::max);
+            if high[i] > sar {
+                up = true;
+                sar = extreme;
+                extreme = high[i];
+                factor = step;
+            } else if low[i] < extreme {
+                extreme = low[i];
+                factor = (factor + step).min(max_step);
+            }
+        }
+        values.push(sar);
+    }
+    values
+}
+
+/// the volume weighted average of the typical price, starting again with every session.
+/// `sessions` tells the sessions of the bars apart, e.g. their trading day
+pub fn vwap<S: PartialEq>(high: &[f64], low: &[f64], close: &[f64], volume: &[f64], sessions: &[S]) -> Vec<f64> {
+    let mut price_volume = 0.0;
+    let mut total_volume = 0.0;
+    typical_price(high, low, close)
+        .into_iter()
+        .enumerate()
+        .map(|(i, price)| {
+            if i > 0 && sessions[i] != sessions[i - 1] {
+                price_volume = 0.0;
+                total_volume = 0.0;
+            }
+            price_volume += price * volume[i];
+            total_volume += volume[i];
+            if total_volume > 0.0 { price_volume / total_volume } else { price }
+        })
+        .collect()
+}
+
+/// The middle, upper and lower line of a channel
+pub struct Channel {
+    pub middle: Vec<f64>,
+    pub upper: Vec<f64>,
+    pub lower: Vec<f64>,
+}
+
+/// the Keltner channel, the EMA of the close with `multiplier` times the ATR around it,
+/// usually over 20 bars, an ATR of 10 bars and twice the ATR
+pub fn keltner(high: &[f64], low: &[f64], close: &[f64], period: usize, atr_period: usize, multiplier: f64) -> Channel {
+    let mut ema = ExponentialMovingAverage::new(period.max(1)).unwrap();
+    let middle: Vec<f64> = close.iter().map(|c| ema.next(*c)).collect();
+    let atr = average_true_range(high, low, close, atr_period);
+    Channel {
+        upper: middle.iter().zip(&atr).map(|(m, a)| m + multiplier * a).collect(),
+        lower: middle.iter().zip(&atr).map(|(m, a)| m - multiplier * a).collect(),
+        middle,
+    }
+}
+
+/// the Donchian channel, the highest high and the lowest low of the period, usually 20 bars
+pub fn donchian(high: &[f64], low: &[f64], period: usize) -> Channel {
+    let upper = rolling_max(high, period);
+    let lower = rolling_min(low, period);
+    Channel { middle: upper.iter().zip(&lower).map(|(u, l)| (u + l) / 2.0).collect(), upper, lower }
+}
+
+/// The Supertrend line with its direction
+pub struct Supertrend {
+    /// the lower band in an up trend, the upper band in a down trend
+    pub supertrend: Vec<f64>,
+    /// 1 in an up trend, -1 in a down trend
+    pub direction: Vec<f64>,
+}
+
+/// the Supertrend, bands of `multiplier` times the ATR around the middle of the bars that only
+/// move with the trend, usually an ATR of 10 bars and 3 times the ATR
+pub fn supertrend(high: &[f64], low: &[f64], close: &[f64], period: usize, multiplier: f64) -> Supertrend {
+    let atr = average_true_range(high, low, close, period);
+    let n = close.len();
+    let mut result = Supertrend { supertrend: Vec::with_capacity(n), direction: Vec::with_capacity(n) };
+    let (mut upper, mut lower, mut up) = (f64::NAN, f64::NAN, true);
+    for i in 0..n {
+        let middle = (high[i] + low[i]) / 2.0;
+        let (basic_upper, basic_lower) = (middle + multiplier * atr[i], middle - multiplier * atr[i]);
+        if i == 0 {
+            upper = basic_upper;
+            lower = basic_lower;
+        } else {
+            if basic_upper < upper || close[i - 1] > upper {
+                upper = basic_upper;
+            }
+            if basic_lower > lower || close[i - 1] < lower {
+                lower = basic_lower;
+            }
+        }
+        if up && close[i] < lower {
+            up = false;
+        } else if !up && close[i] > upper {
+            up = true;
+        }
+        result.supertrend.push(if up { lower } else { upper });
+        result.direction.push(if up { 1.0 } else { -1.0 });
+    }
+    result
+}
+
+/// the commodity channel index of the typical price, usually over 20 bars
+pub fn cci(high: &[f64], low: &[f64], close: &[f64], period: usize) -> Vec<f64> {
+    let typical = typical_price(high, low, close);
+    windows(&typical, period)
+        .map(|w| {
+            let mean = w.iter().sum::<f64>() / w.len() as f64;
+            let deviation = w.iter().map(|v| (v - mean).abs()).sum::<f64>() / w.len() as f64;
+            let last = w[w.len() - 1];
+            if deviation > 0.0 { (last - mean) / (0.015 * deviation) } else { 0.0 }
+        })
+        .collect()
+}
+
+/// Williams %R from -100 at the lowest low to 0 at the highest high, usually over 14 bars
+pub fn williams_r(high: &[f64], low: &[f64], close: &[f64], period: usize) -> Vec<f64> {
+    rolling_max(high, period)
+        .iter()
+        .zip(rolling_min(low, period))
+        .zip(close)
+        .map(|((h, l), c)| if h > &l { -100.0 * (h - c) / (h - l) } else { -50.0 })
+        .collect()
+}
+
+/// the Chaikin money flow, usually over 20 bars
+pub fn cmf(high: &[f64], low: &[f64], close: &[f64], volume: &[f64], period: usize) -> Vec<f64> {
+    let money_flow: Vec<f64> = (0..close.len())
+        .map(|i| {
+            let range = high[i] - low[i];
+            if range > 0.0 { ((close[i] - low[i]) - (high[i] - close[i])) / range * volume[i] } else { 0.0 }
+        })
+        .collect();
+    rolling_sum(&money_flow, period)
+        .into_iter()
+        .zip(rolling_sum(volume, period))
+        .map(|(flow, volume)| if volume > 0.0 { flow / volume } else { 0.0 })
+        .collect()
+}
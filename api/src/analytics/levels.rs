@@ -0,0 +1,364 @@
+//! Support, resistance and trendlines
+//!
+//! A swing high is a high above the highs of `window` bars on both sides, a swing low a low
+//! below them. The prices of the swings are clustered into levels, prices within `tolerance`
+//! of each other are one level and every swing of the cluster is a touch. A level below the
+//! last close is a support, one above a resistance, so a broken resistance becomes a support.
+//! The strength of a level grows with its touches, recent touches weigh more, the strongest
+//! level has strength 1.
+//!
+//! The trendlines are fitted by least squares through the last swing lows (support line) and
+//! swing highs (resistance line), together they form the channel of the prices. A breakout is
+//! the newest bar closing beyond a level or a trendline the bar before was not beyond.
+
+use polars::prelude::DataFrame;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::expressions::Bars;
+use super::rules::Direction;
+use crate::data::sql::TimeSeriesData;
+
+/// Parameters of the detection
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LevelSettings {
+    /// bars on both sides a swing high or low stands out of
+    pub window: usize,
+    /// relative distance of prices belonging to the same level, also the margin of a breakout
+    pub tolerance: f64,
+    /// touches of a level, fewer are no level
+    pub min_touches: usize,
+    /// the last swings the trendlines are fitted through
+    pub trend_swings: usize,
+    /// the strongest levels reported
+    pub max_levels: usize,
+}
+
+impl Default for LevelSettings {
+    fn default() -> Self {
+        LevelSettings { window: 5, tolerance: 0.01, min_touches: 2, trend_swings: 4, max_levels: 8 }
+    }
+}
+
+impl LevelSettings {
+    /// problems of the parameters, empty if they can be used
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.window == 0 {
+            errors.push("the swing window must be positive".to_string());
+        }
+        if !(self.tolerance > 0.0 && self.tolerance < 1.0) {
+            errors.push("the tolerance must be between 0 and 1".to_string());
+        }
+        if self.min_touches == 0 {
+            errors.push("a level needs at least one touch".to_string());
+        }
+        if self.trend_swings < 2 {
+            errors.push("trendlines need at least two swings".to_string());
+        }
+        errors
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SwingKind {
+    High,
+    Low,
+}
+
+/// A swing high or low
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Swing {
+    /// position of the bar
+    pub index: usize,
+    /// time of the bar, seconds since 1.1.1970
+    pub datetime: i64,
+    pub price: f64,
+    pub kind: SwingKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LevelKind {
+    Support,
+    Resistance,
+}
+
+impl LevelKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LevelKind::Support => "support",
+            LevelKind::Resistance => "resistance",
+        }
+    }
+}
+
+/// A price the swings clustered at
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Level {
+    /// average price of the touches
+    pub price: f64,
+    pub kind: LevelKind,
+    pub touches: usize,
+    /// time of the first and the last touch, seconds since 1.1.1970
+    pub first: i64,
+    pub last: i64,
+    /// between 0 and 1, relative to the strongest level
+    pub strength: f64,
+}
+
+/// A line fitted through swing highs or lows
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Trendline {
+    /// a support line runs through swing lows, a resistance line through swing highs
+    pub kind: LevelKind,
+    /// position and time of the first swing of the fit
+    pub start: usize,
+    pub first: i64,
+    /// change of the price per bar
+    pub slope: f64,
+    /// price of the line at the first bar of the series
+    pub intercept: f64,
+    /// swings since the start within the tolerance of the line
+    pub touches: usize,
+}
+
+impl Trendline {
+    /// the price of the line at the bar `index`
+    pub fn value_at(&self, index: usize) -> f64 {
+        self.intercept + self.slope * index as f64
+    }
+}
+
+/// What a breakout went through
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Boundary {
+    Level,
+    Trendline,
+}
+
+/// The newest bar closing beyond a level or trendline
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Breakout {
+    /// time of the bar, seconds since 1.1.1970
+    pub datetime: i64,
+    pub close: f64,
+    /// price of the level or trendline at the bar
+    pub price: f64,
+    pub direction: Direction,
+    pub boundary: Boundary,
+    pub touches: usize,
+}
+
+impl Breakout {
+    /// `above` or `below` and the price, tells apart the breakouts of a symbol
+    pub fn key(&self) -> String {
+        let direction = match self.direction {
+            Direction::Above => "above",
+            Direction::Below => "below",
+        };
+        format!("{}-{:.2}", direction, self.price)
+    }
+}
+
+impl fmt::Display for Breakout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (direction, kind) = match self.direction {
+            Direction::Above => ("above", LevelKind::Resistance),
+            Direction::Below => ("below", LevelKind::Support),
+        };
+        let boundary = match self.boundary {
+            Boundary::Level => kind.as_str().to_string(),
+            Boundary::Trendline => format!("{} line", kind.as_str()),
+        };
+        write!(f, "close {:.2} broke {} the {} at {:.2} touched {} times", self.close, direction, boundary, self.price, self.touches)
+    }
+}
+
+/// The levels, trendlines and breakouts of a series of bars
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Levels {
+    pub swings: Vec<Swing>,
+    /// ordered by price
+    pub levels: Vec<Level>,
+    pub support_line: Option<Trendline>,
+    pub resistance_line: Option<Trendline>,
+    pub breakouts: Vec<Breakout>,
+}
+
+/// the swing highs and lows ordered by time, a plateau counts once at its first bar
+fn swings(bars: &Bars, window: usize) -> Vec<Swing> {
+    let n = bars.close.len();
+    let mut found = Vec::new();
+    if window == 0 || n < 2 * window + 1 {
+        return found;
+    }
+    for i in window..n - window {
+        let (before, after) = (&bars.high[i - window..i], &bars.high[i + 1..=i + window]);
+        let high = bars.high[i];
+        if before.iter().all(|h| *h < high) && after.iter().all(|h| *h <= high) {
+            found.push(Swing { index: i, datetime: bars.timestamp[i], price: high, kind: SwingKind::High });
+        }
+        let (before, after) = (&bars.low[i - window..i], &bars.low[i + 1..=i + window]);
+        let low = bars.low[i];
+        if before.iter().all(|l| *l > low) && after.iter().all(|l| *l >= low) {
+            found.push(Swing { index: i, datetime: bars.timestamp[i], price: low, kind: SwingKind::Low });
+        }
+    }
+    found
+}
+
+/// the swings clustered by price into levels
+fn levels(swings: &[Swing], bars: usize, last_close: f64, settings: &LevelSettings) -> Vec<Level> {
+    let mut sorted: Vec<&Swing> = swings.iter().filter(|s| s.price.is_finite()).collect();
+    sorted.sort_by(|a, b| a.price.total_cmp(&b.price));
+    let mut clusters: Vec<Vec<&Swing>> = Vec::new();
+    for swing in sorted {
+        match clusters.last_mut() {
+            Some(cluster) if swing.price - cluster[0].price <= settings.tolerance * cluster[0].price.abs() => cluster.push(swing),
+            _ => clusters.push(vec![swing]),
+        }
+    }
+    // recent touches weigh up to twice as much as the first bars
+    let weight = |s: &Swing| 0.5 + 0.5 * s.index as f64 / bars.saturating_sub(1).max(1) as f64;
+    let mut found: Vec<(Level, f64)> = clusters
+        .into_iter()
+        .filter(|cluster| cluster.len() >= settings.min_touches)
+        .map(|cluster| {
+            let price = cluster.iter().map(|s| s.price).sum::<f64>() / cluster.len() as f64;
+            let level = Level {
+                price,
+                kind: if price < last_close { LevelKind::Support } else { LevelKind::Resistance },
+                touches: cluster.len(),
+                first: cluster.iter().map(|s| s.datetime).min().unwrap_or_default(),
+                last: cluster.iter().map(|s| s.datetime).max().unwrap_or_default(),
+                strength: 0.0,
+            };
+            (level, cluster.iter().map(|s| weight(s)).sum::<f64>())
+        })
+        .collect();
+    let strongest = found.iter().map(|(_, w)| *w).fold(0.0, f64::max);
+    found.sort_by(|a, b| b.1.total_cmp(&a.1));
+    found.truncate(settings.max_levels);
+    let mut levels: Vec<Level> = found
+        .into_iter()
+        .map(|(mut level, w)| {
+            level.strength = if strongest > 0.0 { w / strongest } else { 0.0 };
+            level
+        })
+        .collect();
+    levels.sort_by(|a, b| a.price.total_cmp(&b.price));
+    levels
+}
+
+/// the least squares line through the last swings of a kind
+fn trendline(swings: &[Swing], kind: SwingKind, settings: &LevelSettings) -> Option<Trendline> {
+    let of_kind: Vec<&Swing> = swings.iter().filter(|s| s.kind == kind).collect();
+    let fitted = &of_kind[of_kind.len().saturating_sub(settings.trend_swings)..];
+    if fitted.len() < 2 {
+        return None;
+    }
+    let n = fitted.len() as f64;
+    let mean_x = fitted.iter().map(|s| s.index as f64).sum::<f64>() / n;
+    let mean_y = fitted.iter().map(|s| s.price).sum::<f64>() / n;
+    let covariance: f64 = fitted.iter().map(|s| (s.index as f64 - mean_x) * (s.price - mean_y)).sum();
+    let variance: f64 = fitted.iter().map(|s| (s.index as f64 - mean_x).powi(2)).sum();
+    let slope = covariance / variance;
+    let mut line = Trendline {
+        kind: if kind == SwingKind::Low { LevelKind::Support } else { LevelKind::Resistance },
+        start: fitted[0].index,
+        first: fitted[0].datetime,
+        slope,
+        intercept: mean_y - slope * mean_x,
+        touches: 0,
+    };
+    line.touches = of_kind
+        .iter()
+        .filter(|s| s.index >= line.start && (s.price - line.value_at(s.index)).abs() <= settings.tolerance * s.price.abs())
+        .count();
+    Some(line)
+}
+
+/// the breakouts of the newest bar
+fn breakouts(bars: &Bars, found: &Levels, settings: &LevelSettings) -> Vec<Breakout> {
+    let n = bars.close.len();
+    let mut breakouts = Vec::new();
+    if n < 2 {
+        return breakouts;
+    }
+    let (previous, last) = (bars.close[n - 2], bars.close[n - 1]);
+    // the side the newest close broke out to, the prices are the ones of the boundary at both bars.
+    // Both closes are compared with the boundary beyond the tolerance, so a close crawling past it
+    // breaks out on the one bar that leaves the margin
+    let crossed = |previous_price: f64, price: f64| {
+        let (upper, lower) = (1.0 + settings.tolerance, 1.0 - settings.tolerance);
+        if previous <= previous_price * upper && last > price * upper {
+            Some(Direction::Above)
+        } else if previous >= previous_price * lower && last < price * lower {
+            Some(Direction::Below)
+        } else {
+            None
+        }
+    };
+    let breakout = |price: f64, direction: Direction, boundary: Boundary, touches: usize| Breakout {
+        datetime: bars.timestamp[n - 1],
+        close: last,
+        price,
+        direction,
+        boundary,
+        touches,
+    };
+    for level in found.levels.iter() {
+        if let Some(direction) = crossed(level.price, level.price) {
+            breakouts.push(breakout(level.price, direction, Boundary::Level, level.touches));
+        }
+    }
+    // the prices leave the channel above the resistance line or below the support line
+    for (line, direction) in [(&found.resistance_line, Direction::Above), (&found.support_line, Direction::Below)] {
+        if let Some(line) = line {
+            if crossed(line.value_at(n - 2), line.value_at(n - 1)) == Some(direction) {
+                breakouts.push(breakout(line.value_at(n - 1), direction, Boundary::Trendline, line.touches));
+            }
+        }
+    }
+    breakouts
+}
+
+fn detect_bars(bars: &Bars, settings: &LevelSettings) -> Levels {
+    let Some(last_close) = bars.close.last().copied() else {
+        return Levels::default();
+    };
+    let swings = swings(bars, settings.window);
+    let mut found = Levels {
+        levels: levels(&swings, bars.close.len(), last_close, settings),
+        support_line: trendline(&swings, SwingKind::Low, settings),
+        resistance_line: trendline(&swings, SwingKind::High, settings),
+        swings,
+        breakouts: Vec::new(),
+    };
+    found.breakouts = breakouts(bars, &found, settings);
+    found
+}
+
+/// the swings, levels, trendlines and breakouts in an OHLCV DataFrame of a `Ticker`, `KLINE` or
+/// the database
+///
+/// # Arguments
+///
+/// * `ohlcv` - `&DataFrame` - bars ordered by time
+/// * `settings` - `&LevelSettings` - parameters of the detection
+///
+/// # Returns
+///
+/// * `Levels` - levels with their touches and strength, trendlines and the breakouts of the newest bar
+pub fn detect(ohlcv: &DataFrame, settings: &LevelSettings) -> crate::error::Result<Levels> {
+    Ok(detect_bars(&Bars::from_dataframe(ohlcv)?, settings))
+}
+
+/// the swings, levels, trendlines and breakouts in bars of the database ordered by time
+pub fn detect_in_series(bars: &[TimeSeriesData], settings: &LevelSettings) -> Levels {
+    detect_bars(&Bars::from_series(bars), settings)
+}
@@ -1,6 +1,12 @@
 pub mod detectors;
+pub mod expressions;
+pub mod incremental;
+pub mod indicators;
+pub mod levels;
 pub mod performance;
 pub  mod technicals;
 pub mod statistics;
 pub mod optimization;
-pub mod stochastics;
\ No newline at end of file
+pub mod patterns;
+pub mod stochastics;
+pub mod rules;
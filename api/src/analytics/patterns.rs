@@ -0,0 +1,386 @@
+//! Candlestick patterns
+//!
+//! Recognises the common patterns of one to three bars in OHLCV data. A pattern is reported on
+//! its last bar with a confidence between 0 and 1 telling how clearly the bars show it: long
+//! shadows for a hammer, a body fully engulfing the one before, a preceding trend for the
+//! reversal patterns. Bodies are judged against the average body of the bars before, so the
+//! patterns work for any price level and volatility.
+//! The events are stored like the jump events, see `data::sql::events::insert_pattern_events`.
+
+use polars::prelude::DataFrame;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use super::expressions::Bars;
+use crate::data::sql::TimeSeriesData;
+
+/// bars the average body is taken over
+const AVERAGE_BODY_BARS: usize = 10;
+/// bars the trend before a pattern is taken over
+const TREND_BARS: usize = 5;
+
+/// Whether a pattern signals rising or falling prices
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bias {
+    Bullish,
+    Bearish,
+    /// indecision, e.g. a doji or an inside bar
+    Neutral,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pattern {
+    /// open and close nearly equal
+    Doji,
+    /// small body at the top of a long lower shadow
+    Hammer,
+    /// small body at the bottom of a long upper shadow
+    ShootingStar,
+    /// a rising body engulfing the falling body before
+    BullishEngulfing,
+    /// a falling body engulfing the rising body before
+    BearishEngulfing,
+    /// a small rising body inside the long falling body before
+    BullishHarami,
+    /// a small falling body inside the long rising body before
+    BearishHarami,
+    /// a long falling bar, a small body below it and a rising bar closing in its upper half
+    MorningStar,
+    /// a long rising bar, a small body above it and a falling bar closing in its lower half
+    EveningStar,
+    /// three rising bars each closing higher
+    ThreeWhiteSoldiers,
+    /// three falling bars each closing lower
+    ThreeBlackCrows,
+    /// high and low within the range of the bar before
+    InsideBar,
+    /// high and low beyond the range of the bar before
+    OutsideBar,
+}
+
+pub const PATTERNS: &[Pattern] = &[
+    Pattern::Doji,
+    Pattern::Hammer,
+    Pattern::ShootingStar,
+    Pattern::BullishEngulfing,
+    Pattern::BearishEngulfing,
+    Pattern::BullishHarami,
+    Pattern::BearishHarami,
+    Pattern::MorningStar,
+    Pattern::EveningStar,
+    Pattern::ThreeWhiteSoldiers,
+    Pattern::ThreeBlackCrows,
+    Pattern::InsideBar,
+    Pattern::OutsideBar,
+];
+
+impl Pattern {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Pattern::Doji => "doji",
+            Pattern::Hammer => "hammer",
+            Pattern::ShootingStar => "shooting_star",
+            Pattern::BullishEngulfing => "bullish_engulfing",
+            Pattern::BearishEngulfing => "bearish_engulfing",
+            Pattern::BullishHarami => "bullish_harami",
+            Pattern::BearishHarami => "bearish_harami",
+            Pattern::MorningStar => "morning_star",
+            Pattern::EveningStar => "evening_star",
+            Pattern::ThreeWhiteSoldiers => "three_white_soldiers",
+            Pattern::ThreeBlackCrows => "three_black_crows",
+            Pattern::InsideBar => "inside_bar",
+            Pattern::OutsideBar => "outside_bar",
+        }
+    }
+
+    /// the name shown in charts and reports
+    pub fn label(&self) -> &'static str {
+        match self {
+            Pattern::Doji => "Doji",
+            Pattern::Hammer => "Hammer",
+            Pattern::ShootingStar => "Shooting Star",
+            Pattern::BullishEngulfing => "Bullish Engulfing",
+            Pattern::BearishEngulfing => "Bearish Engulfing",
+            Pattern::BullishHarami => "Bullish Harami",
+            Pattern::BearishHarami => "Bearish Harami",
+            Pattern::MorningStar => "Morning Star",
+            Pattern::EveningStar => "Evening Star",
+            Pattern::ThreeWhiteSoldiers => "Three White Soldiers",
+            Pattern::ThreeBlackCrows => "Three Black Crows",
+            Pattern::InsideBar => "Inside Bar",
+            Pattern::OutsideBar => "Outside Bar",
+        }
+    }
+
+    pub fn bias(&self) -> Bias {
+        match self {
+            Pattern::Hammer
+            | Pattern::BullishEngulfing
+            | Pattern::BullishHarami
+            | Pattern::MorningStar
+            | Pattern::ThreeWhiteSoldiers => Bias::Bullish,
+            Pattern::ShootingStar
+            | Pattern::BearishEngulfing
+            | Pattern::BearishHarami
+            | Pattern::EveningStar
+            | Pattern::ThreeBlackCrows => Bias::Bearish,
+            Pattern::Doji | Pattern::InsideBar | Pattern::OutsideBar => Bias::Neutral,
+        }
+    }
+
+    /// number of bars of the pattern
+    pub fn bars(&self) -> usize {
+        match self {
+            Pattern::Doji | Pattern::Hammer | Pattern::ShootingStar => 1,
+            Pattern::MorningStar | Pattern::EveningStar | Pattern::ThreeWhiteSoldiers | Pattern::ThreeBlackCrows => 3,
+            _ => 2,
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PATTERNS.iter().find(|p| p.as_str() == s).copied().ok_or_else(|| format!("unknown candlestick pattern {}", s))
+    }
+}
+
+/// A pattern found in the bars of a symbol
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct PatternEvent {
+    /// time of the last bar of the pattern, seconds since 1.1.1970
+    pub datetime: i64,
+    pub symbol: String,
+    pub pattern: Pattern,
+    /// how clearly the bars show the pattern, between 0 and 1
+    pub confidence: f64,
+}
+
+/// one bar with the measures the patterns are judged by
+#[derive(Clone, Copy)]
+struct Candle {
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+}
+
+impl Candle {
+    fn body(&self) -> f64 {
+        (self.close - self.open).abs()
+    }
+
+    fn range(&self) -> f64 {
+        self.high - self.low
+    }
+
+    fn upper_shadow(&self) -> f64 {
+        self.high - self.open.max(self.close)
+    }
+
+    fn lower_shadow(&self) -> f64 {
+        self.open.min(self.close) - self.low
+    }
+
+    fn body_top(&self) -> f64 {
+        self.open.max(self.close)
+    }
+
+    fn body_bottom(&self) -> f64 {
+        self.open.min(self.close)
+    }
+
+    fn midpoint(&self) -> f64 {
+        (self.open + self.close) / 2.0
+    }
+
+    fn rising(&self) -> bool {
+        self.close > self.open
+    }
+
+    fn falling(&self) -> bool {
+        self.close < self.open
+    }
+
+    fn is_valid(&self) -> bool {
+        [self.open, self.high, self.low, self.close].iter().all(|v| v.is_finite()) && self.range() > 0.0
+    }
+}
+
+/// 0 below `from`, 1 above `to` and linear in between
+fn scale(value: f64, from: f64, to: f64) -> f64 {
+    ((value - from) / (to - from)).clamp(0.0, 1.0)
+}
+
+struct Detector<'a> {
+    candles: &'a [Candle],
+}
+
+impl Detector<'_> {
+    /// the average body of the bars before `i`, the body of bar `i` at the start
+    fn average_body(&self, i: usize) -> f64 {
+        let window = &self.candles[i.saturating_sub(AVERAGE_BODY_BARS)..i];
+        if window.is_empty() {
+            return self.candles[i].body();
+        }
+        window.iter().map(Candle::body).sum::<f64>() / window.len() as f64
+    }
+
+    /// the relative change of the close over the bars before bar `i`, 0 at the start
+    fn trend(&self, i: usize) -> f64 {
+        if i < TREND_BARS + 1 {
+            return 0.0;
+        }
+        let (first, last) = (self.candles[i - 1 - TREND_BARS].close, self.candles[i - 1].close);
+        if first > 0.0 {
+            (last - first) / first
+        } else {
+            0.0
+        }
+    }
+
+    /// the patterns ending at bar `i` with their confidence
+    fn patterns(&self, i: usize) -> Vec<(Pattern, f64)> {
+        let mut found = Vec::new();
+        let c = self.candles[i];
+        if !c.is_valid() {
+            return found;
+        }
+        let average = self.average_body(i);
+        let falling_before = self.trend(i) < 0.0;
+        let rising_before = self.trend(i) > 0.0;
+        let trend_bonus = |follows: bool| if follows { 0.25 } else { 0.0 };
+
+        if c.body() <= 0.1 * c.range() {
+            found.push((Pattern::Doji, 0.5 + 0.5 * (1.0 - c.body() / (0.1 * c.range()))));
+        }
+        if c.body() > 0.05 * c.range() {
+            if c.lower_shadow() >= 2.0 * c.body() && c.upper_shadow() <= 0.25 * c.body().max(0.1 * c.range()) {
+                let shadow = scale(c.lower_shadow() / c.body(), 2.0, 4.0);
+                found.push((Pattern::Hammer, 0.5 + 0.25 * shadow + trend_bonus(falling_before)));
+            }
+            if c.upper_shadow() >= 2.0 * c.body() && c.lower_shadow() <= 0.25 * c.body().max(0.1 * c.range()) {
+                let shadow = scale(c.upper_shadow() / c.body(), 2.0, 4.0);
+                found.push((Pattern::ShootingStar, 0.5 + 0.25 * shadow + trend_bonus(rising_before)));
+            }
+        }
+        if i < 1 || !self.candles[i - 1].is_valid() {
+            return found;
+        }
+
+        let p = self.candles[i - 1];
+        if p.body() > 0.0 && c.body() > p.body() {
+            let engulfing = c.body_top() >= p.body_top() && c.body_bottom() <= p.body_bottom();
+            let size = scale(c.body() / p.body(), 1.0, 2.0);
+            if engulfing && p.falling() && c.rising() {
+                found.push((Pattern::BullishEngulfing, 0.5 + 0.25 * size + trend_bonus(falling_before)));
+            }
+            if engulfing && p.rising() && c.falling() {
+                found.push((Pattern::BearishEngulfing, 0.5 + 0.25 * size + trend_bonus(rising_before)));
+            }
+        }
+        if p.body() >= average && c.body() < p.body() && c.body_top() <= p.body_top() && c.body_bottom() >= p.body_bottom() {
+            let size = 1.0 - c.body() / p.body();
+            if p.falling() && c.rising() {
+                found.push((Pattern::BullishHarami, 0.4 + 0.35 * size + trend_bonus(falling_before)));
+            }
+            if p.rising() && c.falling() {
+                found.push((Pattern::BearishHarami, 0.4 + 0.35 * size + trend_bonus(rising_before)));
+            }
+        }
+        if c.high < p.high && c.low > p.low {
+            found.push((Pattern::InsideBar, 0.5 + 0.5 * (1.0 - c.range() / p.range())));
+        }
+        if c.high > p.high && c.low < p.low {
+            found.push((Pattern::OutsideBar, 0.5 + 0.5 * scale(c.range() / p.range(), 1.0, 2.0)));
+        }
+        if i < 2 || !self.candles[i - 2].is_valid() {
+            return found;
+        }
+
+        let f = self.candles[i - 2];
+        let average = self.average_body(i - 2);
+        // the star of the morning and evening star
+        if f.body() >= average && f.body() > 0.0 && p.body() <= 0.5 * f.body() {
+            if f.falling() && p.body_top() <= f.close + 0.1 * f.body() && c.rising() && c.close > f.midpoint() {
+                let recovered = scale(c.close - f.midpoint(), 0.0, f.body() / 2.0);
+                found.push((Pattern::MorningStar, 0.5 + 0.25 * recovered + 0.25 * (1.0 - p.body() / (0.5 * f.body()))));
+            }
+            if f.rising() && p.body_bottom() >= f.close - 0.1 * f.body() && c.falling() && c.close < f.midpoint() {
+                let fallen = scale(f.midpoint() - c.close, 0.0, f.body() / 2.0);
+                found.push((Pattern::EveningStar, 0.5 + 0.25 * fallen + 0.25 * (1.0 - p.body() / (0.5 * f.body()))));
+            }
+        }
+        let three = [f, p, c];
+        let long_bodies = three.iter().all(|b| b.body() >= 0.5 * average && b.body() > 0.0);
+        if long_bodies {
+            // the upper shadows of rising and the lower shadows of falling bars, relative to their bodies
+            let shadows = |shadow: fn(&Candle) -> f64| three.iter().map(|b| shadow(b) / b.body()).sum::<f64>() / 3.0;
+            let soldiers = three.iter().all(Candle::rising)
+                && p.close > f.close
+                && c.close > p.close
+                && (f.open..=f.close).contains(&p.open)
+                && (p.open..=p.close).contains(&c.open);
+            if soldiers {
+                found.push((Pattern::ThreeWhiteSoldiers, 0.5 + 0.5 * (1.0 - scale(shadows(Candle::upper_shadow), 0.0, 0.5))));
+            }
+            let crows = three.iter().all(Candle::falling)
+                && p.close < f.close
+                && c.close < p.close
+                && (f.close..=f.open).contains(&p.open)
+                && (p.close..=p.open).contains(&c.open);
+            if crows {
+                found.push((Pattern::ThreeBlackCrows, 0.5 + 0.5 * (1.0 - scale(shadows(Candle::lower_shadow), 0.0, 0.5))));
+            }
+        }
+        found
+    }
+}
+
+/// the patterns in the bars with a confidence of at least `min_confidence`, ordered by time
+fn detect_bars(symbol: &str, bars: &Bars, min_confidence: f64) -> Vec<PatternEvent> {
+    let candles: Vec<Candle> = (0..bars.close.len())
+        .map(|i| Candle { open: bars.open[i], high: bars.high[i], low: bars.low[i], close: bars.close[i] })
+        .collect();
+    let detector = Detector { candles: &candles };
+    let mut events = Vec::new();
+    for (i, timestamp) in bars.timestamp.iter().enumerate() {
+        for (pattern, confidence) in detector.patterns(i) {
+            let confidence = confidence.clamp(0.0, 1.0);
+            if confidence >= min_confidence {
+                events.push(PatternEvent { datetime: *timestamp, symbol: symbol.to_string(), pattern, confidence });
+            }
+        }
+    }
+    events
+}
+
+/// the candlestick patterns in an OHLCV DataFrame of a `Ticker`, `KLINE` or the database
+///
+/// # Arguments
+///
+/// * `symbol` - `&str` - symbol of the events
+/// * `ohlcv` - `&DataFrame` - bars ordered by time
+/// * `min_confidence` - `f64` - patterns shown less clearly are left out, 0 for all
+///
+/// # Returns
+///
+/// * `Vec<PatternEvent>` - the patterns ordered by the time of their last bar
+pub fn detect(symbol: &str, ohlcv: &DataFrame, min_confidence: f64) -> crate::error::Result<Vec<PatternEvent>> {
+    Ok(detect_bars(symbol, &Bars::from_dataframe(ohlcv)?, min_confidence))
+}
+
+/// the candlestick patterns in bars of the database ordered by time
+pub fn detect_in_series(symbol: &str, bars: &[TimeSeriesData], min_confidence: f64) -> Vec<PatternEvent> {
+    detect_bars(symbol, &Bars::from_series(bars), min_confidence)
+}
@@ -15,6 +15,8 @@ pub struct TickerPerformanceStats {
     pub end_date: String,
     pub dates_array: Vec<String>,
     pub interval: IntervalDays,
+    /// MIC of the exchange annualizing the returns
+    pub exchange: String,
     pub confidence_level: f64,
     pub risk_free_rate: f64,
     pub security_prices: Series,
@@ -82,9 +84,10 @@ impl TickerPerformance for Ticker {
         let security_returns = security_returns.column(&self.ticker)?.as_series().unwrap();
         let benchmark_returns = benchmark_returns.column("roc-1")?.as_series().unwrap();
 
+        let exchange = self.exchange();
         let performance_stats = PerformanceStats::compute_stats(
             security_returns.clone(), benchmark_returns.clone(),
-            self.risk_free_rate, self.confidence_level, interval)?;
+            self.risk_free_rate, self.confidence_level, interval, &exchange)?;
         Ok(TickerPerformanceStats {
             ticker_symbol: self.ticker.clone(),
             benchmark_symbol: self.benchmark_symbol.clone(),
@@ -92,6 +95,7 @@ impl TickerPerformance for Ticker {
             end_date: self.end_date.clone(),
             dates_array,
             interval,
+            exchange,
             confidence_level: self.confidence_level,
             risk_free_rate: self.risk_free_rate,
             security_prices: security_prices.clone(),
@@ -112,6 +116,8 @@ pub struct PortfolioPerformanceStats {
     pub start_date: String,
     pub end_date: String,
     pub interval: IntervalDays,
+    /// MIC of the exchange annualizing the returns, the one of the first ticker
+    pub exchange: String,
     pub dates_array: Vec<String>,
     pub confidence_level: f64,
     pub risk_free_rate: f64,
@@ -225,9 +231,11 @@ impl PortfolioPerformanceStats {
         let optimal_weights = opt_result.optimal_weights;
         let daily_portfolio_returns = daily_portfolio_returns(&optimal_weights, &portfolio_returns);
 
+        // a portfolio of several exchanges is annualized with the sessions of the first one
+        let exchange = tickers.tickers.first().map_or_else(|| crate::calendar::DEFAULT_EXCHANGE.to_string(), |t| t.exchange());
         let performance_stats = PerformanceStats::compute_stats(
             daily_portfolio_returns.clone(), benchmark_returns_roc.clone(),
-            risk_free_rate, confidence_level, interval)?;
+            risk_free_rate, confidence_level, interval, &exchange)?;
 
 
         Ok(PortfolioPerformanceStats{
@@ -236,6 +244,7 @@ impl PortfolioPerformanceStats {
             start_date: start_date.to_string(),
             end_date: end_date.to_string(),
             interval,
+            exchange,
             dates_array: dates_array.clone(),
             confidence_level,
             risk_free_rate,
@@ -0,0 +1,376 @@
+//! User-defined alert rules over the bars of a symbol
+//!
+//! A rule combines conditions on prices and indicators with `all` (AND) and `any` (OR).
+//! Conditions look at the newest bars only, crossing conditions compare the last two bars so
+//! a rule fires when the condition starts to hold and not on every update while it holds.
+//! An `expression` condition is a condition of `analytics::expressions`, it fires on the bar it
+//! starts to hold as well. Rules are stored as JSON in the `alert_rules` table, see
+//! `data::sql::alert_rules`.
+//!
+//! ```json
+//! {"type": "all", "conditions": [
+//!     {"type": "rsi", "period": 14, "level": 70, "direction": "above"},
+//!     {"type": "volume_spike", "period": 20, "factor": 3},
+//!     {"type": "expression", "expression": "ema(12) crosses_above ema(26) and adx(14) > 25"}
+//! ]}
+//! ```
+
+use serde::{Deserialize, Serialize};
+use ta::indicators::{BollingerBands, MovingAverageConvergenceDivergence, RelativeStrengthIndex};
+use ta::Next;
+
+use super::expressions::{Expression, Values};
+use crate::data::sql::TimeSeriesData;
+
+/// Side of a level or crossing
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Above,
+    Below,
+}
+
+impl Direction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Above => "above",
+            Direction::Below => "below",
+        }
+    }
+
+    /// whether `value` is on this side of `level`
+    fn holds(&self, value: f64, level: f64) -> bool {
+        match self {
+            Direction::Above => value > level,
+            Direction::Below => value < level,
+        }
+    }
+
+    /// whether the series moved to this side of the level between the two values
+    fn crossed(&self, previous: f64, last: f64, previous_level: f64, last_level: f64) -> bool {
+        !self.holds(previous, previous_level) && self.holds(last, last_level)
+    }
+}
+
+/// A condition over the bars of a symbol
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Condition {
+    /// the close crosses a price level
+    PriceCrosses { level: f64, direction: Direction },
+    /// the RSI is above or below a level
+    Rsi {
+        #[serde(default = "default_rsi_period")]
+        period: usize,
+        level: f64,
+        direction: Direction,
+    },
+    /// the MACD line crosses its signal line
+    MacdCross {
+        #[serde(default = "default_macd_fast")]
+        fast: usize,
+        #[serde(default = "default_macd_slow")]
+        slow: usize,
+        #[serde(default = "default_macd_signal")]
+        signal: usize,
+        direction: Direction,
+    },
+    /// the close changed by at least `percent` over the last `bars` bars (minutes for live data)
+    Change { bars: usize, percent: f64, direction: Direction },
+    /// the volume of the last bar is `factor` times the average of the `period` bars before
+    VolumeSpike {
+        #[serde(default = "default_period")]
+        period: usize,
+        factor: f64,
+    },
+    /// the close breaks out of the Bollinger bands
+    Bollinger {
+        #[serde(default = "default_period")]
+        period: usize,
+        #[serde(default = "default_std_dev")]
+        std_dev: f64,
+        direction: Direction,
+    },
+    /// an indicator expression like `rsi(14) < 30 and close > sma(200)` starts to hold
+    Expression { expression: String },
+    /// every condition holds
+    All { conditions: Vec<Condition> },
+    /// at least one condition holds
+    Any { conditions: Vec<Condition> },
+}
+
+fn default_rsi_period() -> usize {
+    14
+}
+
+fn default_macd_fast() -> usize {
+    12
+}
+
+fn default_macd_slow() -> usize {
+    26
+}
+
+fn default_macd_signal() -> usize {
+    9
+}
+
+fn default_period() -> usize {
+    20
+}
+
+fn default_std_dev() -> f64 {
+    2.0
+}
+
+/// the last two values of an indicator fed with the closes
+fn last_two<T, O>(indicator: &mut T, bars: &[TimeSeriesData]) -> Option<(O, O)>
+where
+    T: Next<f64, Output = O>,
+    O: Clone,
+{
+    let values: Vec<O> = bars.iter().map(|b| indicator.next(b.close)).collect();
+    match values.as_slice() {
+        [.., previous, last] => Some((previous.clone(), last.clone())),
+        _ => None,
+    }
+}
+
+impl Condition {
+    /// check the condition, returns a description of what happened if it holds
+    pub fn evaluate(&self, bars: &[TimeSeriesData]) -> Option<String> {
+        match self {
+            Condition::PriceCrosses { level, direction } => {
+                let [.., previous, last] = bars else { return None };
+                direction
+                    .crossed(previous.close, last.close, *level, *level)
+                    .then(|| format!("price {:.2} crossed {} {}", last.close, direction.as_str(), level))
+            }
+            Condition::Rsi { period, level, direction } => {
+                let mut rsi = RelativeStrengthIndex::new(*period).ok()?;
+                if bars.len() <= *period {
+                    return None;
+                }
+                let (_, last) = last_two(&mut rsi, bars)?;
+                direction.holds(last, *level).then(|| format!("RSI({}) {:.1} is {} {}", period, last, direction.as_str(), level))
+            }
+            Condition::MacdCross { fast, slow, signal, direction } => {
+                let mut macd = MovingAverageConvergenceDivergence::new(*fast, *slow, *signal).ok()?;
+                if bars.len() <= slow + signal {
+                    return None;
+                }
+                let (previous, last) = last_two(&mut macd, bars)?;
+                direction
+                    .crossed(previous.macd, last.macd, previous.signal, last.signal)
+                    .then(|| format!("MACD({fast},{slow},{signal}) crossed {} its signal line", direction.as_str()))
+            }
+            Condition::Change { bars: n, percent, direction } => {
+                if *n == 0 || bars.len() <= *n {
+                    return None;
+                }
+                let first = bars[bars.len() - 1 - n].close;
+                let last = bars[bars.len() - 1].close;
+                if first <= 0.0 {
+                    return None;
+                }
+                let change = (last - first) / first * 100.0;
+                let reached = match direction {
+                    Direction::Above => change >= percent.abs(),
+                    Direction::Below => change <= -percent.abs(),
+                };
+                reached.then(|| format!("price changed by {:.2}% over {} bars", change, n))
+            }
+            Condition::VolumeSpike { period, factor } => {
+                if *period == 0 || bars.len() <= *period {
+                    return None;
+                }
+                let last = &bars[bars.len() - 1];
+                let window = &bars[bars.len() - 1 - period..bars.len() - 1];
+                let average = window.iter().map(|b| b.volume).sum::<f64>() / *period as f64;
+                (average > 0.0 && last.volume >= average * factor)
+                    .then(|| format!("volume {:.0} is {:.1} times the average of {} bars", last.volume, last.volume / average, period))
+            }
+            Condition::Bollinger { period, std_dev, direction } => {
+                let mut bb = BollingerBands::new(*period, *std_dev).ok()?;
+                if bars.len() <= *period {
+                    return None;
+                }
+                let (previous, last) = last_two(&mut bb, bars)?;
+                let [.., previous_bar, last_bar] = bars else { return None };
+                let (previous_band, last_band) = match direction {
+                    Direction::Above => (previous.upper, last.upper),
+                    Direction::Below => (previous.lower, last.lower),
+                };
+                direction
+                    .crossed(previous_bar.close, last_bar.close, previous_band, last_band)
+                    .then(|| format!("price {:.2} broke {} the Bollinger band {:.2}", last_bar.close, direction.as_str(), last_band))
+            }
+            Condition::Expression { expression } => {
+                let parsed = Expression::parse(expression).ok()?;
+                if bars.len() <= parsed.lookback() {
+                    return None;
+                }
+                let Ok(Values::Conditions(values)) = parsed.evaluate_bars(bars) else { return None };
+                let [.., previous, last] = values.as_slice() else { return None };
+                (*last && !previous).then(|| format!("{} started to hold", parsed))
+            }
+            Condition::All { conditions } => {
+                if conditions.is_empty() {
+                    return None;
+                }
+                let mut reasons = Vec::new();
+                for c in conditions {
+                    reasons.push(c.evaluate(bars)?);
+                }
+                Some(reasons.join(" and "))
+            }
+            Condition::Any { conditions } => conditions.iter().find_map(|c| c.evaluate(bars)),
+        }
+    }
+
+    /// problems of the parameters, empty if the condition can be evaluated
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        match self {
+            Condition::PriceCrosses { level, .. } if !level.is_finite() => errors.push("price level must be a number".to_string()),
+            Condition::Rsi { period, level, .. } => {
+                if *period == 0 {
+                    errors.push("RSI period must be positive".to_string());
+                }
+                if !(0.0..=100.0).contains(level) {
+                    errors.push("RSI level must be between 0 and 100".to_string());
+                }
+            }
+            Condition::MacdCross { fast, slow, signal, .. } if *fast == 0 || *signal == 0 || fast >= slow => {
+                errors.push("MACD needs 0 < fast < slow and a positive signal period".to_string())
+            }
+            Condition::Change { bars, percent, .. } if *bars == 0 || !percent.is_finite() => {
+                errors.push("change needs a positive number of bars and a percentage".to_string())
+            }
+            Condition::VolumeSpike { period, factor } if *period == 0 || *factor <= 0.0 => {
+                errors.push("volume spike needs a positive period and factor".to_string())
+            }
+            Condition::Bollinger { period, std_dev, .. } if *period == 0 || *std_dev <= 0.0 => {
+                errors.push("Bollinger bands need a positive period and standard deviation".to_string())
+            }
+            Condition::Expression { expression } => match Expression::parse(expression) {
+                Ok(parsed) if !parsed.is_condition() => {
+                    errors.push(format!("expression {} is a number, not a condition", expression))
+                }
+                Ok(_) => {}
+                Err(error) => errors.push(format!("expression {}: {}", expression, error)),
+            },
+            Condition::All { conditions } | Condition::Any { conditions } => {
+                if conditions.is_empty() {
+                    errors.push("all/any need at least one condition".to_string());
+                }
+                for c in conditions {
+                    errors.extend(c.validate());
+                }
+            }
+            _ => {}
+        }
+        errors
+    }
+
+    /// number of bars needed before the condition can hold
+    pub fn lookback(&self) -> usize {
+        match self {
+            Condition::PriceCrosses { .. } => 2,
+            Condition::Rsi { period, .. } => period + 1,
+            Condition::MacdCross { slow, signal, .. } => slow + signal + 1,
+            Condition::Change { bars, .. } => bars + 1,
+            Condition::VolumeSpike { period, .. } | Condition::Bollinger { period, .. } => period + 1,
+            Condition::Expression { expression } => Expression::parse(expression).map_or(0, |parsed| parsed.lookback() + 1),
+            Condition::All { conditions } | Condition::Any { conditions } => conditions.iter().map(|c| c.lookback()).max().unwrap_or(0),
+        }
+    }
+}
+
+/// A named rule for one symbol or, with symbol `*`, all active symbols
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct AlertRule {
+    /// row id in `alert_rules`, 0 for rules not stored yet
+    #[serde(default)]
+    pub rule_id: i64,
+    pub name: String,
+    #[serde(default = "default_symbol")]
+    pub symbol: String,
+    pub condition: Condition,
+    /// minutes without another notification of this rule and symbol after it fired
+    #[serde(default = "default_cooldown")]
+    pub cooldown_minutes: u32,
+    /// `info`, `warning` or `critical`
+    #[serde(default = "default_level")]
+    pub level: String,
+    /// names of the notifiers, the default ones if empty
+    #[serde(default)]
+    pub notify: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_symbol() -> String {
+    "*".to_string()
+}
+
+fn default_cooldown() -> u32 {
+    60
+}
+
+fn default_level() -> String {
+    "warning".to_string()
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// A rule that fired for a symbol
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlertEvent {
+    pub rule_id: i64,
+    pub rule: String,
+    pub symbol: String,
+    /// time of the last bar, seconds since 1.1.1970
+    pub timestamp: i64,
+    pub level: String,
+    pub message: String,
+    pub notify: Vec<String>,
+}
+
+impl AlertRule {
+    /// whether the rule watches the symbol
+    pub fn applies_to(&self, symbol: &str) -> bool {
+        self.enabled && (self.symbol == "*" || self.symbol.eq_ignore_ascii_case(symbol))
+    }
+
+    /// problems of the rule, empty if it can be stored
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors: Vec<String> = self.condition.validate().into_iter().map(|e| format!("rule {}: {}", self.name, e)).collect();
+        if self.name.trim().is_empty() {
+            errors.push("rules need a name".to_string());
+        }
+        if !["info", "warning", "critical"].contains(&self.level.as_str()) {
+            errors.push(format!("rule {}: level must be info, warning or critical", self.name));
+        }
+        errors
+    }
+
+    /// evaluate the rule on the bars of a symbol, ordered by time
+    pub fn evaluate(&self, symbol: &str, bars: &[TimeSeriesData]) -> Option<AlertEvent> {
+        if !self.applies_to(symbol) {
+            return None;
+        }
+        let message = self.condition.evaluate(bars)?;
+        Some(AlertEvent {
+            rule_id: self.rule_id,
+            rule: self.name.clone(),
+            symbol: symbol.to_string(),
+            timestamp: bars.last().map(|b| b.datetime).unwrap_or_default(),
+            level: self.level.clone(),
+            message: format!("{}: {}", symbol, message),
+            notify: self.notify.clone(),
+        })
+    }
+}
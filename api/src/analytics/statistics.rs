@@ -1,6 +1,7 @@
 use std::cmp::Ordering;
 use polars::prelude::*;
 use rand::Rng;
+use serde::Serialize;
 use std::error::Error;
 use std::ops::Mul;
 use smartcore::linalg::basic::arrays::{Array, Array2};
@@ -10,7 +11,7 @@ use statrs::statistics::Statistics;
 use statrs::distribution::{ContinuousCDF, Normal};
 use crate::prelude::IntervalDays;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct PerformanceStats {
     pub daily_return: f64,
     pub daily_volatility: f64,
@@ -39,6 +40,8 @@ impl PerformanceStats {
     /// * `benchmark_returns` - Polars Series of benchmark returns
     /// * `risk_free_rate` - Risk-free rate of return in decimal (e.g 0.02 for 2%)
     /// * `confidence_level` - Confidence level for the VaR and CVaR calculations in decimal (e.g. 0.95 for 95%)
+    /// * `interval` - distances of the bars
+    /// * `exchange` - MIC of the exchange whose sessions annualize the returns
     ///
     /// # Returns
     ///
@@ -49,6 +52,7 @@ impl PerformanceStats {
         risk_free_rate: f64,
         confidence_level: f64,
         interval: IntervalDays,
+        exchange: &str,
     ) -> Result<PerformanceStats, Box<dyn Error>> {
         let _len;
         let days = interval.mode;
@@ -81,7 +85,7 @@ impl PerformanceStats {
         if returns.len() != benchmark_returns.len() {
             log::error!("Uneven series with {} and {} elements!", returns.len(), benchmark_returns.len())
         }
-        let annual_days = 365.0/interval.average;
+        let annual_days = crate::calendar::annual_periods(exchange, &interval);
         let risk_free_rate = risk_free_rate * 100.0;
         let cumulative_return = cumulative_return(&returns);
         let daily_return = returns.mean().ok_or("Error calculating mean return")?/days;
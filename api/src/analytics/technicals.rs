@@ -5,8 +5,11 @@ use chrono::{DateTime, NaiveDateTime};
 use polars::prelude::*;
 use crate::models::ticker::Ticker;
 use crate::data::ticker::TickerData;
+use super::indicators;
 
 /// Enum of OHLCV DataFrame Columns
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Column {
     Open,
     High,
@@ -76,6 +79,40 @@ pub trait TechnicalIndicators {
     fn roc_df(&self, ohlcv: DataFrame, period: usize, col: Option<Column>) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
     fn obv(&self) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
     fn obv_df(&self, ohlcv: DataFrame) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn ichimoku(&self, conversion_period: usize, base_period: usize, span_b_period: usize) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn ichimoku_df(&self, ohlcv: DataFrame, conversion_period: usize, base_period: usize, span_b_period: usize) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn adx(&self, period: usize) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn adx_df(&self, ohlcv: DataFrame, period: usize) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn psar(&self, step: f64, max_step: f64) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn psar_df(&self, ohlcv: DataFrame, step: f64, max_step: f64) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn vwap(&self) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn vwap_df(&self, ohlcv: DataFrame) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn kc(&self, period: usize, atr_period: usize, multiplier: f64) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn kc_df(&self, ohlcv: DataFrame, period: usize, atr_period: usize, multiplier: f64) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn dc(&self, period: usize) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn dc_df(&self, ohlcv: DataFrame, period: usize) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn supertrend(&self, period: usize, multiplier: f64) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn supertrend_df(&self, ohlcv: DataFrame, period: usize, multiplier: f64) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn cci(&self, period: usize) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn cci_df(&self, ohlcv: DataFrame, period: usize) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn wr(&self, period: usize) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn wr_df(&self, ohlcv: DataFrame, period: usize) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn cmf(&self, period: usize) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+    fn cmf_df(&self, ohlcv: DataFrame, period: usize) -> impl std::future::Future<Output = Result<DataFrame, Box<dyn Error>>>;
+}
+
+/// the values of a column of the OHLCV data, missing values are NaN
+fn column_values(ohlcv: &DataFrame, name: &str) -> Result<Vec<f64>, Box<dyn Error>> {
+    Ok(ohlcv.column(name)?.f64()?.into_iter().map(|x| x.unwrap_or(f64::NAN)).collect())
+}
+
+/// the OHLCV data with the columns of an indicator
+fn with_indicator(ohlcv: &DataFrame, columns: Vec<(String, Vec<f64>)>) -> Result<DataFrame, Box<dyn Error>> {
+    let mut df = ohlcv.select(["timestamp", "open", "high", "low", "close", "volume"])?;
+    for (name, values) in columns {
+        df.with_column(Series::new(name.as_str().into(), values))?;
+    }
+    Ok(df)
 }
 
 
@@ -559,7 +596,7 @@ impl TechnicalIndicators for Ticker {
         };
         let col_val = ohlcv.column(col_str)?.f64()?.to_vec().iter().map(|x| x.unwrap()).collect::<Vec<f64>>();
         let mut ss = SlowStochastic::new(stochastic_period, ema_period).unwrap();
-        let col = format!("ss-({stochastic_period},{ema_period}`)");
+        let col = format!("ss-({stochastic_period},{ema_period})");
         let ss_series = Series::new(col.as_str().into(), col_val.iter().map(|x| ss.next(*x)).collect::<Vec<f64>>());
         let mut df = df!(
             "timestamp" => ohlcv.column("timestamp")?.as_series().unwrap(),
@@ -576,7 +613,7 @@ impl TechnicalIndicators for Ticker {
         };
         let col_val = ohlcv.column(col_str)?.f64()?.to_vec().iter().map(|x| x.unwrap()).collect::<Vec<f64>>();
         let mut ss = SlowStochastic::new(stochastic_period, ema_period).unwrap();
-        let col = format!("ss-({stochastic_period},{ema_period}`)");
+        let col = format!("ss-({stochastic_period},{ema_period})");
         let ss_series = Series::new(col.as_str().into(), col_val.iter().map(|x| ss.next(*x)).collect::<Vec<f64>>());
         let mut df = df!(
             "timestamp" => ohlcv.column("timestamp")?.as_series().unwrap(),
@@ -1036,4 +1073,238 @@ impl TechnicalIndicators for Ticker {
         )?;
         Ok(df)
     }
+
+    /// Generates a Dataframe of the OHLCV data with the Ichimoku Cloud
+    ///
+    /// # Arguments
+    ///
+    /// * `conversion_period` - Period of the Conversion Line (e.g., 9)
+    /// * `base_period` - Period of the Base Line and the displacement of the spans (e.g., 26)
+    /// * `span_b_period` - Period of the Leading Span B (e.g., 52)
+    ///
+    /// # Returns
+    ///
+    /// * `DataFrame` of the OHLCV data with the Conversion and Base Line, the Leading Spans A and B and the Lagging Span
+    async fn ichimoku(&self, conversion_period: usize, base_period: usize, span_b_period: usize) -> Result<DataFrame, Box<dyn Error>> {
+        let ohlcv = self.get_chart_daily().await?;
+        self.ichimoku_df(ohlcv, conversion_period, base_period, span_b_period).await
+    }
+
+    async fn ichimoku_df(&self, ohlcv: DataFrame, conversion_period: usize, base_period: usize, span_b_period: usize) -> Result<DataFrame, Box<dyn Error>> {
+        let (high, low, close) = (column_values(&ohlcv, "high")?, column_values(&ohlcv, "low")?, column_values(&ohlcv, "close")?);
+        let cloud = indicators::ichimoku(&high, &low, &close, conversion_period, base_period, span_b_period);
+        let p = format!("({conversion_period},{base_period},{span_b_period})");
+        with_indicator(&ohlcv, vec![
+            (format!("ichimoku_conversion-{p}"), cloud.conversion),
+            (format!("ichimoku_base-{p}"), cloud.base),
+            (format!("ichimoku_span_a-{p}"), cloud.span_a),
+            (format!("ichimoku_span_b-{p}"), cloud.span_b),
+            (format!("ichimoku_lagging-{p}"), cloud.lagging),
+        ])
+    }
+
+    /// Generates a Dataframe of the OHLCV data with the Average Directional Index and the Directional Movement Indicators
+    ///
+    /// # Arguments
+    ///
+    /// * `period` - Period for the Average Directional Index (e.g., 14)
+    ///
+    /// # Returns
+    ///
+    /// * `DataFrame` of the OHLCV data with the ADX, +DI and -DI
+    async fn adx(&self, period: usize) -> Result<DataFrame, Box<dyn Error>> {
+        let ohlcv = self.get_chart_daily().await?;
+        self.adx_df(ohlcv, period).await
+    }
+
+    async fn adx_df(&self, ohlcv: DataFrame, period: usize) -> Result<DataFrame, Box<dyn Error>> {
+        let (high, low, close) = (column_values(&ohlcv, "high")?, column_values(&ohlcv, "low")?, column_values(&ohlcv, "close")?);
+        let adx = indicators::adx(&high, &low, &close, period);
+        with_indicator(&ohlcv, vec![
+            (format!("adx-{period}"), adx.adx),
+            (format!("plus_di-{period}"), adx.plus_di),
+            (format!("minus_di-{period}"), adx.minus_di),
+        ])
+    }
+
+    /// Generates a Dataframe of the OHLCV data with the Parabolic SAR
+    ///
+    /// # Arguments
+    ///
+    /// * `step` - Step of the acceleration factor (e.g., 0.02)
+    /// * `max_step` - Maximum of the acceleration factor (e.g., 0.2)
+    ///
+    /// # Returns
+    ///
+    /// * `DataFrame` of the OHLCV data with the Parabolic SAR
+    async fn psar(&self, step: f64, max_step: f64) -> Result<DataFrame, Box<dyn Error>> {
+        let ohlcv = self.get_chart_daily().await?;
+        self.psar_df(ohlcv, step, max_step).await
+    }
+
+    async fn psar_df(&self, ohlcv: DataFrame, step: f64, max_step: f64) -> Result<DataFrame, Box<dyn Error>> {
+        let (high, low) = (column_values(&ohlcv, "high")?, column_values(&ohlcv, "low")?);
+        with_indicator(&ohlcv, vec![(format!("psar-({step},{max_step})"), indicators::psar(&high, &low, step, max_step))])
+    }
+
+    /// Generates a Dataframe of the OHLCV data with the Volume Weighted Average Price of the intraday bars,
+    /// anchored at the start of every session of the ticker's exchange
+    ///
+    /// # Returns
+    ///
+    /// * `DataFrame` of the OHLCV data with the Volume Weighted Average Price
+    async fn vwap(&self) -> Result<DataFrame, Box<dyn Error>> {
+        let ohlcv = self.get_chart().await?;
+        self.vwap_df(ohlcv).await
+    }
+
+    async fn vwap_df(&self, ohlcv: DataFrame) -> Result<DataFrame, Box<dyn Error>> {
+        // the timestamps are milliseconds or datetimes, the sessions local dates at the exchange
+        let timestamps = super::expressions::Bars::from_dataframe(&ohlcv)?.timestamp;
+        let sessions = crate::calendar::calendar(&self.exchange()).session_dates(&timestamps);
+        let (high, low, close, volume) = (column_values(&ohlcv, "high")?, column_values(&ohlcv, "low")?, column_values(&ohlcv, "close")?, column_values(&ohlcv, "volume")?);
+        with_indicator(&ohlcv, vec![("vwap".to_string(), indicators::vwap(&high, &low, &close, &volume, &sessions))])
+    }
+
+    /// Generates a Dataframe of the OHLCV data with the Keltner Channel
+    ///
+    /// # Arguments
+    ///
+    /// * `period` - Period of the Exponential Moving Average in the middle (e.g., 20)
+    /// * `atr_period` - Period of the Average True Range (e.g., 10)
+    /// * `multiplier` - Multiple of the Average True Range between middle and bands (e.g., 2.0)
+    ///
+    /// # Returns
+    ///
+    /// * `DataFrame` of the OHLCV data with the middle, upper and lower line of the Keltner Channel
+    async fn kc(&self, period: usize, atr_period: usize, multiplier: f64) -> Result<DataFrame, Box<dyn Error>> {
+        let ohlcv = self.get_chart_daily().await?;
+        self.kc_df(ohlcv, period, atr_period, multiplier).await
+    }
+
+    async fn kc_df(&self, ohlcv: DataFrame, period: usize, atr_period: usize, multiplier: f64) -> Result<DataFrame, Box<dyn Error>> {
+        let (high, low, close) = (column_values(&ohlcv, "high")?, column_values(&ohlcv, "low")?, column_values(&ohlcv, "close")?);
+        let channel = indicators::keltner(&high, &low, &close, period, atr_period, multiplier);
+        let p = format!("({period},{atr_period},{multiplier})");
+        with_indicator(&ohlcv, vec![
+            (format!("kc-{p}"), channel.middle),
+            (format!("kc_upper-{p}"), channel.upper),
+            (format!("kc_lower-{p}"), channel.lower),
+        ])
+    }
+
+    /// Generates a Dataframe of the OHLCV data with the Donchian Channel
+    ///
+    /// # Arguments
+    ///
+    /// * `period` - Period for the Donchian Channel (e.g., 20)
+    ///
+    /// # Returns
+    ///
+    /// * `DataFrame` of the OHLCV data with the middle, upper and lower line of the Donchian Channel
+    async fn dc(&self, period: usize) -> Result<DataFrame, Box<dyn Error>> {
+        let ohlcv = self.get_chart_daily().await?;
+        self.dc_df(ohlcv, period).await
+    }
+
+    async fn dc_df(&self, ohlcv: DataFrame, period: usize) -> Result<DataFrame, Box<dyn Error>> {
+        let (high, low) = (column_values(&ohlcv, "high")?, column_values(&ohlcv, "low")?);
+        let channel = indicators::donchian(&high, &low, period);
+        with_indicator(&ohlcv, vec![
+            (format!("dc-{period}"), channel.middle),
+            (format!("dc_upper-{period}"), channel.upper),
+            (format!("dc_lower-{period}"), channel.lower),
+        ])
+    }
+
+    /// Generates a Dataframe of the OHLCV data with the Supertrend
+    ///
+    /// # Arguments
+    ///
+    /// * `period` - Period of the Average True Range (e.g., 10)
+    /// * `multiplier` - Multiple of the Average True Range for the bands (e.g., 3.0)
+    ///
+    /// # Returns
+    ///
+    /// * `DataFrame` of the OHLCV data with the Supertrend and its direction, 1 for up and -1 for down
+    async fn supertrend(&self, period: usize, multiplier: f64) -> Result<DataFrame, Box<dyn Error>> {
+        let ohlcv = self.get_chart_daily().await?;
+        self.supertrend_df(ohlcv, period, multiplier).await
+    }
+
+    async fn supertrend_df(&self, ohlcv: DataFrame, period: usize, multiplier: f64) -> Result<DataFrame, Box<dyn Error>> {
+        let (high, low, close) = (column_values(&ohlcv, "high")?, column_values(&ohlcv, "low")?, column_values(&ohlcv, "close")?);
+        let trend = indicators::supertrend(&high, &low, &close, period, multiplier);
+        let p = format!("({period},{multiplier})");
+        with_indicator(&ohlcv, vec![
+            (format!("supertrend-{p}"), trend.supertrend),
+            (format!("supertrend_direction-{p}"), trend.direction),
+        ])
+    }
+
+    /// Generates a Dataframe of the OHLCV data with the Commodity Channel Index
+    ///
+    /// # Arguments
+    ///
+    /// * `period` - Period for the Commodity Channel Index (e.g., 20)
+    ///
+    /// # Returns
+    ///
+    /// * `DataFrame` of the OHLCV data with the Commodity Channel Index
+    async fn cci(&self, period: usize) -> Result<DataFrame, Box<dyn Error>> {
+        let ohlcv = self.get_chart_daily().await?;
+        self.cci_df(ohlcv, period).await
+    }
+
+    async fn cci_df(&self, ohlcv: DataFrame, period: usize) -> Result<DataFrame, Box<dyn Error>> {
+        let (high, low, close) = (column_values(&ohlcv, "high")?, column_values(&ohlcv, "low")?, column_values(&ohlcv, "close")?);
+        with_indicator(&ohlcv, vec![(format!("cci-{period}"), indicators::cci(&high, &low, &close, period))])
+    }
+
+    /// Generates a Dataframe of the OHLCV data with the Williams %R
+    ///
+    /// # Arguments
+    ///
+    /// * `period` - Period for the Williams %R (e.g., 14)
+    ///
+    /// # Returns
+    ///
+    /// * `DataFrame` of the OHLCV data with the Williams %R
+    async fn wr(&self, period: usize) -> Result<DataFrame, Box<dyn Error>> {
+        let ohlcv = self.get_chart_daily().await?;
+        self.wr_df(ohlcv, period).await
+    }
+
+    async fn wr_df(&self, ohlcv: DataFrame, period: usize) -> Result<DataFrame, Box<dyn Error>> {
+        let (high, low, close) = (column_values(&ohlcv, "high")?, column_values(&ohlcv, "low")?, column_values(&ohlcv, "close")?);
+        with_indicator(&ohlcv, vec![(format!("wr-{period}"), indicators::williams_r(&high, &low, &close, period))])
+    }
+
+    /// Generates a Dataframe of the OHLCV data with the Chaikin Money Flow
+    ///
+    /// # Arguments
+    ///
+    /// * `period` - Period for the Chaikin Money Flow (e.g., 20)
+    ///
+    /// # Returns
+    ///
+    /// * `DataFrame` of the OHLCV data with the Chaikin Money Flow
+    async fn cmf(&self, period: usize) -> Result<DataFrame, Box<dyn Error>> {
+        let ohlcv = self.get_chart_daily().await?;
+        self.cmf_df(ohlcv, period).await
+    }
+
+    async fn cmf_df(&self, ohlcv: DataFrame, period: usize) -> Result<DataFrame, Box<dyn Error>> {
+        let (high, low, close, volume) = (column_values(&ohlcv, "high")?, column_values(&ohlcv, "low")?, column_values(&ohlcv, "close")?, column_values(&ohlcv, "volume")?);
+        with_indicator(&ohlcv, vec![(format!("cmf-{period}"), indicators::cmf(&high, &low, &close, &volume, period))])
+    }
+}
+
+/// Last values of the indicators shown on the live charts over bars ordered by time,
+/// keyed like the columns of the `TechnicalIndicators` DataFrames. An indicator is left out
+/// until there are enough bars for its period, see `incremental::IndicatorSet::live_chart`
+pub fn live_indicators(bars: &[crate::data::sql::TimeSeriesData]) -> std::collections::BTreeMap<String, f64> {
+    let mut indicators = super::incremental::IndicatorSet::live_chart();
+    indicators.update(bars);
+    indicators.values(true)
 }
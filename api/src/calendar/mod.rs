@@ -0,0 +1,368 @@
+//! Trading calendars of exchanges
+//!
+//! Every exchange is identified by its MIC code and has one regular session per trading day
+//! in its local timezone, a list of holidays and days with an early close. The calendars are
+//! read from `calendars.json`, the file named by the environment variable `STOCK_CALENDAR`
+//! replaces the built-in data. Exchanges without calendar trade on weekdays from 09:00 to
+//! 17:30 in the timezone listed in `resources/exchanges.json`.
+//! Holidays are only known for the years of the data file, other years only skip weekends.
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use crate::utils::date_utils::IntervalDays;
+
+const CALENDAR_JSON: &str = include_str!("calendars.json");
+const EXCHANGES_JSON: &str = include_str!("../../../resources/exchanges.json");
+
+/// exchange of the symbols without known exchange
+pub const DEFAULT_EXCHANGE: &str = "XFRA";
+
+lazy_static! {
+    static ref CALENDARS: RwLock<Arc<Calendars>> = RwLock::new(Arc::new(Calendars::from_env()));
+}
+
+#[derive(Deserialize)]
+struct CalendarFile {
+    exchanges: Vec<ExchangeEntry>,
+}
+
+#[derive(Deserialize)]
+struct ExchangeEntry {
+    mic: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    title: String,
+    timezone: String,
+    open: String,
+    close: String,
+    #[serde(default)]
+    holidays: Vec<String>,
+    #[serde(default)]
+    early_closes: Vec<EarlyClose>,
+}
+
+#[derive(Deserialize)]
+struct EarlyClose {
+    date: String,
+    close: String,
+}
+
+#[derive(Deserialize)]
+struct ExchangesFile {
+    data: Vec<ExchangeTimezone>,
+}
+
+#[derive(Deserialize)]
+struct ExchangeTimezone {
+    code: String,
+    timezone: String,
+}
+
+fn parse_date(s: &str) -> Result<NaiveDate, Box<dyn Error>> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|e| format!("Invalid date {}: {}", s, e).into())
+}
+
+fn parse_time(s: &str) -> Result<NaiveTime, Box<dyn Error>> {
+    NaiveTime::parse_from_str(s, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M"))
+        .map_err(|e| format!("Invalid time {}: {}", s, e).into())
+}
+
+fn parse_timezone(s: &str) -> Result<Tz, Box<dyn Error>> {
+    s.parse::<Tz>().map_err(|e| format!("Invalid timezone {}: {}", s, e).into())
+}
+
+/// Trading days and hours of one exchange
+#[derive(Clone, Debug)]
+pub struct ExchangeCalendar {
+    pub mic: String,
+    pub title: String,
+    pub timezone: Tz,
+    /// start of the regular session in local time
+    pub open: NaiveTime,
+    /// end of the regular session in local time
+    pub close: NaiveTime,
+    pub holidays: BTreeSet<NaiveDate>,
+    /// end of the session on days closing early
+    pub early_closes: HashMap<NaiveDate, NaiveTime>,
+}
+
+impl ExchangeCalendar {
+    /// an exchange trading every weekday without holidays
+    pub fn new(mic: &str, timezone: Tz, open: NaiveTime, close: NaiveTime) -> ExchangeCalendar {
+        ExchangeCalendar {
+            mic: mic.to_string(),
+            title: String::new(),
+            timezone,
+            open,
+            close,
+            holidays: BTreeSet::new(),
+            early_closes: HashMap::new(),
+        }
+    }
+
+    fn from_entry(entry: &ExchangeEntry) -> Result<ExchangeCalendar, Box<dyn Error>> {
+        let mut calendar = ExchangeCalendar::new(
+            &entry.mic,
+            parse_timezone(&entry.timezone)?,
+            parse_time(&entry.open)?,
+            parse_time(&entry.close)?,
+        );
+        calendar.title = entry.title.clone();
+        for h in entry.holidays.iter() {
+            calendar.holidays.insert(parse_date(h)?);
+        }
+        for e in entry.early_closes.iter() {
+            calendar.early_closes.insert(parse_date(&e.date)?, parse_time(&e.close)?);
+        }
+        Ok(calendar)
+    }
+
+    /// whether the exchange trades on this day
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !self.holidays.contains(&date)
+    }
+
+    /// opening and closing time of the session of a day, None if the exchange is closed
+    pub fn session(&self, date: NaiveDate) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        if !self.is_trading_day(date) {
+            return None;
+        }
+        let close = self.early_closes.get(&date).unwrap_or(&self.close);
+        let open = self.timezone.from_local_datetime(&date.and_time(self.open)).earliest()?;
+        let close = self.timezone.from_local_datetime(&date.and_time(*close)).latest()?;
+        Some((open.with_timezone(&Utc), close.with_timezone(&Utc)))
+    }
+
+    /// local date of an instant at the exchange
+    pub fn local_date(&self, instant: DateTime<Utc>) -> NaiveDate {
+        instant.with_timezone(&self.timezone).date_naive()
+    }
+
+    /// the session of every bar stamped in unix seconds, the local date at the exchange. Anchored
+    /// indicators like the VWAP start again with every session
+    pub fn session_dates(&self, timestamps: &[i64]) -> Vec<NaiveDate> {
+        timestamps.iter()
+            .map(|t| self.local_date(DateTime::from_timestamp(*t, 0).unwrap_or_default()))
+            .collect()
+    }
+
+    /// whether the regular session is running at this instant
+    pub fn is_open(&self, instant: DateTime<Utc>) -> bool {
+        match self.session(self.local_date(instant)) {
+            Some((open, close)) => open <= instant && instant < close,
+            None => false,
+        }
+    }
+
+    /// start of the next session opening after this instant
+    pub fn next_open(&self, instant: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut date = self.local_date(instant);
+        // no exchange closes for more than a year
+        for _ in 0..370 {
+            if let Some((open, _close)) = self.session(date) {
+                if open > instant {
+                    return Some(open);
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+
+    /// end of the running session or of the next one
+    pub fn next_close(&self, instant: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut date = self.local_date(instant);
+        for _ in 0..370 {
+            if let Some((_open, close)) = self.session(date) {
+                if close > instant {
+                    return Some(close);
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+
+    /// number of trading days from `start` up to the day before `end`
+    pub fn trading_days_between(&self, start: NaiveDate, end: NaiveDate) -> usize {
+        start.iter_days().take_while(|d| *d < end).filter(|d| self.is_trading_day(*d)).count()
+    }
+
+    /// number of trading days of a year
+    pub fn trading_days_per_year(&self, year: i32) -> usize {
+        match (NaiveDate::from_ymd_opt(year, 1, 1), NaiveDate::from_ymd_opt(year + 1, 1, 1)) {
+            (Some(start), Some(end)) => self.trading_days_between(start, end),
+            _ => 252,
+        }
+    }
+
+    /// length of the regular session in minutes
+    pub fn session_minutes(&self) -> f64 {
+        (self.close - self.open).num_minutes() as f64
+    }
+
+    /// number of bars per year of a series with these bar distances, used to annualize returns.
+    /// Intraday bars only count the trading hours, daily bars the trading days,
+    /// longer bars the calendar days
+    pub fn annual_periods(&self, interval: &IntervalDays) -> f64 {
+        let trading_days = self.trading_days_per_year(Utc::now().year()) as f64;
+        if interval.mode <= 0.0 {
+            if interval.average > 0.0 {
+                return 365.0 / interval.average;
+            }
+            return trading_days;
+        }
+        if interval.mode < 1.0 {
+            let bars_per_session = (self.session_minutes() / (interval.mode * 1440.0)).max(1.0);
+            trading_days * bars_per_session
+        } else if interval.mode < 1.5 {
+            trading_days
+        } else {
+            365.25 / interval.mode
+        }
+    }
+}
+
+/// Calendars of all known exchanges
+#[derive(Clone, Debug)]
+pub struct Calendars {
+    calendars: HashMap<String, Arc<ExchangeCalendar>>,
+    timezones: HashMap<String, Tz>,
+}
+
+impl Default for Calendars {
+    fn default() -> Calendars {
+        match Calendars::from_json(CALENDAR_JSON) {
+            Ok(c) => c,
+            Err(error) => {
+                log::error!("Failed to read the built-in exchange calendars! {}", error);
+                Calendars {
+                    calendars: HashMap::new(),
+                    timezones: HashMap::new(),
+                }
+            }
+        }
+    }
+}
+
+impl Calendars {
+    /// calendars in the format of `calendars.json`
+    pub fn from_json(json: &str) -> Result<Calendars, Box<dyn Error>> {
+        let file: CalendarFile = serde_json::from_str(json)?;
+        let mut calendars = HashMap::new();
+        for entry in file.exchanges.iter() {
+            let calendar = Arc::new(ExchangeCalendar::from_entry(entry)
+                .map_err(|e| format!("Calendar of {}: {}", entry.mic, e))?);
+            for mic in std::iter::once(&entry.mic).chain(entry.aliases.iter()) {
+                calendars.insert(mic.to_uppercase(), calendar.clone());
+            }
+        }
+        let mut timezones = HashMap::new();
+        match serde_json::from_str::<ExchangesFile>(EXCHANGES_JSON) {
+            Ok(exchanges) => {
+                for e in exchanges.data.iter() {
+                    if let Ok(tz) = e.timezone.parse::<Tz>() {
+                        timezones.insert(e.code.to_uppercase(), tz);
+                    }
+                }
+            }
+            Err(error) => log::error!("Failed to read the exchange timezones! {}", error),
+        }
+        Ok(Calendars { calendars, timezones })
+    }
+
+    /// read calendars from a file in the format of `calendars.json`
+    pub fn from_file(path: &Path) -> Result<Calendars, Box<dyn Error>> {
+        let json = std::fs::read_to_string(path)?;
+        Calendars::from_json(&json).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    /// the file of `STOCK_CALENDAR` or the built-in calendars
+    pub fn from_env() -> Calendars {
+        match std::env::var("STOCK_CALENDAR") {
+            Ok(path) if !path.is_empty() => match Calendars::from_file(Path::new(&path)) {
+                Ok(c) => c,
+                Err(error) => {
+                    log::error!("Failed to read the exchange calendars! {}", error);
+                    Calendars::default()
+                }
+            },
+            _ => Calendars::default(),
+        }
+    }
+
+    /// MIC codes with a calendar
+    pub fn exchanges(&self) -> Vec<String> {
+        let mut v: Vec<String> = self.calendars.keys().cloned().collect();
+        v.sort();
+        v
+    }
+
+    /// the calendar of an exchange, an empty code means `DEFAULT_EXCHANGE`
+    pub fn get(&self, mic: &str) -> Arc<ExchangeCalendar> {
+        let mic = if mic.is_empty() { DEFAULT_EXCHANGE.to_string() } else { mic.to_uppercase() };
+        if let Some(c) = self.calendars.get(&mic) {
+            return c.clone();
+        }
+        let timezone = self.timezones.get(&mic).cloned().unwrap_or(Tz::UTC);
+        Arc::new(ExchangeCalendar::new(
+            &mic,
+            timezone,
+            NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(17, 30, 0).unwrap(),
+        ))
+    }
+}
+
+/// the calendars used by the functions of this module
+pub fn calendars() -> Arc<Calendars> {
+    match CALENDARS.read() {
+        Ok(c) => c.clone(),
+        Err(error) => {
+            log::error!("Failed to read the exchange calendars! {}", error);
+            Arc::new(Calendars::default())
+        }
+    }
+}
+
+/// replace the calendars used by the functions of this module
+pub fn set_calendars(calendars: Calendars) {
+    match CALENDARS.write() {
+        Ok(mut c) => *c = Arc::new(calendars),
+        Err(error) => log::error!("Failed to replace the exchange calendars! {}", error),
+    }
+}
+
+/// the calendar of an exchange
+pub fn calendar(mic: &str) -> Arc<ExchangeCalendar> {
+    calendars().get(mic)
+}
+
+/// whether the exchange is trading at this instant
+pub fn is_open(mic: &str, instant: DateTime<Utc>) -> bool {
+    calendar(mic).is_open(instant)
+}
+
+/// start of the next session of the exchange after this instant
+pub fn next_open(mic: &str, instant: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    calendar(mic).next_open(instant)
+}
+
+/// number of trading days of the exchange from `start` up to the day before `end`
+pub fn trading_days_between(mic: &str, start: NaiveDate, end: NaiveDate) -> usize {
+    calendar(mic).trading_days_between(start, end)
+}
+
+/// number of bars per year of a series of the exchange
+pub fn annual_periods(mic: &str, interval: &IntervalDays) -> f64 {
+    calendar(mic).annual_periods(interval)
+}
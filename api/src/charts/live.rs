@@ -0,0 +1,103 @@
+//! Live mode of the `candlestick_chart_live` HTML output
+//!
+//! The page subscribes to a server-sent events stream, for example the `/stream` endpoint of the
+//! daemon, and updates the chart in place. The stream sends `bar` events with new or updated
+//! minute bars, `indicators` events with the latest values keyed like the indicator columns
+//! and `alert` events, see `live_indicators` in `analytics::technicals`.
+
+/// traces of `candlestick_chart_live` updated from the `indicators` events
+const INDICATOR_TRACES: &[(&str, &str)] = &[("MA 50", "sma-50"), ("MA 200", "sma-200"), ("RSI 14", "rsi-14")];
+
+const SCRIPT: &str = r#"<script>
+(function () {
+    var chart = document.getElementById("plotly-html-element");
+    if (!chart || !window.EventSource) {
+        return;
+    }
+    var status = document.createElement("p");
+    status.style.cssText = "font-family:sans-serif;margin:0.5em";
+    chart.parentNode.insertBefore(status, chart);
+    // the chart labels the bars with their UTC time
+    function time(seconds) {
+        return new Date(seconds * 1000).toISOString().slice(0, 19).replace("T", " ");
+    }
+    function trace(name) {
+        return chart.data.find(function (t) { return t.name === name; });
+    }
+    // index of the bar at x, appended if it is newer than the last one, -1 for older bars
+    function slot(t, x) {
+        var i = t.x.lastIndexOf(x);
+        if (i >= 0) {
+            return i;
+        }
+        if (t.x.length && t.x[t.x.length - 1] > x) {
+            return -1;
+        }
+        t.x.push(x);
+        return t.x.length - 1;
+    }
+    var source = new EventSource(__STREAM__);
+    source.onopen = function () { status.textContent = "live"; };
+    source.onerror = function () { status.textContent = "live updates interrupted, reconnecting"; };
+    source.addEventListener("bar", function (event) {
+        var candles = trace("Prices"), volume = trace("Volume");
+        JSON.parse(event.data).bars.forEach(function (bar) {
+            var x = time(bar.datetime);
+            var i = slot(candles, x);
+            if (i < 0) {
+                return;
+            }
+            candles.open[i] = bar.open;
+            candles.high[i] = bar.high;
+            candles.low[i] = bar.low;
+            candles.close[i] = bar.close;
+            if (volume) {
+                var j = slot(volume, x);
+                if (j >= 0) {
+                    volume.y[j] = bar.volume;
+                }
+            }
+        });
+        Plotly.redraw(chart);
+    });
+    source.addEventListener("indicators", function (event) {
+        var update = JSON.parse(event.data);
+        var x = time(update.timestamp);
+        __INDICATORS__.forEach(function (indicator) {
+            var t = trace(indicator[0]), value = update.values[indicator[1]];
+            if (!t || value === undefined) {
+                return;
+            }
+            var i = slot(t, x);
+            if (i >= 0) {
+                t.y[i] = value;
+            }
+        });
+        Plotly.redraw(chart);
+    });
+    source.addEventListener("alert", function (event) {
+        var alert = JSON.parse(event.data).notification;
+        status.textContent = time(alert.timestamp) + " " + alert.title + ": " + alert.body;
+    });
+})();
+</script>
+"#;
+
+/// Turns the HTML of a `candlestick_chart_live` plot into a page that subscribes to the
+/// server-sent events at `stream_url` and adds the new bars, indicators and alerts in place
+///
+/// # Arguments
+///
+/// * `html` - page of the plot, `Plot::to_html`
+/// * `stream_url` - URL of the event stream, relative to the page or absolute
+pub fn live_chart_html(html: &str, stream_url: &str) -> String {
+    let indicators: Vec<[&str; 2]> = INDICATOR_TRACES.iter().map(|(name, column)| [*name, *column]).collect();
+    // JSON strings are valid JavaScript, `<` is escaped so the URL cannot end the script
+    let script = SCRIPT
+        .replace("__STREAM__", &serde_json::to_string(stream_url).unwrap_or_default().replace('<', "\\u003c"))
+        .replace("__INDICATORS__", &serde_json::to_string(&indicators).unwrap_or_default());
+    match html.rfind("</body>") {
+        Some(end) => format!("{}{}{}", &html[..end], script, &html[end..]),
+        None => format!("{html}{script}"),
+    }
+}
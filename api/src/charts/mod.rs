@@ -1,3 +1,4 @@
+pub mod live;
 pub mod portfolio;
 pub mod ticker;
 pub mod tickers;
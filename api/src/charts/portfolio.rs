@@ -57,7 +57,7 @@ impl PortfolioCharts for Portfolio {
     /// * `Plot` Plotly Chart struct
     fn optimization_chart(&self, height: Option<usize>, width: Option<usize>) -> Result<Plot, Box<dyn Error>> {
         let days = self.performance_stats.interval.mode;
-        let annual_days = 365.0/self.performance_stats.interval.average;
+        let annual_days = crate::calendar::annual_periods(&self.performance_stats.exchange, &self.performance_stats.interval);
 
         let ef_returns = self.performance_stats.efficient_frontier.clone().iter()
             .map(|x| (1.0 + (x[0]/days)/100.0).powf(annual_days) - 1.0).collect::<Vec<f64>>();
@@ -1,14 +1,21 @@
 use std::error::Error;
 use polars::prelude::*;
 use chrono::DateTime;
-use plotly::common::{AxisSide, Fill, Line, LineShape, Mode, Title};
-use plotly::{Bar, Candlestick, Histogram, Layout, Plot, Scatter, Surface};
-use plotly::layout::{Axis, GridPattern, LayoutGrid, LayoutScene, RangeSelector, RangeSlider, RowOrder, SelectorButton, SelectorStep, StepMode};
+use plotly::color::NamedColor;
+use plotly::common::{AxisSide, DashType, Fill, Line, LineShape, Mode, Title};
+use plotly::common::{Marker, MarkerSymbol};
+use std::collections::BTreeMap;
+use plotly::{Bar, Candlestick, Histogram, Layout, Plot, Scatter, Surface, Trace};
+use serde::{Deserialize, Serialize};
+use plotly::layout::{Axis, GridPattern, LayoutGrid, LayoutScene, RangeSelector, RangeSlider, RowOrder, SelectorButton, SelectorStep, Shape, ShapeLine, ShapeType, StepMode};
 
 use crate::models::ticker::Ticker;
 use crate::data::ticker::TickerData;
 use crate::prelude::{DataTable, DataTableDisplay, DataTableFormat, StatementFrequency, StatementType};
 use crate::prelude::TechnicalIndicators;
+use crate::analytics::expressions::Bars;
+use crate::analytics::levels::{self, LevelKind, LevelSettings};
+use crate::analytics::patterns::{self, Bias};
 use crate::analytics::performance::TickerPerformance;
 use crate::analytics::stochastics::VolatilitySurface;
 use crate::analytics::statistics::{cumulative_returns_list, maximum_drawdown};
@@ -32,10 +39,210 @@ pub struct OptionsTables {
     pub volatility_surface: DataTable,
 }
 
+/// Indicators drawn on the candlestick chart with their usual parameters. The price
+/// indicators go over the candles, the oscillators to the pane of the RSI with a scale of their own
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Overlay {
+    /// Bollinger Bands 20, 2
+    Bollinger,
+    /// Ichimoku Cloud 9, 26, 52
+    Ichimoku,
+    /// Parabolic SAR 0.02, 0.2
+    Psar,
+    /// VWAP of every trading day
+    Vwap,
+    /// Keltner Channel 20, 10, 2
+    Keltner,
+    /// Donchian Channel 20
+    Donchian,
+    /// Supertrend 10, 3
+    Supertrend,
+    /// ADX and DMI 14
+    Adx,
+    /// Commodity Channel Index 20
+    Cci,
+    /// Williams %R 14
+    WilliamsR,
+    /// Chaikin Money Flow 20
+    Cmf,
+    /// Candlestick patterns marked above or below their last bar
+    Patterns,
+    /// Support and resistance levels and the trendlines of the channel, drawn as shapes
+    Levels,
+}
+
+/// candlestick patterns shown less clearly are not marked on the charts
+const PATTERN_CONFIDENCE: f64 = 0.6;
+
+/// width of the scale of every oscillator to the right of the chart, as a fraction of the plot
+const OSCILLATOR_AXIS_WIDTH: f64 = 0.06;
+
+impl Overlay {
+    /// the oscillators drawn in the pane of the RSI, in the order of their scales
+    const OSCILLATORS: [Overlay; 4] = [Overlay::Adx, Overlay::Cci, Overlay::WilliamsR, Overlay::Cmf];
+
+    /// the y axis of an oscillator, every oscillator overlays the pane of the RSI with its own scale
+    fn oscillator_axis(self) -> &'static str {
+        match self {
+            Overlay::Adx => "y4",
+            Overlay::Cci => "y5",
+            Overlay::WilliamsR => "y6",
+            Overlay::Cmf => "y7",
+            _ => "y3",
+        }
+    }
+}
+
+/// the traces of an overlay over the bars of `ohlcv`, `x` are the labels of the bars
+async fn overlay_traces(ticker: &Ticker, ohlcv: &DataFrame, x: &[String], overlay: Overlay) -> Result<Vec<Box<dyn Trace>>, Box<dyn Error>> {
+    use crate::data::sql::to_dataframe::f64_column_to_vec as values;
+    let line = |y: Vec<f64>, name: &str| -> Box<dyn Trace> {
+        Scatter::new(x.to_vec(), y).name(name).mode(Mode::Lines)
+    };
+    let oscillator = |y: Vec<f64>, name: &str| -> Box<dyn Trace> {
+        Scatter::new(x.to_vec(), y).name(name).mode(Mode::Lines).x_axis("x").y_axis(overlay.oscillator_axis())
+    };
+    let mut traces: Vec<Box<dyn Trace>> = Vec::new();
+    match overlay {
+        Overlay::Bollinger => {
+            let df = ticker.bb_df(ohlcv.clone(), 20, 2.0, None).await?;
+            traces.push(line(values(&df, "bb_upper-(20,2)")?, "BB Upper"));
+            traces.push(line(values(&df, "bb_lower-(20,2)")?, "BB Lower"));
+        }
+        Overlay::Ichimoku => {
+            let df = ticker.ichimoku_df(ohlcv.clone(), 9, 26, 52).await?;
+            traces.push(line(values(&df, "ichimoku_conversion-(9,26,52)")?, "Conversion Line"));
+            traces.push(line(values(&df, "ichimoku_base-(9,26,52)")?, "Base Line"));
+            traces.push(line(values(&df, "ichimoku_span_b-(9,26,52)")?, "Leading Span B"));
+            // the cloud is the area between the spans
+            traces.push(Scatter::new(x.to_vec(), values(&df, "ichimoku_span_a-(9,26,52)")?)
+                .name("Leading Span A")
+                .mode(Mode::Lines)
+                .fill(Fill::ToNextY));
+            // the lagging span plots every close at the bar 26 periods before it, the column of the
+            // indicator holds it the other way round to stay free of future closes
+            let close = values(ohlcv, "close")?;
+            let shift = close.len().min(26);
+            traces.push(Scatter::new(x[..x.len() - shift].to_vec(), close[shift..].to_vec())
+                .name("Lagging Span")
+                .mode(Mode::Lines));
+        }
+        Overlay::Psar => {
+            let df = ticker.psar_df(ohlcv.clone(), 0.02, 0.2).await?;
+            traces.push(Scatter::new(x.to_vec(), values(&df, "psar-(0.02,0.2)")?)
+                .name("Parabolic SAR")
+                .mode(Mode::Markers)
+                .marker(Marker::new().size(3)));
+        }
+        Overlay::Vwap => {
+            let df = ticker.vwap_df(ohlcv.clone()).await?;
+            traces.push(line(values(&df, "vwap")?, "VWAP"));
+        }
+        Overlay::Keltner => {
+            let df = ticker.kc_df(ohlcv.clone(), 20, 10, 2.0).await?;
+            traces.push(line(values(&df, "kc_upper-(20,10,2)")?, "Keltner Upper"));
+            traces.push(line(values(&df, "kc-(20,10,2)")?, "Keltner"));
+            traces.push(line(values(&df, "kc_lower-(20,10,2)")?, "Keltner Lower"));
+        }
+        Overlay::Donchian => {
+            let df = ticker.dc_df(ohlcv.clone(), 20).await?;
+            traces.push(line(values(&df, "dc_upper-20")?, "Donchian Upper"));
+            traces.push(line(values(&df, "dc-20")?, "Donchian"));
+            traces.push(line(values(&df, "dc_lower-20")?, "Donchian Lower"));
+        }
+        Overlay::Supertrend => {
+            let df = ticker.supertrend_df(ohlcv.clone(), 10, 3.0).await?;
+            traces.push(line(values(&df, "supertrend-(10,3)")?, "Supertrend"));
+        }
+        Overlay::Adx => {
+            let df = ticker.adx_df(ohlcv.clone(), 14).await?;
+            traces.push(oscillator(values(&df, "adx-14")?, "ADX 14"));
+            traces.push(oscillator(values(&df, "plus_di-14")?, "+DI 14"));
+            traces.push(oscillator(values(&df, "minus_di-14")?, "-DI 14"));
+        }
+        Overlay::Cci => {
+            let df = ticker.cci_df(ohlcv.clone(), 20).await?;
+            traces.push(oscillator(values(&df, "cci-20")?, "CCI 20"));
+        }
+        Overlay::WilliamsR => {
+            let df = ticker.wr_df(ohlcv.clone(), 14).await?;
+            traces.push(oscillator(values(&df, "wr-14")?, "Williams %R 14"));
+        }
+        Overlay::Cmf => {
+            let df = ticker.cmf_df(ohlcv.clone(), 20).await?;
+            traces.push(oscillator(values(&df, "cmf-20")?, "CMF 20"));
+        }
+        // shapes of the layout, see `level_shapes`
+        Overlay::Levels => {}
+        Overlay::Patterns => {
+            let bars = Bars::from_dataframe(ohlcv)?;
+            let events = patterns::detect(&ticker.ticker, ohlcv, PATTERN_CONFIDENCE)?;
+            for (bias, name, symbol) in [
+                (Bias::Bullish, "Bullish Patterns", MarkerSymbol::TriangleUp),
+                (Bias::Bearish, "Bearish Patterns", MarkerSymbol::TriangleDown),
+                (Bias::Neutral, "Patterns", MarkerSymbol::Diamond),
+            ] {
+                // the patterns ending on the same bar share a marker
+                let mut marked: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+                for event in events.iter().filter(|e| e.pattern.bias() == bias) {
+                    if let Some(i) = bars.timestamp.iter().position(|t| *t == event.datetime) {
+                        marked.entry(i).or_default().push(format!("{} {:.0}%", event.pattern.label(), event.confidence * 100.0));
+                    }
+                }
+                if marked.is_empty() {
+                    continue;
+                }
+                let y = marked.keys().map(|i| if bias == Bias::Bullish { bars.low[*i] * 0.99 } else { bars.high[*i] * 1.01 });
+                traces.push(Scatter::new(marked.keys().map(|i| x[*i].clone()).collect::<Vec<String>>(), y.collect::<Vec<f64>>())
+                    .name(name)
+                    .mode(Mode::Markers)
+                    .marker(Marker::new().size(9).symbol(symbol))
+                    .hover_text_array(marked.values().map(|labels| labels.join("<br>")).collect::<Vec<String>>()));
+            }
+        }
+    }
+    Ok(traces)
+}
+
+/// the support and resistance levels from their first touch and the trendlines from their first
+/// swing to the last bar, stronger levels are drawn wider
+fn level_shapes(ohlcv: &DataFrame, x: &[String], settings: &LevelSettings) -> Result<Vec<Shape>, Box<dyn Error>> {
+    let mut shapes = Vec::new();
+    let Some(last) = x.len().checked_sub(1) else {
+        return Ok(shapes);
+    };
+    let bars = Bars::from_dataframe(ohlcv)?;
+    let found = levels::detect(ohlcv, settings)?;
+    let color = |kind: LevelKind| if kind == LevelKind::Support { NamedColor::Green } else { NamedColor::Red };
+    let line = |x0: usize, y0: f64, y1: f64, shape_line: ShapeLine| {
+        Shape::new()
+            .shape_type(ShapeType::Line)
+            .x_ref("x")
+            .y_ref("y")
+            .x0(x[x0].clone())
+            .y0(y0)
+            .x1(x[last].clone())
+            .y1(y1)
+            .line(shape_line)
+    };
+    for level in found.levels.iter() {
+        let first = bars.timestamp.iter().position(|t| *t == level.first).unwrap_or(0);
+        let style = ShapeLine::new().color(color(level.kind)).width(1.0 + 2.0 * level.strength).dash(DashType::Dash);
+        shapes.push(line(first, level.price, level.price, style));
+    }
+    for trend in [&found.support_line, &found.resistance_line].into_iter().flatten() {
+        let style = ShapeLine::new().color(color(trend.kind)).width(1.5);
+        shapes.push(line(trend.start, trend.value_at(trend.start), trend.value_at(last), style));
+    }
+    Ok(shapes)
+}
+
 pub trait TickerCharts {
     fn ohlcv_table(&self) -> impl std::future::Future<Output = Result<DataTable, Box<dyn Error>>>;
     fn ohlcv_table_live(&self) -> impl std::future::Future<Output = Result<DataTable, Box<dyn Error>>>;
     fn candlestick_chart(&self, height: Option<usize>, width: Option<usize>) -> impl std::future::Future<Output = Result<Plot, Box<dyn Error>>>;
+    fn candlestick_chart_overlays(&self, overlays: &[Overlay], levels: &LevelSettings, height: Option<usize>, width: Option<usize>) -> impl std::future::Future<Output = Result<Plot, Box<dyn Error>>>;
     fn candlestick_chart_live(&self, height: Option<usize>, width: Option<usize>) -> impl std::future::Future<Output = Result<Plot, Box<dyn Error>>>;
     fn performance_chart(&self, height: Option<usize>, width: Option<usize>) -> impl std::future::Future<Output = Result<Plot, Box<dyn Error>>>;
     fn summary_stats_table(&self) -> impl std::future::Future<Output = Result<DataTable, Box<dyn Error>>>;
@@ -125,7 +332,8 @@ impl TickerCharts for Ticker {
         Ok(table)
     }
 
-    /// Generates an OHLCV candlestick chart for the ticker with technical indicators
+    /// Generates an OHLCV candlestick chart for the ticker with technical indicators and the
+    /// support and resistance levels
     ///
     /// # Arguments
     ///
@@ -136,6 +344,22 @@ impl TickerCharts for Ticker {
     ///
     /// * `Plot` Plotly Chart struct
     async fn candlestick_chart(&self, height: Option<usize>, width: Option<usize>) -> Result<Plot, Box<dyn Error>> {
+        self.candlestick_chart_overlays(&[Overlay::Levels], &LevelSettings::default(), height, width).await
+    }
+
+    /// Generates a candlestick chart for the ticker with indicators drawn on it
+    ///
+    /// # Arguments
+    ///
+    /// * `overlays` - `&[Overlay]` - Indicators drawn over the candles or in the pane of the RSI
+    /// * `levels` - `&LevelSettings` - Detection of the levels drawn by `Overlay::Levels`
+    /// * `height` - `usize` - Height of the chart
+    /// * `width` - `usize` - Width of the chart
+    ///
+    /// # Returns
+    ///
+    /// * `Plot` Plotly Chart struct
+    async fn candlestick_chart_overlays(&self, overlays: &[Overlay], levels: &LevelSettings, height: Option<usize>, width: Option<usize>) -> Result<Plot, Box<dyn Error>> {
         let ohlcv = self.get_chart_daily().await?;
         let datetimes = match crate::data::sql::to_dataframe::i64_column_to_datetime_vec(&ohlcv) {
             Ok(df) => df,
@@ -186,7 +410,11 @@ impl TickerCharts for Ticker {
             .mode(Mode::Lines)
             .line(Line::new().shape(LineShape::Spline));
 
-        let layout = Layout::new()
+        // the scales of the oscillators are lined up to the right of the plot
+        let oscillators = Overlay::OSCILLATORS.into_iter().filter(|o| overlays.contains(o)).collect::<Vec<Overlay>>();
+        let right = 1.0 - OSCILLATOR_AXIS_WIDTH * oscillators.len() as f64;
+        let x_axis = if oscillators.is_empty() { Axis::new() } else { Axis::new().domain(&[0.0, right]) };
+        let mut layout = Layout::new()
             .title(&*format!("<span style=\"font-weight:bold; color:darkgreen;\">{} Candlestick Chart</span>", self.ticker))
             .grid(
                 LayoutGrid::new()
@@ -196,7 +424,7 @@ impl TickerCharts for Ticker {
                     .row_order(RowOrder::TopToBottom)
             )
             .x_axis(
-                Axis::new()
+                x_axis
                     .range_slider(RangeSlider::new().visible(true))
                     .range_selector(RangeSelector::new().buttons(vec![
                         SelectorButton::new()
@@ -246,6 +474,21 @@ impl TickerCharts for Ticker {
                 Axis::new()
                     .domain(&[0.0, 0.2])
             );
+        for (i, oscillator) in oscillators.iter().enumerate() {
+            let axis = Axis::new()
+                .show_grid(false)
+                .overlaying("y3")
+                .side(AxisSide::Right)
+                .anchor("free")
+                .position(right + OSCILLATOR_AXIS_WIDTH * i as f64);
+            layout = match oscillator {
+                Overlay::Adx => layout.y_axis4(axis.title("ADX").range(vec![0.0, 100.0])),
+                Overlay::Cci => layout.y_axis5(axis.title("CCI")),
+                Overlay::WilliamsR => layout.y_axis6(axis.title("%R").range(vec![-100.0, 0.0])),
+                Overlay::Cmf => layout.y_axis7(axis.title("CMF").range(vec![-1.0, 1.0])),
+                _ => layout,
+            };
+        }
 
         let mut plot = Plot::new();
         plot.add_trace(Box::new(candlestick_trace));
@@ -253,6 +496,16 @@ impl TickerCharts for Ticker {
         plot.add_trace(ma50_trace);
         plot.add_trace(ma200_trace);
         plot.add_trace(rsi_trace);
+        for overlay in overlays {
+            for trace in overlay_traces(self, &ohlcv, &x, *overlay).await? {
+                plot.add_trace(trace);
+            }
+        }
+        if overlays.contains(&Overlay::Levels) {
+            for shape in level_shapes(&ohlcv, &x, levels)? {
+                layout.add_shape(shape);
+            }
+        }
         
         let plot = set_layout(plot, layout, height, width);
 
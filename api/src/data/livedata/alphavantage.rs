@@ -1,160 +1,89 @@
-use anyhow::Result;
-use chrono::DateTime;
-use lazy_static::lazy_static;
-use market_data::{AlphaVantage, EnhancedMarketSeries, MarketClient, OutputSize, Interval};
-use std::env::var;
+use chrono::{NaiveDate, NaiveDateTime};
+use market_data::{AlphaVantage, MarketClient, OutputSize};
+use std::error::Error;
 
-use crate::data::sql;
+use super::provider::{daily_to_timeseries, exchange_timestamp, filter_range, get_json, json_number, daily_timestamp, MarketDataProvider, RateLimit};
+use crate::data::sql::TimeSeriesData;
 
-lazy_static! {
-    static ref TOKEN: String =
-        var("AlphaVantage_TOKEN").expect("AlphaVantage_TOKEN env variable is required");
+/// AlphaVantage backend, requires an API key
+pub struct AlphaVantageProvider {
+    token: String,
 }
 
-pub fn live_data(
-    symbol: &str,
-    _start_time: chrono::NaiveDateTime,
-    _end_time: chrono::NaiveDateTime,
-)  -> Result<Vec<EnhancedMarketSeries>, Box<dyn std::error::Error>> {
-    let mut site = AlphaVantage::new(TOKEN.to_string());
-    // retrieve per minute data
-    match site.intraday_series(symbol, OutputSize::Compact, Interval::Min1) {
-        Ok(()) => {},
-        Err(error) => return Err(Box::new(error))
+impl AlphaVantageProvider {
+    pub fn new(token: &str) -> AlphaVantageProvider {
+        AlphaVantageProvider { token: token.to_string() }
     }
-    // create the MarketClient
-    let mut client = MarketClient::new(site);
-
-    // creates the query URL & download the raw data
-    client = client.create_endpoint()?.get_data()?;
-    // transform into MarketSeries, that can be used for further processing
-    let resvec = client.transform_data();
-
-    //data.iter().for_each(|output| match output {
-    //    Ok(data) =>  {
-    //        log::debug!("{}\n\n", data);
-    //        let _ret = crate::sql::insert_timeseries(sql_connection.clone(), &metadata, data);
-    //    },
-    //    Err(err) => log::error!("{}", err),
-    //});
-    // the data can be enhanced with the calculation of a number of  market indicators
-    let enhanced_data: Vec<EnhancedMarketSeries> = resvec
-        .into_iter()
-        .filter_map(|series| series.ok())
-        .map(|series| {
-            series
-                .enhance_data()
-                .with_sma(10)
-                .with_ema(20)
-                .with_ema(6)
-                .with_rsi(14)
-                .calculate()
-        })
-        .collect();
-
-    Ok(enhanced_data)
 }
 
-/// Update the database at night for all active symbols with missing daily data
-pub fn update_nightly(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>, 
-    symbols: &Vec<String>
-) {
-    let site = AlphaVantage::new(TOKEN.to_string());
-    let exchange_code = "XFRA";
-     // create the MarketClient
-    let mut client = MarketClient::new(site);
-
-    // check if we have data for this symbol
-    let mut first_day = chrono::Utc::now();
-    let today = chrono::Utc::now();
-    for i in 0..symbols.len() {
-        let stock_symbol = symbols[i].clone();
-        let metadata = sql::metadata(sql_connection.clone(), exchange_code, &stock_symbol);
-        let daily_data = sql::timeseries(sql_connection.clone(), &metadata);
-        if daily_data.len() == 0 {
-            // start a new series\
-            client.site.daily_series(stock_symbol.clone(), OutputSize::Full);
-            // creates the query URL & download the raw data
-            client = match client.create_endpoint() {
-                Ok(client) => client,
-                Err(error) => {
-                    log::error!("Failed to create the endpoint: {}", error);
-                    break;
-                }
-            };
-            client = match client.get_data() {
-                Ok(client) => client,
-                Err(error) => {
-                    log::error!("Failed to create the endpoint: {}", error);
-                    break;
-                }
-            };
-
-            // transform into MarketSeries, that can be used for further processing
-            let data = client.transform_data();
+impl MarketDataProvider for AlphaVantageProvider {
+    fn name(&self) -> &str {
+        "alphavantage"
+    }
 
-            // store the data
-            for res in data {
-                match res {
-                    Ok(data) =>  {
-                        log::debug!("{}\n\n", data);
-                        let _ret = sql::insert_timeseries(sql_connection.clone(), &metadata, &data);
-                    },
-                    Err(err) => log::error!("{}", err),
-                }
-            }
+    /// AlphaVantage always returns the latest 100 minutes of the symbol,
+    /// the bar times are in the timezone given in the answer
+    fn intraday(
+        &self,
+        symbol: &str,
+        start_time: NaiveDateTime,
+        end_time: NaiveDateTime,
+    ) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+        let url = format!(
+            "https://www.alphavantage.co/query?function=TIME_SERIES_INTRADAY&symbol={}&interval=1min&outputsize=compact&apikey={}",
+            symbol,
+            self.token,
+        );
+        let result = get_json(&url)?;
+        if let Some(message) = result["Error Message"].as_str().or(result["Note"].as_str()) {
+            return Err(format!("AlphaVantage: {}", message).into());
+        }
+        let timezone = result["Meta Data"]["6. Time Zone"].as_str().unwrap_or_default();
+        let values = result["Time Series (1min)"].as_object().ok_or("AlphaVantage: no time series in the answer")?;
+        let mut series = Vec::new();
+        for (time, v) in values.iter() {
+            let datetime = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S")?;
+            series.push(TimeSeriesData {
+                datetime: exchange_timestamp(datetime, timezone)?,
+                open: json_number(&v["1. open"]),
+                high: json_number(&v["2. high"]),
+                low: json_number(&v["3. low"]),
+                close: json_number(&v["4. close"]),
+                volume: json_number(&v["5. volume"]),
+            });
+        }
+        series.sort_by_key(|t| t.datetime);
+        Ok(filter_range(series, start_time.and_utc().timestamp(), end_time.and_utc().timestamp()))
+    }
 
+    fn daily(
+        &self,
+        symbol: &str,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+    ) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+        let site = AlphaVantage::new(self.token.clone());
+        let mut client = MarketClient::new(site);
+        // the compact output contains the latest 100 data points
+        let output_size = if (end_date - start_date).num_days() > 100 {
+            OutputSize::Full
         } else {
-            let stock_first_date = match DateTime::from_timestamp_millis(daily_data[0].datetime * 1000) {
-                Some(d) => d,
-                None => {
-                    continue;
-                }
-            };
-            if stock_first_date < first_day {
-                first_day = stock_first_date;
-            }
-            let stock_last_date = match DateTime::from_timestamp_millis(daily_data[daily_data.len() - 1].datetime * 1000) {
-                Some(d) => d,
-                None => {
-                    continue;
-                }
-            };
-            let num_days = (today - stock_last_date).num_days();
-            if num_days == 0 {
-                continue;
-            }
-            client.site.daily_series(stock_symbol.clone(), OutputSize::Full);
-            // creates the query URL & download the raw data
-            client = match client.create_endpoint() {
-                Ok(client) => client,
-                Err(error) => {
-                    log::error!("Failed to create the endpoint: {}", error);
-                    break;
-                }
-            };
-            client = match client.get_data() {
-                Ok(client) => client,
-                Err(error) => {
-                    log::error!("Failed to create the endpoint: {}", error);
-                    break;
-                }
-            };
-
-            // transform into MarketSeries, that can be used for further processing
-            let data = client.transform_data();
+            OutputSize::Compact
+        };
+        client.site.daily_series(symbol.to_string(), output_size);
+        client = client.create_endpoint()?.get_data()?;
+        let mut series = Vec::new();
+        for res in client.transform_data() {
+            series.extend(daily_to_timeseries(&res?));
+        }
+        Ok(filter_range(series, daily_timestamp(start_date), daily_timestamp(end_date)))
+    }
 
-            // store the data
-            for res in data {
-                match res {
-                    Ok(data) =>  {
-                        log::debug!("{}\n\n", data);
-                        let _ret = sql::insert_timeseries(sql_connection.clone(), &metadata, &data);
-                    },
-                    Err(err) => log::error!("{}", err),
-                }
-            }
+    /// limits of the free plan
+    fn rate_limit(&self) -> RateLimit {
+        RateLimit {
+            requests_per_minute: Some(5),
+            requests_per_day: Some(25),
         }
     }
-}
\ No newline at end of file
+}
@@ -1,71 +1,207 @@
 //! Module to retrieve live stock data aka intra-day series
-//! Requires API keys as Environment variable of one of the supported providers.
+//! The data comes from the `MarketDataProvider`s registered with `set_providers`.
+//! Without explicit configuration every supported provider with an API key
+//! in the environment is used, followed by Yahoo.
 
 mod alphavantage;
 mod polygon_io;
+pub mod provider;
+pub mod replay;
 mod twelvedata;
+mod yahoo_chart;
 
-use super::sql::TimeSeriesData;
+pub use alphavantage::AlphaVantageProvider;
+pub use polygon_io::PolygonProvider;
+pub use provider::{MarketDataProvider, MarketDataProviders, ProviderKind, ProviderSettings, RateLimit};
+pub use replay::ReplayProvider;
+pub use twelvedata::TwelvedataProvider;
+pub use yahoo_chart::YahooProvider;
 
-use std::env::var;
+use super::sql::{self, TimeSeriesData};
+
+use std::sync::{Arc, RwLock};
+use lazy_static::lazy_static;
 use polars::prelude::*;
 use std::error::Error;
-use chrono::NaiveDateTime;
-use market_data::EnhancedMarketSeries;
+use chrono::{Datelike, NaiveDateTime};
+
+lazy_static! {
+    static ref PROVIDERS: RwLock<Arc<MarketDataProviders>> =
+        RwLock::new(Arc::new(MarketDataProviders::from_env()));
+}
+
+/// the providers used by the functions of this module
+pub fn providers() -> Arc<MarketDataProviders> {
+    match PROVIDERS.read() {
+        Ok(p) => p.clone(),
+        Err(error) => {
+            log::error!("Failed to read the market data providers! {}", error);
+            Arc::new(MarketDataProviders::new())
+        }
+    }
+}
+
+/// replace the providers used by the functions of this module
+pub fn set_providers(providers: MarketDataProviders) {
+    match PROVIDERS.write() {
+        Ok(mut p) => *p = Arc::new(providers),
+        Err(error) => log::error!("Failed to replace the market data providers! {}", error),
+    }
+}
+
+/// the current time, follows the replay clock when recorded data are replayed
+pub fn now() -> chrono::DateTime<chrono::Utc> {
+    providers().now()
+}
 
-// find out which provider is used and use the according function
+/// retrieve minutely data from the first provider that delivers them
 pub fn live_data(
     symbol: &str,
     start_time: NaiveDateTime,
     end_time: NaiveDateTime,
-)  -> Result<Vec<EnhancedMarketSeries>, Box<dyn std::error::Error>> {
-
-    match var("Twelvedata_TOKEN") {
-        Ok(_val) => twelvedata::live_data(symbol, start_time, end_time),
-        Err(_e) => match var("AlphaVantage_TOKEN") {
-            Ok(_val) => alphavantage::live_data(symbol, start_time, end_time),
-            Err(_e) => match var("Polygon_APIKey") {
-                Ok(_val) => polygon_io::live_data(symbol, start_time, end_time),
-                Err(e) => {
-                    print!("Please use one of the supported suppliers for live stock data!");
-                    Err(Box::new(e))
-                }
-            }
-        }
-    }
+) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+    providers().intraday(symbol, start_time, end_time)
+}
+
+/// retrieve minutely data and store them, returns the newly stored bars
+pub fn update_live_data(
+    sql_connection: sql::Database,
+    metadata: &sql::MetaData,
+    start_time: NaiveDateTime,
+    end_time: NaiveDateTime,
+) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+    providers().update_live_data(sql_connection, metadata, start_time, end_time)
 }
 
-// find out which provider is used and use the according function
+/// Update the database at night for all active symbols with missing daily data,
+/// returns the failures, the other symbols are updated anyway. `exchange` is the MIC the
+/// metadata of symbols without a stored exchange are looked up with
 pub fn update_nightly(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>, 
+    sql_connection: sql::Database,
+    exchange: &str,
     symbols: &Vec<String>
-) {
-
-    match var("Twelvedata_TOKEN") {
-        Ok(_val) => twelvedata::update_nightly(sql_connection.clone(), symbols),
-        Err(_e) => match var("AlphaVantage_TOKEN") {
-            Ok(_val) => alphavantage::update_nightly(sql_connection.clone(), symbols),
-            Err(_e) => match var("Polygon_APIKey") {
-                Ok(_val) => polygon_io::update_nightly(sql_connection.clone(), symbols),
-                Err(_e) => {
-                    print!("Please use one of the supported supplier for live stock data!");
+) -> Vec<crate::Error> {
+    providers().update_nightly(sql_connection, exchange, symbols)
+}
+
+impl MarketDataProviders {
+    /// retrieve minutely data and store them, returns the newly stored bars
+    pub fn update_live_data(
+        &self,
+        sql_connection: sql::Database,
+        metadata: &sql::MetaData,
+        start_time: NaiveDateTime,
+        end_time: NaiveDateTime,
+    ) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+        // only ask for the time the exchange was trading
+        let calendar = crate::calendar::calendar(&metadata.exchange_code);
+        let start = start_time.and_utc();
+        let start = if calendar.is_open(start) {
+            start
+        } else {
+            match calendar.next_open(start) {
+                Some(open) => open,
+                None => return Ok(Vec::new()),
+            }
+        };
+        if start >= end_time.and_utc() {
+            return Ok(Vec::new());
+        }
+        let series = self.intraday(&metadata.symbol, start.naive_utc(), end_time)?;
+        for gap in provider::find_session_gaps(&series, 60, &calendar) {
+            log::warn!(
+                "{} minutes missing in the data of {} between {} and {}",
+                gap.missing,
+                metadata.symbol,
+                sql::to_dataframe::to_datetime(gap.after * 1000),
+                sql::to_dataframe::to_datetime(gap.before * 1000),
+            );
+        }
+        Ok(sql::insert_live_bars(sql_connection, metadata, &series))
+    }
+
+    /// Update the database for all symbols with missing daily data, returns the failures
+    pub fn update_nightly(
+        &self,
+        sql_connection: sql::Database,
+        exchange: &str,
+        symbols: &Vec<String>
+    ) -> Vec<crate::Error> {
+        let mut failures = Vec::new();
+        // the replayed day when recorded data are replayed
+        let today = self.now();
+        let mut first_day = today;
+        let mut updates = Vec::new();
+        for stock_symbol in symbols.iter() {
+            let mut metadata = sql::metadata(sql_connection.clone(), exchange, stock_symbol);
+            if metadata.exchange.is_empty() {
+                if let Some(m) = self.metadata(stock_symbol) {
+                    metadata.currency = m.currency;
+                    metadata.exchange = m.exchange;
+                    metadata.exchange_timezone = m.exchange_timezone;
+                    metadata.r#type = m.r#type;
+                }
+            }
+            let daily_data = sql::timeseries(sql_connection.clone(), &metadata);
+            let start_date = if daily_data.len() == 0 {
+                // start a new series
+                if first_day.day() == today.day() {
+                    today - chrono::Days::new(2000)
+                } else {
+                    first_day
+                }
+            } else {
+                let stock_first_date = match chrono::DateTime::from_timestamp(daily_data[0].datetime, 0) {
+                    Some(d) => d,
+                    None => continue,
+                };
+                if stock_first_date < first_day {
+                    first_day = stock_first_date;
+                }
+                let stock_last_date = match chrono::DateTime::from_timestamp(daily_data[daily_data.len() - 1].datetime, 0) {
+                    Some(d) => d,
+                    None => continue,
+                };
+                if (today - stock_last_date).num_days() == 0 {
+                    continue;
+                }
+                stock_last_date
+            };
+            match self.daily(stock_symbol, start_date.date_naive(), today.date_naive()) {
+                Ok(series) => {
+                    log::debug!("Storing {} daily values of {}", series.len(), stock_symbol);
+                    updates.push((metadata, series));
+                },
+                Err(error) => {
+                    log::error!("Failed to update daily data of {}: {}", stock_symbol, error);
+                    failures.push(crate::Error::from_provider(stock_symbol, error));
                 }
             }
         }
+        // one transaction for all symbols
+        let batch: Vec<(&sql::MetaData, &[TimeSeriesData])> = updates.iter().map(|(m, s)| (m, s.as_slice())).collect();
+        if !batch.is_empty() && sql::insert_timeseries_batch(sql_connection, &batch) == 0 {
+            log::error!("Failed to store the daily data of {} symbols", batch.len());
+            failures.push(crate::Error::storage(format!("failed to store the daily data of {} symbols", batch.len())));
+        }
+        failures
     }
 }
 
-
+/// converts the minutely bars of the market-data crate, which only keeps the date of a bar.
+/// `datetimes` are the bar times of the provider's answer in the exchange timezone
 pub fn marketdata_to_timeseries(
     timeseries: &market_data::EnhancedMarketSeries,
-) -> Vec<TimeSeriesData> {
+    datetimes: &[NaiveDateTime],
+    exchange_timezone: &str,
+) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+    if datetimes.len() != timeseries.series.len() {
+        return Err(format!("{} bar times for {} bars", datetimes.len(), timeseries.series.len()).into());
+    }
     let mut series = Vec::new();
-    let num_values = timeseries.series.len();
-    let base_timestamp = chrono::Utc::now().timestamp();
-    for i in 0..num_values {
-        let timestamp = base_timestamp - (num_values - i) as i64 * 60;
+    for (i, datetime) in datetimes.iter().enumerate() {
         let v = TimeSeriesData {
-            datetime: timestamp,
+            datetime: provider::exchange_timestamp(*datetime, exchange_timezone)?,
             open: timeseries.series[i].open as f64,
             high: timeseries.series[i].high as f64,
             low: timeseries.series[i].low as f64,
@@ -74,32 +210,36 @@ pub fn marketdata_to_timeseries(
         };
         series.push(v);
     }
-    series
+    Ok(series)
 }
 
+/// append the minutely bars after the last row of `df` from the providers, adjusted with the
+/// corporate actions stored in `sql_connection`
 pub fn update_dataframe(
+    sql_connection: sql::Database,
     df: &DataFrame,
-    stock_symbol: &str, 
-) -> Result<DataFrame, Box<dyn Error>> {
-    let col_val = df.column("timestamp")?.i64()?.to_vec().iter().map(|x| x.unwrap()).collect::<Vec<i64>>();
-    let start_timestamp = col_val[col_val.len()-1];
-    let start_time = chrono::DateTime::from_timestamp_millis(start_timestamp).unwrap().naive_utc();
-    let end_time = chrono::Utc::now().naive_utc();
-    let enhanced_data: Vec<EnhancedMarketSeries> = match live_data(&stock_symbol, start_time, end_time) {
+    stock_symbol: &str,
+) -> crate::error::Result<DataFrame> {
+    let Some(start_timestamp) = df.column("timestamp")?.i64()?.last() else {
+        return Err(crate::Error::analytics(format!("no timestamps in the data of {}", stock_symbol)));
+    };
+    let start_time = match chrono::DateTime::from_timestamp_millis(start_timestamp) {
+        Some(t) => t.naive_utc(),
+        None => return Err(crate::Error::analytics(format!("invalid timestamp {} in the data of {}", start_timestamp, stock_symbol))),
+    };
+    let end_time = now().naive_utc();
+    let series: Vec<TimeSeriesData> = match live_data(&stock_symbol, start_time, end_time) {
         Ok(res) => res,
         Err(error) => {
             log::error!("Failed to update timeseries data for {}: {}", stock_symbol, error);
-            std::process::exit(1)
+            return Err(crate::Error::from_provider(stock_symbol, error));
         }
     };
-    let mut series = Vec::new();
-    for data in enhanced_data.iter() {
-        series.extend(marketdata_to_timeseries(data));
-    }
 
     let timestamp = series
         .iter()
-        .map(|o| super::sql::to_dataframe::to_datetime(o.datetime))
+        // bars are stored in seconds, the DataFrames use milliseconds
+        .map(|o| super::sql::to_dataframe::to_datetime(o.datetime * 1000))
         .collect::<Vec<NaiveDateTime>>();
 
     let open = series
@@ -126,11 +266,25 @@ pub fn update_dataframe(
         .iter()
         .map(|o| o.volume)
         .collect::<Vec<f64>>();
-    
-    let adjclose = series
-        .iter()
-        .map(|o| o.close)
-        .collect::<Vec<f64>>();
+
+    // adjust across the last bar of the existing data, a split or dividend in between
+    // scales the adjusted prices of all existing rows
+    let actions = sql::corporate_actions(sql_connection, stock_symbol);
+    let last_close = df.column("close")?.f64()?.get(df.height() - 1).unwrap_or(0.0);
+    let mut bars = vec![TimeSeriesData {
+        datetime: start_timestamp / 1000,
+        close: last_close,
+        ..Default::default()
+    }];
+    bars.extend(series.iter().cloned());
+    let adjusted = sql::adjusted_close(&bars, &actions);
+    let factor = if last_close > 0.0 { adjusted[0] / last_close } else { 1.0 };
+    let adjclose = adjusted[1..].to_vec();
+    let mut df = df.clone();
+    if factor != 1.0 {
+        let scaled = df.column("adjclose")?.f64()? * factor;
+        df.with_column(scaled.into_series().with_name("adjclose".into()))?;
+    }
 
     let df2 = df!(
         "timestamp" => &timestamp,
@@ -147,9 +301,9 @@ pub fn update_dataframe(
         Ok(res) => res,
         Err(error) => {
             log::error!("Failed to append new data to the existing dataframe for {}: {}", stock_symbol, error);
-            std::process::exit(1)
+            return Err(crate::Error::analytics(error));
         }
     };
 
     Ok(df3)
-}
\ No newline at end of file
+}
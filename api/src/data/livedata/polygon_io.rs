@@ -1,174 +1,83 @@
-use anyhow::Result;
-use chrono::{DateTime, Datelike};
-use lazy_static::lazy_static;
-use market_data::{EnhancedMarketSeries, Interval, MarketClient, Polygon};
-use std::env::var;
+use chrono::{NaiveDate, NaiveDateTime};
+use market_data::{MarketClient, Polygon};
+use std::error::Error;
 
-use super::super::sql;
+use super::provider::{daily_to_timeseries, filter_range, get_json, json_number, daily_timestamp, MarketDataProvider, RateLimit};
+use crate::data::sql::TimeSeriesData;
 
-lazy_static! {
-    static ref TOKEN: String =
-        var("Polygon_APIKey").expect("Polygon_APIKey env variable is required");
+/// Polygon.io backend, requires an API key
+pub struct PolygonProvider {
+    token: String,
 }
 
-pub fn live_data(
-    symbol: &str,
-    start_time: chrono::NaiveDateTime,
-    end_time: chrono::NaiveDateTime,
-)  -> Result<Vec<EnhancedMarketSeries>, Box<dyn std::error::Error>> {
-    let site: Polygon = Polygon::new(TOKEN.to_string());
-    // create the MarketClient
-    let mut client: MarketClient<Polygon> = MarketClient::new(site);
-
-    // check if we have data for this symbol
-    let stock_symbol = symbol.to_string();
-    // retrieve per minute data for the last 10 minutes
-    client.site.intraday_series(
-        stock_symbol, 
-        &start_time.to_string(), 
-        &end_time.to_string(), 
-        Interval::Min1, 
-        960
-    )?;
-    // creates the query URL & download the raw data
-    client = client.create_endpoint()?.get_data()?;
-    // transform into MarketSeries, that can be used for further processing
-    let resvec = client.transform_data();
-
-    //data.iter().for_each(|output| match output {
-    //    Ok(data) =>  {
-    //        log::debug!("{}\n\n", data);
-    //        let _ret = crate::sql::insert_timeseries(sql_connection.clone(), &metadata, data);
-    //    },
-    //    Err(err) => log::error!("{}", err),
-    //});
-    // the data can be enhanced with the calculation of a number of  market indicators
-    let enhanced_data: Vec<EnhancedMarketSeries> = resvec
-        .into_iter()
-        .filter_map(|series| series.ok())
-        .map(|series| {
-            series
-                .enhance_data()
-                .with_sma(10)
-                .with_ema(20)
-                .with_ema(6)
-                .with_rsi(14)
-                .calculate()
-        })
-        .collect();
-
-    // store the data
-    Ok(enhanced_data)
+impl PolygonProvider {
+    pub fn new(token: &str) -> PolygonProvider {
+        PolygonProvider { token: token.to_string() }
+    }
 }
 
+impl MarketDataProvider for PolygonProvider {
+    fn name(&self) -> &str {
+        "polygon"
+    }
 
-/// Update the database at night for all active symbols with missing daily data
-pub fn update_nightly(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>, 
-    symbols: &Vec<String>
-) {
-    let site = Polygon::new(TOKEN.to_string());
-    let exchange_code = "XFRA";
-     // create the MarketClient
-    let mut client = MarketClient::new(site);
-
-    // check if we have data for this symbol
-    let mut first_day = chrono::Utc::now();
-    let today = chrono::Utc::now();
-    for i in 0..symbols.len() {
-        let stock_symbol = symbols[i].clone();
-        let metadata = sql::metadata(sql_connection.clone(), exchange_code, &stock_symbol);
-        let daily_data = sql::timeseries(sql_connection.clone(), &metadata);
-        if daily_data.len() == 0 {
-            // start a new series\
-            let num_days;
-            if first_day.day() == today.day() {
-                num_days = 2000;
-            } else {
-                let diff = today - first_day;
-                num_days = diff.num_days();
-            }
-            client.site.daily_series(stock_symbol.clone(), &first_day.date_naive().to_string(), &today.date_naive().to_string(), num_days as i32);
-            // creates the query URL & download the raw data
-            client = match client.create_endpoint() {
-                Ok(client) => client,
-                Err(error) => {
-                    log::error!("Failed to create the endpoint: {}", error);
-                    break;
-                }
-            };
-            client = match client.get_data() {
-                Ok(client) => client,
-                Err(error) => {
-                    log::error!("Failed to create the endpoint: {}", error);
-                    break;
-                }
-            };
-
-            // transform into MarketSeries, that can be used for further processing
-            let data = client.transform_data();
-
-            // store the data
-            for res in data {
-                match res {
-                    Ok(data) =>  {
-                        log::debug!("{}\n\n", data);
-                        let _ret = sql::insert_timeseries(sql_connection.clone(), &metadata, &data);
-                    },
-                    Err(err) => log::error!("{}", err),
-                }
-            }
-
-        } else {
-            let stock_first_date = match DateTime::from_timestamp_millis(daily_data[0].datetime * 1000) {
-                Some(d) => d,
-                None => {
-                    continue;
-                }
-            };
-            if stock_first_date < first_day {
-                first_day = stock_first_date;
-            }
-            let stock_last_date = match DateTime::from_timestamp_millis(daily_data[daily_data.len() - 1].datetime * 1000) {
-                Some(d) => d,
-                None => {
-                    continue;
-                }
-            };
-            let num_days = (today - stock_last_date).num_days();
-            if num_days == 0 {
-                continue;
+    /// the aggregates carry their start time as UTC milliseconds
+    fn intraday(
+        &self,
+        symbol: &str,
+        start_time: NaiveDateTime,
+        end_time: NaiveDateTime,
+    ) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+        let url = format!(
+            "https://api.polygon.io/v2/aggs/ticker/{}/range/1/minute/{}/{}?adjusted=true&sort=asc&limit=50000&apiKey={}",
+            symbol,
+            start_time.and_utc().timestamp_millis(),
+            end_time.and_utc().timestamp_millis(),
+            self.token,
+        );
+        let result = get_json(&url)?;
+        if result["status"] == "ERROR" {
+            return Err(format!("Polygon: {}", result["error"]).into());
+        }
+        let mut series = Vec::new();
+        if let Some(values) = result["results"].as_array() {
+            for v in values.iter() {
+                series.push(TimeSeriesData {
+                    datetime: v["t"].as_i64().unwrap_or_default() / 1000,
+                    open: json_number(&v["o"]),
+                    high: json_number(&v["h"]),
+                    low: json_number(&v["l"]),
+                    close: json_number(&v["c"]),
+                    volume: json_number(&v["v"]),
+                });
             }
-            client.site.daily_series(stock_symbol.clone(), &stock_last_date.date_naive().to_string(), &today.date_naive().to_string(), num_days as i32);
-            // creates the query URL & download the raw data
-            client = match client.create_endpoint() {
-                Ok(client) => client,
-                Err(error) => {
-                    log::error!("Failed to create the endpoint: {}", error);
-                    break;
-                }
-            };
-            client = match client.get_data() {
-                Ok(client) => client,
-                Err(error) => {
-                    log::error!("Failed to create the endpoint: {}", error);
-                    break;
-                }
-            };
+        }
+        Ok(series)
+    }
 
-            // transform into MarketSeries, that can be used for further processing
-            let data = client.transform_data();
+    fn daily(
+        &self,
+        symbol: &str,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+    ) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+        let site = Polygon::new(self.token.clone());
+        let mut client = MarketClient::new(site);
+        let num_days = (end_date - start_date).num_days().max(1);
+        client.site.daily_series(symbol.to_string(), &start_date.to_string(), &end_date.to_string(), num_days as i32);
+        client = client.create_endpoint()?.get_data()?;
+        let mut series = Vec::new();
+        for res in client.transform_data() {
+            series.extend(daily_to_timeseries(&res?));
+        }
+        Ok(filter_range(series, daily_timestamp(start_date), daily_timestamp(end_date)))
+    }
 
-            // store the data
-            for res in data {
-                match res {
-                    Ok(data) =>  {
-                        log::debug!("{}\n\n", data);
-                        let _ret = sql::insert_timeseries(sql_connection.clone(), &metadata, &data);
-                    },
-                    Err(err) => log::error!("{}", err),
-                }
-            }
+    /// limits of the free plan
+    fn rate_limit(&self) -> RateLimit {
+        RateLimit {
+            requests_per_minute: Some(5),
+            requests_per_day: None,
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,420 @@
+//! Pluggable market data providers
+//!
+//! Every source of price data (the subscription based backends, Yahoo or an in-house feed)
+//! implements `MarketDataProvider`. `MarketDataProviders` keeps them ordered by priority
+//! and fails over to the next provider if one returns an error or is out of requests.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::data::sql::{MetaData, TimeSeriesData};
+
+/// Request limits of a provider, `None` means unlimited
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct RateLimit {
+    /// maximum number of requests per minute
+    pub requests_per_minute: Option<u32>,
+    /// maximum number of requests per day
+    pub requests_per_day: Option<u32>,
+}
+
+/// A source of intraday and daily price data
+pub trait MarketDataProvider: Send + Sync {
+    /// short name used in configuration and log messages
+    fn name(&self) -> &str;
+
+    /// minutely bars of the symbol between start_time and end_time (UTC)
+    fn intraday(
+        &self,
+        symbol: &str,
+        start_time: NaiveDateTime,
+        end_time: NaiveDateTime,
+    ) -> Result<Vec<TimeSeriesData>, Box<dyn Error>>;
+
+    /// daily bars of the symbol between start_date and end_date
+    fn daily(
+        &self,
+        symbol: &str,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+    ) -> Result<Vec<TimeSeriesData>, Box<dyn Error>>;
+
+    /// metadata (currency, exchange, timezone) of the symbol if the provider knows it
+    fn metadata(&self, _symbol: &str) -> Option<MetaData> {
+        None
+    }
+
+    /// request limits of the provider
+    fn rate_limit(&self) -> RateLimit {
+        RateLimit::default()
+    }
+
+    /// the current time of providers replaying recorded data, `None` for live providers
+    fn clock(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+}
+
+/// The built-in providers
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    Twelvedata,
+    AlphaVantage,
+    Polygon,
+    Yahoo,
+}
+
+impl ProviderKind {
+    /// name of the environment variable holding the API key
+    pub fn env_var(&self) -> Option<&'static str> {
+        match self {
+            ProviderKind::Twelvedata => Some("Twelvedata_TOKEN"),
+            ProviderKind::AlphaVantage => Some("AlphaVantage_TOKEN"),
+            ProviderKind::Polygon => Some("Polygon_APIKey"),
+            ProviderKind::Yahoo => None,
+        }
+    }
+
+    /// create the provider, reading the API key from the environment if none is given
+    pub fn build(&self, api_key: Option<String>) -> Result<Box<dyn MarketDataProvider>, Box<dyn Error>> {
+        let api_key = match api_key {
+            Some(key) => Some(key),
+            None => self.env_var().and_then(|v| std::env::var(v).ok()),
+        };
+        let provider: Box<dyn MarketDataProvider> = match self {
+            ProviderKind::Twelvedata => Box::new(super::twelvedata::TwelvedataProvider::new(
+                &api_key.ok_or("Twelvedata requires an API key")?,
+            )),
+            ProviderKind::AlphaVantage => Box::new(super::alphavantage::AlphaVantageProvider::new(
+                &api_key.ok_or("AlphaVantage requires an API key")?,
+            )),
+            ProviderKind::Polygon => Box::new(super::polygon_io::PolygonProvider::new(
+                &api_key.ok_or("Polygon requires an API key")?,
+            )),
+            ProviderKind::Yahoo => Box::new(super::yahoo_chart::YahooProvider::new()),
+        };
+        Ok(provider)
+    }
+}
+
+impl FromStr for ProviderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "twelvedata" => Ok(ProviderKind::Twelvedata),
+            "alphavantage" => Ok(ProviderKind::AlphaVantage),
+            "polygon" | "polygon_io" => Ok(ProviderKind::Polygon),
+            "yahoo" => Ok(ProviderKind::Yahoo),
+            _ => Err(format!("Unknown market data provider {}", s)),
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Configuration entry of one provider
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ProviderSettings {
+    /// which provider to use
+    pub kind: ProviderKind,
+    /// API key, read from the provider's environment variable if not set
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// providers with a lower number are asked first
+    #[serde(default)]
+    pub priority: u32,
+    /// disabled providers are skipped
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+struct RegisteredProvider {
+    priority: u32,
+    provider: Box<dyn MarketDataProvider>,
+    requests: Mutex<VecDeque<Instant>>,
+}
+
+impl RegisteredProvider {
+    /// check the rate limit and record the request if it may be sent
+    fn try_acquire(&self) -> bool {
+        let limit = self.provider.rate_limit();
+        let mut requests = match self.requests.lock() {
+            Ok(r) => r,
+            Err(error) => {
+                log::error!("Failed to lock request history of {}! {}", self.provider.name(), error);
+                return true;
+            }
+        };
+        let now = Instant::now();
+        let day = Duration::from_secs(24 * 3600);
+        while let Some(first) = requests.front() {
+            if now.duration_since(*first) > day {
+                requests.pop_front();
+            } else {
+                break;
+            }
+        }
+        if let Some(per_day) = limit.requests_per_day {
+            if requests.len() >= per_day as usize {
+                return false;
+            }
+        }
+        if let Some(per_minute) = limit.requests_per_minute {
+            let minute = Duration::from_secs(60);
+            let last_minute = requests.iter().filter(|t| now.duration_since(**t) <= minute).count();
+            if last_minute >= per_minute as usize {
+                return false;
+            }
+        }
+        requests.push_back(now);
+        true
+    }
+}
+
+/// Providers ordered by priority with failover
+pub struct MarketDataProviders {
+    providers: Vec<RegisteredProvider>,
+}
+
+impl Default for MarketDataProviders {
+    fn default() -> MarketDataProviders {
+        MarketDataProviders::new()
+    }
+}
+
+impl MarketDataProviders {
+    /// an empty list, add providers with `register`
+    pub fn new() -> MarketDataProviders {
+        MarketDataProviders { providers: Vec::new() }
+    }
+
+    /// all built-in providers with an API key in the environment in the order
+    /// Twelvedata, AlphaVantage, Polygon, followed by Yahoo which needs no key
+    pub fn from_env() -> MarketDataProviders {
+        let mut providers = MarketDataProviders::new();
+        let kinds = [ProviderKind::Twelvedata, ProviderKind::AlphaVantage, ProviderKind::Polygon, ProviderKind::Yahoo];
+        for (priority, kind) in kinds.iter().enumerate() {
+            if let Some(env_var) = kind.env_var() {
+                if std::env::var(env_var).is_err() {
+                    continue;
+                }
+            }
+            match kind.build(None) {
+                Ok(provider) => providers.register(provider, priority as u32),
+                Err(error) => log::error!("Failed to create market data provider {:?}: {}", kind, error),
+            }
+        }
+        providers
+    }
+
+    /// the providers described by the configuration
+    pub fn from_settings(settings: &[ProviderSettings]) -> Result<MarketDataProviders, Box<dyn Error>> {
+        let mut providers = MarketDataProviders::new();
+        for s in settings.iter().filter(|s| s.enabled) {
+            providers.register(s.kind.build(s.api_key.clone())?, s.priority);
+        }
+        if providers.is_empty() {
+            return Err("No market data provider configured!".into());
+        }
+        Ok(providers)
+    }
+
+    /// add a provider, providers with equal priority keep the order they were added in
+    pub fn register(&mut self, provider: Box<dyn MarketDataProvider>, priority: u32) {
+        let position = self.providers.iter().position(|p| p.priority > priority).unwrap_or(self.providers.len());
+        self.providers.insert(position, RegisteredProvider {
+            priority,
+            provider,
+            requests: Mutex::new(VecDeque::new()),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+
+    /// names of the providers in the order they are asked
+    pub fn names(&self) -> Vec<String> {
+        self.providers.iter().map(|p| p.provider.name().to_string()).collect()
+    }
+
+    fn first_success<T>(
+        &self,
+        what: &str,
+        symbol: &str,
+        request: impl Fn(&dyn MarketDataProvider) -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let mut last_error: Box<dyn Error> = "Please use one of the supported suppliers for live stock data!".into();
+        for p in self.providers.iter() {
+            if !p.try_acquire() {
+                crate::metrics::provider_rate_limited(p.provider.name());
+                log::warn!("Rate limit of {} reached, skipping it for {} of {}", p.provider.name(), what, symbol);
+                last_error = format!("Rate limit of {} reached", p.provider.name()).into();
+                continue;
+            }
+            let started = std::time::Instant::now();
+            let result = request(p.provider.as_ref());
+            crate::metrics::provider_request(p.provider.name(), what, started.elapsed().as_secs_f64(), result.is_ok());
+            match result {
+                Ok(res) => return Ok(res),
+                Err(error) => {
+                    log::warn!("Provider {} failed to deliver {} of {}: {}", p.provider.name(), what, symbol, error);
+                    last_error = error;
+                }
+            }
+        }
+        Err(crate::Error::provider(symbol, format!("no provider delivered {}: {}", what, last_error)).into())
+    }
+
+    /// minutely bars from the first provider that delivers them
+    pub fn intraday(
+        &self,
+        symbol: &str,
+        start_time: NaiveDateTime,
+        end_time: NaiveDateTime,
+    ) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+        self.first_success("intraday data", symbol, |p| p.intraday(symbol, start_time, end_time))
+    }
+
+    /// daily bars from the first provider that delivers them
+    pub fn daily(
+        &self,
+        symbol: &str,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+    ) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+        self.first_success("daily data", symbol, |p| p.daily(symbol, start_date, end_date))
+    }
+
+    /// metadata from the first provider that knows the symbol
+    pub fn metadata(&self, symbol: &str) -> Option<MetaData> {
+        self.providers.iter().find_map(|p| p.provider.metadata(symbol))
+    }
+
+    /// the time as seen by the providers, the replay clock if one replays recorded data
+    pub fn now(&self) -> DateTime<Utc> {
+        self.providers.iter().find_map(|p| p.provider.clock()).unwrap_or_else(Utc::now)
+    }
+}
+
+/// daily bars are stored with a timestamp of 22:00 UTC of their date
+pub fn daily_timestamp(date: NaiveDate) -> i64 {
+    let time = NaiveTime::from_num_seconds_from_midnight_opt(22 * 3600, 0).expect("If this does not work the chrono code is crap!");
+    date.and_time(time).and_utc().timestamp()
+}
+
+/// converts daily bars of the market-data crate
+pub fn daily_to_timeseries(series: &market_data::MarketSeries) -> Vec<TimeSeriesData> {
+    series.data.iter().map(|s| TimeSeriesData {
+        datetime: daily_timestamp(s.date),
+        open: s.open as f64,
+        high: s.high as f64,
+        low: s.low as f64,
+        close: s.close as f64,
+        volume: s.volume as f64,
+    }).collect()
+}
+
+/// converts a bar time in the timezone of the exchange (IANA name, e.g. `America/New_York`)
+/// into a UTC timestamp in seconds, an empty timezone means UTC
+pub fn exchange_timestamp(datetime: NaiveDateTime, exchange_timezone: &str) -> Result<i64, Box<dyn Error>> {
+    if exchange_timezone.is_empty() || exchange_timezone == "UTC" {
+        return Ok(datetime.and_utc().timestamp());
+    }
+    let timezone: chrono_tz::Tz = exchange_timezone.parse()
+        .map_err(|e| format!("Unknown exchange timezone {}: {}", exchange_timezone, e))?;
+    match timezone.from_local_datetime(&datetime).earliest() {
+        Some(dt) => Ok(dt.timestamp()),
+        None => Err(format!("{} does not exist in timezone {}", datetime, exchange_timezone).into()),
+    }
+}
+
+/// request a JSON document, blocks until the answer arrives. The blocking client must not run
+/// on the worker threads of an async runtime, async callers move the providers to
+/// `tokio::task::spawn_blocking` or `block_in_place`
+pub(crate) fn get_json(url: &str) -> Result<serde_json::Value, Box<dyn Error>> {
+    let response = reqwest::blocking::get(url)?;
+    if !response.status().is_success() {
+        // the URL is not part of the message, it contains the API key
+        return Err(format!("Request failed with status {}", response.status()).into());
+    }
+    Ok(response.json::<serde_json::Value>()?)
+}
+
+/// numbers in provider answers come as JSON numbers or strings
+pub(crate) fn json_number(value: &serde_json::Value) -> f64 {
+    match value {
+        serde_json::Value::Number(n) => n.as_f64().unwrap_or_default(),
+        serde_json::Value::String(s) => s.parse().unwrap_or_default(),
+        _ => 0.0,
+    }
+}
+
+/// A range of missing bars between two stored bars
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gap {
+    /// timestamp of the last bar before the gap
+    pub after: i64,
+    /// timestamp of the first bar after the gap
+    pub before: i64,
+    /// number of missing bars
+    pub missing: i64,
+}
+
+/// finds missing bars in a sorted series with bars every `interval` seconds.
+/// Only gaps within a day are reported, the time between two sessions is no gap
+pub fn find_gaps(series: &[TimeSeriesData], interval: i64) -> Vec<Gap> {
+    let mut gaps = Vec::new();
+    if interval <= 0 {
+        return gaps;
+    }
+    for w in series.windows(2) {
+        let (after, before) = (w[0].datetime, w[1].datetime);
+        if after.div_euclid(86400) != before.div_euclid(86400) {
+            continue;
+        }
+        let missing = (before - after) / interval - 1;
+        if missing > 0 {
+            gaps.push(Gap { after, before, missing });
+        }
+    }
+    gaps
+}
+
+/// finds missing bars like `find_gaps`, only bars during the sessions of the exchange count.
+/// Unlike `find_gaps` bars missing at the end or start of consecutive sessions are reported too
+pub fn find_session_gaps(
+    series: &[TimeSeriesData],
+    interval: i64,
+    calendar: &crate::calendar::ExchangeCalendar,
+) -> Vec<Gap> {
+    let mut gaps = Vec::new();
+    if interval <= 0 {
+        return gaps;
+    }
+    for w in series.windows(2) {
+        let (after, before) = (w[0].datetime, w[1].datetime);
+        let missing = (after + interval..before)
+            .step_by(interval as usize)
+            .filter(|t| DateTime::from_timestamp(*t, 0).map(|t| calendar.is_open(t)).unwrap_or(false))
+            .count() as i64;
+        if missing > 0 {
+            gaps.push(Gap { after, before, missing });
+        }
+    }
+    gaps
+}
+
+/// keep only the bars between start and end (timestamps in seconds)
+pub fn filter_range(series: Vec<TimeSeriesData>, start: i64, end: i64) -> Vec<TimeSeriesData> {
+    series.into_iter().filter(|t| start <= t.datetime && t.datetime <= end).collect()
+}
@@ -0,0 +1,194 @@
+//! Offline provider replaying recorded bars
+//!
+//! Bars are loaded from CSV or JSON files in the `KLINE` format, e.g. `examples/datasets`.
+//! Files with a typical bar distance below a day are treated as minutely data, the others
+//! as daily data. The replay clock starts at the first bar and advances `speed` times
+//! faster than the wall clock, only bars up to the clock are delivered.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::time::Instant;
+
+use super::provider::{daily_timestamp, MarketDataProvider};
+use crate::data::sql::{MetaData, TimeSeriesData};
+use crate::models::kline::KLINE;
+
+/// Provider delivering recorded bars instead of asking a web service
+pub struct ReplayProvider {
+    intraday: HashMap<String, Vec<TimeSeriesData>>,
+    daily: HashMap<String, Vec<TimeSeriesData>>,
+    speed: f64,
+    start: Option<i64>,
+    started: Instant,
+}
+
+impl Default for ReplayProvider {
+    fn default() -> ReplayProvider {
+        ReplayProvider::new(0.0)
+    }
+}
+
+fn kline_to_timeseries(kline: &KLINE) -> Vec<TimeSeriesData> {
+    let mut v = Vec::new();
+    for i in 0..kline.timestamp.len() {
+        let close = kline.close[i];
+        v.push(TimeSeriesData {
+            datetime: kline.timestamp[i],
+            open: kline.open.as_ref().map(|x| x[i]).unwrap_or(close),
+            high: kline.high.as_ref().map(|x| x[i]).unwrap_or(close),
+            low: kline.low.as_ref().map(|x| x[i]).unwrap_or(close),
+            close,
+            volume: kline.volume.as_ref().map(|x| x[i]).unwrap_or(0.0),
+        });
+    }
+    v.sort_by_key(|t| t.datetime);
+    v
+}
+
+impl ReplayProvider {
+    /// an empty replay, `speed` is the number of replayed seconds per second,
+    /// a speed of 0 delivers all bars at once
+    pub fn new(speed: f64) -> ReplayProvider {
+        ReplayProvider {
+            intraday: HashMap::new(),
+            daily: HashMap::new(),
+            speed,
+            start: None,
+            started: Instant::now(),
+        }
+    }
+
+    /// load every CSV and JSON file of the directory, the file name is the symbol
+    pub fn from_dir(dir: &Path, speed: f64) -> Result<ReplayProvider, Box<dyn Error>> {
+        let mut replay = ReplayProvider::new(speed);
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let symbol = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(s) => s.to_uppercase(),
+                None => continue,
+            };
+            let path_str = match path.to_str() {
+                Some(s) => s,
+                None => continue,
+            };
+            let kline = match path.extension().and_then(|s| s.to_str()) {
+                Some("csv") => KLINE::from_csv(&symbol, path_str),
+                Some("json") => KLINE::from_json(&symbol, path_str),
+                _ => continue,
+            };
+            match kline {
+                Ok(kline) => replay.add(kline),
+                Err(error) => log::error!("Failed to load replay data from {}: {}", path.display(), error),
+            }
+        }
+        Ok(replay)
+    }
+
+    /// add the bars of one symbol, a symbol loaded twice keeps the later bars
+    pub fn add(&mut self, kline: KLINE) {
+        if kline.timestamp.len() == 0 {
+            return;
+        }
+        let series = kline_to_timeseries(&kline);
+        if kline.interval_days().mode < 0.5 {
+            self.intraday.insert(kline.ticker.clone(), series);
+        } else {
+            self.daily.insert(kline.ticker.clone(), series);
+        }
+    }
+
+    /// start the replay at this time instead of the first bar
+    pub fn with_start(mut self, start: NaiveDateTime) -> ReplayProvider {
+        self.start = Some(start.and_utc().timestamp());
+        self
+    }
+
+    /// restart the replay clock
+    pub fn restart(&mut self) {
+        self.started = Instant::now();
+    }
+
+    /// symbols with recorded bars
+    pub fn symbols(&self) -> Vec<String> {
+        let mut v: Vec<String> = self.intraday.keys().chain(self.daily.keys()).cloned().collect();
+        v.sort();
+        v.dedup();
+        v
+    }
+
+    fn first_timestamp(&self) -> i64 {
+        // prefer minutely data, the daily data usually reach much further back
+        let intraday = self.intraday.values().filter_map(|s| s.first()).map(|t| t.datetime).min();
+        let daily = self.daily.values().filter_map(|s| s.first()).map(|t| t.datetime).min();
+        intraday.or(daily).unwrap_or(0)
+    }
+
+    /// the current time of the replay in seconds
+    fn replay_timestamp(&self) -> i64 {
+        if self.speed <= 0.0 {
+            return i64::MAX;
+        }
+        let start = self.start.unwrap_or_else(|| self.first_timestamp());
+        start + (self.started.elapsed().as_secs_f64() * self.speed) as i64
+    }
+
+    fn bars(series: Option<&Vec<TimeSeriesData>>, start: i64, end: i64) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+        match series {
+            Some(s) => Ok(s.iter().filter(|t| start <= t.datetime && t.datetime <= end).cloned().collect()),
+            None => Err("No recorded data for this symbol".into()),
+        }
+    }
+}
+
+impl MarketDataProvider for ReplayProvider {
+    fn name(&self) -> &str {
+        "replay"
+    }
+
+    fn intraday(
+        &self,
+        symbol: &str,
+        start_time: NaiveDateTime,
+        end_time: NaiveDateTime,
+    ) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+        let end = end_time.and_utc().timestamp().min(self.replay_timestamp());
+        ReplayProvider::bars(self.intraday.get(symbol), start_time.and_utc().timestamp(), end)
+    }
+
+    fn daily(
+        &self,
+        symbol: &str,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+    ) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+        let start = start_date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+        let end = end_date.and_hms_opt(23, 59, 59).unwrap().and_utc().timestamp().min(self.replay_timestamp());
+        let bars = ReplayProvider::bars(self.daily.get(symbol), start, end)?;
+        // stored like the daily data of the other providers
+        Ok(bars.into_iter().map(|t| TimeSeriesData {
+            datetime: daily_timestamp(DateTime::from_timestamp(t.datetime, 0).unwrap_or_default().date_naive()),
+            ..t
+        }).collect())
+    }
+
+    fn metadata(&self, symbol: &str) -> Option<MetaData> {
+        if self.intraday.contains_key(symbol) || self.daily.contains_key(symbol) {
+            Some(MetaData {
+                symbol: symbol.to_string(),
+                exchange: "replay".to_string(),
+                ..Default::default()
+            })
+        } else {
+            None
+        }
+    }
+
+    fn clock(&self) -> Option<DateTime<Utc>> {
+        if self.speed <= 0.0 {
+            return None;
+        }
+        DateTime::from_timestamp(self.replay_timestamp(), 0)
+    }
+}
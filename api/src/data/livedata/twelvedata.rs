@@ -1,176 +1,88 @@
-use anyhow::Result;
-use chrono::{DateTime, Datelike};
-use lazy_static::lazy_static;
-use market_data::{EnhancedMarketSeries, Interval, MarketClient, Twelvedata};
-use std::env::var;
+use chrono::{NaiveDate, NaiveDateTime};
+use market_data::{MarketClient, Twelvedata};
+use std::error::Error;
 
-use crate::data::sql;
+use super::provider::{daily_to_timeseries, filter_range, get_json, json_number, daily_timestamp, MarketDataProvider, RateLimit};
+use crate::data::sql::TimeSeriesData;
 
-lazy_static! {
-    static ref TOKEN: String =
-        var("Twelvedata_TOKEN").expect("Twelvedata_TOKEN env variable is required");
+/// Twelvedata backend, requires an API key
+pub struct TwelvedataProvider {
+    token: String,
 }
 
-/// retrieve a number of minutely data for a stock symbol
-pub fn live_data(
-    symbol: &str,
-    start_time: chrono::NaiveDateTime,
-    end_time: chrono::NaiveDateTime,
-)  -> Result<Vec<EnhancedMarketSeries>, Box<dyn std::error::Error>> {
-    let site: Twelvedata = Twelvedata::new(TOKEN.to_string());
-    // create the MarketClient
-    let mut client: MarketClient<Twelvedata> = MarketClient::new(site);
-    // calculate the number of seconds since the last timestamp
-    let mut num_data = ((end_time - start_time).as_seconds_f32() / 60.0).round().abs() as u32;
-    if num_data == 0 {
-        return Err(format!("No new data available for this stock!").into())
+impl TwelvedataProvider {
+    pub fn new(token: &str) -> TwelvedataProvider {
+        TwelvedataProvider { token: token.to_string() }
     }
-    if end_time.date() != start_time.date() {
-        // last data from yesterday
-        let start_time_today = end_time.date().and_hms_opt(7, 0, 0).unwrap();
-        num_data = ((end_time - start_time_today).as_seconds_f32() / 60.0).round().abs() as u32;
-    }
-    // check if we have data for this symbol
-    let stock_symbol = symbol.to_string();
-    // retrieve per minute data for the last 10 minutes
-    client.site.intraday_series(stock_symbol, num_data, Interval::Min1)?;
-    // creates the query URL & download the raw data
-    client = client.create_endpoint()?.get_data()?;
-    // transform into MarketSeries, that can be used for further processing
-    let resvec = client.transform_data();
-
-    //data.iter().for_each(|output| match output {
-    //    Ok(data) =>  {
-    //        log::debug!("{}\n\n", data);
-    //        let _ret = crate::sql::insert_timeseries(sql_connection.clone(), &metadata, data);
-    //    },
-    //    Err(err) => log::error!("{}", err),
-    //});
-    // the data can be enhanced with the calculation of a number of  market indicators
-    let enhanced_data: Vec<EnhancedMarketSeries> = resvec
-        .into_iter()
-        .filter_map(|series| series.ok())
-        .map(|series| {
-            series
-                .enhance_data()
-                .with_sma(10)
-                .with_ema(20)
-                .with_ema(6)
-                .with_rsi(14)
-                .calculate()
-        })
-        .collect();
-
-    Ok(enhanced_data)
 }
 
-/// Update the database at night for all active symbols with missing daily data
-pub fn update_nightly(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>, 
-    symbols: &Vec<String>
-) {
-    let site: Twelvedata = Twelvedata::new(TOKEN.to_string());
-    let exchange_code = "XFRA";
-     // create the MarketClient
-    let mut client: MarketClient<Twelvedata> = MarketClient::new(site);
-
-    // check if we have data for this symbol
-    let mut first_day = chrono::Utc::now();
-    let today = chrono::Utc::now();
-    for i in 0..symbols.len() {
-        let stock_symbol = symbols[i].clone();
-        let metadata = sql::metadata(sql_connection.clone(), exchange_code, &stock_symbol);
-        let daily_data = sql::timeseries(sql_connection.clone(), &metadata);
-        if daily_data.len() == 0 {
-            // start a new series\
-            let num_days;
-            if first_day.day() == today.day() {
-                num_days = 2000;
-            } else {
-                let diff = today - first_day;
-                num_days = diff.num_days();
-            }
-            client.site.daily_series(stock_symbol.clone(), num_days as u32);
-            // creates the query URL & download the raw data
-            client = match client.create_endpoint() {
-                Ok(client) => client,
-                Err(error) => {
-                    log::error!("Failed to create the endpoint: {}", error);
-                    break;
-                }
-            };
-            client = match client.get_data() {
-                Ok(client) => client,
-                Err(error) => {
-                    log::error!("Failed to create the endpoint: {}", error);
-                    break;
-                }
-            };
-
-            // transform into MarketSeries, that can be used for further processing
-            let data = client.transform_data();
-
-            // store the data
-            for res in data {
-                match res {
-                    Ok(data) =>  {
-                        log::debug!("{}\n\n", data);
-                        let _ret = sql::insert_timeseries(sql_connection.clone(), &metadata, &data);
-                    },
-                    Err(err) => log::error!("{}", err),
-                }
-            }
+impl MarketDataProvider for TwelvedataProvider {
+    fn name(&self) -> &str {
+        "twelvedata"
+    }
 
-        } else {
-            let stock_first_date = match DateTime::from_timestamp_millis(daily_data[0].datetime * 1000) {
-                Some(d) => d,
-                None => {
-                    continue;
-                }
-            };
-            if stock_first_date < first_day {
-                first_day = stock_first_date;
-            }
-            let stock_last_date = match DateTime::from_timestamp_millis(daily_data[daily_data.len() - 1].datetime * 1000) {
-                Some(d) => d,
-                None => {
-                    continue;
-                }
-            };
-            let num_days = (today - stock_last_date).num_days();
-            if num_days == 0 {
-                continue;
-            }
-            client.site.daily_series(stock_symbol.clone(), num_days as u32);
-            // creates the query URL & download the raw data
-            client = match client.create_endpoint() {
-                Ok(client) => client,
-                Err(error) => {
-                    log::error!("Failed to create the endpoint: {}", error);
-                    break;
-                }
-            };
-            client = match client.get_data() {
-                Ok(client) => client,
-                Err(error) => {
-                    log::error!("Failed to create the endpoint: {}", error);
-                    break;
-                }
-            };
+    /// retrieve the minutely data of a stock symbol, the times are requested in UTC
+    fn intraday(
+        &self,
+        symbol: &str,
+        start_time: NaiveDateTime,
+        end_time: NaiveDateTime,
+    ) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+        if end_time <= start_time {
+            return Err(format!("No new data available for this stock!").into())
+        }
+        let url = format!(
+            "https://api.twelvedata.com/time_series?symbol={}&interval=1min&start_date={}&end_date={}&timezone=UTC&order=ASC&outputsize=5000&apikey={}",
+            symbol,
+            start_time.format("%Y-%m-%d %H:%M:%S"),
+            end_time.format("%Y-%m-%d %H:%M:%S"),
+            self.token,
+        );
+        let result = get_json(&url)?;
+        if result["status"] == "error" {
+            return Err(format!("Twelvedata: {}", result["message"]).into());
+        }
+        let values = result["values"].as_array().ok_or("Twelvedata: no values in the answer")?;
+        let mut series = Vec::new();
+        for v in values.iter() {
+            let datetime = NaiveDateTime::parse_from_str(v["datetime"].as_str().unwrap_or_default(), "%Y-%m-%d %H:%M:%S")?;
+            series.push(TimeSeriesData {
+                // requested in UTC instead of the exchange timezone
+                datetime: datetime.and_utc().timestamp(),
+                open: json_number(&v["open"]),
+                high: json_number(&v["high"]),
+                low: json_number(&v["low"]),
+                close: json_number(&v["close"]),
+                volume: json_number(&v["volume"]),
+            });
+        }
+        series.sort_by_key(|t| t.datetime);
+        Ok(series)
+    }
 
-            // transform into MarketSeries, that can be used for further processing
-            let data = client.transform_data();
+    fn daily(
+        &self,
+        symbol: &str,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+    ) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+        let site: Twelvedata = Twelvedata::new(self.token.clone());
+        let mut client: MarketClient<Twelvedata> = MarketClient::new(site);
+        let num_days = (end_date - start_date).num_days().max(1);
+        client.site.daily_series(symbol.to_string(), num_days as u32);
+        client = client.create_endpoint()?.get_data()?;
+        let mut series = Vec::new();
+        for res in client.transform_data() {
+            series.extend(daily_to_timeseries(&res?));
+        }
+        Ok(filter_range(series, daily_timestamp(start_date), daily_timestamp(end_date)))
+    }
 
-            // store the data
-            for res in data {
-                match res {
-                    Ok(data) =>  {
-                        log::debug!("{}\n\n", data);
-                        let _ret = sql::insert_timeseries(sql_connection.clone(), &metadata, &data);
-                    },
-                    Err(err) => log::error!("{}", err),
-                }
-            }
+    /// limits of the free plan
+    fn rate_limit(&self) -> RateLimit {
+        RateLimit {
+            requests_per_minute: Some(8),
+            requests_per_day: Some(800),
         }
     }
-}
\ No newline at end of file
+}
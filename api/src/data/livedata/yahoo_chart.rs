@@ -0,0 +1,136 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use lazy_static::lazy_static;
+use polars::prelude::*;
+use std::error::Error;
+use tokio::runtime::{Runtime, RuntimeFlavor};
+
+use super::provider::{daily_timestamp, filter_range, MarketDataProvider};
+use crate::data::sql::{MetaData, TimeSeriesData};
+use crate::data::yahoo;
+use crate::data::yahoo::config::Interval;
+
+/// Yahoo Finance chart backend, needs no API key
+/// minutely data is only available for the last seven days
+pub struct YahooProvider {}
+
+impl Default for YahooProvider {
+    fn default() -> YahooProvider {
+        YahooProvider::new()
+    }
+}
+
+impl YahooProvider {
+    pub fn new() -> YahooProvider {
+        YahooProvider {}
+    }
+}
+
+lazy_static! {
+    /// runs the calls of the async Yahoo API for callers without a runtime
+    static ref RUNTIME: Option<Runtime> = match tokio::runtime::Builder::new_multi_thread().worker_threads(1).enable_all().build() {
+        Ok(runtime) => Some(runtime),
+        Err(error) => {
+            log::error!("Failed to start the runtime of the Yahoo provider! {}", error);
+            None
+        }
+    };
+}
+
+/// waits for a call of the async Yahoo API. Inside a multi-threaded runtime the calling thread
+/// blocks in place, so the runtime hands its other tasks to the remaining workers
+fn wait<F: std::future::Future>(future: F) -> Result<F::Output, Box<dyn Error>> {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            Ok(tokio::task::block_in_place(|| handle.block_on(future)))
+        }
+        Ok(_) => Err("the Yahoo provider blocks, call it from tokio::task::spawn_blocking".into()),
+        Err(_) => match RUNTIME.as_ref() {
+            Some(runtime) => Ok(runtime.block_on(future)),
+            None => Err("no runtime for the Yahoo API".into()),
+        },
+    }
+}
+
+fn chart(symbol: &str, start_date: NaiveDate, end_date: NaiveDate, interval: Interval) -> Result<DataFrame, Box<dyn Error>> {
+    wait(yahoo::api::get_chart(symbol, &start_date.to_string(), &end_date.to_string(), interval))?
+}
+
+/// converts the chart DataFrame into bars, `daily` moves the timestamps to 22:00 UTC
+fn dataframe_to_timeseries(df: &DataFrame, daily: bool) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+    let timestamp = df["timestamp"]
+        .datetime()?
+        .as_datetime_iter()
+        .map(|x| x.unwrap_or_default())
+        .collect::<Vec<NaiveDateTime>>();
+    let open = df.column("open")?.f64()?.to_vec().iter().map(|x| x.unwrap_or_default()).collect::<Vec<f64>>();
+    let high = df.column("high")?.f64()?.to_vec().iter().map(|x| x.unwrap_or_default()).collect::<Vec<f64>>();
+    let low = df.column("low")?.f64()?.to_vec().iter().map(|x| x.unwrap_or_default()).collect::<Vec<f64>>();
+    let close = df.column("close")?.f64()?.to_vec().iter().map(|x| x.unwrap_or_default()).collect::<Vec<f64>>();
+    let volume = df.column("volume")?.f64()?.to_vec().iter().map(|x| x.unwrap_or_default()).collect::<Vec<f64>>();
+    let mut series = Vec::new();
+    for i in 0..timestamp.len() {
+        let datetime = if daily {
+            daily_timestamp(timestamp[i].date())
+        } else {
+            timestamp[i].and_utc().timestamp()
+        };
+        series.push(TimeSeriesData {
+            datetime,
+            open: open[i],
+            high: high[i],
+            low: low[i],
+            close: close[i],
+            volume: volume[i],
+        });
+    }
+    Ok(series)
+}
+
+impl MarketDataProvider for YahooProvider {
+    fn name(&self) -> &str {
+        "yahoo"
+    }
+
+    fn intraday(
+        &self,
+        symbol: &str,
+        start_time: NaiveDateTime,
+        end_time: NaiveDateTime,
+    ) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+        // the chart API works on whole days
+        let end_date = end_time.date().succ_opt().unwrap_or(end_time.date());
+        let df = chart(symbol, start_time.date(), end_date, Interval::OneMinute)?;
+        let series = dataframe_to_timeseries(&df, false)?;
+        Ok(filter_range(series, start_time.and_utc().timestamp(), end_time.and_utc().timestamp()))
+    }
+
+    fn daily(
+        &self,
+        symbol: &str,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+    ) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+        let end = end_date.succ_opt().unwrap_or(end_date);
+        let df = chart(symbol, start_date, end, Interval::OneDay)?;
+        let series = dataframe_to_timeseries(&df, true)?;
+        Ok(filter_range(series, daily_timestamp(start_date), daily_timestamp(end_date)))
+    }
+
+    fn metadata(&self, symbol: &str) -> Option<MetaData> {
+        let quote = match wait(yahoo::api::get_quote(symbol)).and_then(|quote| quote) {
+            Ok(q) => q,
+            Err(error) => {
+                log::warn!("Failed to get quote of {} from Yahoo: {}", symbol, error);
+                return None;
+            }
+        };
+        Some(MetaData {
+            symbol: quote.symbol,
+            currency: quote.currency,
+            exchange: quote.exchange,
+            exchange_timezone: quote.exchange_timezone,
+            r#type: quote.asset_class,
+            ..Default::default()
+        })
+    }
+}
@@ -1,6 +1,7 @@
 pub mod yahoo;
 pub mod google;
 pub mod livedata;
+pub mod quality;
 pub mod sql;
 pub mod ticker;
 pub mod tickers;
\ No newline at end of file
@@ -0,0 +1,393 @@
+//! Validation and repair of stored bars
+//!
+//! The validator checks the bars of `time_series` and `live_data` for impossible prices,
+//! duplicates, stale repeated prices and single-bar spikes that revert on the next bar.
+//! Every exchange of a symbol is its own series, their prices and trading times differ.
+//! Bad bars are flagged in the `quality` column or moved to the `quarantine` table, flagged
+//! bars with an `EXCLUDED` issue are not read for analysis. Short gaps of the trading
+//! sessions can be filled with linearly interpolated bars.
+
+use chrono::{DateTime, Days};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::data::livedata::provider::daily_timestamp;
+use crate::data::sql::{self, quality::StoredBar, TimeSeriesData};
+
+/// high below low, or open or close outside of high and low
+pub const INVALID_RANGE: u32 = 1;
+/// price of zero or below, negative volume
+pub const NONPOSITIVE: u32 = 2;
+/// missing or infinite value
+pub const NOT_A_NUMBER: u32 = 4;
+/// another bar of the same exchange with the same timestamp follows, only bars stored
+/// without exchange escape the unique index
+pub const DUPLICATE: u32 = 8;
+/// single bar far off its neighbours
+pub const SPIKE: u32 = 16;
+/// price did not change for many bars
+pub const STALE: u32 = 32;
+/// bar was interpolated by the validator
+pub const INTERPOLATED: u32 = 64;
+/// issues keeping a bar from being used
+pub const EXCLUDED: u32 = INVALID_RANGE | NONPOSITIVE | NOT_A_NUMBER | DUPLICATE | SPIKE;
+
+const ISSUES: [(u32, &str); 7] = [
+    (INVALID_RANGE, "invalid range"),
+    (NONPOSITIVE, "nonpositive"),
+    (NOT_A_NUMBER, "not a number"),
+    (DUPLICATE, "duplicate"),
+    (SPIKE, "spike"),
+    (STALE, "stale"),
+    (INTERPOLATED, "interpolated"),
+];
+
+/// names of the issues of a set of flags
+pub fn issue_names(flags: u32) -> Vec<&'static str> {
+    ISSUES.iter().filter(|(bit, _)| flags & bit != 0).map(|(_, name)| *name).collect()
+}
+
+/// Table of stored bars
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BarTable {
+    /// daily bars
+    TimeSeries,
+    /// minutely bars
+    LiveData,
+}
+
+impl BarTable {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BarTable::TimeSeries => "time_series",
+            BarTable::LiveData => "live_data",
+        }
+    }
+}
+
+/// What happens to bad bars
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Repair {
+    /// only report them
+    Report,
+    /// mark them in the `quality` column
+    Flag,
+    /// move bars with excluded issues to the `quarantine` table
+    Quarantine,
+}
+
+impl FromStr for Repair {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "report" => Ok(Repair::Report),
+            "flag" => Ok(Repair::Flag),
+            "quarantine" => Ok(Repair::Quarantine),
+            _ => Err(format!("Unknown repair mode {}, expected report, flag or quarantine", s)),
+        }
+    }
+}
+
+/// Thresholds and actions of the validator
+#[derive(Clone, Debug, PartialEq)]
+pub struct QualitySettings {
+    pub repair: Repair,
+    /// fill short gaps of the sessions with interpolated bars
+    pub interpolate: bool,
+    /// longest gap in bars that is interpolated
+    pub max_interpolated: usize,
+    /// change in percent of a daily bar reverting on the next bar to count as spike
+    pub spike_percent_daily: f64,
+    /// change in percent of a minutely bar reverting on the next bar to count as spike
+    pub spike_percent_intraday: f64,
+    /// number of identical daily bars in a row counted as stale
+    pub stale_bars_daily: usize,
+    /// number of identical minutely bars in a row counted as stale
+    pub stale_bars_intraday: usize,
+}
+
+impl Default for QualitySettings {
+    fn default() -> QualitySettings {
+        QualitySettings {
+            repair: Repair::Flag,
+            interpolate: false,
+            max_interpolated: 5,
+            spike_percent_daily: 30.0,
+            spike_percent_intraday: 10.0,
+            stale_bars_daily: 5,
+            stale_bars_intraday: 60,
+        }
+    }
+}
+
+/// quality flags of each bar of a series ordered by time.
+/// Spikes and stale prices are only searched among bars without other issues
+pub fn validate(bars: &[TimeSeriesData], spike_percent: f64, stale_bars: usize) -> Vec<u32> {
+    let mut flags = vec![0_u32; bars.len()];
+    for (i, b) in bars.iter().enumerate() {
+        let prices = [b.open, b.high, b.low, b.close];
+        if prices.iter().chain(std::iter::once(&b.volume)).any(|x| !x.is_finite()) {
+            flags[i] |= NOT_A_NUMBER;
+        } else {
+            if prices.iter().any(|x| *x <= 0.0) || b.volume < 0.0 {
+                flags[i] |= NONPOSITIVE;
+            }
+            if b.high < b.low || b.high < b.open.max(b.close) || b.low > b.open.min(b.close) {
+                flags[i] |= INVALID_RANGE;
+            }
+        }
+        // keep the last of bars with the same time, it was stored last
+        if i + 1 < bars.len() && bars[i + 1].datetime == b.datetime {
+            flags[i] |= DUPLICATE;
+        }
+    }
+
+    let valid: Vec<usize> = (0..bars.len()).filter(|i| flags[*i] == 0).collect();
+    let threshold = spike_percent / 100.0;
+    for w in valid.windows(3) {
+        let (before, bar, after) = (bars[w[0]].close, bars[w[1]].close, bars[w[2]].close);
+        let up = bar / before - 1.0;
+        let down = after / bar - 1.0;
+        let back = after / before - 1.0;
+        if up.abs() > threshold && down.abs() > threshold && up.signum() != down.signum() && back.abs() < threshold / 2.0 {
+            flags[w[1]] |= SPIKE;
+        }
+    }
+
+    if stale_bars > 1 {
+        let flat = |i: usize| {
+            let b = &bars[i];
+            b.open == b.close && b.high == b.close && b.low == b.close
+        };
+        let mut run: Vec<usize> = Vec::new();
+        for &i in valid.iter().chain(std::iter::once(&usize::MAX)) {
+            let continues = i != usize::MAX
+                && flat(i)
+                && run.last().map(|j| bars[*j].close == bars[i].close).unwrap_or(true);
+            if continues {
+                run.push(i);
+                continue;
+            }
+            if run.len() >= stale_bars {
+                for j in run.iter().skip(1) {
+                    flags[*j] |= STALE;
+                }
+            }
+            run.clear();
+            if i != usize::MAX && flat(i) {
+                run.push(i);
+            }
+        }
+    }
+    flags
+}
+
+/// Result of the validation of the bars of one symbol in one table
+#[derive(Clone, Debug)]
+pub struct QualityReport {
+    pub symbol: String,
+    /// exchange of the bars, empty for bars stored without exchange
+    pub exchange: String,
+    pub table: BarTable,
+    /// number of checked bars
+    pub bars: usize,
+    /// number of bars per issue
+    pub counts: BTreeMap<&'static str, usize>,
+    /// timestamp and flags of the bars with issues
+    pub issues: Vec<(i64, u32)>,
+    pub flagged: usize,
+    pub quarantined: usize,
+    pub interpolated: usize,
+}
+
+impl QualityReport {
+    fn new(symbol: &str, exchange: &str, table: BarTable, bars: usize) -> QualityReport {
+        QualityReport {
+            symbol: symbol.to_string(),
+            exchange: exchange.to_string(),
+            table,
+            bars,
+            counts: BTreeMap::new(),
+            issues: Vec::new(),
+            flagged: 0,
+            quarantined: 0,
+            interpolated: 0,
+        }
+    }
+
+    /// whether no issues were found
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl std::fmt::Display for QualityReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol)?;
+        if !self.exchange.is_empty() {
+            write!(f, " {}", self.exchange)?;
+        }
+        write!(f, " {}: {} bars", self.table.name(), self.bars)?;
+        for (name, count) in self.counts.iter() {
+            write!(f, ", {} {}", count, name)?;
+        }
+        if self.flagged > 0 {
+            write!(f, ", {} flagged", self.flagged)?;
+        }
+        if self.quarantined > 0 {
+            write!(f, ", {} quarantined", self.quarantined)?;
+        }
+        if self.interpolated > 0 {
+            write!(f, ", {} interpolated", self.interpolated)?;
+        }
+        // the first few bars, the rest is in the database
+        for (timestamp, flags) in self.issues.iter().take(10) {
+            write!(
+                f,
+                "\n  {} {}",
+                DateTime::from_timestamp(*timestamp, 0).unwrap_or_default().naive_utc(),
+                issue_names(*flags).join(", ")
+            )?;
+        }
+        if self.issues.len() > 10 {
+            write!(f, "\n  ... {} more", self.issues.len() - 10)?;
+        }
+        Ok(())
+    }
+}
+
+/// bars between two bars of the same series, linear in time
+fn interpolate_between(a: &StoredBar, b: &StoredBar, timestamps: &[i64]) -> Vec<StoredBar> {
+    let span = (b.bar.datetime - a.bar.datetime) as f64;
+    timestamps.iter().map(|t| {
+        let close = a.bar.close + (b.bar.close - a.bar.close) * (t - a.bar.datetime) as f64 / span;
+        StoredBar {
+            id: 0,
+            exchange: a.exchange.clone(),
+            currency: a.currency.clone(),
+            bar: TimeSeriesData { datetime: *t, open: close, high: close, low: close, close, volume: 0.0 },
+            quality: INTERPOLATED,
+        }
+    }).collect()
+}
+
+/// bars filling the gaps of the trading sessions of up to `max` bars
+fn interpolate(
+    table: BarTable,
+    bars: &[&StoredBar],
+    calendar: &crate::calendar::ExchangeCalendar,
+    max: usize,
+) -> Vec<StoredBar> {
+    let mut v = Vec::new();
+    for w in bars.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        let (Some(start), Some(end)) = (DateTime::from_timestamp(a.bar.datetime, 0), DateTime::from_timestamp(b.bar.datetime, 0)) else {
+            continue;
+        };
+        let missing: Vec<i64> = match table {
+            BarTable::LiveData => {
+                // no interpolation over night
+                if calendar.local_date(start) != calendar.local_date(end) {
+                    continue;
+                }
+                (a.bar.datetime + 60..b.bar.datetime)
+                    .step_by(60)
+                    .filter(|t| DateTime::from_timestamp(*t, 0).map(|t| calendar.is_open(t)).unwrap_or(false))
+                    .collect()
+            }
+            BarTable::TimeSeries => {
+                let mut dates = Vec::new();
+                let mut date = start.date_naive() + Days::new(1);
+                while date < end.date_naive() && dates.len() <= max {
+                    if calendar.is_trading_day(date) {
+                        dates.push(daily_timestamp(date));
+                    }
+                    date = date + Days::new(1);
+                }
+                dates
+            }
+        };
+        if !missing.is_empty() && missing.len() <= max {
+            v.extend(interpolate_between(a, b, &missing));
+        }
+    }
+    v
+}
+
+/// validate the bars of a symbol at one exchange in one table and repair them as configured
+pub fn check_symbol(
+    sql_connection: sql::Database,
+    table: BarTable,
+    symbol: &str,
+    exchange: &str,
+    settings: &QualitySettings,
+) -> QualityReport {
+    let stored = sql::quality::stored_bars(sql_connection.clone(), table, symbol, exchange);
+    let bars: Vec<TimeSeriesData> = stored.iter().map(|s| s.bar.clone()).collect();
+    let flags = match table {
+        BarTable::TimeSeries => validate(&bars, settings.spike_percent_daily, settings.stale_bars_daily),
+        BarTable::LiveData => validate(&bars, settings.spike_percent_intraday, settings.stale_bars_intraday),
+    };
+
+    let mut report = QualityReport::new(symbol, exchange, table, stored.len());
+    let mut changed = Vec::new();
+    let mut excluded = Vec::new();
+    for (s, f) in stored.iter().zip(flags.iter()) {
+        if *f != 0 {
+            report.issues.push((s.bar.datetime, *f));
+            for name in issue_names(*f) {
+                *report.counts.entry(name).or_insert(0) += 1;
+            }
+        }
+        let quality = f | (s.quality & INTERPOLATED);
+        if f & EXCLUDED != 0 {
+            excluded.push((s.id, quality));
+        }
+        if quality != s.quality {
+            changed.push((s.id, quality));
+        }
+    }
+
+    match settings.repair {
+        Repair::Report => return report,
+        Repair::Flag => {}
+        Repair::Quarantine => {
+            if sql::quality::quarantine_bars(sql_connection.clone(), table, &excluded) == 1 {
+                report.quarantined = excluded.len();
+                changed.retain(|(_, q)| q & EXCLUDED == 0);
+            }
+        }
+    }
+    if sql::quality::set_quality(sql_connection.clone(), table, &changed) == 1 {
+        report.flagged = changed.iter().filter(|(_, q)| q & !INTERPOLATED != 0).count();
+    }
+
+    if settings.interpolate {
+        let good: Vec<&StoredBar> = stored.iter().zip(flags.iter()).filter(|(_, f)| *f & EXCLUDED == 0).map(|(s, _)| s).collect();
+        // the bars of one exchange follow its sessions, bars without exchange the default one
+        let calendar = crate::calendar::calendar(exchange);
+        let fills = interpolate(table, &good, &calendar, settings.max_interpolated);
+        if !fills.is_empty() && sql::quality::insert_interpolated(sql_connection.clone(), table, symbol, &fills) == 1 {
+            report.interpolated = fills.len();
+        }
+    }
+    report
+}
+
+/// validate the daily and minutely bars of all symbols, one report per symbol, exchange and table
+pub fn validate_symbols(
+    sql_connection: sql::Database,
+    symbols: &Vec<String>,
+    settings: &QualitySettings,
+) -> Vec<QualityReport> {
+    let mut v = Vec::new();
+    for symbol in symbols.iter() {
+        for table in [BarTable::TimeSeries, BarTable::LiveData] {
+            for exchange in sql::quality::bar_exchanges(sql_connection.clone(), table, symbol) {
+                v.push(check_symbol(sql_connection.clone(), table, symbol, &exchange, settings));
+            }
+        }
+    }
+    v
+}
@@ -0,0 +1,159 @@
+//! Storage of the user defined alert rules
+
+use rusqlite::params;
+
+use super::alerts::{fingerprint, raise_alert, AlertPolicy, NewAlert};
+use crate::analytics::rules::{AlertEvent, AlertRule};
+
+/// store a rule, a rule with the same name is replaced, returns the id of the rule
+pub fn insert_alert_rule(
+    sql_connection: super::Database,
+    rule: &AlertRule,
+) -> Option<i64> {
+    let condition = match serde_json::to_string(&rule.condition) {
+        Ok(c) => c,
+        Err(error) => {
+            log::error!("Failed to serialize the condition of rule {}! {}", rule.name, error);
+            return None;
+        }
+    };
+    let connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return None;
+        }
+    };
+    match connection.query_row(
+        "INSERT INTO alert_rules (name, symbol, condition, cooldown_minutes, level, notify, enabled)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT(name) DO UPDATE SET symbol = excluded.symbol, condition = excluded.condition,
+                cooldown_minutes = excluded.cooldown_minutes, level = excluded.level,
+                notify = excluded.notify, enabled = excluded.enabled
+            RETURNING rule_id",
+        params![
+            &rule.name,
+            &rule.symbol,
+            &condition,
+            &rule.cooldown_minutes,
+            &rule.level,
+            &rule.notify.join(","),
+            &rule.enabled
+        ],
+        |row| row.get(0),
+    ) {
+        Ok(rule_id) => Some(rule_id),
+        Err(error) => {
+            log::error!("Failed insert alert rule {}! {}", rule.name, error);
+            None
+        }
+    }
+}
+
+/// all stored rules, ordered by name
+pub fn alert_rules(
+    sql_connection: super::Database,
+) -> Vec<AlertRule> {
+    let mut v = Vec::new();
+    let connection = match sql_connection.read() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return v;
+        }
+    };
+    let query = "SELECT rule_id, name, symbol, condition, cooldown_minutes, level, notify, enabled
+        FROM alert_rules ORDER BY name";
+    let mut statement = match connection.prepare(query) {
+        Ok(s) => s,
+        Err(error) => {
+            log::error!("could not prepare SQL statement: {}", error);
+            return v;
+        }
+    };
+    let rows = statement.query_map([], |row| {
+        let condition: String = row.get(3)?;
+        let notify = row.get::<_, Option<String>>(6)?.unwrap_or_default();
+        Ok(AlertRule {
+            rule_id: row.get(0)?,
+            name: row.get(1)?,
+            symbol: row.get(2)?,
+            condition: serde_json::from_str(&condition).map_err(|error| {
+                rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(error))
+            })?,
+            cooldown_minutes: row.get(4)?,
+            level: row.get(5)?,
+            notify: notify.split(',').filter(|n| !n.is_empty()).map(|n| n.to_string()).collect(),
+            enabled: row.get(7)?,
+        })
+    });
+    match rows {
+        Ok(rows) => {
+            for row in rows {
+                match row {
+                    Ok(r) => v.push(r),
+                    Err(error) => log::error!("Failed to read a row from alert_rules: {}", error),
+                }
+            }
+        }
+        Err(error) => log::error!("could not read from alert_rules: {}", error),
+    }
+    v
+}
+
+/// remove a rule by name
+pub fn delete_alert_rule(
+    sql_connection: super::Database,
+    name: &str,
+) -> u32 {
+    let connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return 0;
+        }
+    };
+    match connection.execute("DELETE FROM alert_rules WHERE name = ?1", params![name]) {
+        Ok(_) => 1,
+        Err(error) => {
+            log::error!("Failed to delete alert rule {}! {}", name, error);
+            0
+        }
+    }
+}
+
+/// evaluate the stored rules on the bars of a symbol and raise the alerts of the rules that fired,
+/// returns the events to notify. The cooldown of the rule replaces the one of `policy`
+pub fn evaluate_alert_rules(
+    sql_connection: super::Database,
+    rules: &[AlertRule],
+    symbol: &str,
+    bars: &[super::TimeSeriesData],
+    policy: &AlertPolicy,
+    now: i64,
+) -> Vec<AlertEvent> {
+    let mut events = Vec::new();
+    for rule in rules.iter() {
+        let Some(mut event) = rule.evaluate(symbol, bars) else {
+            continue;
+        };
+        let new = NewAlert {
+            fingerprint: fingerprint("rule", symbol, &rule.name),
+            source: rule.name.clone(),
+            symbol: symbol.to_string(),
+            level: rule.level.clone(),
+            title: rule.name.clone(),
+            body: event.message.clone(),
+        };
+        let rule_policy = AlertPolicy { cooldown: rule.cooldown_minutes as i64 * 60, ..*policy };
+        match raise_alert(sql_connection.clone(), &new, &rule_policy, now) {
+            Some(raised) if raised.notify => {
+                event.level = raised.level;
+                events.push(event);
+            }
+            Some(_) => log::debug!("Alert rule {} for {} is already notified", rule.name, symbol),
+            None => log::error!("Failed to record the alert of rule {} for {}", rule.name, symbol),
+        }
+    }
+    events
+}
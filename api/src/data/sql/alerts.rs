@@ -0,0 +1,373 @@
+//! State of the alerts raised by the daemon
+//!
+//! An alert is identified by its fingerprint, the source, the symbol and a key of the event,
+//! for example `slope|AAPL|up`. Raising an alert whose fingerprint is still active updates the
+//! existing row instead of notifying again, so every real event is notified once. An alert
+//! ends when it is resolved explicitly or was not raised for `resolve_after` seconds.
+//! Until then it can be acknowledged, which silences it, or snoozed until a given time.
+//! Alerts nobody acknowledged `escalate_after` seconds after the notification are escalated once
+//! to the next level.
+
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// State of an alert
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertState {
+    Open,
+    Acknowledged,
+    /// silenced until `snoozed_until`
+    Snoozed,
+    Resolved,
+}
+
+impl AlertState {
+    /// name stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertState::Open => "open",
+            AlertState::Acknowledged => "acknowledged",
+            AlertState::Snoozed => "snoozed",
+            AlertState::Resolved => "resolved",
+        }
+    }
+}
+
+impl FromStr for AlertState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(AlertState::Open),
+            "acknowledged" => Ok(AlertState::Acknowledged),
+            "snoozed" => Ok(AlertState::Snoozed),
+            "resolved" => Ok(AlertState::Resolved),
+            _ => Err(format!("Unknown alert state {}", s)),
+        }
+    }
+}
+
+/// the fingerprint of an event of `source` for a symbol
+pub fn fingerprint(source: &str, symbol: &str, key: &str) -> String {
+    format!("{}|{}|{}", source, symbol, key)
+}
+
+/// the level after escalating `level`, levels are `info`, `warning` and `critical`
+pub fn escalated_level(level: &str) -> &'static str {
+    match level {
+        "info" => "warning",
+        _ => "critical",
+    }
+}
+
+/// An alert and its history, times are seconds since 1.1.1970
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Alert {
+    pub alert_id: i64,
+    pub fingerprint: String,
+    /// what raised the alert, `slope` or the name of an alert rule
+    pub source: String,
+    pub symbol: String,
+    pub level: String,
+    pub title: String,
+    pub body: String,
+    pub state: AlertState,
+    pub first_seen: i64,
+    pub last_seen: i64,
+    /// number of times the alert was raised
+    pub occurrences: u32,
+    pub notified_at: Option<i64>,
+    pub escalated: bool,
+    pub acknowledged_at: Option<i64>,
+    pub snoozed_until: Option<i64>,
+    pub resolved_at: Option<i64>,
+}
+
+/// How often the alerts of a source notify, in seconds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AlertPolicy {
+    /// minimum time between the notifications of a fingerprint, also over separate events
+    pub cooldown: i64,
+    /// quiet time after which an alert is resolved and the next one is a new event
+    pub resolve_after: i64,
+    /// time after the notification an open alert is escalated, 0 disables the escalation
+    pub escalate_after: i64,
+}
+
+impl Default for AlertPolicy {
+    fn default() -> Self {
+        AlertPolicy { cooldown: 3600, resolve_after: 1800, escalate_after: 0 }
+    }
+}
+
+/// An alert to raise
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NewAlert {
+    /// see `fingerprint`
+    pub fingerprint: String,
+    pub source: String,
+    pub symbol: String,
+    pub level: String,
+    pub title: String,
+    pub body: String,
+}
+
+/// What to do after raising an alert
+#[derive(Clone, Debug, PartialEq)]
+pub struct RaisedAlert {
+    pub alert_id: i64,
+    /// whether the user should be notified now
+    pub notify: bool,
+    /// the level of the alert, raised by an escalation
+    pub level: String,
+    /// whether the alert was escalated by this call
+    pub escalated: bool,
+}
+
+const ALERT_COLUMNS: &str = "alert_id, fingerprint, source, symbol, level, title, body, state, first_seen, last_seen,
+    occurrences, notified_at, escalated, acknowledged_at, snoozed_until, resolved_at";
+
+fn row_to_alert(row: &rusqlite::Row) -> rusqlite::Result<Alert> {
+    let state: String = row.get(7)?;
+    Ok(Alert {
+        alert_id: row.get(0)?,
+        fingerprint: row.get(1)?,
+        source: row.get(2)?,
+        symbol: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+        level: row.get(4)?,
+        title: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+        body: row.get::<_, Option<String>>(6)?.unwrap_or_default(),
+        state: state.parse().unwrap_or(AlertState::Open),
+        first_seen: row.get(8)?,
+        last_seen: row.get(9)?,
+        occurrences: row.get(10)?,
+        notified_at: row.get(11)?,
+        escalated: row.get(12)?,
+        acknowledged_at: row.get(13)?,
+        snoozed_until: row.get(14)?,
+        resolved_at: row.get(15)?,
+    })
+}
+
+/// raise an alert, returns whether to notify, `None` on database errors
+pub fn raise_alert(
+    sql_connection: super::Database,
+    new: &NewAlert,
+    policy: &AlertPolicy,
+    now: i64,
+) -> Option<RaisedAlert> {
+    let mut connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return None;
+        }
+    };
+    let result = (|| -> rusqlite::Result<RaisedAlert> {
+        let transaction = connection.transaction()?;
+        let active = transaction
+            .query_row(
+                &format!("SELECT {} FROM alerts WHERE fingerprint = ?1 AND state != ?2 ORDER BY alert_id DESC LIMIT 1", ALERT_COLUMNS),
+                params![&new.fingerprint, AlertState::Resolved.as_str()],
+                row_to_alert,
+            )
+            .optional()?;
+        let active = match active {
+            Some(alert) if now - alert.last_seen > policy.resolve_after => {
+                transaction.execute(
+                    "UPDATE alerts SET state = ?2, resolved_at = ?3 WHERE alert_id = ?1",
+                    params![alert.alert_id, AlertState::Resolved.as_str(), alert.last_seen],
+                )?;
+                None
+            }
+            other => other,
+        };
+        let last_notified: Option<i64> = transaction.query_row(
+            "SELECT MAX(notified_at) FROM alerts WHERE fingerprint = ?1",
+            params![&new.fingerprint],
+            |row| row.get(0),
+        )?;
+        let cooled_down = last_notified.map_or(true, |t| now - t >= policy.cooldown);
+        let raised = match active {
+            Some(alert) => {
+                let mut state = alert.state;
+                // a snooze that ran out reminds of the alert still going on
+                let woke_up = state == AlertState::Snoozed && alert.snoozed_until.map_or(true, |until| until <= now);
+                if woke_up {
+                    state = AlertState::Open;
+                }
+                let escalate = state == AlertState::Open
+                    && !alert.escalated
+                    && policy.escalate_after > 0
+                    && alert.notified_at.map_or(false, |t| now - t >= policy.escalate_after)
+                    && alert.level != "critical";
+                // an event held back by the cooldown of the previous one is notified once it is over
+                let notify = escalate || woke_up || (state == AlertState::Open && alert.notified_at.is_none() && cooled_down);
+                let level = if escalate { escalated_level(&alert.level).to_string() } else { alert.level.clone() };
+                let notified_at = if notify { Some(now) } else { alert.notified_at };
+                transaction.execute(
+                    "UPDATE alerts SET last_seen = ?2, occurrences = occurrences + 1, state = ?3, level = ?4,
+                        escalated = ?5, notified_at = ?6, body = ?7 WHERE alert_id = ?1",
+                    params![alert.alert_id, now, state.as_str(), &level, alert.escalated || escalate, notified_at, &new.body],
+                )?;
+                RaisedAlert { alert_id: alert.alert_id, notify, level, escalated: escalate }
+            }
+            None => {
+                transaction.execute(
+                    "INSERT INTO alerts (fingerprint, source, symbol, level, title, body, state, first_seen, last_seen,
+                        occurrences, notified_at, escalated) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8, 1, ?9, 0)",
+                    params![
+                        &new.fingerprint,
+                        &new.source,
+                        &new.symbol,
+                        &new.level,
+                        &new.title,
+                        &new.body,
+                        AlertState::Open.as_str(),
+                        now,
+                        if cooled_down { Some(now) } else { None }
+                    ],
+                )?;
+                RaisedAlert { alert_id: transaction.last_insert_rowid(), notify: cooled_down, level: new.level.clone(), escalated: false }
+            }
+        };
+        transaction.commit()?;
+        Ok(raised)
+    })();
+    match result {
+        Ok(raised) => Some(raised),
+        Err(error) => {
+            log::error!("Failed to raise alert {}! {}", new.fingerprint, error);
+            None
+        }
+    }
+}
+
+fn set_state(
+    sql_connection: super::Database,
+    query: &str,
+    params: impl rusqlite::Params,
+) -> u32 {
+    let connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return 0;
+        }
+    };
+    match connection.execute(query, params) {
+        Ok(num) => num.min(1) as u32,
+        Err(error) => {
+            log::error!("Failed to update alerts! {}", error);
+            0
+        }
+    }
+}
+
+/// resolve the active alert of a fingerprint, the condition ended
+pub fn resolve_alert(
+    sql_connection: super::Database,
+    fingerprint: &str,
+    now: i64,
+) -> u32 {
+    set_state(
+        sql_connection,
+        "UPDATE alerts SET state = ?2, resolved_at = ?3 WHERE fingerprint = ?1 AND state != ?2",
+        params![fingerprint, AlertState::Resolved.as_str(), now],
+    )
+}
+
+/// acknowledge an active alert, it does not notify or escalate any more, returns 0 if there is none
+pub fn acknowledge_alert(
+    sql_connection: super::Database,
+    alert_id: i64,
+    now: i64,
+) -> u32 {
+    set_state(
+        sql_connection,
+        "UPDATE alerts SET state = ?2, acknowledged_at = ?3 WHERE alert_id = ?1 AND state != ?4",
+        params![alert_id, AlertState::Acknowledged.as_str(), now, AlertState::Resolved.as_str()],
+    )
+}
+
+/// silence an active alert until `until`, returns 0 if there is none
+pub fn snooze_alert(
+    sql_connection: super::Database,
+    alert_id: i64,
+    until: i64,
+) -> u32 {
+    set_state(
+        sql_connection,
+        "UPDATE alerts SET state = ?2, snoozed_until = ?3 WHERE alert_id = ?1 AND state != ?4",
+        params![alert_id, AlertState::Snoozed.as_str(), until, AlertState::Resolved.as_str()],
+    )
+}
+
+/// one alert by id
+pub fn alert(
+    sql_connection: super::Database,
+    alert_id: i64,
+) -> Option<Alert> {
+    let connection = match sql_connection.read() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return None;
+        }
+    };
+    match connection
+        .query_row(&format!("SELECT {} FROM alerts WHERE alert_id = ?1", ALERT_COLUMNS), params![alert_id], row_to_alert)
+        .optional()
+    {
+        Ok(a) => a,
+        Err(error) => {
+            log::error!("could not read from alerts: {}", error);
+            None
+        }
+    }
+}
+
+/// the alerts last seen since `since`, newest first, optionally of one symbol and state
+pub fn alerts(
+    sql_connection: super::Database,
+    symbol: Option<&str>,
+    state: Option<AlertState>,
+    since: i64,
+    limit: usize,
+) -> Vec<Alert> {
+    let mut v = Vec::new();
+    let connection = match sql_connection.read() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return v;
+        }
+    };
+    let query = format!(
+        "SELECT {} FROM alerts WHERE last_seen >= ?1 AND (?2 IS NULL OR symbol = ?2) AND (?3 IS NULL OR state = ?3)
+            ORDER BY last_seen DESC, alert_id DESC LIMIT ?4",
+        ALERT_COLUMNS
+    );
+    let mut statement = match connection.prepare(&query) {
+        Ok(s) => s,
+        Err(error) => {
+            log::error!("could not prepare SQL statement: {}", error);
+            return v;
+        }
+    };
+    let rows = statement.query_map(params![since, symbol, state.map(|s| s.as_str()), limit as i64], row_to_alert);
+    match rows {
+        Ok(rows) => {
+            for row in rows {
+                match row {
+                    Ok(r) => v.push(r),
+                    Err(error) => log::error!("Failed to read a row from alerts: {}", error),
+                }
+            }
+        }
+        Err(error) => log::error!("could not read from alerts: {}", error),
+    }
+    v
+}
@@ -0,0 +1,290 @@
+//! Splits and dividends of stocks and the back-adjustment of prices
+//!
+//! Actions are stored with the ex-date as midnight UTC, bars before that time are adjusted.
+//! A split stores the number of new shares per old share (`4.0` for a 4:1 split, `0.1` for a
+//! 1:10 reverse split), a dividend the cash amount per share in the currency of the quotes.
+
+use chrono::NaiveDate;
+use rusqlite::params;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Kind of a corporate action
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CorporateActionKind {
+    Split,
+    Dividend,
+}
+
+impl CorporateActionKind {
+    /// name stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CorporateActionKind::Split => "split",
+            CorporateActionKind::Dividend => "dividend",
+        }
+    }
+}
+
+impl FromStr for CorporateActionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "split" | "splits" => Ok(CorporateActionKind::Split),
+            "dividend" | "dividends" | "div" => Ok(CorporateActionKind::Dividend),
+            _ => Err(format!("Unknown corporate action {}", s)),
+        }
+    }
+}
+
+/// A split or dividend of a stock
+#[derive(Clone, Debug, PartialEq)]
+pub struct CorporateAction {
+    pub symbol: String,
+    /// ex-date as seconds since 1.1.1970, midnight UTC
+    pub timestamp: i64,
+    pub kind: CorporateActionKind,
+    /// split ratio or dividend per share
+    pub value: f64,
+}
+
+impl CorporateAction {
+    /// a split of `numerator` new shares for `denominator` old shares
+    pub fn split(symbol: &str, ex_date: NaiveDate, numerator: f64, denominator: f64) -> CorporateAction {
+        CorporateAction {
+            symbol: symbol.to_string(),
+            timestamp: ex_date_timestamp(ex_date),
+            kind: CorporateActionKind::Split,
+            value: numerator / denominator,
+        }
+    }
+
+    /// a dividend of `amount` per share
+    pub fn dividend(symbol: &str, ex_date: NaiveDate, amount: f64) -> CorporateAction {
+        CorporateAction {
+            symbol: symbol.to_string(),
+            timestamp: ex_date_timestamp(ex_date),
+            kind: CorporateActionKind::Dividend,
+            value: amount,
+        }
+    }
+
+    /// factor for the prices before the ex-date, `close` is the last close before it
+    pub fn adjustment_factor(&self, close: f64) -> f64 {
+        match self.kind {
+            CorporateActionKind::Split if self.value > 0.0 => 1.0 / self.value,
+            CorporateActionKind::Dividend if close > self.value && self.value > 0.0 => 1.0 - self.value / close,
+            _ => 1.0,
+        }
+    }
+}
+
+/// timestamp of an ex-date as stored in the database
+pub fn ex_date_timestamp(date: NaiveDate) -> i64 {
+    date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp()
+}
+
+/// the stored actions of a symbol ordered by ex-date
+pub fn corporate_actions(
+    sql_connection: super::Database,
+    symbol: &str,
+) -> Vec<CorporateAction> {
+    let mut v = Vec::new();
+    let connection = match sql_connection.read() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return v;
+        }
+    };
+    let query = "SELECT timestamp, kind, value FROM corporate_actions WHERE symbol = ?1 ORDER BY timestamp ASC";
+    let mut statement = match connection.prepare(query) {
+        Ok(s) => s,
+        Err(error) => {
+            log::error!("could not prepare SQL statement: {}", error);
+            return v;
+        }
+    };
+    let rows = statement.query_map(params![symbol], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, f64>(2)?))
+    });
+    match rows {
+        Ok(rows) => {
+            for row in rows {
+                match row {
+                    Ok((timestamp, kind, value)) => match kind.parse() {
+                        Ok(kind) => v.push(CorporateAction { symbol: symbol.to_string(), timestamp, kind, value }),
+                        Err(error) => log::error!("Failed to read corporate action of {}: {}", symbol, error),
+                    },
+                    Err(error) => log::error!("Failed to read a row from corporate_actions: {}", error),
+                }
+            }
+        }
+        Err(error) => log::error!("could not read from corporate_actions: {}", error),
+    }
+    v
+}
+
+/// store corporate actions, an action of the same symbol, ex-date and kind is updated
+pub fn insert_corporate_actions(
+    sql_connection: super::Database,
+    actions: &[CorporateAction],
+) -> u32 {
+    let mut connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return 0;
+        }
+    };
+    let transaction = match connection.transaction() {
+        Ok(t) => t,
+        Err(error) => {
+            log::error!("Failed to start transaction! {}", error);
+            return 0;
+        }
+    };
+    {
+        let mut statement = match transaction.prepare_cached(
+            "INSERT INTO corporate_actions (timestamp, symbol, kind, value) VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT(symbol, timestamp, kind) DO UPDATE SET value = excluded.value",
+        ) {
+            Ok(s) => s,
+            Err(error) => {
+                log::error!("could not prepare SQL statement: {}", error);
+                return 0;
+            }
+        };
+        for a in actions.iter() {
+            if let Err(error) = statement.execute(params![&a.timestamp, &a.symbol, a.kind.as_str(), &a.value]) {
+                log::error!("Failed insert corporate_actions of {}! {}", a.symbol, error);
+                return 0;
+            }
+        }
+    }
+    match transaction.commit() {
+        Ok(()) => 1,
+        Err(error) => {
+            log::error!("Failed to commit corporate_actions! {}", error);
+            0
+        }
+    }
+}
+
+/// parse a split ratio like `4:1`, `1/10` or `2.5`
+fn parse_ratio(value: &str) -> Result<f64, Box<dyn Error>> {
+    let value = value.trim();
+    match value.split_once(|c| c == ':' || c == '/') {
+        Some((numerator, denominator)) => {
+            let denominator: f64 = denominator.trim().parse()?;
+            if denominator == 0.0 {
+                return Err(format!("Invalid split ratio {}", value).into());
+            }
+            Ok(numerator.trim().parse::<f64>()? / denominator)
+        }
+        None => Ok(value.parse()?),
+    }
+}
+
+/// read corporate actions from CSV lines `symbol,date,kind,value`.
+/// The date is `YYYY-MM-DD`, kind `split` or `dividend`, a split value may be
+/// written as ratio `4:1`. A header line and empty lines are skipped
+pub fn corporate_actions_from_csv(path: &Path) -> Result<Vec<CorporateAction>, Box<dyn Error>> {
+    let content = std::fs::read_to_string(path)?;
+    let mut v = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if fields.len() != 4 {
+            return Err(format!("{} line {}: expected symbol,date,kind,value", path.display(), i + 1).into());
+        }
+        let date = match NaiveDate::parse_from_str(fields[1], "%Y-%m-%d") {
+            Ok(d) => d,
+            Err(_) if i == 0 => continue,
+            Err(error) => return Err(format!("{} line {}: {}", path.display(), i + 1, error).into()),
+        };
+        let kind: CorporateActionKind = fields[2].parse().map_err(|e| format!("{} line {}: {}", path.display(), i + 1, e))?;
+        let value = match kind {
+            CorporateActionKind::Split => parse_ratio(fields[3]),
+            CorporateActionKind::Dividend => fields[3].parse::<f64>().map_err(|e| e.into()),
+        }
+        .map_err(|e| format!("{} line {}: {}", path.display(), i + 1, e))?;
+        v.push(CorporateAction {
+            symbol: fields[0].to_string(),
+            timestamp: ex_date_timestamp(date),
+            kind,
+            value,
+        });
+    }
+    Ok(v)
+}
+
+/// store the corporate actions of a CSV file, returns the number of actions
+pub fn import_corporate_actions_csv(
+    sql_connection: super::Database,
+    path: &Path,
+) -> Result<usize, Box<dyn Error>> {
+    let actions = corporate_actions_from_csv(path)?;
+    if insert_corporate_actions(sql_connection, &actions) == 0 {
+        return Err(format!("Failed to store the corporate actions of {}", path.display()).into());
+    }
+    Ok(actions.len())
+}
+
+/// retrieve the splits and dividends of the symbols from Yahoo and store them.
+/// Symbols without stored actions get the history of the nightly update, the others the last month
+pub fn update_corporate_actions(
+    sql_connection: super::Database,
+    symbols: &Vec<String>,
+) {
+    let today = chrono::Utc::now().date_naive();
+    for symbol in symbols.iter() {
+        let days = if corporate_actions(sql_connection.clone(), symbol).is_empty() { 2000 } else { 31 };
+        let start_date = today - chrono::Days::new(days);
+        let handle = tokio::runtime::Handle::current();
+        let _ = handle.enter();
+        let actions = match futures::executor::block_on(crate::data::yahoo::api::get_corporate_actions(
+            symbol,
+            &start_date.to_string(),
+            &today.to_string(),
+        )) {
+            Ok(a) => a,
+            Err(error) => {
+                log::error!("Failed to retrieve corporate actions of {}: {}", symbol, error);
+                continue;
+            }
+        };
+        if actions.len() > 0 && insert_corporate_actions(sql_connection.clone(), &actions) == 0 {
+            log::error!("Failed to store the corporate actions of {}", symbol);
+        }
+    }
+}
+
+/// back-adjusted close prices of bars ordered by time.
+/// Every bar before an ex-date is multiplied with the factor of the action, for a dividend
+/// that is `1 - dividend / close` with the last close before the ex-date, for a split
+/// `1 / ratio`. Actions after the last bar are applied with the close of the last bar
+pub fn adjusted_close(
+    series: &[super::TimeSeriesData],
+    actions: &[CorporateAction],
+) -> Vec<f64> {
+    let mut actions = actions.to_vec();
+    actions.sort_by_key(|a| std::cmp::Reverse(a.timestamp));
+    let mut adjclose = vec![0.0; series.len()];
+    let mut factor = 1.0;
+    let mut next = 0;
+    for i in (0..series.len()).rev() {
+        while next < actions.len() && actions[next].timestamp > series[i].datetime {
+            factor *= actions[next].adjustment_factor(series[i].close);
+            next += 1;
+        }
+        adjclose[i] = series[i].close * factor;
+    }
+    adjclose
+}
+
@@ -0,0 +1,128 @@
+//! Handle of a stock database
+//!
+//! A `Database` owns one connection for writing and a small pool of read-only connections.
+//! File databases run in WAL mode so readers are not blocked by the writer, in-memory
+//! databases use the write connection for reading as well. Cloning the handle is cheap,
+//! all clones share the same connections.
+
+use rusqlite::{Connection, OpenFlags};
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LockResult, Mutex, MutexGuard, TryLockError};
+use std::time::Duration;
+
+/// number of read connections of file databases
+pub const READ_POOL_SIZE: usize = 4;
+
+/// Handle of a database, hand it to the functions of `data::sql`
+#[derive(Clone)]
+pub struct Database {
+    location: String,
+    writer: Arc<Mutex<Connection>>,
+    readers: Arc<Vec<Mutex<Connection>>>,
+    next_reader: Arc<AtomicUsize>,
+}
+
+impl std::fmt::Debug for Database {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Database")
+            .field("location", &self.location)
+            .field("readers", &self.readers.len())
+            .finish()
+    }
+}
+
+impl Database {
+    /// open the database at a path or URL, `:memory:` opens a new in-memory database.
+    /// Accepted are plain paths, `sqlite://path` and SQLite `file:` URIs.
+    /// New databases are initialized, existing ones migrated to the current schema
+    pub fn open(location: &str) -> Result<Database, Box<dyn Error>> {
+        Database::with_readers(location, READ_POOL_SIZE)
+    }
+
+    /// like `open` with the given number of read connections
+    pub fn with_readers(location: &str, readers: usize) -> Result<Database, Box<dyn Error>> {
+        let location = location.strip_prefix("sqlite://").unwrap_or(location);
+        if location.is_empty() || location == ":memory:" {
+            return Database::open_in_memory();
+        }
+        let flags = OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let path = std::path::Path::new(location.strip_prefix("file:").unwrap_or(location));
+        if !location.starts_with("file:") && !path.exists() {
+            super::init::init_database(path.to_path_buf());
+        }
+        let writer = Connection::open_with_flags(location, flags)?;
+        writer.busy_timeout(Duration::from_secs(10))?;
+        let mode: String = writer.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+        if mode.to_lowercase() != "wal" {
+            log::warn!("Database {} does not support WAL mode, using {}", location, mode);
+        }
+        let mut database = Database::from_connection(writer);
+        database.location = location.to_string();
+        database.migrate()?;
+
+        let read_flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let mut pool = Vec::with_capacity(readers);
+        for _ in 0..readers {
+            let reader = Connection::open_with_flags(location, read_flags)?;
+            reader.busy_timeout(Duration::from_secs(10))?;
+            pool.push(Mutex::new(reader));
+        }
+        database.readers = Arc::new(pool);
+        Ok(database)
+    }
+
+    /// a new, empty database in memory with the current schema
+    pub fn open_in_memory() -> Result<Database, Box<dyn Error>> {
+        let database = Database::from_connection(Connection::open_in_memory()?);
+        database.migrate()?;
+        Ok(database)
+    }
+
+    /// wrap an open connection, it is used for reading and writing
+    pub fn from_connection(connection: Connection) -> Database {
+        let location = connection.path().unwrap_or(":memory:").to_string();
+        Database {
+            location: if location.is_empty() { ":memory:".to_string() } else { location },
+            writer: Arc::new(Mutex::new(connection)),
+            readers: Arc::new(Vec::new()),
+            next_reader: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// apply the pending migrations, returns the applied versions
+    pub fn migrate(&self) -> Result<Vec<u32>, Box<dyn Error>> {
+        let mut connection = match self.writer.lock() {
+            Ok(conn) => conn,
+            Err(error) => return Err(format!("Failed to lock sql connection for use! {}", error).into()),
+        };
+        super::migrations::migrate(&mut connection)
+    }
+
+    /// path or URL the database was opened from
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    /// the connection for statements changing the database
+    pub fn write(&self) -> LockResult<MutexGuard<'_, Connection>> {
+        self.writer.lock()
+    }
+
+    /// a connection for queries, a free one of the read pool if there is one
+    pub fn read(&self) -> LockResult<MutexGuard<'_, Connection>> {
+        if self.readers.is_empty() {
+            return self.writer.lock();
+        }
+        let start = self.next_reader.fetch_add(1, Ordering::Relaxed);
+        for i in 0..self.readers.len() {
+            match self.readers[(start + i) % self.readers.len()].try_lock() {
+                Ok(conn) => return Ok(conn),
+                Err(TryLockError::Poisoned(error)) => return Err(error),
+                Err(TryLockError::WouldBlock) => continue,
+            }
+        }
+        // all readers busy, wait for the next in line
+        self.readers[start % self.readers.len()].lock()
+    }
+}
@@ -1,11 +1,13 @@
 use rusqlite::params;
 
+use crate::analytics::patterns::{Pattern, PatternEvent};
+
 pub fn jump_events_count(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     symbol: &str
 ) -> usize {
     let mut num = 0_usize;
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -53,11 +55,11 @@ pub fn jump_events_count(
 }
 
 pub fn jump_events(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     symbol: &str
 ) -> Vec<super::JumpEventData> {
     let mut t = Vec::new();
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -125,43 +127,45 @@ pub fn jump_events(
     t
 }
 
+/// store the jumps not stored yet, a jump is identified by symbol and timestamp.
+/// Returns the number of new jumps
 pub fn insert_jump_events(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     series: &Vec<super::JumpEventData>,
-) {
+) -> usize {
     if series.len() == 0 {
-        return;
+        return 0;
     }
-    let existing = jump_events(sql_connection.clone(), &series[0].symbol);
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.write() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
-            return;
+            return 0;
         }
     };
+    let mut inserted = 0;
     for j in series.iter() {
-        if existing.contains(j) {
-            continue;
-        }
         match connection.execute(
-            "INSERT INTO jump_events (timestamp, symbol, percent ) VALUES (?1, ?2, ?3)",
+            "INSERT INTO jump_events (timestamp, symbol, percent ) VALUES (?1, ?2, ?3)
+                ON CONFLICT(symbol, timestamp) DO NOTHING",
             params![&j.datetime, &j.symbol, &j.percent],
         ) {
-            Ok(_retval) => {} //log::warn!("Inserted {} video with ID {} and location {} into candidates.", video.id, video.index, candidate_id),
+            Ok(num) => inserted += num,
             Err(error) => {
                 log::error!("Failed insert jump_events! {}", error);
-                return;
+                break;
             }
         }
     }
+    crate::metrics::rows_inserted("jump_events", inserted);
+    inserted
 }
 
 pub fn _delete_jump_events(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     series: &Vec<super::JumpEventData>,
 ) {
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.write() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -175,7 +179,7 @@ pub fn _delete_jump_events(
 }
 
 pub fn _update_jump_events(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     series: &Vec<super::JumpEventData>,
 ) {
     _delete_jump_events(sql_connection.clone(), series);
@@ -183,11 +187,11 @@ pub fn _update_jump_events(
 }
 
 pub fn drop_events_count(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     symbol: &str
 ) -> usize {
     let mut num = 0_usize;
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -235,11 +239,11 @@ pub fn drop_events_count(
 }
 
 pub fn drop_events(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     symbol: &str
 ) -> Vec<super::JumpEventData> {
     let mut t = Vec::new();
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -307,43 +311,45 @@ pub fn drop_events(
     t
 }
 
+/// store the drops not stored yet, a drop is identified by symbol and timestamp.
+/// Returns the number of new drops
 pub fn insert_drop_events(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     series: &Vec<super::JumpEventData>,
-) {
+) -> usize {
     if series.len() == 0 {
-        return;
+        return 0;
     }
-    let existing = jump_events(sql_connection.clone(), &series[0].symbol);
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.write() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
-            return;
+            return 0;
         }
     };
+    let mut inserted = 0;
     for j in series.iter() {
-        if existing.contains(j) {
-            continue;
-        }
         match connection.execute(
-            "INSERT INTO drop_events (timestamp, symbol, percent ) VALUES (?1, ?2, ?3)",
+            "INSERT INTO drop_events (timestamp, symbol, percent ) VALUES (?1, ?2, ?3)
+                ON CONFLICT(symbol, timestamp) DO NOTHING",
             params![&j.datetime, &j.symbol, &j.percent],
         ) {
-            Ok(_retval) => {} //log::warn!("Inserted {} video with ID {} and location {} into candidates.", video.id, video.index, candidate_id),
+            Ok(num) => inserted += num,
             Err(error) => {
                 log::error!("Failed insert drop_events! {}", error);
-                return;
+                break;
             }
         }
     }
+    crate::metrics::rows_inserted("drop_events", inserted);
+    inserted
 }
 
 pub fn _delete_drop_events(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     series: &Vec<super::JumpEventData>,
 ) {
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.write() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -357,19 +363,90 @@ pub fn _delete_drop_events(
 }
 
 pub fn _update_drop_events(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     series: &Vec<super::JumpEventData>,
 ) {
     _delete_drop_events(sql_connection.clone(), series);
     insert_drop_events(sql_connection.clone(), series);
 }
 
+/// the candlestick patterns of a symbol ordered by time, patterns unknown to this build are left out
+pub fn pattern_events(
+    sql_connection: super::Database,
+    symbol: &str
+) -> Vec<PatternEvent> {
+    let connection = match sql_connection.read() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return Vec::new();
+        }
+    };
+    let read = || -> Result<Vec<(i64, String, f64)>, rusqlite::Error> {
+        let mut statement = connection.prepare(
+            "SELECT timestamp, pattern, confidence FROM pattern_events WHERE symbol = ?1 ORDER BY timestamp ASC, pattern ASC",
+        )?;
+        let rows = statement.query_map(params![symbol], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        rows.collect()
+    };
+    match read() {
+        Ok(rows) => rows
+            .into_iter()
+            .filter_map(|(datetime, pattern, confidence)| match pattern.parse::<Pattern>() {
+                Ok(pattern) => Some(PatternEvent { datetime, symbol: symbol.to_string(), pattern, confidence }),
+                Err(error) => {
+                    log::warn!("Skipping pattern event of {}: {}", symbol, error);
+                    None
+                }
+            })
+            .collect(),
+        Err(error) => {
+            log::error!("Failed to read pattern_events of {}! {}", symbol, error);
+            Vec::new()
+        }
+    }
+}
+
+/// store the patterns not stored yet, a pattern is identified by symbol, timestamp and pattern.
+/// Returns the number of new patterns
+pub fn insert_pattern_events(
+    sql_connection: super::Database,
+    events: &[PatternEvent],
+) -> usize {
+    if events.is_empty() {
+        return 0;
+    }
+    let connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return 0;
+        }
+    };
+    let mut inserted = 0;
+    for e in events.iter() {
+        match connection.execute(
+            "INSERT INTO pattern_events (timestamp, symbol, pattern, confidence) VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT(symbol, timestamp, pattern) DO NOTHING",
+            params![&e.datetime, &e.symbol, e.pattern.as_str(), &e.confidence],
+        ) {
+            Ok(num) => inserted += num,
+            Err(error) => {
+                log::error!("Failed insert pattern_events! {}", error);
+                break;
+            }
+        }
+    }
+    crate::metrics::rows_inserted("pattern_events", inserted);
+    inserted
+}
+
 pub fn recurring_events_count(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     symbol: &str
 ) -> usize {
     let mut num = 0_usize;
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -417,11 +494,11 @@ pub fn recurring_events_count(
 }
 
 pub fn recurring_events(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     symbol: &str
 ) -> Vec<super::RecurringEventData> {
     let mut t = Vec::new();
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -490,14 +567,14 @@ pub fn recurring_events(
 }
 
 pub fn insert_recurring_events(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     series: &Vec<super::RecurringEventData>,
 ) {
     if series.len() == 0 {
         return;
     }
     let existing = recurring_events(sql_connection.clone(), &series[0].symbol);
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.write() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -522,10 +599,10 @@ pub fn insert_recurring_events(
 }
 
 pub fn _delete_recurring_events(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     series: &Vec<super::RecurringEventData>,
 ) {
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.write() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -539,7 +616,7 @@ pub fn _delete_recurring_events(
 }
 
 pub fn _update_recurring_events(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     series: &Vec<super::RecurringEventData>,
 ) {
     _delete_recurring_events(sql_connection.clone(), series);
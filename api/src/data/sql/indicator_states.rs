@@ -0,0 +1,112 @@
+//! Storage of the state of the incremental indicators
+//!
+//! The state of an `IndicatorSet` is kept as JSON per symbol, exchange and name, the name tells
+//! the sets of a symbol apart, e.g. `live_data` for the indicator columns of the live bars.
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::analytics::incremental::IndicatorSet;
+
+/// the stored state, `None` if there is none or it can not be read
+pub(crate) fn load(
+    connection: &Connection,
+    symbol: &str,
+    exchange: &str,
+    name: &str,
+) -> Result<Option<IndicatorSet>, rusqlite::Error> {
+    let state: Option<String> = connection
+        .prepare_cached("SELECT state FROM indicator_states WHERE symbol = ?1 AND exchange = ?2 AND name = ?3")?
+        .query_row(params![symbol, exchange, name], |row| row.get(0))
+        .optional()?;
+    Ok(state.and_then(|state| match serde_json::from_str(&state) {
+        Ok(set) => Some(set),
+        Err(error) => {
+            log::warn!("Dropping unreadable indicator state {} of {}! {}", name, symbol, error);
+            None
+        }
+    }))
+}
+
+/// store the state, replacing the previous one
+pub(crate) fn save(
+    connection: &Connection,
+    symbol: &str,
+    exchange: &str,
+    name: &str,
+    set: &IndicatorSet,
+) -> Result<usize, rusqlite::Error> {
+    let state = serde_json::to_string(set).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    connection.prepare_cached(
+        "INSERT INTO indicator_states (symbol, exchange, name, state, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(symbol, exchange, name) DO UPDATE SET state = excluded.state, updated_at = excluded.updated_at",
+    )?
+    .execute(params![symbol, exchange, name, &state, chrono::Utc::now().timestamp()])
+}
+
+/// the stored indicator state of a symbol
+pub fn indicator_state(
+    sql_connection: super::Database,
+    symbol: &str,
+    exchange: &str,
+    name: &str,
+) -> Option<IndicatorSet> {
+    let connection = match sql_connection.read() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return None;
+        }
+    };
+    match load(&connection, symbol, exchange, name) {
+        Ok(set) => set,
+        Err(error) => {
+            log::error!("Failed to read indicator state {} of {}! {}", name, symbol, error);
+            None
+        }
+    }
+}
+
+/// store the indicator state of a symbol, returns the number of stored states
+pub fn save_indicator_state(
+    sql_connection: super::Database,
+    symbol: &str,
+    exchange: &str,
+    name: &str,
+    set: &IndicatorSet,
+) -> u32 {
+    let connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return 0;
+        }
+    };
+    match save(&connection, symbol, exchange, name, set) {
+        Ok(n) => n as u32,
+        Err(error) => {
+            log::error!("Failed to store indicator state {} of {}! {}", name, symbol, error);
+            0
+        }
+    }
+}
+
+/// forget the indicator states of a symbol, e.g. after its history was replaced
+pub fn delete_indicator_states(
+    sql_connection: super::Database,
+    symbol: &str,
+) -> u32 {
+    let connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return 0;
+        }
+    };
+    match connection.execute("DELETE FROM indicator_states WHERE symbol = ?1", params![symbol]) {
+        Ok(n) => n as u32,
+        Err(error) => {
+            log::error!("Failed to delete the indicator states of {}! {}", symbol, error);
+            0
+        }
+    }
+}
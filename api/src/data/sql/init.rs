@@ -1,229 +1,16 @@
 use std::path::PathBuf;
 use rusqlite::{params, Connection};
 
+/// create the schema of a new database and fill it with the initial data
 pub fn init_database(sqlite_file: PathBuf) {
     use std::fs::File;
     use std::io::Read;
 
-    if let Ok(connection) = Connection::open(sqlite_file) {
-        println!("{}", connection.is_autocommit());
-        // file_type: 0 other file, 1 image, 2, video, 3 audio
-        match connection.execute(
-            "CREATE TABLE IF NOT EXISTS active_symbols (
-                symbols_id INTEGER,
-                symbol TEXT,
-                PRIMARY KEY(symbols_id AUTOINCREMENT)
-            )",
-            (),
-        ) {
-            Ok(_ret) => {}
+    if let Ok(mut connection) = Connection::open(sqlite_file) {
+        match super::migrations::migrate(&mut connection) {
+            Ok(_applied) => {}
             Err(error) => {
-                log::error!("Failed to create table active_symbols: {}", error);
-                return;
-            }
-        }
-        match connection.execute(
-            "CREATE INDEX index_symbol_active_symbols ON active_symbols (symbol)",
-            (),
-        ) {
-            Ok(_ret) => {}
-            Err(error) => {
-                log::error!("Failed to create index on active_symbols: {}", error);
-                return;
-            }
-        }
-        match connection.execute(
-            "CREATE TABLE exchanges (
-                exchange_id INTEGER,
-                title TEXT,
-                name TEXT,
-                code TEXT,
-                country TEXT,
-                timezone TEXT,
-                PRIMARY KEY(exchange_id AUTOINCREMENT)
-            )",
-            (),
-        ) {
-            Ok(_ret) => {}
-            Err(error) => {
-                log::error!("Failed to create table time_series: {}", error);
-                return;
-            }
-        }
-        match connection.execute(
-            "CREATE TABLE live_data (
-                timestamp_id INTEGER,
-                timestamp INTEGER,
-                symbol TEXT,
-                currency TEXT,
-                exchange TEXT,
-                open DOUBLE,
-                high DOUBLE,
-                low DOUBLE,
-                close DOUBLE,
-                volume DOUBLE,
-                sma DOUBLE,
-                ema DOUBLE,
-                rsi DOUBLE,
-                stochastic DOUBLE,
-                macd_value DOUBLE,
-                signal_value DOUBLE,
-                hist_value DOUBLE,
-                PRIMARY KEY(timestamp_id AUTOINCREMENT)
-            )",
-            (),
-        ) {
-            Ok(_ret) => {}
-            Err(error) => {
-                log::error!("Failed to create table live_data: {}", error);
-                return;
-            }
-        }
-        match connection.execute(
-            "CREATE INDEX index_timestamp_live_data ON live_data (timestamp)",
-            (),
-        ) {
-            Ok(_ret) => {}
-            Err(error) => {
-                log::error!("Failed to create index on live_data: {}", error);
-                return;
-            }
-        }
-        match connection.execute(
-            "CREATE INDEX index_symbol_live_data ON live_data (symbol)",
-            (),
-        ) {
-            Ok(_ret) => {}
-            Err(error) => {
-                log::error!("Failed to create index on time_series: {}", error);
-                return;
-            }
-        }
-        match connection.execute(
-            "CREATE TABLE stocks (
-                            stock_id INTEGER,
-                            symbol TEXT,
-                            name TEXT,
-                            currency TEXT,
-                            exchange TEXT,
-                            mic_code TEXT,
-                            country TEXT,
-                            type TEXT,
-                            figi_code TEXT,
-                            cfi_code TEXT,
-                            isin TEXT,
-                            cusip TEXT,
-                            PRIMARY KEY(stock_id AUTOINCREMENT)
-            )",
-            (),
-        ) {
-            Ok(_ret) => {}
-            Err(error) => {
-                log::error!("Failed to create table stocks: {}", error);
-                return;
-            }
-        }
-        match connection.execute("CREATE INDEX index_symbod_stocks ON stocks (symbol)", ()) {
-            Ok(_ret) => {}
-            Err(error) => {
-                log::error!("Failed to create index on stocks: {}", error);
-                return;
-            }
-        }
-        match connection.execute(
-            "CREATE TABLE IF NOT EXISTS time_series (
-                timestamp_id INTEGER,
-                timestamp INTEGER,
-                symbol TEXT,
-                currency TEXT,
-                exchange TEXT,
-                open DOUBLE,
-                high DOUBLE,
-                low DOUBLE,
-                close DOUBLE,
-                volume DOUBLE,
-                PRIMARY KEY(timestamp_id AUTOINCREMENT)
-            )",
-            (),
-        ) {
-            Ok(_ret) => {}
-            Err(error) => {
-                log::error!("Failed to create table time_series: {}", error);
-                return;
-            }
-        }
-        match connection.execute(
-            "CREATE INDEX index_timestamp_time_series ON time_series (timestamp)",
-            (),
-        ) {
-            Ok(_ret) => {}
-            Err(error) => {
-                log::error!("Failed to create index on time_series: {}", error);
-                return;
-            }
-        }
-        match connection.execute(
-            "CREATE INDEX index_symbol_time_series ON time_series (symbol)",
-            (),
-        ) {
-            Ok(_ret) => {}
-            Err(error) => {
-                log::error!("Failed to create index on time_series: {}", error);
-                return;
-            }
-        }
-        match connection.execute(
-            "CREATE TABLE yahoosymbols(
-                ysymbol_id INTEGER, 
-                symbol TEXT, 
-                name TEXT, 
-                PRIMARY KEY(ysymbol_id AUTOINCREMENT) 
-            )",
-            (),
-        ) {
-            Ok(_ret) => {}
-            Err(error) => {
-                log::error!("Failed to create table yahoosymbols: {}", error);
-                return;
-            }
-        }
-        match connection.execute(
-            "CREATE INDEX index_symbol_yahoosymbols ON yahoosymbols (symbol)",
-            (),
-        ) {
-            Ok(_ret) => {}
-            Err(error) => {
-                log::error!("Failed to create index on yahoosymbols: {}", error);
-                return;
-            }
-        }
-        match connection.execute(
-            "CREATE TABLE jump_events(jump_id INTEGER, timestamp INTEGER, symbol TEXT, percent DOUBLE, PRIMARY KEY(jump_id AUTOINCREMENT) )",
-            (),
-        ) {
-            Ok(_ret) => {}
-            Err(error) => {
-                log::error!("Failed to create table yahoosymbols: {}", error);
-                return;
-            }
-        }
-        match connection.execute(
-            "CREATE TABLE drop_events(drop_id INTEGER, timestamp INTEGER, symbol TEXT, percent DOUBLE, PRIMARY KEY(drop_id AUTOINCREMENT) )",
-            (),
-        ) {
-            Ok(_ret) => {}
-            Err(error) => {
-                log::error!("Failed to create table yahoosymbols: {}", error);
-                return;
-            }
-        }
-        match connection.execute(
-            "CREATE TABLE recurring_events(recur_id INTEGER, symbol TEXT, minutes_period INTEGER, percent DOUBLE, PRIMARY KEY(recur_id AUTOINCREMENT) )",
-            (),
-        ) {
-            Ok(_ret) => {}
-            Err(error) => {
-                log::error!("Failed to create table yahoosymbols: {}", error);
+                log::error!("Failed to create the database schema: {}", error);
                 return;
             }
         }
@@ -0,0 +1,241 @@
+//! Record of the runs of scheduled jobs
+
+use rusqlite::params;
+use std::str::FromStr;
+
+/// State of a job run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobStatus {
+    Running,
+    Ok,
+    Failed,
+    /// not started because the previous run was still busy
+    Skipped,
+    /// the daemon stopped while the job was running
+    Interrupted,
+}
+
+impl JobStatus {
+    /// name stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Running => "running",
+            JobStatus::Ok => "ok",
+            JobStatus::Failed => "failed",
+            JobStatus::Skipped => "skipped",
+            JobStatus::Interrupted => "interrupted",
+        }
+    }
+}
+
+impl FromStr for JobStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "running" => Ok(JobStatus::Running),
+            "ok" => Ok(JobStatus::Ok),
+            "failed" => Ok(JobStatus::Failed),
+            "skipped" => Ok(JobStatus::Skipped),
+            "interrupted" => Ok(JobStatus::Interrupted),
+            _ => Err(format!("Unknown job status {}", s)),
+        }
+    }
+}
+
+/// One run of a job, times are seconds since 1.1.1970
+#[derive(Clone, Debug, PartialEq)]
+pub struct JobRun {
+    pub run_id: i64,
+    pub job: String,
+    /// the time the schedule was due, earlier than `started_at` for a catch-up run
+    pub scheduled_at: i64,
+    pub started_at: i64,
+    pub finished_at: Option<i64>,
+    pub status: JobStatus,
+    pub message: String,
+}
+
+/// record the start of a run, returns its id
+pub fn start_job_run(
+    sql_connection: super::Database,
+    job: &str,
+    scheduled_at: i64,
+    started_at: i64,
+) -> Option<i64> {
+    let connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return None;
+        }
+    };
+    match connection.execute(
+        "INSERT INTO job_runs (job, scheduled_at, started_at, status) VALUES (?1, ?2, ?3, ?4)",
+        params![job, scheduled_at, started_at, JobStatus::Running.as_str()],
+    ) {
+        Ok(_) => Some(connection.last_insert_rowid()),
+        Err(error) => {
+            log::error!("Failed insert job_runs of {}! {}", job, error);
+            None
+        }
+    }
+}
+
+/// record the end of a run
+pub fn finish_job_run(
+    sql_connection: super::Database,
+    run_id: i64,
+    status: JobStatus,
+    message: &str,
+) -> u32 {
+    let connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return 0;
+        }
+    };
+    match connection.execute(
+        "UPDATE job_runs SET finished_at = ?2, status = ?3, message = ?4 WHERE run_id = ?1",
+        params![run_id, chrono::Utc::now().timestamp(), status.as_str(), message],
+    ) {
+        Ok(_) => 1,
+        Err(error) => {
+            log::error!("Failed to update job run {}! {}", run_id, error);
+            0
+        }
+    }
+}
+
+/// record a run that was not started
+pub fn skip_job_run(
+    sql_connection: super::Database,
+    job: &str,
+    scheduled_at: i64,
+    message: &str,
+) -> u32 {
+    let now = chrono::Utc::now().timestamp();
+    match start_job_run(sql_connection.clone(), job, scheduled_at, now) {
+        Some(run_id) => finish_job_run(sql_connection, run_id, JobStatus::Skipped, message),
+        None => 0,
+    }
+}
+
+/// mark the runs left as running by a previous process as interrupted, returns their number
+pub fn interrupt_running_jobs(
+    sql_connection: super::Database,
+) -> usize {
+    let connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return 0;
+        }
+    };
+    match connection.execute(
+        "UPDATE job_runs SET status = ?1 WHERE status = ?2",
+        params![JobStatus::Interrupted.as_str(), JobStatus::Running.as_str()],
+    ) {
+        Ok(num) => num,
+        Err(error) => {
+            log::error!("Failed to update job_runs! {}", error);
+            0
+        }
+    }
+}
+
+/// the latest runs of a job, newest first
+pub fn job_runs(
+    sql_connection: super::Database,
+    job: &str,
+    limit: usize,
+) -> Vec<JobRun> {
+    let mut v = Vec::new();
+    let connection = match sql_connection.read() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return v;
+        }
+    };
+    let query = "SELECT run_id, job, scheduled_at, started_at, finished_at, status, message FROM job_runs
+        WHERE job = ?1 ORDER BY scheduled_at DESC, run_id DESC LIMIT ?2";
+    let mut statement = match connection.prepare(query) {
+        Ok(s) => s,
+        Err(error) => {
+            log::error!("could not prepare SQL statement: {}", error);
+            return v;
+        }
+    };
+    let rows = statement.query_map(params![job, limit as i64], |row| {
+        let status: String = row.get(5)?;
+        Ok(JobRun {
+            run_id: row.get(0)?,
+            job: row.get(1)?,
+            scheduled_at: row.get(2)?,
+            started_at: row.get(3)?,
+            finished_at: row.get(4)?,
+            status: status.parse().unwrap_or(JobStatus::Interrupted),
+            message: row.get::<_, Option<String>>(6)?.unwrap_or_default(),
+        })
+    });
+    match rows {
+        Ok(rows) => {
+            for row in rows {
+                match row {
+                    Ok(r) => v.push(r),
+                    Err(error) => log::error!("Failed to read a row from job_runs: {}", error),
+                }
+            }
+        }
+        Err(error) => log::error!("could not read from job_runs: {}", error),
+    }
+    v
+}
+
+/// the scheduled time of the newest run of a job that finished, successful or not
+pub fn last_scheduled_run(
+    sql_connection: super::Database,
+    job: &str,
+) -> Option<i64> {
+    let connection = match sql_connection.read() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return None;
+        }
+    };
+    match connection.query_row(
+        "SELECT MAX(scheduled_at) FROM job_runs WHERE job = ?1 AND status IN (?2, ?3)",
+        params![job, JobStatus::Ok.as_str(), JobStatus::Failed.as_str()],
+        |row| row.get(0),
+    ) {
+        Ok(t) => t,
+        Err(error) => {
+            log::error!("could not read from job_runs: {}", error);
+            None
+        }
+    }
+}
+
+/// remove the runs scheduled before `timestamp`, returns their number
+pub fn delete_job_runs_before(
+    sql_connection: super::Database,
+    timestamp: i64,
+) -> usize {
+    let connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return 0;
+        }
+    };
+    match connection.execute("DELETE FROM job_runs WHERE scheduled_at < ?1", params![timestamp]) {
+        Ok(num) => num,
+        Err(error) => {
+            log::error!("Failed to delete from job_runs! {}", error);
+            0
+        }
+    }
+}
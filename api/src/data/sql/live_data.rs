@@ -2,11 +2,11 @@ use rusqlite::params;
 
 /// return the number of time series data for the stock
 pub fn live_data_count(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     metadata: &super::MetaData,
 ) -> usize {
     let mut num = 0_usize;
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -53,22 +53,44 @@ pub fn live_data_count(
     num
 }
 
+/// time of the newest minute bar of the symbol at the exchange of the metadata
+pub fn last_live_timestamp(
+    sql_connection: super::Database,
+    metadata: &super::MetaData,
+) -> Option<i64> {
+    let connection = match sql_connection.read() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return None;
+        }
+    };
+    let query = "SELECT MAX(timestamp) FROM live_data WHERE symbol = ?1 AND IFNULL(exchange, '') = ?2";
+    match connection.query_row(query, params![&metadata.symbol, &metadata.exchange], |row| row.get(0)) {
+        Ok(timestamp) => timestamp,
+        Err(error) => {
+            log::error!("could not read the newest live_data of {}: {}", metadata.symbol, error);
+            None
+        }
+    }
+}
+
 pub fn live_data_all(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     metadata: &super::MetaData,
 ) -> Vec<super::TimeSeriesData> {
     let mut t = Vec::new();
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
             return t;
         }
     };
-    let query = "SELECT timestamp, open, high, low, close, volume FROM live_data WHERE symbol = ?1 ORDER BY timestamp ASC";
+    let query = "SELECT timestamp, open, high, low, close, volume FROM live_data WHERE symbol = ?1 AND (quality & ?2) = 0 ORDER BY timestamp ASC";
     match connection.prepare(query) {
         Ok(mut statement) => {
-            match statement.query(params![&metadata.symbol]) {
+            match statement.query(params![&metadata.symbol, crate::data::quality::EXCLUDED]) {
                 Ok(mut rows) => {
                     loop {
                         match rows.next() {
@@ -161,11 +183,11 @@ fn timestamps_daily(start_date: &chrono::DateTime<chrono::Utc>, offset: i64) ->
 }
 
 pub fn live_data(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     metadata: &super::MetaData,
 ) -> Vec<Vec<super::TimeSeriesData>> {
     let mut v = Vec::new();
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -179,10 +201,10 @@ pub fn live_data(
         if start == 0 && end == 0 {
             continue;
         }
-        let query = "SELECT timestamp, open, high, low, close, volume FROM live_data WHERE symbol = ?1 AND timestamp BETWEEN ?2 AND ?3 ORDER BY timestamp ASC";
+        let query = "SELECT timestamp, open, high, low, close, volume FROM live_data WHERE symbol = ?1 AND timestamp BETWEEN ?2 AND ?3 AND (quality & ?4) = 0 ORDER BY timestamp ASC";
         match connection.prepare(query) {
             Ok(mut statement) => {
-                match statement.query(params![&metadata.symbol, &start, &end]) {
+                match statement.query(params![&metadata.symbol, &start, &end, crate::data::quality::EXCLUDED]) {
                     Ok(mut rows) => {
                         loop {
                             match rows.next() {
@@ -267,11 +289,11 @@ pub fn live_data(
 }
 
 pub fn all_live_data(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     metadata: &super::MetaData,
 ) -> Vec<super::TimeSeriesData> {
     let mut t = Vec::new();
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -280,10 +302,10 @@ pub fn all_live_data(
     };
     let start = metadata.start_date.timestamp();
     let end = metadata.end_date.timestamp();
-    let query = "SELECT timestamp, open, high, low, close, volume FROM live_data WHERE symbol = ?1 AND timestamp BETWEEN ?2 AND ?3 ORDER BY timestamp ASC";
+    let query = "SELECT timestamp, open, high, low, close, volume FROM live_data WHERE symbol = ?1 AND timestamp BETWEEN ?2 AND ?3 AND (quality & ?4) = 0 ORDER BY timestamp ASC";
     match connection.prepare(query) {
         Ok(mut statement) => {
-            match statement.query(params![&metadata.symbol, &start, &end]) {
+            match statement.query(params![&metadata.symbol, &start, &end, crate::data::quality::EXCLUDED]) {
                 Ok(mut rows) => {
                     loop {
                         match rows.next() {
@@ -365,98 +387,310 @@ pub fn all_live_data(
     t
 }
 
+/// the quality flags of a bar delivered again are kept unless its values changed
+const UPSERT_LIVE_DATA: &str = "INSERT INTO live_data (timestamp, symbol, currency, exchange, open, high, low, close, volume, sma, ema, rsi, stochastic, macd_value, signal_value, hist_value ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+    ON CONFLICT(symbol, exchange, timestamp) DO UPDATE SET currency = excluded.currency, open = excluded.open, high = excluded.high, low = excluded.low, close = excluded.close, volume = excluded.volume,
+    quality = CASE WHEN open IS excluded.open AND high IS excluded.high AND low IS excluded.low AND close IS excluded.close AND volume IS excluded.volume THEN quality ELSE 0 END,
+    sma = COALESCE(excluded.sma, sma), ema = COALESCE(excluded.ema, ema), rsi = COALESCE(excluded.rsi, rsi), stochastic = COALESCE(excluded.stochastic, stochastic),
+    macd_value = COALESCE(excluded.macd_value, macd_value), signal_value = COALESCE(excluded.signal_value, signal_value), hist_value = COALESCE(excluded.hist_value, hist_value)";
+
+/// name of the indicator state of the indicator columns in `indicator_states`
+const LIVE_DATA_INDICATORS: &str = "live_data";
+
+/// stored bars the indicators are seeded with at most, well beyond the longest warmup
+const SEED_BARS: i64 = 300;
+
+/// timestamps already stored in the time range of the series
+fn existing_timestamps(
+    transaction: &rusqlite::Transaction,
+    metadata: &super::MetaData,
+    series: &[super::TimeSeriesData],
+) -> Result<std::collections::BTreeSet<i64>, rusqlite::Error> {
+    let mut exists = std::collections::BTreeSet::new();
+    let start = match series.iter().map(|t| t.datetime).min() {
+        Some(s) => s,
+        None => return Ok(exists),
+    };
+    let end = series.iter().map(|t| t.datetime).max().unwrap_or(start);
+    let mut statement = transaction.prepare_cached(
+        "SELECT timestamp FROM live_data WHERE symbol = ?1 AND exchange = ?2 AND timestamp BETWEEN ?3 AND ?4",
+    )?;
+    let rows = statement.query_map(params![&metadata.symbol, &metadata.exchange, &start, &end], |row| row.get(0))?;
+    for timestamp in rows {
+        exists.insert(timestamp?);
+    }
+    Ok(exists)
+}
+
+/// upsert the bars with their indicators, returns the bars that were not stored before.
+/// Bars without indicators keep the stored ones
+fn upsert_live_data(
+    transaction: &rusqlite::Transaction,
+    metadata: &super::MetaData,
+    series: &[super::TimeSeriesData],
+    indicators: &[Option<LiveIndicators>],
+) -> Result<Vec<super::TimeSeriesData>, rusqlite::Error> {
+    let exists = existing_timestamps(transaction, metadata, series)?;
+    let mut v = Vec::new();
+    let mut statement = transaction.prepare_cached(UPSERT_LIVE_DATA)?;
+    for (t, ind) in series.iter().zip(indicators.iter()) {
+        let value = |f: fn(&LiveIndicators) -> f64| ind.as_ref().map(f);
+        statement.execute(params![&t.datetime, &metadata.symbol, &metadata.currency, &metadata.exchange, &t.open, &t.high, &t.low, &t.close, &t.volume,
+            value(|i| i.sma), value(|i| i.ema), value(|i| i.rsi), value(|i| i.stochastic), value(|i| i.macd_value), value(|i| i.signal_value), value(|i| i.hist_value) ])?;
+        if !exists.contains(&t.datetime) {
+            v.push(t.clone());
+        }
+    }
+    Ok(v)
+}
+
+/// store minutely bars of the market-data crate with the indicators it calculated.
+/// `datetimes` are the bar times of the provider's answer in `metadata.exchange_timezone`
 pub fn insert_live_data(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     metadata: &super::MetaData,
     series: &market_data::EnhancedMarketSeries,
+    datetimes: &[chrono::NaiveDateTime],
 ) -> Vec<super::TimeSeriesData> {
-    let existing = all_live_data(sql_connection.clone(), metadata);
-    let exists: std::collections::BTreeSet<i64> = existing.iter().map(|t| t.datetime).collect();
-    let mut v = Vec::new();
-    let connection = match sql_connection.lock() {
-        Ok(conn) => conn,
+    let timeseries = match crate::data::livedata::marketdata_to_timeseries(series, datetimes, &metadata.exchange_timezone) {
+        Ok(t) => t,
         Err(error) => {
-            log::error!("Failed to lock sql connection for use! {}", error);
-            return v;
+            log::error!("Failed to convert live_data of {}! {}", metadata.symbol, error);
+            return Vec::new();
         }
     };
     let num_values = series.series.len();
-    let base_timestamp = chrono::Utc::now().timestamp();
+    let mut indicators = Vec::with_capacity(num_values);
     for i in 0..num_values {
-        let timestamp = base_timestamp - (num_values - i) as i64 * 60;
-        if exists.contains(&timestamp) {
-            continue;
-        }
-        let mut sma = 0.0_f32;
-        let mut ema = 0.0_f32;
-        let mut rsi = 0.0_f32;
-        let mut stochastic = 0.0_f32;
-        let mut macd_value = 0.0_f32;
-        let mut signal_value = 0.0_f32;
-        let mut hist_value = 0.0_f32;
+        let mut ind = LiveIndicators::default();
         for (_indicator_name, indicator_values) in &series.indicators.sma {
             if let Some(value) = indicator_values.get(i) {
-                sma = value.to_owned();
+                ind.sma = *value as f64;
             }
         }
 
         for (_indicator_name, indicator_values) in &series.indicators.ema {
             if let Some(value) = indicator_values.get(i) {
-                ema = value.to_owned();
+                ind.ema = *value as f64;
             }
         }
 
         for (_indicator_name, indicator_values) in &series.indicators.rsi {
             if let Some(value) = indicator_values.get(i) {
-                rsi = value.to_owned();
+                ind.rsi = *value as f64;
             }
         }
 
         for (_indicator_name, indicator_values) in &series.indicators.stochastic {
             if let Some(value) = indicator_values.get(i) {
-                stochastic = value.to_owned();
+                ind.stochastic = *value as f64;
             }
         }
         for (_indicator_name, (macd, signal, histogram)) in &series.indicators.macd {
             if let Some(val1) = macd.get(i) {
                 if let Some(val2) = signal.get(i) {
                     if let Some(val3) = histogram.get(i) {
-                        macd_value = val1.to_owned();
-                        signal_value = val2.to_owned();
-                        hist_value = val3.to_owned();
+                        ind.macd_value = *val1 as f64;
+                        ind.signal_value = *val2 as f64;
+                        ind.hist_value = *val3 as f64;
                     }
                 }
             }
         }
-        match connection.execute(
-            "INSERT INTO live_data (timestamp, symbol, currency, exchange, open, high, low, close, volume, sma, ema, rsi, stochastic, macd_value, signal_value, hist_value ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
-            params![&timestamp, &metadata.symbol, &metadata.currency, &metadata.exchange, &series.series[i].open, &series.series[i].high, &series.series[i].low, &series.series[i].close, &series.series[i].volume, &sma, &ema, &rsi, &stochastic, &macd_value, &signal_value, &hist_value ],
-        ) {
-            Ok(_retval) => {} //log::warn!("Inserted {} video with ID {} and location {} into candidates.", video.id, video.index, candidate_id),
+        indicators.push(Some(ind));
+    }
+
+    let mut connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return Vec::new();
+        }
+    };
+    let transaction = match connection.transaction() {
+        Ok(t) => t,
+        Err(error) => {
+            log::error!("Failed to start transaction! {}", error);
+            return Vec::new();
+        }
+    };
+    let v = match upsert_live_data(&transaction, metadata, &timeseries, &indicators) {
+        Ok(v) => v,
+        Err(error) => {
+            log::error!("Failed insert live_data! {}", error);
+            return Vec::new();
+        }
+    };
+    match transaction.commit() {
+        Ok(()) => {
+            crate::metrics::rows_inserted("live_data", timeseries.len());
+            v
+        }
+        Err(error) => {
+            log::error!("Failed to commit live_data! {}", error);
+            Vec::new()
+        }
+    }
+}
+
+/// indicator columns stored with every row of live_data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LiveIndicators {
+    pub sma: f64,
+    pub ema: f64,
+    pub rsi: f64,
+    pub stochastic: f64,
+    pub macd_value: f64,
+    pub signal_value: f64,
+    pub hist_value: f64,
+}
+
+impl LiveIndicators {
+    /// the columns from the values of `IndicatorSet::live_data`, missing values are 0
+    pub fn from_values(values: &std::collections::BTreeMap<String, f64>) -> Self {
+        let value = |name: &str| values.get(name).copied().unwrap_or_default();
+        LiveIndicators {
+            sma: value("sma-10"),
+            ema: value("ema-20"),
+            rsi: value("rsi-14"),
+            stochastic: value("fs-14"),
+            macd_value: value("macd-(12,26,9)"),
+            signal_value: value("macd_signal-(12,26,9)"),
+            hist_value: value("macd_divergence-(12,26,9)"),
+        }
+    }
+}
+
+/// stored bars of a symbol newer than `after` and older than `before`, at most the newest
+/// `SEED_BARS` of them ordered by time
+fn stored_bars(
+    transaction: &rusqlite::Transaction,
+    metadata: &super::MetaData,
+    after: Option<i64>,
+    before: i64,
+) -> Result<Vec<super::TimeSeriesData>, rusqlite::Error> {
+    let mut statement = transaction.prepare_cached(
+        "SELECT timestamp, open, high, low, close, volume FROM live_data
+            WHERE symbol = ?1 AND exchange = ?2 AND timestamp > ?3 AND timestamp < ?4 AND (quality & ?5) = 0
+            ORDER BY timestamp DESC LIMIT ?6",
+    )?;
+    let rows = statement.query_map(
+        params![&metadata.symbol, &metadata.exchange, after.unwrap_or(i64::MIN), before, crate::data::quality::EXCLUDED, SEED_BARS],
+        |row| {
+            Ok(super::TimeSeriesData {
+                datetime: row.get(0)?,
+                open: row.get(1)?,
+                high: row.get(2)?,
+                low: row.get(3)?,
+                close: row.get(4)?,
+                volume: row.get(5)?,
+            })
+        },
+    )?;
+    let mut bars = rows.collect::<Result<Vec<_>, _>>()?;
+    bars.reverse();
+    Ok(bars)
+}
+
+/// the indicator columns of the bars, continued from the stored indicator state: SMA 10,
+/// EMA 20, RSI 14, fast stochastic 14 and MACD 12/26/9 like the market-data crate calculates them.
+/// Without a state the indicators are seeded from the stored bars. Bars the state has seen
+/// already get `None` and keep their stored columns, the newest bar is not added to the state
+/// as the provider may still update it
+fn live_indicators(
+    transaction: &rusqlite::Transaction,
+    metadata: &super::MetaData,
+    series: &[super::TimeSeriesData],
+) -> Result<Vec<Option<LiveIndicators>>, rusqlite::Error> {
+    use crate::analytics::incremental::IndicatorSet;
+
+    let (Some(first), Some(newest)) = (series.iter().map(|t| t.datetime).min(), series.iter().map(|t| t.datetime).max()) else {
+        return Ok(Vec::new());
+    };
+    let mut set = super::indicator_states::load(transaction, &metadata.symbol, &metadata.exchange, LIVE_DATA_INDICATORS)?
+        .unwrap_or_else(IndicatorSet::live_data);
+    // catch up with the bars stored since the state was saved
+    set.update(&stored_bars(transaction, metadata, set.last(), first)?);
+    let mut indicators = Vec::with_capacity(series.len());
+    for t in series.iter() {
+        if set.last().is_some_and(|last| t.datetime <= last) {
+            indicators.push(None);
+        } else if t.datetime == newest {
+            indicators.push(Some(LiveIndicators::from_values(&set.peek(t))));
+        } else {
+            set.update(std::slice::from_ref(t));
+            indicators.push(Some(LiveIndicators::from_values(&set.values(false))));
+        }
+    }
+    super::indicator_states::save(transaction, &metadata.symbol, &metadata.exchange, LIVE_DATA_INDICATORS, &set)?;
+    Ok(indicators)
+}
+
+/// store minutely bars delivered by a `MarketDataProvider` together with their indicators,
+/// existing bars are updated. Returns the bars that were not yet in the database
+pub fn insert_live_bars(
+    sql_connection: super::Database,
+    metadata: &super::MetaData,
+    series: &[super::TimeSeriesData],
+) -> Vec<super::TimeSeriesData> {
+    insert_live_bars_batch(sql_connection, &[(metadata, series)]).pop().unwrap_or_default()
+}
+
+/// store the minutely bars of several symbols in one transaction,
+/// returns the newly stored bars of every symbol. Nothing is stored if one of the inserts fails
+pub fn insert_live_bars_batch(
+    sql_connection: super::Database,
+    batch: &[(&super::MetaData, &[super::TimeSeriesData])],
+) -> Vec<Vec<super::TimeSeriesData>> {
+    let mut connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return Vec::new();
+        }
+    };
+    let transaction = match connection.transaction() {
+        Ok(t) => t,
+        Err(error) => {
+            log::error!("Failed to start transaction! {}", error);
+            return Vec::new();
+        }
+    };
+    let mut stored = Vec::new();
+    for (metadata, series) in batch.iter() {
+        let indicators = match live_indicators(&transaction, metadata, series) {
+            Ok(i) => i,
             Err(error) => {
-                log::error!("Failed insert live_data! {}", error);
-                return v;
+                log::error!("Failed to calculate the indicators of {}! {}", metadata.symbol, error);
+                return Vec::new();
             }
-        }
-        let t = super::TimeSeriesData {
-            datetime: timestamp,
-            open: series.series[i].open as f64,
-            high: series.series[i].high as f64,
-            low: series.series[i].low as f64,
-            close: series.series[i].close as f64,
-            volume: series.series[i].volume as f64,
         };
-        v.push(t);
+        match upsert_live_data(&transaction, metadata, series, &indicators) {
+            Ok(v) => stored.push(v),
+            Err(error) => {
+                log::error!("Failed insert live_data of {}! {}", metadata.symbol, error);
+                return Vec::new();
+            }
+        }
+    }
+    match transaction.commit() {
+        Ok(()) => {
+            crate::metrics::rows_inserted("live_data", batch.iter().map(|(_, series)| series.len()).sum());
+            stored
+        }
+        Err(error) => {
+            log::error!("Failed to commit live_data! {}", error);
+            Vec::new()
+        }
     }
-    v
 }
 
 pub fn _delete_live_data(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     metadata: &super::MetaData,
     _timeseries: &market_data::EnhancedMarketSeries,
 ) {
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.write() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -470,10 +704,11 @@ pub fn _delete_live_data(
 }
 
 pub fn _update_live_data(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     metadata: &super::MetaData,
     timeseries: &market_data::EnhancedMarketSeries,
+    datetimes: &[chrono::NaiveDateTime],
 ) {
     _delete_live_data(sql_connection.clone(), metadata, timeseries);
-    insert_live_data(sql_connection.clone(), metadata, timeseries);
+    insert_live_data(sql_connection.clone(), metadata, timeseries, datetimes);
 }
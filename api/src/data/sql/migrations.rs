@@ -0,0 +1,409 @@
+//! Versioned schema migrations
+//!
+//! Every change of the database schema is appended to `MIGRATIONS` with the next version number.
+//! Applied versions are recorded in the `schema_version` table, each migration runs in its own
+//! transaction so a failing migration leaves the database at the previous version.
+//! Migrations must never be edited once released, add a new one instead.
+
+use rusqlite::{params, Connection};
+use std::error::Error;
+
+/// One forward migration of the schema
+#[derive(Debug)]
+pub struct Migration {
+    /// version of the schema after this migration
+    pub version: u32,
+    /// short description shown in the status report
+    pub description: &'static str,
+    /// SQL statements executed as one batch
+    pub sql: &'static str,
+}
+
+/// all migrations in ascending version order
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        // IF NOT EXISTS, databases created before the migrations already have these tables
+        sql: "
+            CREATE TABLE IF NOT EXISTS active_symbols (
+                symbols_id INTEGER,
+                symbol TEXT,
+                PRIMARY KEY(symbols_id AUTOINCREMENT)
+            );
+            CREATE INDEX IF NOT EXISTS index_symbol_active_symbols ON active_symbols (symbol);
+            CREATE TABLE IF NOT EXISTS exchanges (
+                exchange_id INTEGER,
+                title TEXT,
+                name TEXT,
+                code TEXT,
+                country TEXT,
+                timezone TEXT,
+                PRIMARY KEY(exchange_id AUTOINCREMENT)
+            );
+            CREATE TABLE IF NOT EXISTS live_data (
+                timestamp_id INTEGER,
+                timestamp INTEGER,
+                symbol TEXT,
+                currency TEXT,
+                exchange TEXT,
+                open DOUBLE,
+                high DOUBLE,
+                low DOUBLE,
+                close DOUBLE,
+                volume DOUBLE,
+                sma DOUBLE,
+                ema DOUBLE,
+                rsi DOUBLE,
+                stochastic DOUBLE,
+                macd_value DOUBLE,
+                signal_value DOUBLE,
+                hist_value DOUBLE,
+                PRIMARY KEY(timestamp_id AUTOINCREMENT)
+            );
+            CREATE INDEX IF NOT EXISTS index_timestamp_live_data ON live_data (timestamp);
+            CREATE INDEX IF NOT EXISTS index_symbol_live_data ON live_data (symbol);
+            CREATE TABLE IF NOT EXISTS stocks (
+                stock_id INTEGER,
+                symbol TEXT,
+                name TEXT,
+                currency TEXT,
+                exchange TEXT,
+                mic_code TEXT,
+                country TEXT,
+                type TEXT,
+                figi_code TEXT,
+                cfi_code TEXT,
+                isin TEXT,
+                cusip TEXT,
+                PRIMARY KEY(stock_id AUTOINCREMENT)
+            );
+            CREATE INDEX IF NOT EXISTS index_symbod_stocks ON stocks (symbol);
+            CREATE TABLE IF NOT EXISTS time_series (
+                timestamp_id INTEGER,
+                timestamp INTEGER,
+                symbol TEXT,
+                currency TEXT,
+                exchange TEXT,
+                open DOUBLE,
+                high DOUBLE,
+                low DOUBLE,
+                close DOUBLE,
+                volume DOUBLE,
+                PRIMARY KEY(timestamp_id AUTOINCREMENT)
+            );
+            CREATE INDEX IF NOT EXISTS index_timestamp_time_series ON time_series (timestamp);
+            CREATE INDEX IF NOT EXISTS index_symbol_time_series ON time_series (symbol);
+            CREATE TABLE IF NOT EXISTS yahoosymbols (
+                ysymbol_id INTEGER,
+                symbol TEXT,
+                name TEXT,
+                PRIMARY KEY(ysymbol_id AUTOINCREMENT)
+            );
+            CREATE INDEX IF NOT EXISTS index_symbol_yahoosymbols ON yahoosymbols (symbol);
+            CREATE TABLE IF NOT EXISTS jump_events (
+                jump_id INTEGER,
+                timestamp INTEGER,
+                symbol TEXT,
+                percent DOUBLE,
+                PRIMARY KEY(jump_id AUTOINCREMENT)
+            );
+            CREATE TABLE IF NOT EXISTS drop_events (
+                drop_id INTEGER,
+                timestamp INTEGER,
+                symbol TEXT,
+                percent DOUBLE,
+                PRIMARY KEY(drop_id AUTOINCREMENT)
+            );
+            CREATE TABLE IF NOT EXISTS recurring_events (
+                recur_id INTEGER,
+                symbol TEXT,
+                minutes_period INTEGER,
+                percent DOUBLE,
+                PRIMARY KEY(recur_id AUTOINCREMENT)
+            );
+        ",
+    },
+    Migration {
+        version: 2,
+        description: "unique bars per symbol, exchange and timestamp",
+        // keep the latest of duplicated rows, they were stored by the last update
+        sql: "
+            DELETE FROM time_series WHERE timestamp_id NOT IN (
+                SELECT MAX(timestamp_id) FROM time_series GROUP BY symbol, exchange, timestamp
+            );
+            CREATE UNIQUE INDEX IF NOT EXISTS unique_time_series ON time_series (symbol, exchange, timestamp);
+            DELETE FROM live_data WHERE timestamp_id NOT IN (
+                SELECT MAX(timestamp_id) FROM live_data GROUP BY symbol, exchange, timestamp
+            );
+            CREATE UNIQUE INDEX IF NOT EXISTS unique_live_data ON live_data (symbol, exchange, timestamp);
+        ",
+    },
+    Migration {
+        version: 3,
+        description: "corporate actions (splits and dividends)",
+        // timestamp is midnight UTC of the ex-date, value the split ratio or the dividend per share
+        sql: "
+            CREATE TABLE IF NOT EXISTS corporate_actions (
+                action_id INTEGER,
+                timestamp INTEGER,
+                symbol TEXT,
+                kind TEXT,
+                value DOUBLE,
+                PRIMARY KEY(action_id AUTOINCREMENT)
+            );
+            CREATE UNIQUE INDEX IF NOT EXISTS unique_corporate_actions ON corporate_actions (symbol, timestamp, kind);
+        ",
+    },
+    Migration {
+        version: 4,
+        description: "data quality flags and quarantine",
+        // quality holds the bits of data::quality, rows with excluded bits are not read for analysis
+        sql: "
+            ALTER TABLE time_series ADD COLUMN quality INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE live_data ADD COLUMN quality INTEGER NOT NULL DEFAULT 0;
+            CREATE TABLE IF NOT EXISTS quarantine (
+                quarantine_id INTEGER,
+                source TEXT,
+                timestamp INTEGER,
+                symbol TEXT,
+                currency TEXT,
+                exchange TEXT,
+                open DOUBLE,
+                high DOUBLE,
+                low DOUBLE,
+                close DOUBLE,
+                volume DOUBLE,
+                quality INTEGER,
+                quarantined_at INTEGER,
+                PRIMARY KEY(quarantine_id AUTOINCREMENT)
+            );
+            CREATE INDEX IF NOT EXISTS index_symbol_quarantine ON quarantine (symbol);
+        ",
+    },
+    Migration {
+        version: 5,
+        description: "runs of the scheduled jobs",
+        // status is one of running, ok, failed, skipped or interrupted
+        sql: "
+            CREATE TABLE IF NOT EXISTS job_runs (
+                run_id INTEGER,
+                job TEXT NOT NULL,
+                scheduled_at INTEGER NOT NULL,
+                started_at INTEGER NOT NULL,
+                finished_at INTEGER,
+                status TEXT NOT NULL,
+                message TEXT,
+                PRIMARY KEY(run_id AUTOINCREMENT)
+            );
+            CREATE INDEX IF NOT EXISTS index_job_job_runs ON job_runs (job, scheduled_at);
+        ",
+    },
+    Migration {
+        version: 6,
+        description: "delivered notifications",
+        // one row per notification and channel, status is sent or failed
+        sql: "
+            CREATE TABLE IF NOT EXISTS notifications (
+                notification_id INTEGER,
+                channel TEXT NOT NULL,
+                symbol TEXT,
+                level TEXT,
+                title TEXT,
+                body TEXT,
+                created_at INTEGER NOT NULL,
+                sent_at INTEGER,
+                attempts INTEGER NOT NULL,
+                status TEXT NOT NULL,
+                error TEXT,
+                PRIMARY KEY(notification_id AUTOINCREMENT)
+            );
+            CREATE INDEX IF NOT EXISTS index_created_notifications ON notifications (created_at);
+        ",
+    },
+    Migration {
+        version: 7,
+        description: "user defined alert rules",
+        // the condition is stored as JSON, see analytics::rules::Condition
+        sql: "
+            CREATE TABLE IF NOT EXISTS alert_rules (
+                rule_id INTEGER,
+                name TEXT NOT NULL UNIQUE,
+                symbol TEXT NOT NULL,
+                condition TEXT NOT NULL,
+                cooldown_minutes INTEGER NOT NULL,
+                level TEXT NOT NULL,
+                notify TEXT,
+                enabled INTEGER NOT NULL,
+                PRIMARY KEY(rule_id AUTOINCREMENT)
+            );
+            CREATE TABLE IF NOT EXISTS alert_rule_triggers (
+                rule_id INTEGER NOT NULL,
+                symbol TEXT NOT NULL,
+                triggered_at INTEGER NOT NULL,
+                PRIMARY KEY(rule_id, symbol)
+            );
+        ",
+    },
+    Migration {
+        version: 8,
+        description: "alert state, unique jump and drop events",
+        // the cooldowns of the alert rules are kept with the alerts now
+        sql: "
+            CREATE TABLE IF NOT EXISTS alerts (
+                alert_id INTEGER,
+                fingerprint TEXT NOT NULL,
+                source TEXT NOT NULL,
+                symbol TEXT,
+                level TEXT NOT NULL,
+                title TEXT,
+                body TEXT,
+                state TEXT NOT NULL,
+                first_seen INTEGER NOT NULL,
+                last_seen INTEGER NOT NULL,
+                occurrences INTEGER NOT NULL,
+                notified_at INTEGER,
+                escalated INTEGER NOT NULL,
+                acknowledged_at INTEGER,
+                snoozed_until INTEGER,
+                resolved_at INTEGER,
+                PRIMARY KEY(alert_id AUTOINCREMENT)
+            );
+            CREATE INDEX IF NOT EXISTS index_fingerprint_alerts ON alerts (fingerprint, state);
+            CREATE INDEX IF NOT EXISTS index_last_seen_alerts ON alerts (last_seen);
+            DROP TABLE IF EXISTS alert_rule_triggers;
+            DELETE FROM jump_events WHERE jump_id NOT IN (SELECT MIN(jump_id) FROM jump_events GROUP BY symbol, timestamp);
+            DELETE FROM drop_events WHERE drop_id NOT IN (SELECT MIN(drop_id) FROM drop_events GROUP BY symbol, timestamp);
+            CREATE UNIQUE INDEX IF NOT EXISTS index_symbol_timestamp_jump_events ON jump_events (symbol, timestamp);
+            CREATE UNIQUE INDEX IF NOT EXISTS index_symbol_timestamp_drop_events ON drop_events (symbol, timestamp);
+        ",
+    },
+    Migration {
+        version: 9,
+        description: "indicator states",
+        sql: "
+            CREATE TABLE IF NOT EXISTS indicator_states (
+                symbol TEXT NOT NULL,
+                exchange TEXT NOT NULL,
+                name TEXT NOT NULL,
+                state TEXT NOT NULL,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY(symbol, exchange, name)
+            );
+        ",
+    },
+    Migration {
+        version: 10,
+        description: "candlestick pattern events",
+        sql: "
+            CREATE TABLE IF NOT EXISTS pattern_events (
+                pattern_id INTEGER,
+                timestamp INTEGER NOT NULL,
+                symbol TEXT NOT NULL,
+                pattern TEXT NOT NULL,
+                confidence DOUBLE NOT NULL,
+                PRIMARY KEY(pattern_id AUTOINCREMENT)
+            );
+            CREATE UNIQUE INDEX IF NOT EXISTS index_symbol_timestamp_pattern_events ON pattern_events (symbol, timestamp, pattern);
+        ",
+    },
+];
+
+/// version of the newest migration known to this build
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+fn create_version_table(connection: &Connection) -> Result<(), rusqlite::Error> {
+    connection.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT,
+            applied_at INTEGER
+        )",
+        (),
+    )?;
+    Ok(())
+}
+
+/// version of the schema of the database, 0 if no migration was applied yet
+pub fn schema_version(connection: &Connection) -> Result<u32, rusqlite::Error> {
+    create_version_table(connection)?;
+    let version: Option<u32> = connection.query_row(
+        "SELECT MAX(version) FROM schema_version",
+        (),
+        |row| row.get(0),
+    )?;
+    Ok(version.unwrap_or(0))
+}
+
+/// migrations not yet applied to the database
+pub fn pending_migrations(connection: &Connection) -> Result<Vec<&'static Migration>, rusqlite::Error> {
+    let version = schema_version(connection)?;
+    Ok(MIGRATIONS.iter().filter(|m| m.version > version).collect())
+}
+
+/// State of the schema of a database
+#[derive(Debug)]
+pub struct SchemaStatus {
+    /// version of the database
+    pub version: u32,
+    /// newest version known to this build
+    pub latest: u32,
+    /// migrations still to be applied
+    pub pending: Vec<&'static Migration>,
+}
+
+impl std::fmt::Display for SchemaStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "schema version {} of {}", self.version, self.latest)?;
+        if self.version > self.latest {
+            writeln!(f, "the database was created by a newer version of this program")?;
+        }
+        if self.pending.is_empty() {
+            write!(f, "no pending migrations")
+        } else {
+            write!(f, "{} pending migrations:", self.pending.len())?;
+            for m in self.pending.iter() {
+                write!(f, "\n  {:>4} {}", m.version, m.description)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// report the schema version and the pending migrations
+pub fn status(connection: &Connection) -> Result<SchemaStatus, rusqlite::Error> {
+    Ok(SchemaStatus {
+        version: schema_version(connection)?,
+        latest: latest_version(),
+        pending: pending_migrations(connection)?,
+    })
+}
+
+/// apply all pending migrations in order, returns the applied versions
+pub fn migrate(connection: &mut Connection) -> Result<Vec<u32>, Box<dyn Error>> {
+    let version = schema_version(connection)?;
+    if version > latest_version() {
+        return Err(format!(
+            "Database schema version {} is newer than the supported version {}",
+            version,
+            latest_version()
+        ).into());
+    }
+    let mut applied = Vec::new();
+    for m in MIGRATIONS.iter().filter(|m| m.version > version) {
+        let transaction = connection.transaction()?;
+        if let Err(error) = transaction.execute_batch(m.sql) {
+            return Err(format!("Migration {} ({}) failed: {}", m.version, m.description, error).into());
+        }
+        transaction.execute(
+            "INSERT INTO schema_version (version, description, applied_at) VALUES (?1, ?2, ?3)",
+            params![m.version, m.description, chrono::Utc::now().timestamp()],
+        )?;
+        transaction.commit()?;
+        log::info!("Applied database migration {}: {}", m.version, m.description);
+        applied.push(m.version);
+    }
+    Ok(applied)
+}
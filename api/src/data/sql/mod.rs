@@ -1,36 +1,43 @@
 //! Database connection to the data stored by stock-livedata
 //!
 use chrono::{DateTime, Utc, Months};
-use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use lazy_static::lazy_static;
 
+pub mod alert_rules;
+pub use alert_rules::{alert_rules, insert_alert_rule};
+pub mod alerts;
+pub use alerts::{Alert, AlertPolicy, AlertState};
+pub mod corporate_actions;
+pub use corporate_actions::{CorporateAction, CorporateActionKind, adjusted_close, corporate_actions, insert_corporate_actions};
+pub mod database;
+pub use database::Database;
 pub mod events;
+pub mod indicator_states;
+pub use indicator_states::{indicator_state, save_indicator_state};
 pub mod init;
+pub mod job_runs;
+pub use job_runs::{JobRun, JobStatus};
 pub mod live_data;
-pub use live_data::{live_data, insert_live_data};
+pub mod migrations;
+pub mod notifications;
+pub mod quality;
+pub use live_data::{live_data, insert_live_data, insert_live_bars, insert_live_bars_batch};
 pub mod symbols;
 pub use symbols::{active_symbols, insert_active_symbols, check_equity_exists};
 pub mod time_series;
-pub use time_series::{timeseries, insert_timeseries};
+pub use time_series::{timeseries, insert_timeseries, insert_timeseries_data, insert_timeseries_batch};
 pub mod to_dataframe;
 pub use to_dataframe::{ohlcv_to_dataframe, daily_ohlcv_to_dataframe, i64_column_to_datetime_vec};
 
 /// Metadata stock metadata
 #[derive(Debug, Deserialize, Serialize)]
 pub struct MetaData {
-    symbol: String,
-    #[allow(dead_code)]
-    currency: String,
-    #[allow(dead_code)]
-    exchange_timezone: String,
-    #[allow(dead_code)]
-    exchange: String,
-    #[allow(dead_code)]
-    exchange_code: String,
-    #[allow(dead_code)]
-    r#type: String,
+    pub symbol: String,
+    pub currency: String,
+    pub exchange_timezone: String,
+    pub exchange: String,
+    pub exchange_code: String,
+    pub r#type: String,
     pub start_date: DateTime<Utc>,
     pub end_date: DateTime<Utc>,
 }
@@ -163,7 +170,7 @@ impl Default for YahooSymbol {
 
 /// Return Metadata
 pub fn metadata(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: Database,
     exchange_code: &str,
     stock_symbol: &str,
 ) -> MetaData {
@@ -213,13 +220,14 @@ pub fn metadata(
         }
     }
     let exchange = symbols::exchange(sql_connection.clone(), &exchange_string);
+    m.exchange_code = exchange_string;
     m.exchange = exchange.title.clone();
     m.exchange_timezone = exchange.timezone.clone();
     m
 }
 
 /// Stock data time series
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct TimeSeriesData {
     /// Datetime stored as i64
     pub datetime: i64,
@@ -290,6 +298,29 @@ impl Default for RecurringEventData {
     }
 }
 
+/// Size of the database in bytes, the pages in use and the free ones
+pub fn database_size(sql_connection: Database) -> Option<u64> {
+    let connection = match sql_connection.read() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return None;
+        }
+    };
+    let size = connection.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |row| row.get::<_, i64>(0),
+    );
+    match size {
+        Ok(size) => u64::try_from(size).ok(),
+        Err(error) => {
+            log::error!("Failed to read the size of the database! {}", error);
+            None
+        }
+    }
+}
+
 fn sql_file_path() -> std::path::PathBuf {
     let sqlite_file;
     match dirs::data_local_dir() {
@@ -309,22 +340,22 @@ fn sql_file_path() -> std::path::PathBuf {
             sqlite_file = dir.join("time_series.sqlite");
         }
     }
-    if !sqlite_file.exists() {
-        init::init_database(sqlite_file.clone());
-    }
-
     sqlite_file
 }
 
-/// connect to the database
-pub fn connect() -> Arc<std::sync::Mutex<rusqlite::Connection>> {
-    lazy_static! {
-        static ref SQL_CONNECTION: Arc<std::sync::Mutex<rusqlite::Connection>> =
-            std::sync::Arc::new(std::sync::Mutex::new(
-                Connection::open(sql_file_path().as_path()).unwrap()
-            ));
+/// location of the database, `STOCK_DATABASE` from the environment or the default file
+/// below the local data directory. The database is not created or migrated
+pub fn database_path() -> std::path::PathBuf {
+    match std::env::var("STOCK_DATABASE") {
+        Ok(location) if !location.is_empty() => {
+            std::path::PathBuf::from(location.strip_prefix("sqlite://").unwrap_or(&location))
+        }
+        _ => sql_file_path(),
     }
-
-    SQL_CONNECTION.clone()
 }
 
+/// open the default database, see `database_path`. Every call opens a handle with its own
+/// read pool, open it once and pass it on; use `Database::open` for a separate store
+pub fn connect() -> Result<Database, Box<dyn std::error::Error>> {
+    Database::open(&database_path().to_string_lossy())
+}
@@ -0,0 +1,115 @@
+//! Record of the notifications sent by the daemon
+
+use rusqlite::params;
+
+/// A notification delivered, or given up, on one channel
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NotificationRecord {
+    pub notification_id: i64,
+    /// name of the configured notifier
+    pub channel: String,
+    pub symbol: String,
+    pub level: String,
+    pub title: String,
+    pub body: String,
+    /// seconds since 1.1.1970 the notification was raised
+    pub created_at: i64,
+    /// seconds since 1.1.1970 of the successful delivery
+    pub sent_at: Option<i64>,
+    pub attempts: u32,
+    /// `sent` or `failed`
+    pub status: String,
+    /// the error of the last failed attempt
+    pub error: String,
+}
+
+/// store the outcome of a delivery, returns the id of the record
+pub fn insert_notification(
+    sql_connection: super::Database,
+    record: &NotificationRecord,
+) -> Option<i64> {
+    let connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return None;
+        }
+    };
+    match connection.execute(
+        "INSERT INTO notifications (channel, symbol, level, title, body, created_at, sent_at, attempts, status, error)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            &record.channel,
+            &record.symbol,
+            &record.level,
+            &record.title,
+            &record.body,
+            &record.created_at,
+            &record.sent_at,
+            &record.attempts,
+            &record.status,
+            &record.error
+        ],
+    ) {
+        Ok(_) => {
+            crate::metrics::rows_inserted("notifications", 1);
+            Some(connection.last_insert_rowid())
+        }
+        Err(error) => {
+            log::error!("Failed insert notifications! {}", error);
+            None
+        }
+    }
+}
+
+/// the notifications raised since `since`, newest first
+pub fn notifications(
+    sql_connection: super::Database,
+    since: i64,
+    limit: usize,
+) -> Vec<NotificationRecord> {
+    let mut v = Vec::new();
+    let connection = match sql_connection.read() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return v;
+        }
+    };
+    let query = "SELECT notification_id, channel, symbol, level, title, body, created_at, sent_at, attempts, status, error
+        FROM notifications WHERE created_at >= ?1 ORDER BY created_at DESC, notification_id DESC LIMIT ?2";
+    let mut statement = match connection.prepare(query) {
+        Ok(s) => s,
+        Err(error) => {
+            log::error!("could not prepare SQL statement: {}", error);
+            return v;
+        }
+    };
+    let rows = statement.query_map(params![since, limit as i64], |row| {
+        Ok(NotificationRecord {
+            notification_id: row.get(0)?,
+            channel: row.get(1)?,
+            symbol: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            level: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+            title: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+            body: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+            created_at: row.get(6)?,
+            sent_at: row.get(7)?,
+            attempts: row.get(8)?,
+            status: row.get(9)?,
+            error: row.get::<_, Option<String>>(10)?.unwrap_or_default(),
+        })
+    });
+    match rows {
+        Ok(rows) => {
+            for row in rows {
+                match row {
+                    Ok(r) => v.push(r),
+                    Err(error) => log::error!("Failed to read a row from notifications: {}", error),
+                }
+            }
+        }
+        Err(error) => log::error!("could not read from notifications: {}", error),
+    }
+    v
+}
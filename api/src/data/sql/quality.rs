@@ -0,0 +1,258 @@
+//! Storage of the quality flags of bars, see `data::quality`
+
+use rusqlite::params;
+
+use crate::data::quality::BarTable;
+
+/// A stored bar with row id and quality flags
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredBar {
+    /// row id in its table
+    pub id: i64,
+    pub exchange: String,
+    pub currency: String,
+    /// prices stored as NULL are NaN
+    pub bar: super::TimeSeriesData,
+    pub quality: u32,
+}
+
+/// the exchanges a symbol has bars of in a table, bars without exchange are the empty one
+pub fn bar_exchanges(
+    sql_connection: super::Database,
+    table: BarTable,
+    symbol: &str,
+) -> Vec<String> {
+    let mut v = Vec::new();
+    let connection = match sql_connection.read() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return v;
+        }
+    };
+    let query = format!("SELECT DISTINCT IFNULL(exchange, '') FROM {} WHERE symbol = ?1 ORDER BY 1", table.name());
+    let mut statement = match connection.prepare(&query) {
+        Ok(s) => s,
+        Err(error) => {
+            log::error!("could not prepare SQL statement: {}", error);
+            return v;
+        }
+    };
+    match statement.query_map(params![symbol], |row| row.get::<_, String>(0)) {
+        Ok(rows) => {
+            for row in rows {
+                match row {
+                    Ok(e) => v.push(e),
+                    Err(error) => log::error!("Failed to read a row from {}: {}", table.name(), error),
+                }
+            }
+        }
+        Err(error) => log::error!("could not read from {}: {}", table.name(), error),
+    }
+    v
+}
+
+/// all bars of a symbol at one exchange including the flagged ones, ordered by time and row.
+/// Bars without exchange are read with the empty exchange
+pub fn stored_bars(
+    sql_connection: super::Database,
+    table: BarTable,
+    symbol: &str,
+    exchange: &str,
+) -> Vec<StoredBar> {
+    let mut v = Vec::new();
+    let connection = match sql_connection.read() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return v;
+        }
+    };
+    let query = format!(
+        "SELECT timestamp_id, timestamp, exchange, currency, open, high, low, close, volume, quality FROM {} WHERE symbol = ?1 AND IFNULL(exchange, '') = ?2 ORDER BY timestamp ASC, timestamp_id ASC",
+        table.name()
+    );
+    let mut statement = match connection.prepare(&query) {
+        Ok(s) => s,
+        Err(error) => {
+            log::error!("could not prepare SQL statement: {}", error);
+            return v;
+        }
+    };
+    let rows = statement.query_map(params![symbol, exchange], |row| {
+        let price = |i: usize| -> rusqlite::Result<f64> { Ok(row.get::<_, Option<f64>>(i)?.unwrap_or(f64::NAN)) };
+        Ok(StoredBar {
+            id: row.get(0)?,
+            exchange: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            currency: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+            bar: super::TimeSeriesData {
+                datetime: row.get(1)?,
+                open: price(4)?,
+                high: price(5)?,
+                low: price(6)?,
+                close: price(7)?,
+                volume: price(8)?,
+            },
+            quality: row.get(9)?,
+        })
+    });
+    match rows {
+        Ok(rows) => {
+            for row in rows {
+                match row {
+                    Ok(b) => v.push(b),
+                    Err(error) => log::error!("Failed to read a row from {}: {}", table.name(), error),
+                }
+            }
+        }
+        Err(error) => log::error!("could not read from {}: {}", table.name(), error),
+    }
+    v
+}
+
+/// store the quality flags of rows given as (row id, flags)
+pub fn set_quality(
+    sql_connection: super::Database,
+    table: BarTable,
+    flags: &[(i64, u32)],
+) -> u32 {
+    let mut connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return 0;
+        }
+    };
+    let transaction = match connection.transaction() {
+        Ok(t) => t,
+        Err(error) => {
+            log::error!("Failed to start transaction! {}", error);
+            return 0;
+        }
+    };
+    let query = format!("UPDATE {} SET quality = ?2 WHERE timestamp_id = ?1", table.name());
+    for (id, quality) in flags.iter() {
+        if let Err(error) = transaction.execute(&query, params![id, quality]) {
+            log::error!("Failed to flag {} row {}! {}", table.name(), id, error);
+            return 0;
+        }
+    }
+    match transaction.commit() {
+        Ok(()) => 1,
+        Err(error) => {
+            log::error!("Failed to commit quality flags! {}", error);
+            0
+        }
+    }
+}
+
+/// move rows given as (row id, flags) to the quarantine table
+pub fn quarantine_bars(
+    sql_connection: super::Database,
+    table: BarTable,
+    flags: &[(i64, u32)],
+) -> u32 {
+    let mut connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return 0;
+        }
+    };
+    let transaction = match connection.transaction() {
+        Ok(t) => t,
+        Err(error) => {
+            log::error!("Failed to start transaction! {}", error);
+            return 0;
+        }
+    };
+    let copy = format!(
+        "INSERT INTO quarantine (source, timestamp, symbol, currency, exchange, open, high, low, close, volume, quality, quarantined_at)
+            SELECT ?1, timestamp, symbol, currency, exchange, open, high, low, close, volume, ?3, ?4 FROM {} WHERE timestamp_id = ?2",
+        table.name()
+    );
+    let delete = format!("DELETE FROM {} WHERE timestamp_id = ?1", table.name());
+    let now = chrono::Utc::now().timestamp();
+    for (id, quality) in flags.iter() {
+        let result = transaction
+            .execute(&copy, params![table.name(), id, quality, now])
+            .and_then(|_| transaction.execute(&delete, params![id]));
+        if let Err(error) = result {
+            log::error!("Failed to quarantine {} row {}! {}", table.name(), id, error);
+            return 0;
+        }
+    }
+    match transaction.commit() {
+        Ok(()) => 1,
+        Err(error) => {
+            log::error!("Failed to commit quarantine! {}", error);
+            0
+        }
+    }
+}
+
+/// number of quarantined bars of a symbol
+pub fn quarantined_count(
+    sql_connection: super::Database,
+    symbol: &str,
+) -> usize {
+    let connection = match sql_connection.read() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return 0;
+        }
+    };
+    match connection.query_row("SELECT COUNT(quarantine_id) FROM quarantine WHERE symbol = ?1", params![symbol], |row| row.get(0)) {
+        Ok(num) => num,
+        Err(error) => {
+            log::error!("could not count quarantined bars: {}", error);
+            0
+        }
+    }
+}
+
+/// store bars filling gaps, marked with `quality`. Existing bars are kept
+pub fn insert_interpolated(
+    sql_connection: super::Database,
+    table: BarTable,
+    symbol: &str,
+    bars: &[StoredBar],
+) -> u32 {
+    let mut connection = match sql_connection.write() {
+        Ok(conn) => conn,
+        Err(error) => {
+            log::error!("Failed to lock sql connection for use! {}", error);
+            return 0;
+        }
+    };
+    let transaction = match connection.transaction() {
+        Ok(t) => t,
+        Err(error) => {
+            log::error!("Failed to start transaction! {}", error);
+            return 0;
+        }
+    };
+    let query = format!(
+        "INSERT INTO {} (timestamp, symbol, currency, exchange, open, high, low, close, volume, quality) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            ON CONFLICT(symbol, exchange, timestamp) DO NOTHING",
+        table.name()
+    );
+    for b in bars.iter() {
+        let t = &b.bar;
+        if let Err(error) = transaction.execute(
+            &query,
+            params![&t.datetime, symbol, &b.currency, &b.exchange, &t.open, &t.high, &t.low, &t.close, &t.volume, &b.quality],
+        ) {
+            log::error!("Failed insert interpolated {} of {}! {}", table.name(), symbol, error);
+            return 0;
+        }
+    }
+    match transaction.commit() {
+        Ok(()) => 1,
+        Err(error) => {
+            log::error!("Failed to commit interpolated bars! {}", error);
+            0
+        }
+    }
+}
@@ -1,11 +1,11 @@
 use rusqlite::params;
 
 pub fn check_equity_exists(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     symbol: &str,
 ) -> bool {
     let mut num = 0_usize;
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -53,11 +53,11 @@ pub fn check_equity_exists(
 }
 
 pub fn read_equity(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     stock_symbol: &str,
 ) -> Vec<super::StockEquity> {
     let mut v = Vec::new();
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -203,11 +203,11 @@ pub fn read_equity(
 }
 
 pub fn match_yahoo_symbol_with_equity(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     stock_symbol: &str,
 ) -> String {
     let mut new_symbol = stock_symbol.to_string();
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -341,7 +341,7 @@ pub fn match_yahoo_symbol_with_equity(
 
 /// return Stock Equity
 pub fn equity(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     stock_symbol: &str,
 ) -> Vec<super::StockEquity> {
     if check_equity_exists(sql_connection.clone(), stock_symbol) {
@@ -359,13 +359,13 @@ pub fn equity(
 
 /// Return Stock Exchange
 pub fn exchange(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     exchange_code: &str,
 ) -> super::Exchange {
     let mut s = super::Exchange {
         ..Default::default()
     };
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -457,11 +457,11 @@ pub fn exchange(
 }
 
 pub fn check_symbol_exists(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     symbol: &str,
 ) -> bool {
     let mut num = 0_usize;
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -510,11 +510,11 @@ pub fn check_symbol_exists(
 
 /// return the number of time series data for the stock
 pub fn active_symbols_count(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     metadata: &super::MetaData,
 ) -> usize {
     let mut num = 0_usize;
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -562,10 +562,10 @@ pub fn active_symbols_count(
 }
 
 pub fn active_symbols(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
 ) -> Vec<String> {
     let mut t = Vec::new();
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -614,11 +614,11 @@ pub fn active_symbols(
 }
 
 pub fn insert_active_symbols(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     symbols: &Vec<String>,
 ) -> u32 {
     let existing = active_symbols(sql_connection.clone());
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.write() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -643,29 +643,35 @@ pub fn insert_active_symbols(
     0
 }
 
-pub fn _delete_active_symbols(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+/// remove symbols from the active symbols, returns the number of removed symbols
+pub fn delete_active_symbols(
+    sql_connection: super::Database,
     symbols: &Vec<String>,
-) {
-    let connection = match sql_connection.lock() {
+) -> usize {
+    let connection = match sql_connection.write() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
-            return;
+            return 0;
         }
     };
+    let mut removed = 0;
     for i in 0..symbols.len() {
-        let _ret = connection.execute(
+        match connection.execute(
             "DELETE FROM active_symbols WHERE symbol = ?1",
             params![&symbols[i]],
-        );
+        ) {
+            Ok(num) => removed += num,
+            Err(error) => log::error!("Failed to delete {} from active_symbols! {}", &symbols[i], error),
+        }
     }
+    removed
 }
 
 pub fn _update_active_symbols(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     symbols: &Vec<String>,
 ) {
-    _delete_active_symbols(sql_connection.clone(), symbols);
+    delete_active_symbols(sql_connection.clone(), symbols);
     insert_active_symbols(sql_connection.clone(), symbols);
 }
@@ -1,11 +1,11 @@
 use rusqlite::params;
 
 pub fn timeseries_count(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     metadata: &super::MetaData,
 ) -> usize {
     let mut num = 0_usize;
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -53,21 +53,21 @@ pub fn timeseries_count(
 }
 
 pub fn timeseries_all(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     metadata: &super::MetaData,
 ) -> Vec<super::TimeSeriesData> {
     let mut t = Vec::new();
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
             return t;
         }
     };
-    let query = "SELECT timestamp, open, high, low, close, volume FROM time_series WHERE symbol = ?1 ORDER BY timestamp ASC";
+    let query = "SELECT timestamp, open, high, low, close, volume FROM time_series WHERE symbol = ?1 AND (quality & ?2) = 0 ORDER BY timestamp ASC";
     match connection.prepare(query) {
         Ok(mut statement) => {
-            match statement.query(params![&metadata.symbol]) {
+            match statement.query(params![&metadata.symbol, crate::data::quality::EXCLUDED]) {
                 Ok(mut rows) => {
                     loop {
                         match rows.next() {
@@ -147,11 +147,11 @@ pub fn timeseries_all(
 }
 
 pub fn timeseries(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     metadata: &super::MetaData,
 ) -> Vec<super::TimeSeriesData> {
     let mut t = Vec::new();
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.read() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -160,10 +160,10 @@ pub fn timeseries(
     };
     let start = metadata.start_date.timestamp();
     let end = metadata.end_date.timestamp();
-    let query = "SELECT timestamp, open, high, low, close, volume FROM time_series WHERE symbol = ?1 AND timestamp BETWEEN ?2 AND ?3 ORDER BY timestamp ASC";
+    let query = "SELECT timestamp, open, high, low, close, volume FROM time_series WHERE symbol = ?1 AND timestamp BETWEEN ?2 AND ?3 AND (quality & ?4) = 0 ORDER BY timestamp ASC";
     match connection.prepare(query) {
         Ok(mut statement) => {
-            match statement.query(params![&metadata.symbol, &start, &end]) {
+            match statement.query(params![&metadata.symbol, &start, &end, crate::data::quality::EXCLUDED]) {
                 Ok(mut rows) => {
                     loop {
                         match rows.next() {
@@ -242,47 +242,88 @@ pub fn timeseries(
     t
 }
 
+/// the quality flags of a bar delivered again are kept unless its values changed
+const UPSERT_TIMESERIES: &str = "INSERT INTO time_series (timestamp, symbol, currency, exchange, open, high, low, close, volume) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+    ON CONFLICT(symbol, exchange, timestamp) DO UPDATE SET currency = excluded.currency, open = excluded.open, high = excluded.high, low = excluded.low, close = excluded.close, volume = excluded.volume,
+    quality = CASE WHEN open IS excluded.open AND high IS excluded.high AND low IS excluded.low AND close IS excluded.close AND volume IS excluded.volume THEN quality ELSE 0 END";
+
+fn upsert_timeseries(
+    transaction: &rusqlite::Transaction,
+    metadata: &super::MetaData,
+    series: &[super::TimeSeriesData],
+) -> Result<(), rusqlite::Error> {
+    let mut statement = transaction.prepare_cached(UPSERT_TIMESERIES)?;
+    for t in series.iter() {
+        statement.execute(params![&t.datetime, &metadata.symbol, &metadata.currency, &metadata.exchange, &t.open, &t.high, &t.low, &t.close, &t.volume ])?;
+    }
+    Ok(())
+}
+
 pub fn insert_timeseries(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     metadata: &super::MetaData,
     series: &market_data::MarketSeries,
 ) -> u32 {
-    let existing = timeseries(sql_connection.clone(), metadata);
-    let exists: std::collections::BTreeSet<i64> = existing.iter().map(|t| t.datetime).collect();
-    let connection = match sql_connection.lock() {
+    let data = crate::data::livedata::provider::daily_to_timeseries(series);
+    insert_timeseries_data(sql_connection, metadata, &data)
+}
+
+/// store daily bars delivered by a `MarketDataProvider`, existing bars are updated
+pub fn insert_timeseries_data(
+    sql_connection: super::Database,
+    metadata: &super::MetaData,
+    series: &[super::TimeSeriesData],
+) -> u32 {
+    insert_timeseries_batch(sql_connection, &[(metadata, series)])
+}
+
+/// store the daily bars of several symbols in one transaction, existing bars are updated
+/// nothing is stored if one of the inserts fails
+pub fn insert_timeseries_batch(
+    sql_connection: super::Database,
+    batch: &[(&super::MetaData, &[super::TimeSeriesData])],
+) -> u32 {
+    let mut connection = match sql_connection.write() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
             return 0;
         }
     };
-
-    for i in 0..series.data.len() {
-        let time= chrono::NaiveTime::from_num_seconds_from_midnight_opt(22 * 3600, 0).expect("If this does not work the chrono code is crap!");
-        let timestamp = series.data[i].date.and_time(time).and_utc().timestamp();
-        if exists.contains(&timestamp) {
-            continue;
+    let transaction = match connection.transaction() {
+        Ok(t) => t,
+        Err(error) => {
+            log::error!("Failed to start transaction! {}", error);
+            return 0;
         }
-        match connection.execute(
-            "INSERT INTO time_series (timestamp, symbol, currency, exchange, open, high, low, close, volume) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-            params![&timestamp, &metadata.symbol, &metadata.currency, &metadata.exchange, &series.data[i].open, &series.data[i].high, &series.data[i].low, &series.data[i].close, &series.data[i].volume ],
-        ) {
-            Ok(_retval) => {} //log::warn!("Inserted {} video with ID {} and location {} into candidates.", video.id, video.index, candidate_id),
+    };
+    for (metadata, series) in batch.iter() {
+        match upsert_timeseries(&transaction, metadata, series) {
+            Ok(()) => {}
             Err(error) => {
-                log::error!("Failed insert time_series! {}", error);
+                log::error!("Failed insert time_series of {}! {}", metadata.symbol, error);
                 return 0;
             }
         }
     }
-    1
+    match transaction.commit() {
+        Ok(()) => {
+            crate::metrics::rows_inserted("time_series", batch.iter().map(|(_, series)| series.len()).sum());
+            1
+        }
+        Err(error) => {
+            log::error!("Failed to commit time_series! {}", error);
+            0
+        }
+    }
 }
 
 pub fn _delete_timeseries(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     metadata: &super::MetaData,
     _timeseries: &market_data::MarketSeries,
 ) {
-    let connection = match sql_connection.lock() {
+    let connection = match sql_connection.write() {
         Ok(conn) => conn,
         Err(error) => {
             log::error!("Failed to lock sql connection for use! {}", error);
@@ -296,7 +337,7 @@ pub fn _delete_timeseries(
 }
 
 pub fn _update_timeseries(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     metadata: &super::MetaData,
     timeseries: &market_data::MarketSeries,
 ) {
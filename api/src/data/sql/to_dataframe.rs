@@ -1,6 +1,7 @@
 //! data retrieval from database or website
 //!
-use chrono::{DateTime, NaiveDateTime, Timelike, Utc};
+use chrono::{DateTime, Datelike, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc};
+use crate::data::yahoo::config::Interval;
 use polars::prelude::*;
 use std::error::Error;
 //use finalytics::utils::date_utils::{round_datetime_to_day, round_datetime_to_hour, round_datetime_to_minute};
@@ -69,7 +70,7 @@ pub fn i64_column_to_vec(
 
 /// Returns the Ticker OHLCV Data from the database for a given time range
 pub fn ohlcv_to_dataframe(
-    sql_connection: Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     stock_symbol: &str,
     start_date: NaiveDateTime,
     end_date: NaiveDateTime,
@@ -80,6 +81,7 @@ pub fn ohlcv_to_dataframe(
     metadata.start_date = start_date.clone().and_utc();
     metadata.end_date = end_date.clone().and_utc();
     let serieses = super::live_data::live_data(sql_connection.clone(), &metadata);
+    let actions = super::corporate_actions::corporate_actions(sql_connection.clone(), stock_symbol);
     for series in serieses {
         // timestamps are expected to be number of milliseconds since 1.1. 1970.
         let timestamp = series
@@ -97,7 +99,7 @@ pub fn ohlcv_to_dataframe(
 
         let volume = series.iter().map(|o| o.volume).collect::<Vec<f64>>();
 
-        let adjclose = series.iter().map(|o| o.close).collect::<Vec<f64>>();
+        let adjclose = super::corporate_actions::adjusted_close(&series, &actions);
 
         let df = df!(
             "timestamp" => &timestamp.clone(),
@@ -132,7 +134,7 @@ pub fn ohlcv_to_dataframe(
 
 /// Returns the Ticker OHLCV Data from the database for a given time range
 pub fn daily_ohlcv_to_dataframe(
-    sql_connection: Arc<std::sync::Mutex<rusqlite::Connection>>,
+    sql_connection: super::Database,
     stock_symbol: &str,
     start_date: DateTime<Utc>,
     end_date: DateTime<Utc>,
@@ -143,6 +145,7 @@ pub fn daily_ohlcv_to_dataframe(
     metadata.end_date = end_date;
     let series: Vec<super::TimeSeriesData> = super::time_series::timeseries(sql_connection.clone(), &metadata);
     if series.len() > 0 {
+        let actions = super::corporate_actions::corporate_actions(sql_connection.clone(), stock_symbol);
         // timestamps are expected to be number of milliseconds since 1.1. 1970.
         let timestamp = series
             .iter()
@@ -159,7 +162,7 @@ pub fn daily_ohlcv_to_dataframe(
 
         let volume = series.iter().map(|o| o.volume).collect::<Vec<f64>>();
 
-        let adjclose = series.iter().map(|o| o.close).collect::<Vec<f64>>();
+        let adjclose = super::corporate_actions::adjusted_close(&series, &actions);
 
         let df = df!(
             "timestamp" => &timestamp,
@@ -233,3 +236,150 @@ pub fn ohlcv_hourly(ohlcv: DataFrame) -> Result<DataFrame, Box<dyn Error>> {
     )?;
     Ok(df)
 }
+
+/// length of the intraday intervals in milliseconds, `None` for a day or longer
+fn interval_millis(interval: Interval) -> Option<i64> {
+    let minutes = match interval {
+        Interval::OneMinute => 1,
+        Interval::TwoMinutes => 2,
+        Interval::FiveMinutes => 5,
+        Interval::FifteenMinutes => 15,
+        Interval::ThirtyMinutes => 30,
+        Interval::SixtyMinutes | Interval::OneHour => 60,
+        Interval::NinetyMinutes => 90,
+        Interval::OneDay | Interval::FiveDays | Interval::OneWeek | Interval::OneMonth | Interval::ThreeMonths => return None,
+    };
+    Some(minutes * 60 * 1000)
+}
+
+/// the timestamp column in milliseconds, works for i64 columns and datetime columns
+fn timestamp_millis(df: &DataFrame) -> Result<(Vec<i64>, bool), Box<dyn Error>> {
+    let column = df.column("timestamp")?;
+    match column.dtype() {
+        DataType::Datetime(unit, _) => {
+            let divisor = match unit {
+                TimeUnit::Nanoseconds => 1_000_000,
+                TimeUnit::Microseconds => 1_000,
+                TimeUnit::Milliseconds => 1,
+            };
+            let v = column.cast(&DataType::Int64)?.i64()?.into_no_null_iter().map(|x| x / divisor).collect();
+            Ok((v, true))
+        }
+        _ => Ok((column.i64()?.into_no_null_iter().collect(), false)),
+    }
+}
+
+/// Aggregates OHLCV bars, usually minutely `live_data`, into bars of the interval.
+///
+/// open is the first, high the maximum, low the minimum, close and adjclose the last value
+/// and volume the sum of the bars. A trading session is a local date at the exchange `exchange`
+/// (a MIC, see `calendar`). Intraday bars are aligned to the clock from the opening of the
+/// session, from local midnight on days without session, so no bar straddles two days.
+/// Daily bars are one session, `FiveDays` five sessions, the longer intervals calendar weeks,
+/// months and quarters. The timestamp column keeps its type (i64 milliseconds or datetime).
+pub fn resample(df: &DataFrame, interval: Interval, exchange: &str) -> Result<DataFrame, Box<dyn Error>> {
+    let calendar = crate::calendar::calendar(exchange);
+    let (timestamp, is_datetime) = timestamp_millis(df)?;
+    let open = f64_column_to_vec(df, "open")?;
+    let high = f64_column_to_vec(df, "high")?;
+    let low = f64_column_to_vec(df, "low")?;
+    let close = f64_column_to_vec(df, "close")?;
+    let volume = f64_column_to_vec(df, "volume")?;
+    let adjclose = match df.column("adjclose") {
+        Ok(_) => f64_column_to_vec(df, "adjclose")?,
+        Err(_) => close.clone(),
+    };
+
+    // the key of the bar every input row belongs to and the session date of the row,
+    // rows are expected in ascending order
+    let mut keys = Vec::with_capacity(timestamp.len());
+    let mut dates = Vec::with_capacity(timestamp.len());
+    let mut session_count = 0_i64;
+    for i in 0..timestamp.len() {
+        let instant = DateTime::from_timestamp_millis(timestamp[i]).ok_or(format!("Invalid timestamp {}", timestamp[i]))?;
+        let session_date = calendar.local_date(instant);
+        if dates.last() != Some(&session_date) {
+            session_count += 1;
+        }
+        let key = match interval_millis(interval) {
+            Some(length) => {
+                let anchor = match calendar.session(session_date) {
+                    Some((opening, _)) => opening.timestamp_millis(),
+                    None => calendar
+                        .timezone
+                        .from_local_datetime(&session_date.and_time(NaiveTime::MIN))
+                        .earliest()
+                        .map_or(timestamp[i] - timestamp[i].rem_euclid(86_400_000), |t| t.timestamp_millis()),
+                };
+                anchor + (timestamp[i] - anchor).div_euclid(length) * length
+            }
+            None => match interval {
+                Interval::FiveDays => (session_count - 1) / 5,
+                Interval::OneWeek => {
+                    let week = session_date.iso_week();
+                    week.year() as i64 * 100 + week.week() as i64
+                }
+                Interval::OneMonth => session_date.year() as i64 * 100 + session_date.month() as i64,
+                Interval::ThreeMonths => session_date.year() as i64 * 100 + session_date.month0() as i64 / 3,
+                _ => session_count,
+            },
+        };
+        keys.push(key);
+        dates.push(session_date);
+    }
+
+    let mut t = Vec::new();
+    let mut o = Vec::new();
+    let mut h = Vec::new();
+    let mut l = Vec::new();
+    let mut c = Vec::new();
+    let mut v = Vec::new();
+    let mut a = Vec::new();
+    for i in 0..timestamp.len() {
+        if i > 0 && keys[i] == keys[i - 1] {
+            let last = t.len() - 1;
+            h[last] = f64::max(h[last], high[i]);
+            l[last] = f64::min(l[last], low[i]);
+            c[last] = close[i];
+            v[last] += volume[i];
+            a[last] = adjclose[i];
+            continue;
+        }
+        let bar_start = match interval_millis(interval) {
+            Some(_) => keys[i],
+            // longer bars are stamped with the date of their first session
+            None => date_to_timestamp_millis(dates[i].and_time(NaiveTime::MIN)),
+        };
+        t.push(bar_start);
+        o.push(open[i]);
+        h.push(high[i]);
+        l.push(low[i]);
+        c.push(close[i]);
+        v.push(volume[i]);
+        a.push(adjclose[i]);
+    }
+
+    let df = if is_datetime {
+        let datetime = t.iter().map(|x| to_datetime(*x)).collect::<Vec<NaiveDateTime>>();
+        df!(
+            "timestamp" => &datetime,
+            "open" => &o,
+            "high" => &h,
+            "low" => &l,
+            "close" => &c,
+            "volume" => &v,
+            "adjclose" => &a
+        )?
+    } else {
+        df!(
+            "timestamp" => &t,
+            "open" => &o,
+            "high" => &h,
+            "low" => &l,
+            "close" => &c,
+            "volume" => &v,
+            "adjclose" => &a
+        )?
+    };
+    Ok(df)
+}
@@ -4,7 +4,7 @@ use std::error::Error;
 use crate::data::yahoo;
 use crate::data::google;
 use crate::models::ticker::Ticker;
-use crate::data::yahoo::config::{Options, Quote, StatementFrequency, StatementType, TickerSummaryStats};
+use crate::data::yahoo::config::{Interval, Options, Quote, StatementFrequency, StatementType, TickerSummaryStats};
 
 pub trait TickerData {
     fn get_quote(&self) -> impl std::future::Future<Output = Result<Quote, Box<dyn Error>>>;
@@ -32,9 +32,9 @@ impl TickerData for Ticker {
         if let Some(ticker_data) = &self.ticker_data {
             ticker_data.clone().to_dataframe()
             // deactivated until there is a subscription for live data Germany
-            //super::livedata::update_dataframe(&ticker_data.to_dataframe()?, &self.ticker)
+            //super::livedata::update_dataframe(self.database()?, &ticker_data.to_dataframe()?, &self.ticker)
         } else {
-            let sql_connection = crate::data::sql::connect();
+            let sql_connection = self.database()?;
             let start_date = match chrono::NaiveDate::parse_from_str(&self.start_date, "%Y-%m-%d") {
                     Ok(dt) => dt,
                     Err(_e) => {
@@ -70,7 +70,14 @@ impl TickerData for Ticker {
                         }
                     }
                     if df.height() > 0 {
-                        return Ok(df);
+                        // only minutely bars are stored, build the other intraday intervals from them
+                        return match self.interval {
+                            Interval::TwoMinutes | Interval::FiveMinutes | Interval::FifteenMinutes | Interval::ThirtyMinutes
+                            | Interval::SixtyMinutes | Interval::OneHour | Interval::NinetyMinutes => {
+                                super::sql::to_dataframe::resample(&df, self.interval, &self.exchange())
+                            }
+                            _ => Ok(df),
+                        };
                     } else {
                         // no entries in database or symbol not found, search yahoo instead
                         let yahoo_ohlcv = yahoo::api::get_chart(
@@ -104,9 +111,9 @@ impl TickerData for Ticker {
         if let Some(ticker_data) = &self.ticker_data {
             ticker_data.clone().to_dataframe()
             // deactivated until there is a subscription for live data Germany
-            //super::livedata::update_dataframe(&ticker_data.to_dataframe()?, &self.ticker)
+            //super::livedata::update_dataframe(self.database()?, &ticker_data.to_dataframe()?, &self.ticker)
         } else {
-            let sql_connection = crate::data::sql::connect();
+            let sql_connection = self.database()?;
             let start_date = match chrono::NaiveDate::parse_from_str(&self.start_date, "%Y-%m-%d") {
                     Ok(dt) => dt,
                     Err(_e) => {
@@ -126,7 +133,13 @@ impl TickerData for Ticker {
             match super::sql::to_dataframe::daily_ohlcv_to_dataframe(sql_connection, &self.ticker, start_date, end_date) {
                 Ok(ohlcv) => {
                     if ohlcv.height() > 0 {
-                        return Ok(ohlcv);
+                        // only daily bars are stored, build the longer intervals from them
+                        return match self.interval {
+                            Interval::FiveDays | Interval::OneWeek | Interval::OneMonth | Interval::ThreeMonths => {
+                                super::sql::to_dataframe::resample(&ohlcv, self.interval, &self.exchange())
+                            }
+                            _ => Ok(ohlcv),
+                        };
                     } else {
                         // no entries in database or symbol not found, search yahoo instead
                         let yahoo_ohlcv = yahoo::api::get_chart(
@@ -1,6 +1,7 @@
 use polars::prelude::*;
 use std::error::Error;
 use chrono::{DateTime, NaiveDateTime};
+use crate::data::sql::CorporateAction;
 use crate::data::yahoo::web::get_json_response;
 use crate::data::yahoo::financials::{income_statement, balance_sheet, cashflow_statement, financial_ratios};
 use crate::data::yahoo::config::{Interval, OptionContract, Options, Quote, StatementFrequency, StatementType, TickerSummaryStats};
@@ -51,7 +52,7 @@ pub async fn get_chart(symbol: &str, start_date: &str, end_date: &str, interval:
                 Interval::SixtyMinutes | Interval::OneHour => {
                     round_datetime_to_hour(DateTime::from_timestamp(timestamp, 0).unwrap())
                 },
-                Interval::NinetyMinutes | Interval::ThirtyMinutes | Interval::FifteenMinutes | Interval::FiveMinutes | Interval::TwoMinutes => {
+                Interval::NinetyMinutes | Interval::ThirtyMinutes | Interval::FifteenMinutes | Interval::FiveMinutes | Interval::TwoMinutes | Interval::OneMinute => {
                     round_datetime_to_minute(DateTime::from_timestamp(timestamp, 0).unwrap())
                 },
             }
@@ -136,6 +137,42 @@ pub async fn get_chart(symbol: &str, start_date: &str, end_date: &str, interval:
     Ok(df)
 }
 
+/// Returns the splits and dividends of a Ticker from Yahoo Finance for a given time range
+pub async fn get_corporate_actions(symbol: &str, start_date: &str, end_date: &str) -> Result<Vec<CorporateAction>, Box<dyn Error>> {
+    let period1 = to_timestamp(start_date)?;
+    let period2 = to_timestamp(end_date)?;
+    let url = format!(
+        "https://query1.finance.yahoo.com/v8/finance/chart/{symbol}?period1={period1}&period2={period2}&interval=1d&events=div,splits"
+    );
+    let result = get_json_response(url).await?;
+
+    let events = &result["chart"]["result"][0]["events"];
+    let ex_date = |value: &serde_json::Value| {
+        value["date"].as_i64().and_then(|d| DateTime::from_timestamp(d, 0)).map(|d| d.date_naive())
+    };
+    let mut actions = Vec::new();
+    if let Some(dividends) = events["dividends"].as_object() {
+        for dividend in dividends.values() {
+            match (ex_date(dividend), dividend["amount"].as_f64()) {
+                (Some(date), Some(amount)) => actions.push(CorporateAction::dividend(symbol, date, amount)),
+                _ => log::warn!("Skipping invalid dividend of {symbol}: {dividend}"),
+            }
+        }
+    }
+    if let Some(splits) = events["splits"].as_object() {
+        for split in splits.values() {
+            match (ex_date(split), split["numerator"].as_f64(), split["denominator"].as_f64()) {
+                (Some(date), Some(numerator), Some(denominator)) if numerator > 0.0 && denominator > 0.0 => {
+                    actions.push(CorporateAction::split(symbol, date, numerator, denominator))
+                }
+                _ => log::warn!("Skipping invalid split of {symbol}: {split}"),
+            }
+        }
+    }
+    actions.sort_by_key(|a| a.timestamp);
+    Ok(actions)
+}
+
 /// Returns Ticker Option Chain Data from Yahoo Finance for all available expirations
 pub async fn get_options(symbol: &str) -> Result<Options, Box<dyn Error>> {
     let url = format!("https://query2.finance.yahoo.com/v7/finance/options/{symbol}");
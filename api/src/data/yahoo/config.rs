@@ -18,6 +18,9 @@ pub struct Quote {
     #[serde(rename = "fullExchangeName")]
     pub exchange: String,
     #[serde(default)]
+    #[serde(rename = "exchangeTimezoneName")]
+    pub exchange_timezone: String,
+    #[serde(default)]
     #[serde(rename = "financialCurrency")]
     pub currency: String,
     #[serde(rename = "regularMarketTime")]
@@ -665,6 +668,7 @@ pub struct Figure {
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 pub enum Interval {
+    OneMinute,
     TwoMinutes,
     FiveMinutes,
     FifteenMinutes,
@@ -683,6 +687,7 @@ pub enum Interval {
 impl fmt::Display for Interval {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s = match self {
+            Interval::OneMinute => "1m",
             Interval::TwoMinutes => "2m",
             Interval::FiveMinutes => "5m",
             Interval::FifteenMinutes => "15m",
@@ -706,6 +711,7 @@ impl FromStr for Interval {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let interval = match s {
+            "1m" => Interval::OneMinute,
             "2m" => Interval::TwoMinutes,
             "5m" => Interval::FiveMinutes,
             "15m" => Interval::FifteenMinutes,
@@ -727,6 +733,7 @@ impl FromStr for Interval {
 impl Interval {
     pub fn to_days(&self) -> f64 {
         match self {
+            Interval::OneMinute => 1.0 / 24.0 * 60.0,
             Interval::TwoMinutes => 2.0 / 24.0 * 60.0,
             Interval::FiveMinutes => 5.0 / 24.0 * 60.0,
             Interval::FifteenMinutes => 15.0 / 24.0 * 60.0,
@@ -0,0 +1,87 @@
+//! Errors of the data pipeline
+//!
+//! The functions used by the daemon's jobs report what failed with `Error` instead of exiting or
+//! panicking, so a job can skip one symbol and carry on with the others. `Error` converts into
+//! `Box<dyn std::error::Error>`, the boxed errors of the providers can be downcast to it.
+
+use std::fmt;
+
+/// What part of the pipeline failed
+#[derive(Debug)]
+pub enum Error {
+    /// no market data provider delivered the data of a symbol
+    Provider { symbol: String, message: String },
+    /// reading or writing the database or the report files failed
+    Storage(String),
+    /// the data could not be analyzed, e.g. missing columns or too few values
+    Analytics(String),
+    /// a chart, table or report could not be rendered
+    Rendering(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn provider(symbol: &str, error: impl fmt::Display) -> Self {
+        Self::Provider { symbol: symbol.to_string(), message: error.to_string() }
+    }
+
+    /// the `Error` of a boxed provider error, any other error becomes a `Provider` error of `symbol`
+    pub fn from_provider(symbol: &str, error: Box<dyn std::error::Error>) -> Self {
+        match error.downcast::<Self>() {
+            Ok(error) => *error,
+            Err(error) => Self::provider(symbol, error),
+        }
+    }
+
+    pub fn storage(error: impl fmt::Display) -> Self {
+        Self::Storage(error.to_string())
+    }
+
+    pub fn analytics(error: impl fmt::Display) -> Self {
+        Self::Analytics(error.to_string())
+    }
+
+    pub fn rendering(error: impl fmt::Display) -> Self {
+        Self::Rendering(error.to_string())
+    }
+
+    /// the symbol a provider failed for
+    pub fn symbol(&self) -> Option<&str> {
+        match self {
+            Self::Provider { symbol, .. } => Some(symbol),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Provider { symbol, message } => write!(f, "provider error for {}: {}", symbol, message),
+            Self::Storage(message) => write!(f, "storage error: {}", message),
+            Self::Analytics(message) => write!(f, "analytics error: {}", message),
+            Self::Rendering(message) => write!(f, "rendering error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<rusqlite::Error> for Error {
+    fn from(error: rusqlite::Error) -> Self {
+        Self::storage(error)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Self::storage(error)
+    }
+}
+
+impl From<polars::prelude::PolarsError> for Error {
+    fn from(error: polars::prelude::PolarsError) -> Self {
+        Self::analytics(error)
+    }
+}
@@ -93,9 +93,13 @@
 
 pub mod models;
 pub mod analytics;
+pub mod calendar;
 pub mod charts;
 pub mod utils;
 pub mod data;
+pub mod error;
+pub use error::Error;
+pub mod metrics;
 pub mod reports;
 #[cfg(test)]
 mod tests;
@@ -146,6 +150,7 @@ pub mod prelude {
     // Traits
     pub use crate::data::ticker::TickerData;
     pub use crate::data::tickers::TickersData;
+    pub use crate::data::livedata::MarketDataProvider;
     pub use crate::charts::ticker::TickerCharts;
     pub use crate::charts::tickers::TickersCharts;
     pub use crate::charts::portfolio::PortfolioCharts;
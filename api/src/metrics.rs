@@ -0,0 +1,67 @@
+//! Metrics of the data pipeline
+//!
+//! With the `metrics` feature the values are recorded in the default `prometheus` registry,
+//! the daemon serves them with its own metrics. Without the feature recording does nothing.
+
+#[cfg(feature = "metrics")]
+mod registry {
+    use once_cell::sync::Lazy;
+    use prometheus::{
+        exponential_buckets, register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec,
+    };
+
+    pub static PROVIDER_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+        register_int_counter_vec!("provider_requests_total", "Requests to the market data providers.", &["provider", "request"])
+            .unwrap()
+    });
+    pub static PROVIDER_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+        register_int_counter_vec!("provider_errors_total", "Failed requests to the market data providers.", &["provider", "request"])
+            .unwrap()
+    });
+    pub static PROVIDER_RATE_LIMITED: Lazy<IntCounterVec> = Lazy::new(|| {
+        register_int_counter_vec!("provider_rate_limited_total", "Requests not sent because of the rate limit.", &["provider"])
+            .unwrap()
+    });
+    pub static PROVIDER_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+        register_histogram_vec!(
+            "provider_request_duration_seconds",
+            "Duration of the requests to the market data providers.",
+            &["provider"],
+            exponential_buckets(0.05, 2.0, 10).unwrap()
+        )
+        .unwrap()
+    });
+    pub static ROWS_INSERTED: Lazy<IntCounterVec> = Lazy::new(|| {
+        register_int_counter_vec!("db_rows_inserted_total", "Rows inserted or updated per table.", &["table"]).unwrap()
+    });
+}
+
+/// a request to a market data provider, `request` names the data asked for
+pub fn provider_request(provider: &str, request: &str, seconds: f64, ok: bool) {
+    #[cfg(feature = "metrics")]
+    {
+        registry::PROVIDER_REQUESTS.with_label_values(&[provider, request]).inc();
+        registry::PROVIDER_SECONDS.with_label_values(&[provider]).observe(seconds);
+        if !ok {
+            registry::PROVIDER_ERRORS.with_label_values(&[provider, request]).inc();
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (provider, request, seconds, ok);
+}
+
+/// a request skipped because the provider reached its rate limit
+pub fn provider_rate_limited(provider: &str) {
+    #[cfg(feature = "metrics")]
+    registry::PROVIDER_RATE_LIMITED.with_label_values(&[provider]).inc();
+    #[cfg(not(feature = "metrics"))]
+    let _ = provider;
+}
+
+/// rows written to a table
+pub fn rows_inserted(table: &str, rows: usize) {
+    #[cfg(feature = "metrics")]
+    registry::ROWS_INSERTED.with_label_values(&[table]).inc_by(rows as u64);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (table, rows);
+}
@@ -2,6 +2,7 @@ use std::error::Error;
 use crate::prelude::{Interval, Tickers, KLINE};
 use crate::analytics::optimization::ObjectiveFunction;
 use crate::analytics::performance::PortfolioPerformanceStats;
+use crate::data::sql::Database;
 
 pub struct PortfolioBuilder {
     pub ticker_symbols: Vec<String>,
@@ -15,6 +16,7 @@ pub struct PortfolioBuilder {
     pub constraints: Option<Vec<(f64, f64)>>,
     pub tickers_data: Option<Vec<KLINE>>,
     pub benchmark_data: Option<KLINE>,
+    pub database: Option<Database>,
 }
 
 impl Default for PortfolioBuilder {
@@ -38,6 +40,7 @@ impl PortfolioBuilder {
             constraints: None,
             tickers_data: None,
             benchmark_data: None,
+            database: None,
         }
     }
 
@@ -96,6 +99,12 @@ impl PortfolioBuilder {
         self
     }
 
+    /// database to read the bars from instead of the default database
+    pub fn database(mut self, database: Option<Database>) -> PortfolioBuilder {
+        self.database = database;
+        self
+    }
+
     pub async fn build(self) -> Result<Portfolio, Box<dyn Error>> {
         let tickers = if self.tickers_data.is_some() {
             Tickers::builder()
@@ -113,6 +122,7 @@ impl PortfolioBuilder {
                 .interval(self.interval)
                 .confidence_level(self.confidence_level)
                 .risk_free_rate(self.risk_free_rate)
+                .database(self.database.clone())
                 .build()
         };
 
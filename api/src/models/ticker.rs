@@ -1,3 +1,4 @@
+use crate::data::sql::Database;
 use crate::prelude::{Interval, KLINE};
 
 
@@ -11,6 +12,7 @@ pub struct TickerBuilder {
     risk_free_rate: f64,
     ticker_data: Option<KLINE>,
     benchmark_data: Option<KLINE>,
+    database: Option<Database>,
 }
 
 impl Default for TickerBuilder {
@@ -31,6 +33,7 @@ impl TickerBuilder {
             risk_free_rate: 0.0,
             ticker_data: None,
             benchmark_data: None,
+            database: None,
         }
     }
 
@@ -79,6 +82,12 @@ impl TickerBuilder {
         self
     }
 
+    /// database to read the bars from instead of the default database
+    pub fn database(mut self, database: Option<Database>) -> TickerBuilder {
+        self.database = database;
+        self
+    }
+
     pub fn build(self) -> Ticker {
         let (ticker, start_date, end_date) = if let Some(ticker_data) = self.ticker_data.clone() {
             (ticker_data.ticker.clone(),
@@ -109,6 +118,7 @@ impl TickerBuilder {
                 ticker_data: benchmark_data.clone(),
                 benchmark_data: None,
                 benchmark_ticker: None,
+                database: self.database.clone(),
             };
 
         Ticker {
@@ -122,6 +132,7 @@ impl TickerBuilder {
             ticker_data: self.ticker_data,
             benchmark_data,
             benchmark_ticker: Some(benchmark_ticker.into()),
+            database: self.database,
         }
     }
 }
@@ -181,12 +192,40 @@ pub struct Ticker {
     pub ticker_data: Option<KLINE>,
     pub benchmark_data: Option<KLINE>,
     pub benchmark_ticker: Option<Box<Ticker>>,
+    /// database with the bars, the default database if not set
+    pub database: Option<Database>,
 }
 
 impl  Ticker {
     pub fn builder() -> TickerBuilder {
         TickerBuilder::new()
     }
+
+    /// the database with the bars, the default database if none was set
+    pub fn database(&self) -> Result<Database, Box<dyn std::error::Error>> {
+        match &self.database {
+            Some(database) => Ok(database.clone()),
+            None => crate::data::sql::connect(),
+        }
+    }
+
+    /// MIC of the exchange the bars of the ticker are stored for, the default exchange if the
+    /// symbol is unknown
+    pub fn exchange(&self) -> String {
+        let sql_connection = match self.database() {
+            Ok(database) => database,
+            Err(error) => {
+                log::error!("Failed to open the database of {}! {}", self.ticker, error);
+                return crate::calendar::DEFAULT_EXCHANGE.to_string();
+            }
+        };
+        let exchange_code = crate::data::sql::metadata(sql_connection, crate::calendar::DEFAULT_EXCHANGE, &self.ticker).exchange_code;
+        if exchange_code.is_empty() {
+            crate::calendar::DEFAULT_EXCHANGE.to_string()
+        } else {
+            exchange_code
+        }
+    }
 }
 
 
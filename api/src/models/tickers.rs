@@ -1,5 +1,6 @@
 use std::error::Error;
 use crate::analytics::performance::PortfolioPerformanceStats;
+use crate::data::sql::Database;
 use crate::prelude::{Interval, ObjectiveFunction, Portfolio, Ticker, KLINE};
 
 
@@ -13,6 +14,7 @@ pub struct TickersBuilder {
     risk_free_rate: f64,
     tickers_data: Option<Vec<KLINE>>,
     benchmark_data: Option<KLINE>,
+    database: Option<Database>,
 }
 
 impl Default for TickersBuilder {
@@ -33,6 +35,7 @@ impl TickersBuilder {
             risk_free_rate: 0.0,
             tickers_data: None,
             benchmark_data: None,
+            database: None,
         }
     }
 
@@ -80,6 +83,13 @@ impl TickersBuilder {
         self.benchmark_data = benchmark_data;
         self
     }
+
+    /// database to read the bars from instead of the default database
+    pub fn database(mut self, database: Option<Database>) -> TickersBuilder {
+        self.database = database;
+        self
+    }
+
     pub fn build(self) -> Tickers {
         let benchmark_ticker = if let Some(benchmark_data) = self.benchmark_data.clone() {
             Ticker::builder()
@@ -95,6 +105,7 @@ impl TickersBuilder {
                 .interval(self.interval)
                 .confidence_level(self.confidence_level)
                 .risk_free_rate(self.risk_free_rate)
+                .database(self.database.clone())
                 .build()
         };
 
@@ -116,6 +127,7 @@ impl TickersBuilder {
                     .benchmark_symbol(&self.benchmark_symbol)
                     .confidence_level(self.confidence_level)
                     .risk_free_rate(self.risk_free_rate)
+                    .database(self.database.clone())
                     .build()
             ).collect::<Vec<Ticker>>()
         };
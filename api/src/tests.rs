@@ -158,7 +158,7 @@ async fn test_ticker_data() -> Result<(), Box<dyn Error>> {
     }
 
        // get a list of symbols from the database
-    let sql_connection = super::data::sql::connect();
+    let sql_connection = super::data::sql::connect().unwrap();
     let symbolsstrings = super::data::sql::active_symbols(sql_connection.clone());
 
     // 
@@ -290,7 +290,7 @@ async fn test_portfolio() -> Result<(), Box<dyn Error>> {
     }
 
     // get a list of symbols from the database
-    let sql_connection = super::data::sql::connect();
+    let sql_connection = super::data::sql::connect().unwrap();
     let symbolsstrings = super::data::sql::active_symbols(sql_connection.clone());
     //let symbolsstrings = vec!("ADS".to_string(), "BAS".to_string(), "MSFT".to_string());
 
@@ -472,3 +472,946 @@ async fn test_portfolio() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+
+struct FailingProvider {}
+
+impl MarketDataProvider for FailingProvider {
+    fn name(&self) -> &str {
+        "failing"
+    }
+
+    fn intraday(&self, _symbol: &str, _start_time: NaiveDateTime, _end_time: NaiveDateTime) -> Result<Vec<super::data::sql::TimeSeriesData>, Box<dyn Error>> {
+        Err("provider is down".into())
+    }
+
+    fn daily(&self, _symbol: &str, _start_date: chrono::NaiveDate, _end_date: chrono::NaiveDate) -> Result<Vec<super::data::sql::TimeSeriesData>, Box<dyn Error>> {
+        Err("provider is down".into())
+    }
+}
+
+struct ConstantProvider {}
+
+impl MarketDataProvider for ConstantProvider {
+    fn name(&self) -> &str {
+        "constant"
+    }
+
+    fn intraday(&self, _symbol: &str, start_time: NaiveDateTime, _end_time: NaiveDateTime) -> Result<Vec<super::data::sql::TimeSeriesData>, Box<dyn Error>> {
+        Ok(vec![super::data::sql::TimeSeriesData {
+            datetime: start_time.and_utc().timestamp(),
+            open: 1.0,
+            high: 1.0,
+            low: 1.0,
+            close: 1.0,
+            volume: 1.0,
+        }])
+    }
+
+    fn daily(&self, _symbol: &str, _start_date: chrono::NaiveDate, _end_date: chrono::NaiveDate) -> Result<Vec<super::data::sql::TimeSeriesData>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    fn rate_limit(&self) -> super::data::livedata::RateLimit {
+        super::data::livedata::RateLimit {
+            requests_per_minute: Some(1),
+            requests_per_day: None,
+        }
+    }
+}
+
+#[test]
+fn test_provider_failover() {
+    let mut providers = super::data::livedata::MarketDataProviders::new();
+    providers.register(Box::new(ConstantProvider {}), 1);
+    providers.register(Box::new(FailingProvider {}), 0);
+    assert_eq!(providers.names(), vec!["failing".to_string(), "constant".to_string()]);
+
+    let start = NaiveDateTime::parse_from_str("2025-03-03 09:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let end = NaiveDateTime::parse_from_str("2025-03-03 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let series = providers.intraday("AAPL", start, end).unwrap();
+    assert_eq!(series.len(), 1);
+    // the constant provider allows one request per minute
+    assert!(providers.intraday("AAPL", start, end).is_err());
+}
+
+#[test]
+fn test_replay_provider() {
+    let mut replay = super::data::livedata::ReplayProvider::new(0.0);
+    replay.add(super::models::kline::KLINE::from_csv("AAPL", "../examples/datasets/aapl.csv").unwrap());
+    assert_eq!(replay.symbols(), vec!["AAPL".to_string()]);
+
+    let start = chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+    let end = chrono::NaiveDate::from_ymd_opt(2023, 1, 31).unwrap();
+    let series = replay.daily("AAPL", start, end).unwrap();
+    assert!(!series.is_empty());
+    assert_eq!(series[0].close, 125.07);
+    assert!(replay.daily("MSFT", start, end).is_err());
+    // without a speed the replay follows the wall clock
+    assert!(replay.clock().is_none());
+}
+
+#[test]
+fn test_migrations() {
+    use super::data::sql::migrations;
+    let mut connection = rusqlite::Connection::open_in_memory().unwrap();
+    assert_eq!(migrations::schema_version(&connection).unwrap(), 0);
+    assert_eq!(migrations::pending_migrations(&connection).unwrap().len(), migrations::MIGRATIONS.len());
+
+    let applied = migrations::migrate(&mut connection).unwrap();
+    assert_eq!(applied.len(), migrations::MIGRATIONS.len());
+    assert_eq!(migrations::schema_version(&connection).unwrap(), migrations::latest_version());
+    // applying again is a no-op
+    assert!(migrations::migrate(&mut connection).unwrap().is_empty());
+    assert!(migrations::status(&connection).unwrap().pending.is_empty());
+}
+
+#[test]
+fn test_timeseries_upsert() {
+    use super::data::sql::{self, TimeSeriesData};
+    let sql_connection = sql::Database::open(":memory:").unwrap();
+    let metadata = sql::MetaData {
+        symbol: "TEST".to_string(),
+        exchange: "XFRA".to_string(),
+        ..Default::default()
+    };
+    let bar = |datetime: i64, close: f64| TimeSeriesData { datetime, open: close, high: close, low: close, close, volume: 1.0 };
+
+    assert_eq!(sql::insert_timeseries_data(sql_connection.clone(), &metadata, &[bar(1, 1.0), bar(2, 2.0)]), 1);
+    // the overlapping bar is updated instead of duplicated
+    assert_eq!(sql::insert_timeseries_data(sql_connection.clone(), &metadata, &[bar(2, 2.5), bar(3, 3.0)]), 1);
+    let stored = sql::time_series::timeseries_all(sql_connection.clone(), &metadata);
+    assert_eq!(stored.len(), 3);
+    assert_eq!(stored[1].close, 2.5);
+
+    let new_bars = sql::insert_live_bars(sql_connection.clone(), &metadata, &[bar(60, 1.0), bar(120, 2.0)]);
+    assert_eq!(new_bars.len(), 2);
+    let new_bars = sql::insert_live_bars(sql_connection.clone(), &metadata, &[bar(120, 2.0), bar(180, 3.0)]);
+    assert_eq!(new_bars.len(), 1);
+    assert_eq!(new_bars[0].datetime, 180);
+    // the live updates continue after the newest bar of the exchange
+    assert_eq!(sql::live_data::last_live_timestamp(sql_connection.clone(), &metadata), Some(180));
+    let other = sql::MetaData { symbol: "TEST".to_string(), exchange: "XNYS".to_string(), ..Default::default() };
+    assert_eq!(sql::live_data::last_live_timestamp(sql_connection.clone(), &other), None);
+}
+
+#[test]
+fn test_database_read_pool() {
+    let dir = std::env::temp_dir().join(format!("stock-database-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("time_series.sqlite");
+    let database = super::data::sql::Database::with_readers(&format!("sqlite://{}", path.display()), 2).unwrap();
+    let mode: String = database.read().unwrap().query_row("PRAGMA journal_mode", (), |row| row.get(0)).unwrap();
+    assert_eq!(mode.to_lowercase(), "wal");
+
+    super::data::sql::insert_active_symbols(database.clone(), &vec!["TEST".to_string()]);
+    // two readers at the same time do not block each other or the writer
+    let first = database.read().unwrap();
+    let second = database.read().unwrap();
+    drop((first, second));
+    assert!(super::data::sql::active_symbols(database.clone()).contains(&"TEST".to_string()));
+    drop(database);
+    let _ret = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_bar_timestamps_and_gaps() {
+    use super::data::livedata::provider::{exchange_timestamp, find_gaps};
+    let datetime = NaiveDateTime::parse_from_str("2025-03-03 09:30:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    // New York is five hours behind UTC in winter
+    assert_eq!(exchange_timestamp(datetime, "America/New_York").unwrap(), datetime.and_utc().timestamp() + 5 * 3600);
+    assert_eq!(exchange_timestamp(datetime, "").unwrap(), datetime.and_utc().timestamp());
+    assert!(exchange_timestamp(datetime, "Nowhere/Atlantis").is_err());
+
+    let base = datetime.and_utc().timestamp();
+    let bar = |datetime: i64| super::data::sql::TimeSeriesData { datetime, open: 1.0, high: 1.0, low: 1.0, close: 1.0, volume: 1.0 };
+    let series = vec![bar(base), bar(base + 60), bar(base + 240), bar(base + 300), bar(base + 86400)];
+    let gaps = find_gaps(&series, 60);
+    assert_eq!(gaps.len(), 1);
+    assert_eq!(gaps[0].missing, 2);
+    assert_eq!(gaps[0].after, base + 60);
+}
+
+#[test]
+fn test_resample() {
+    use super::data::sql::to_dataframe::resample;
+    // 7 minutes of one session and 3 minutes of the next day
+    let start = NaiveDateTime::parse_from_str("2025-03-03 14:30:00", "%Y-%m-%d %H:%M:%S").unwrap().and_utc().timestamp_millis();
+    let next_day = start + 86_400_000;
+    let mut timestamp: Vec<i64> = (0..7).map(|i| start + i * 60_000).collect();
+    timestamp.extend((0..3).map(|i| next_day + i * 60_000));
+    let close: Vec<f64> = (0..10).map(|i| i as f64 + 1.0).collect();
+    let df = polars::df!(
+        "timestamp" => &timestamp,
+        "open" => &close,
+        "high" => &close.iter().map(|x| x + 0.5).collect::<Vec<f64>>(),
+        "low" => &close.iter().map(|x| x - 0.5).collect::<Vec<f64>>(),
+        "close" => &close,
+        "volume" => &vec![10.0; 10],
+        "adjclose" => &close
+    ).unwrap();
+
+    let bars = resample(&df, Interval::FiveMinutes, "XNYS").unwrap();
+    // 5 + 2 minutes on the first day, the second bar ends with the session
+    assert_eq!(bars.height(), 3);
+    let open = bars.column("open").unwrap().f64().unwrap().to_vec();
+    let high = bars.column("high").unwrap().f64().unwrap().to_vec();
+    let close = bars.column("close").unwrap().f64().unwrap().to_vec();
+    let volume = bars.column("volume").unwrap().f64().unwrap().to_vec();
+    assert_eq!(open, vec![Some(1.0), Some(6.0), Some(8.0)]);
+    assert_eq!(high, vec![Some(5.5), Some(7.5), Some(10.5)]);
+    assert_eq!(close, vec![Some(5.0), Some(7.0), Some(10.0)]);
+    assert_eq!(volume, vec![Some(50.0), Some(20.0), Some(30.0)]);
+
+    let days = resample(&df, Interval::OneDay, "XNYS").unwrap();
+    assert_eq!(days.height(), 2);
+
+    // the buckets are aligned to the opening at 09:30 New York time, not to the first bar
+    let late = &timestamp[2..7];
+    let df = polars::df!(
+        "timestamp" => late,
+        "open" => &close[2..7],
+        "high" => &close[2..7],
+        "low" => &close[2..7],
+        "close" => &close[2..7],
+        "volume" => &vec![10.0; 5]
+    ).unwrap();
+    let bars = resample(&df, Interval::FiveMinutes, "XNYS").unwrap();
+    assert_eq!(bars.column("timestamp").unwrap().i64().unwrap().to_vec(), vec![Some(start), Some(start + 300_000)]);
+
+    // an hourly series trading around the clock over a weekend, one bar per local date in New York
+    let weekend = NaiveDateTime::parse_from_str("2025-03-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap().and_utc().timestamp_millis();
+    let timestamp: Vec<i64> = (0..48).map(|i| weekend + i * 3_600_000).collect();
+    let close: Vec<f64> = (0..48).map(|i| i as f64).collect();
+    let df = polars::df!(
+        "timestamp" => &timestamp,
+        "open" => &close,
+        "high" => &close,
+        "low" => &close,
+        "close" => &close,
+        "volume" => &vec![1.0; 48]
+    ).unwrap();
+    let days = resample(&df, Interval::OneDay, "XNYS").unwrap();
+    assert_eq!(days.column("volume").unwrap().f64().unwrap().to_vec(), vec![Some(5.0), Some(24.0), Some(19.0)]);
+    let hours = resample(&df, Interval::SixtyMinutes, "XNYS").unwrap();
+    assert_eq!(hours.height(), 48);
+}
+
+#[test]
+fn test_corporate_actions() {
+    use super::data::sql::{self, CorporateAction, CorporateActionKind, TimeSeriesData};
+    let sql_connection = sql::Database::open(":memory:").unwrap();
+    let day = |d: u32| chrono::NaiveDate::from_ymd_opt(2024, 6, d).unwrap();
+    let bar = |d: u32, close: f64| TimeSeriesData {
+        datetime: super::data::livedata::provider::daily_timestamp(day(d)),
+        open: close, high: close, low: close, close, volume: 1.0,
+    };
+    let series = vec![bar(3, 100.0), bar(4, 102.0), bar(5, 50.0), bar(6, 49.0)];
+    let actions = vec![
+        CorporateAction::dividend("TEST", day(4), 1.0),
+        CorporateAction::split("TEST", day(5), 2.0, 1.0),
+    ];
+    assert_eq!(sql::insert_corporate_actions(sql_connection.clone(), &actions), 1);
+    // storing again updates instead of duplicating
+    assert_eq!(sql::insert_corporate_actions(sql_connection.clone(), &actions), 1);
+    let stored = sql::corporate_actions(sql_connection.clone(), "TEST");
+    assert_eq!(stored, actions);
+
+    let adjclose = sql::adjusted_close(&series, &stored);
+    assert_eq!(adjclose[3], 49.0);
+    assert_eq!(adjclose[2], 50.0);
+    assert!((adjclose[1] - 51.0).abs() < 1e-9);
+    assert!((adjclose[0] - 100.0 * 0.5 * 0.99).abs() < 1e-9);
+
+    let dir = std::env::temp_dir().join(format!("stock-corporate-actions-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("actions.csv");
+    std::fs::write(&path, "symbol,date,kind,value\nTEST,2024-06-05,split,2:1\nTEST,2024-06-04,dividend,1.0\n").unwrap();
+    let from_csv = sql::corporate_actions::corporate_actions_from_csv(&path).unwrap();
+    assert_eq!(from_csv.len(), 2);
+    assert_eq!(from_csv[0].kind, CorporateActionKind::Split);
+    assert_eq!(from_csv[0].value, 2.0);
+    std::fs::write(&path, "TEST,2024-06-05,merger,1\n").unwrap();
+    assert!(sql::corporate_actions::corporate_actions_from_csv(&path).is_err());
+    let _ret = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_calendar() {
+    use super::calendar;
+    let utc = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap().and_utc();
+    let date = |s: &str| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+    // New York opens 9:30 local, 14:30 UTC in winter and 13:30 UTC in summer
+    assert!(!calendar::is_open("XNYS", utc("2025-03-03 14:29")));
+    assert!(calendar::is_open("XNYS", utc("2025-03-03 14:30")));
+    assert!(calendar::is_open("XNAS", utc("2025-07-01 13:30")));
+    assert!(!calendar::is_open("XNYS", utc("2025-07-01 20:00")));
+    // early close and holiday
+    assert!(!calendar::is_open("XNYS", utc("2025-11-28 18:30")));
+    assert!(!calendar::is_open("XNYS", utc("2025-12-25 15:00")));
+    // Friday evening to Monday morning
+    assert_eq!(calendar::next_open("XNYS", utc("2025-03-07 21:00")), Some(utc("2025-03-10 13:30")));
+    // Good Friday and Easter Monday in Frankfurt
+    assert_eq!(calendar::next_open("XFRA", utc("2025-04-17 21:00")), Some(utc("2025-04-22 06:00")));
+    assert!(calendar::is_open("", utc("2025-04-22 19:59")));
+
+    assert_eq!(calendar::trading_days_between("XETR", date("2025-04-14"), date("2025-04-28")), 8);
+    assert_eq!(calendar::calendar("XNYS").trading_days_per_year(2025), 250);
+
+    // unknown exchanges trade on weekdays in the timezone of the exchange list
+    let tokyo = calendar::calendar("XJPX");
+    assert_eq!(tokyo.timezone, chrono_tz::Asia::Tokyo);
+    assert!(tokyo.is_open(utc("2025-03-03 01:00")));
+    assert!(!tokyo.is_open(utc("2025-03-01 01:00")));
+
+    let daily = IntervalDays { average: 1.4, mode: 1.0 };
+    assert_eq!(calendar::annual_periods("XNYS", &daily), calendar::calendar("XNYS").trading_days_per_year(chrono::Utc::now().year()) as f64);
+}
+
+#[test]
+fn test_data_quality() {
+    use super::data::quality::{self, BarTable, QualitySettings, Repair};
+    use super::data::sql::{self, TimeSeriesData};
+    let bar = |datetime: i64, close: f64| TimeSeriesData { datetime, open: close, high: close + 1.0, low: close - 1.0, close, volume: 1.0 };
+    let mut series: Vec<TimeSeriesData> = (0..10).map(|i| bar(i * 60, 100.0 + i as f64)).collect();
+    series[2].high = series[2].low - 1.0;
+    series[3].close = -1.0;
+    series[4].volume = f64::NAN;
+    // a single bar far off that returns to the level before
+    series[6].close = 200.0;
+    series[6].high = 201.0;
+    series[8].datetime = series[9].datetime;
+    let flags = quality::validate(&series, 10.0, 0);
+    assert_eq!(flags[0], 0);
+    assert_eq!(flags[2], quality::INVALID_RANGE);
+    assert_ne!(flags[3] & quality::NONPOSITIVE, 0);
+    assert_eq!(flags[4], quality::NOT_A_NUMBER);
+    assert_eq!(flags[6], quality::SPIKE);
+    assert_eq!(flags[8], quality::DUPLICATE);
+    assert_eq!(flags[9], 0);
+
+    let flat: Vec<TimeSeriesData> = (0..8).map(|i| TimeSeriesData { datetime: i, open: 5.0, high: 5.0, low: 5.0, close: 5.0, volume: 0.0 }).collect();
+    let flags = quality::validate(&flat, 10.0, 5);
+    assert_eq!(flags[0], 0);
+    assert!(flags[1..].iter().all(|f| *f == quality::STALE));
+
+    // flagged bars are not read any more, quarantined ones are moved
+    let sql_connection = sql::Database::open(":memory:").unwrap();
+    let metadata = sql::MetaData { symbol: "TEST".to_string(), exchange: "XFRA".to_string(), ..Default::default() };
+    let daily: Vec<TimeSeriesData> = (0..5).map(|i| bar(1_700_000_000 + i * 86400, if i == 2 { 500.0 } else { 100.0 })).collect();
+    assert_eq!(sql::insert_timeseries_data(sql_connection.clone(), &metadata, &daily), 1);
+    let settings = QualitySettings { repair: Repair::Flag, ..Default::default() };
+    let report = quality::check_symbol(sql_connection.clone(), BarTable::TimeSeries, "TEST", "XFRA", &settings);
+    assert_eq!(report.counts.get("spike"), Some(&1));
+    assert_eq!(report.flagged, 1);
+    assert_eq!(sql::time_series::timeseries_all(sql_connection.clone(), &metadata).len(), 4);
+    // the same bars delivered again keep their flags, a corrected bar is read again
+    assert_eq!(sql::insert_timeseries_data(sql_connection.clone(), &metadata, &daily), 1);
+    assert_eq!(sql::time_series::timeseries_all(sql_connection.clone(), &metadata).len(), 4);
+    let corrected: Vec<TimeSeriesData> = daily.iter().map(|b| bar(b.datetime, 100.0)).collect();
+    assert_eq!(sql::insert_timeseries_data(sql_connection.clone(), &metadata, &corrected), 1);
+    assert_eq!(sql::time_series::timeseries_all(sql_connection.clone(), &metadata).len(), 5);
+    assert_eq!(sql::insert_timeseries_data(sql_connection.clone(), &metadata, &daily), 1);
+
+    let settings = QualitySettings { repair: Repair::Quarantine, ..Default::default() };
+    let report = quality::check_symbol(sql_connection.clone(), BarTable::TimeSeries, "TEST", "XFRA", &settings);
+    assert_eq!(report.quarantined, 1);
+    assert_eq!(sql::quality::quarantined_count(sql_connection.clone(), "TEST"), 1);
+    assert_eq!(sql::quality::stored_bars(sql_connection.clone(), BarTable::TimeSeries, "TEST", "XFRA").len(), 4);
+
+    // the bars of another exchange at the same times and other prices are a series of their own
+    let other = sql::MetaData { symbol: "DUAL".to_string(), exchange: "XNYS".to_string(), ..Default::default() };
+    let home = sql::MetaData { symbol: "DUAL".to_string(), exchange: "XFRA".to_string(), ..Default::default() };
+    let prices = |factor: f64| -> Vec<TimeSeriesData> { (0..5).map(|i| bar(1_700_000_000 + i * 86400, factor * (100.0 + i as f64))).collect() };
+    assert_eq!(sql::insert_timeseries_data(sql_connection.clone(), &home, &prices(1.0)), 1);
+    assert_eq!(sql::insert_timeseries_data(sql_connection.clone(), &other, &prices(1.6)), 1);
+    assert_eq!(sql::quality::bar_exchanges(sql_connection.clone(), BarTable::TimeSeries, "DUAL"), vec!["XFRA", "XNYS"]);
+    let settings = QualitySettings { repair: Repair::Flag, ..Default::default() };
+    let reports = quality::validate_symbols(sql_connection.clone(), &vec!["DUAL".to_string()], &settings);
+    assert_eq!(reports.len(), 2);
+    assert!(reports.iter().all(|r| r.is_clean() && r.bars == 5), "{:?}", reports);
+    assert_eq!(reports[1].to_string(), "DUAL XNYS time_series: 5 bars");
+    // no bar of either exchange was flagged
+    assert_eq!(sql::time_series::timeseries_all(sql_connection.clone(), &other).len(), 10);
+}
+
+#[test]
+fn test_alert_rules() {
+    use super::analytics::rules::{AlertRule, Condition, Direction};
+    use super::data::sql::{self, alert_rules, TimeSeriesData};
+    let bar = |i: i64, close: f64, volume: f64| TimeSeriesData { datetime: 1_700_000_000 + i * 60, open: close, high: close, low: close, close, volume };
+    let mut bars: Vec<TimeSeriesData> = (0..30).map(|i| bar(i, 100.0 + (i % 2) as f64 * 0.1, 1000.0)).collect();
+    let crosses = Condition::PriceCrosses { level: 105.0, direction: Direction::Above };
+    let spike = Condition::VolumeSpike { period: 20, factor: 3.0 };
+    assert_eq!(crosses.evaluate(&bars), None);
+    bars.push(bar(30, 106.0, 5000.0));
+    assert!(crosses.evaluate(&bars).is_some());
+    assert!(spike.evaluate(&bars).is_some());
+    assert!(Condition::Change { bars: 5, percent: 5.0, direction: Direction::Above }.evaluate(&bars).is_some());
+    assert!(Condition::Change { bars: 5, percent: 5.0, direction: Direction::Below }.evaluate(&bars).is_none());
+    assert!(Condition::Bollinger { period: 20, std_dev: 2.0, direction: Direction::Above }.evaluate(&bars).is_some());
+    assert!(Condition::Rsi { period: 14, level: 70.0, direction: Direction::Above }.evaluate(&bars).is_some());
+    let both = Condition::All { conditions: vec![crosses.clone(), Condition::Rsi { period: 14, level: 30.0, direction: Direction::Below }] };
+    assert_eq!(both.evaluate(&bars), None);
+    let either = Condition::Any { conditions: vec![crosses.clone(), spike.clone()] };
+    assert!(either.evaluate(&bars).is_some());
+    // the level was crossed by the bar before, the price staying above does not fire again
+    bars.push(bar(31, 107.0, 1000.0));
+    assert_eq!(crosses.evaluate(&bars), None);
+    bars.pop();
+
+    let json = r#"{"type": "all", "conditions": [{"type": "macd_cross", "direction": "above"}, {"type": "rsi", "level": 70, "direction": "above"}]}"#;
+    let parsed: Condition = serde_json::from_str(json).unwrap();
+    assert!(matches!(&parsed, Condition::All { conditions } if matches!(conditions[0], Condition::MacdCross { fast: 12, slow: 26, signal: 9, .. })));
+    assert!(parsed.validate().is_empty());
+    assert_eq!(Condition::Rsi { period: 0, level: 170.0, direction: Direction::Above }.validate().len(), 2);
+
+    // stored rules fire once per cooldown and symbol
+    let sql_connection = sql::Database::open(":memory:").unwrap();
+    let rule = AlertRule {
+        rule_id: 0,
+        name: "breakout".to_string(),
+        symbol: "*".to_string(),
+        condition: either,
+        cooldown_minutes: 30,
+        level: "critical".to_string(),
+        notify: vec!["phone".to_string()],
+        enabled: true,
+    };
+    let rule_id = alert_rules::insert_alert_rule(sql_connection.clone(), &rule).unwrap();
+    assert_eq!(alert_rules::insert_alert_rule(sql_connection.clone(), &rule), Some(rule_id));
+    let rules = alert_rules::alert_rules(sql_connection.clone());
+    assert_eq!(rules, vec![AlertRule { rule_id, ..rule }]);
+    let now = bars.last().unwrap().datetime;
+    let policy = sql::AlertPolicy { resolve_after: 300, ..Default::default() };
+    let events = alert_rules::evaluate_alert_rules(sql_connection.clone(), &rules, "AAPL", &bars, &policy, now);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].rule, "breakout");
+    assert_eq!(events[0].notify, vec!["phone".to_string()]);
+    // the next breakout of the same symbol is within the cooldown of the rule, other symbols are not
+    assert!(alert_rules::evaluate_alert_rules(sql_connection.clone(), &rules, "AAPL", &bars, &policy, now + 600).is_empty());
+    assert_eq!(alert_rules::evaluate_alert_rules(sql_connection.clone(), &rules, "MSFT", &bars, &policy, now + 600).len(), 1);
+    assert_eq!(alert_rules::evaluate_alert_rules(sql_connection.clone(), &rules, "AAPL", &bars, &policy, now + 1800).len(), 1);
+    assert_eq!(alert_rules::delete_alert_rule(sql_connection.clone(), "breakout"), 1);
+    assert!(alert_rules::alert_rules(sql_connection.clone()).is_empty());
+}
+
+#[test]
+fn test_alerts() {
+    use super::data::sql::{self, alerts, AlertPolicy, AlertState, JumpEventData};
+    let sql_connection = sql::Database::open(":memory:").unwrap();
+    let policy = AlertPolicy { cooldown: 3600, resolve_after: 600, escalate_after: 1800 };
+    let new = alerts::NewAlert {
+        fingerprint: alerts::fingerprint("slope", "AAPL", "up"),
+        source: "slope".to_string(),
+        symbol: "AAPL".to_string(),
+        level: "warning".to_string(),
+        title: "stock-analysis".to_string(),
+        body: "Symbol AAPL increased".to_string(),
+    };
+    let t = 1_700_000_000;
+    // one notification while the condition holds every minute
+    let first = alerts::raise_alert(sql_connection.clone(), &new, &policy, t).unwrap();
+    assert!(first.notify);
+    for minute in 1..10 {
+        let raised = alerts::raise_alert(sql_connection.clone(), &new, &policy, t + minute * 60).unwrap();
+        assert_eq!(raised.alert_id, first.alert_id);
+        assert!(!raised.notify);
+    }
+    let alert = alerts::alert(sql_connection.clone(), first.alert_id).unwrap();
+    assert_eq!(alert.occurrences, 10);
+    assert_eq!(alert.state, AlertState::Open);
+
+    // still going on and not acknowledged, escalated once
+    let mut minute = 10;
+    let escalated = loop {
+        let raised = alerts::raise_alert(sql_connection.clone(), &new, &policy, t + minute * 60).unwrap();
+        if raised.notify {
+            break raised;
+        }
+        minute += 1;
+    };
+    assert_eq!(minute, 30);
+    assert!(escalated.escalated);
+    assert_eq!(escalated.level, "critical");
+    assert!(!alerts::raise_alert(sql_connection.clone(), &new, &policy, t + 31 * 60).unwrap().notify);
+
+    // an acknowledged alert stays silent, a snoozed one reminds after the snooze
+    assert_eq!(alerts::acknowledge_alert(sql_connection.clone(), first.alert_id, t + 32 * 60), 1);
+    assert!(!alerts::raise_alert(sql_connection.clone(), &new, &policy, t + 33 * 60).unwrap().notify);
+    assert_eq!(alerts::snooze_alert(sql_connection.clone(), first.alert_id, t + 40 * 60), 1);
+    assert!(!alerts::raise_alert(sql_connection.clone(), &new, &policy, t + 34 * 60).unwrap().notify);
+    assert!(alerts::raise_alert(sql_connection.clone(), &new, &policy, t + 41 * 60).unwrap().notify);
+
+    // the condition ended, the next slope is a new event held back by the cooldown until it is over
+    assert_eq!(alerts::resolve_alert(sql_connection.clone(), &new.fingerprint, t + 42 * 60), 1);
+    let second = alerts::raise_alert(sql_connection.clone(), &new, &policy, t + 50 * 60).unwrap();
+    assert_ne!(second.alert_id, first.alert_id);
+    assert!(!second.notify);
+    for minute in 51..101 {
+        assert!(!alerts::raise_alert(sql_connection.clone(), &new, &policy, t + minute * 60).unwrap().notify);
+    }
+    let held_back = alerts::raise_alert(sql_connection.clone(), &new, &policy, t + 101 * 60).unwrap();
+    assert_eq!(held_back.alert_id, second.alert_id);
+    assert!(held_back.notify);
+    // a quiet time ends the alert too
+    let third = alerts::raise_alert(sql_connection.clone(), &new, &policy, t + 200 * 60).unwrap();
+    assert_ne!(third.alert_id, second.alert_id);
+    assert!(third.notify);
+
+    let history = alerts::alerts(sql_connection.clone(), Some("AAPL"), None, t, 10);
+    assert_eq!(history.iter().map(|a| a.alert_id).collect::<Vec<i64>>(), vec![third.alert_id, second.alert_id, first.alert_id]);
+    assert_eq!(alerts::alerts(sql_connection.clone(), None, Some(AlertState::Open), t, 10).len(), 1);
+    assert!(alerts::alerts(sql_connection.clone(), Some("MSFT"), None, t, 10).is_empty());
+    assert_eq!(alerts::acknowledge_alert(sql_connection.clone(), first.alert_id, t + 300 * 60), 0);
+
+    // rescanning the day stores every jump once
+    let jumps: Vec<JumpEventData> = (0..3).map(|i| JumpEventData { datetime: t + i * 60, symbol: "AAPL".to_string(), percent: 0.6 }).collect();
+    assert_eq!(sql::events::insert_jump_events(sql_connection.clone(), &jumps), 3);
+    assert_eq!(sql::events::insert_jump_events(sql_connection.clone(), &jumps), 0);
+    assert_eq!(sql::events::jump_events_count(sql_connection.clone(), "AAPL"), 3);
+}
+
+#[test]
+fn test_live_chart() {
+    use super::analytics::technicals::live_indicators;
+    use super::charts::live::live_chart_html;
+    use super::data::sql::TimeSeriesData;
+    let bars: Vec<TimeSeriesData> = (0..60)
+        .map(|i| TimeSeriesData { datetime: 1_700_000_000 + i * 60, open: 100.0, high: 101.0, low: 99.0, close: 100.0 + i as f64, volume: 1000.0 })
+        .collect();
+    let values = live_indicators(&bars);
+    // mean of the closes 110 to 159
+    assert_eq!(values.get("sma-50"), Some(&134.5));
+    assert!(values.contains_key("rsi-14"));
+    assert!(values.contains_key("macd-(12,26,9)"));
+    assert!(values.contains_key("bb_upper-(20,2)"));
+    assert!(!values.contains_key("sma-200"));
+    assert!(live_indicators(&bars[..10]).is_empty());
+
+    let html = live_chart_html("<html><body><div id=\"plotly-html-element\"></div></body></html>", "/stream?symbols=AAPL</script>");
+    assert!(html.contains("new EventSource(\"/stream?symbols=AAPL\\u003c/script>\")"));
+    assert!(html.contains("[[\"MA 50\",\"sma-50\"]"));
+    assert!(html.ends_with("</script>\n</body></html>"));
+}
+
+#[test]
+fn test_provider_errors() {
+    use super::data::livedata::MarketDataProviders;
+    let start = chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(9, 0, 0).unwrap();
+    let error = MarketDataProviders::new().intraday("AAPL", start, start + chrono::Duration::hours(8)).unwrap_err();
+    // the boxed error of the providers keeps the symbol it failed for
+    let error = super::Error::from_provider("MSFT", error);
+    assert_eq!(error.symbol(), Some("AAPL"));
+    assert!(error.to_string().starts_with("provider error for AAPL: no provider delivered intraday data"));
+
+    let error = super::Error::from_provider("MSFT", "timeout".into());
+    assert_eq!(error.to_string(), "provider error for MSFT: timeout");
+    assert_eq!(super::Error::storage("disk full").symbol(), None);
+}
+
+/// a daily OHLCV DataFrame of made up, valid bars
+fn synthetic_ohlcv(len: usize) -> polars::prelude::DataFrame {
+    use polars::prelude::*;
+    let close: Vec<f64> = (0..len).map(|i| 100.0 + 10.0 * (i as f64 / 5.0).sin() + 0.1 * i as f64).collect();
+    let open: Vec<f64> = close.iter().enumerate().map(|(i, c)| c - 0.5 * (i as f64 / 3.0).cos()).collect();
+    let high: Vec<f64> = open.iter().zip(close.iter()).map(|(o, c)| o.max(*c) + 1.0).collect();
+    let low: Vec<f64> = open.iter().zip(close.iter()).map(|(o, c)| o.min(*c) - 1.0).collect();
+    let volume: Vec<f64> = (0..len).map(|i| 1000.0 + (i % 7) as f64 * 100.0).collect();
+    df!(
+        "timestamp" => (0..len as i64).map(|i| (1_700_000_000 + i * 86400) * 1000).collect::<Vec<i64>>(),
+        "open" => open,
+        "high" => high,
+        "low" => low,
+        "close" => close.clone(),
+        "volume" => volume,
+        "adjclose" => close,
+    ).unwrap()
+}
+
+/// daily bars of (open, high, low, close, volume) candles at the times of `synthetic_ohlcv`
+fn daily_bars(candles: impl IntoIterator<Item = (f64, f64, f64, f64, f64)>) -> Vec<super::data::sql::TimeSeriesData> {
+    candles
+        .into_iter()
+        .enumerate()
+        .map(|(i, (open, high, low, close, volume))| super::data::sql::TimeSeriesData {
+            datetime: 1_700_000_000 + i as i64 * 86400,
+            open,
+            high,
+            low,
+            close,
+            volume,
+        })
+        .collect()
+}
+
+/// the rows of an OHLCV DataFrame as stored bars
+fn ohlcv_bars(ohlcv: &polars::prelude::DataFrame) -> Vec<super::data::sql::TimeSeriesData> {
+    let value = |name: &str, i: usize| ohlcv.column(name).unwrap().f64().unwrap().get(i).unwrap();
+    daily_bars((0..ohlcv.height()).map(|i| (value("open", i), value("high", i), value("low", i), value("close", i), value("volume", i))))
+}
+
+#[tokio::test]
+async fn test_incremental_indicators() {
+    use super::analytics::incremental::{IncrementalIndicator, Indicator};
+    use super::analytics::technicals::TechnicalIndicators;
+    let ohlcv = synthetic_ohlcv(120);
+    let ticker = super::models::ticker::TickerBuilder::new().ticker("TEST").build();
+    let cases = vec![
+        (Indicator::Sma { period: 20 }, ticker.sma_df(ohlcv.clone(), 20, None).await.unwrap()),
+        (Indicator::Ema { period: 12 }, ticker.ema_df(ohlcv.clone(), 12, None).await.unwrap()),
+        (Indicator::Rsi { period: 14 }, ticker.rsi_df(ohlcv.clone(), 14, None).await.unwrap()),
+        (Indicator::Macd { fast_period: 12, slow_period: 26, signal_period: 9 }, ticker.macd_df(ohlcv.clone(), 12, 26, 9, None).await.unwrap()),
+        (Indicator::Bb { period: 20, std_dev: 2.0 }, ticker.bb_df(ohlcv.clone(), 20, 2.0, None).await.unwrap()),
+        (Indicator::Atr { period: 14 }, ticker.atr_df(ohlcv.clone(), 14).await.unwrap()),
+        (Indicator::Obv, ticker.obv_df(ohlcv.clone()).await.unwrap()),
+        (Indicator::Roc { period: 10 }, ticker.roc_df(ohlcv.clone(), 10, None).await.unwrap()),
+        (Indicator::Ppo { fast_period: 12, slow_period: 26, signal_period: 9 }, ticker.ppo_df(ohlcv.clone(), 12, 26, 9, None).await.unwrap()),
+        (Indicator::Mfi { period: 14 }, ticker.mfi_df(ohlcv.clone(), 14).await.unwrap()),
+        (Indicator::Fs { period: 14 }, ticker.fs_df(ohlcv.clone(), 14, None).await.unwrap()),
+        (Indicator::Ss { stochastic_period: 14, ema_period: 3 }, ticker.ss_df(ohlcv.clone(), 14, 3, None).await.unwrap()),
+        (Indicator::Sd { period: 20 }, ticker.sd_df(ohlcv.clone(), 20, None).await.unwrap()),
+        (Indicator::Mad { period: 20 }, ticker.mad_df(ohlcv.clone(), 20, None).await.unwrap()),
+        (Indicator::Max { period: 10 }, ticker.max_df(ohlcv.clone(), 10, None).await.unwrap()),
+        (Indicator::Min { period: 10 }, ticker.min_df(ohlcv.clone(), 10, None).await.unwrap()),
+    ];
+    // every indicator is compared with its batch method
+    assert_eq!(cases.len(), 16);
+    for (indicator, df) in cases {
+        let rows = IncrementalIndicator::new(indicator.clone(), None).unwrap().over_dataframe(&ohlcv).unwrap();
+        for (name, row) in indicator.columns().iter().zip(rows.iter()) {
+            let batch: Vec<f64> = df.column(name).unwrap().f64().unwrap().into_no_null_iter().collect();
+            assert_eq!(&batch, row, "{}", name);
+        }
+    }
+
+    // seeded with the first half, stored and continued bar by bar it ends where the batch does
+    let bars = ohlcv_bars(&ohlcv);
+    let indicator = Indicator::Macd { fast_period: 12, slow_period: 26, signal_period: 9 };
+    let seeded = IncrementalIndicator::seeded(indicator.clone(), None, &bars[..60]).unwrap();
+    assert!(seeded.is_ready());
+    let mut restored: IncrementalIndicator = serde_json::from_str(&serde_json::to_string(&seeded).unwrap()).unwrap();
+    for bar in &bars[60..] {
+        restored.update(bar);
+    }
+    // bars seen already are ignored
+    assert!(restored.update(&bars[100]).is_none());
+    let batch = IncrementalIndicator::new(indicator, None).unwrap().over_dataframe(&ohlcv).unwrap();
+    assert_eq!(restored.values(), &[batch[0][119], batch[1][119], batch[2][119]]);
+}
+
+#[test]
+fn test_live_data_indicator_state() {
+    use super::analytics::incremental::IndicatorSet;
+    use super::data::sql::{self, TimeSeriesData};
+    let sql_connection = sql::Database::open(":memory:").unwrap();
+    let metadata = sql::MetaData {
+        symbol: "TEST".to_string(),
+        exchange: "XFRA".to_string(),
+        ..Default::default()
+    };
+    let bars: Vec<TimeSeriesData> = (0..80)
+        .map(|i| {
+            let close = 100.0 + (i as f64 / 4.0).sin();
+            TimeSeriesData { datetime: 1_700_000_000 + i * 60, open: close, high: close + 0.5, low: close - 0.5, close, volume: 1000.0 }
+        })
+        .collect();
+    // two fetches overlapping by a few minutes
+    assert_eq!(sql::insert_live_bars(sql_connection.clone(), &metadata, &bars[..50]).len(), 50);
+    assert_eq!(sql::insert_live_bars(sql_connection.clone(), &metadata, &bars[45..]).len(), 30);
+
+    // the newest bar is not part of the state yet
+    let state = sql::indicator_state(sql_connection.clone(), "TEST", "XFRA", "live_data").unwrap();
+    assert_eq!(state.last(), Some(bars[78].datetime));
+
+    let mut batch = IndicatorSet::live_data();
+    batch.update(&bars);
+    let expected = sql::live_data::LiveIndicators::from_values(&batch.values(false));
+    let rsi: f64 = sql_connection
+        .read()
+        .unwrap()
+        .query_row("SELECT rsi FROM live_data WHERE timestamp = ?1", [bars[79].datetime], |row| row.get(0))
+        .unwrap();
+    assert_eq!(rsi, expected.rsi);
+}
+
+#[tokio::test]
+async fn test_indicator_library() {
+    use super::analytics::indicators;
+    use super::analytics::technicals::TechnicalIndicators;
+    // a steady up trend
+    let close: Vec<f64> = (0..80).map(|i| 100.0 + i as f64).collect();
+    let high: Vec<f64> = close.iter().map(|c| c + 1.0).collect();
+    let low: Vec<f64> = close.iter().map(|c| c - 1.0).collect();
+    let volume = vec![1000.0; 80];
+
+    let adx = indicators::adx(&high, &low, &close, 14);
+    assert!(adx.plus_di[79] > adx.minus_di[79]);
+    assert!(adx.adx[79] > 50.0 && adx.adx[79] <= 100.0);
+    let psar = indicators::psar(&high, &low, 0.02, 0.2);
+    assert!(psar.iter().zip(&low).all(|(sar, low)| sar <= low));
+    let trend = indicators::supertrend(&high, &low, &close, 10, 3.0);
+    assert!(trend.direction.iter().all(|d| *d == 1.0));
+    let channel = indicators::donchian(&high, &low, 20);
+    assert_eq!((channel.upper[79], channel.lower[79], channel.middle[79]), (180.0, 159.0, 169.5));
+    let wr = indicators::williams_r(&high, &low, &close, 14);
+    assert!(wr.iter().all(|w| (-100.0..=0.0).contains(w)));
+    assert!(indicators::cci(&high, &low, &close, 20)[79] > 100.0);
+    assert_eq!(indicators::cmf(&high, &low, &close, &volume, 20)[79], 0.0);
+
+    let cloud = indicators::ichimoku(&high, &low, &close, 9, 26, 52);
+    assert!(cloud.span_a[25].is_nan() && !cloud.span_a[26].is_nan());
+    assert_eq!(cloud.span_a[26], (cloud.conversion[0] + cloud.base[0]) / 2.0);
+    // no close from after the bar
+    assert!(cloud.lagging[25].is_nan());
+    assert_eq!(cloud.lagging[26], close[0]);
+    assert_eq!(cloud.lagging[79], close[53]);
+
+    // the vwap starts again with the second session
+    let vwap = indicators::vwap(&[2.0, 4.0, 6.0], &[2.0, 4.0, 6.0], &[2.0, 4.0, 6.0], &[1.0, 3.0, 5.0], &[1, 1, 2]);
+    assert_eq!(vwap, vec![2.0, 3.5, 6.0]);
+
+    let ohlcv = synthetic_ohlcv(120);
+    let ticker = super::models::ticker::TickerBuilder::new().ticker("TEST").build();
+    let df = ticker.ichimoku_df(ohlcv.clone(), 9, 26, 52).await.unwrap();
+    assert_eq!(df.height(), 120);
+    assert!(df.column("ichimoku_span_b-(9,26,52)").is_ok());
+    for (df, column) in [
+        (ticker.adx_df(ohlcv.clone(), 14).await.unwrap(), "minus_di-14"),
+        (ticker.psar_df(ohlcv.clone(), 0.02, 0.2).await.unwrap(), "psar-(0.02,0.2)"),
+        (ticker.vwap_df(ohlcv.clone()).await.unwrap(), "vwap"),
+        (ticker.kc_df(ohlcv.clone(), 20, 10, 2.0).await.unwrap(), "kc_upper-(20,10,2)"),
+        (ticker.dc_df(ohlcv.clone(), 20).await.unwrap(), "dc_lower-20"),
+        (ticker.supertrend_df(ohlcv.clone(), 10, 3.0).await.unwrap(), "supertrend_direction-(10,3)"),
+        (ticker.cci_df(ohlcv.clone(), 20).await.unwrap(), "cci-20"),
+        (ticker.wr_df(ohlcv.clone(), 14).await.unwrap(), "wr-14"),
+        (ticker.cmf_df(ohlcv.clone(), 20).await.unwrap(), "cmf-20"),
+    ] {
+        assert_eq!(df.height(), 120, "{}", column);
+        assert_eq!(df.column(column).unwrap().f64().unwrap().len(), 120);
+    }
+
+    // minute bars with datetimes, the third bar is on the same UTC day but after midnight in Frankfurt
+    use polars::prelude::*;
+    let mut intraday = df!(
+        "timestamp" => [0i64, 1800, 3600].map(|s| (1_700_000_000 + s) * 1000),
+        "open" => [2.0, 4.0, 6.0],
+        "high" => [2.0, 4.0, 6.0],
+        "low" => [2.0, 4.0, 6.0],
+        "close" => [2.0, 4.0, 6.0],
+        "volume" => [1.0, 3.0, 5.0],
+    ).unwrap();
+    let timestamp = intraday.column("timestamp").unwrap().cast(&DataType::Datetime(TimeUnit::Milliseconds, None)).unwrap();
+    intraday.with_column(timestamp).unwrap();
+    let df = ticker.vwap_df(intraday).await.unwrap();
+    assert_eq!(df.column("vwap").unwrap().f64().unwrap().into_no_null_iter().collect::<Vec<f64>>(), vec![2.0, 3.5, 6.0]);
+}
+
+#[tokio::test]
+async fn test_expressions() {
+    use super::analytics::expressions::{Expression, Values};
+    use super::analytics::rules::Condition;
+    use super::analytics::technicals::TechnicalIndicators;
+
+    let error = |source: &str| Expression::parse(source).unwrap_err();
+    assert_eq!(error("").to_string(), "empty expression at column 1");
+    assert_eq!(error("rsi(14) < ").column, 11);
+    assert_eq!(error("rsi(14) = 70").message, "unexpected character `=`, compare with `==`");
+    assert_eq!(error("close > smma(20)").column, 9);
+    assert!(error("close > smma(20)").message.starts_with("unknown indicator `smma`"));
+    assert!(error("closing > 1").message.starts_with("unknown column `closing`"));
+    assert_eq!(error("rsi(14.5) > 1").message, "the period of `rsi` must be a whole number from 1 to 10000");
+    assert_eq!(error("macd(12, 100000, 9)").column, 10);
+    assert_eq!(error("lag(close, 18446744073709551616) > 1").message, "the bars of `lag` must be a whole number from 1 to 10000");
+    assert!(Expression::parse("sma(10000) > 1").is_ok());
+    // nesting is limited before it overflows the stack
+    let nested = |depth: usize| format!("{}close{} > 1", "(".repeat(depth), ")".repeat(depth));
+    assert!(Expression::parse(&nested(32)).is_ok());
+    assert_eq!(error(&nested(33)).to_string(), "the expression nests deeper than 32 levels at column 33");
+    assert_eq!(error(&format!("{}close > 1", "not ".repeat(100))).column, 129);
+    assert_eq!(error(&format!("{}1", "-".repeat(100_000))).column, 33);
+    assert!(Expression::parse(&format!("{}close{}", "abs(".repeat(40), ")".repeat(40))).is_err());
+    assert_eq!(error("ema(close, 12, 26)").message, "`ema` takes ema([series, ]period), found 3 arguments");
+    assert_eq!(error("sma(close, close)").column, 12);
+    assert_eq!(error("close > 1 and 2").message, "`and` needs conditions like `rsi(14) < 70`, found a number");
+    assert_eq!(error("(close > 1) + 2").column, 1);
+    assert_eq!(error("close 1").to_string(), "expected an operator, found number 1 at column 7");
+
+    let ohlcv = synthetic_ohlcv(120);
+    let ticker = super::models::ticker::TickerBuilder::new().ticker("TEST").build();
+    let ema12 = ticker.ema_df(ohlcv.clone(), 12, None).await.unwrap();
+    let ema26 = ticker.ema_df(ohlcv.clone(), 26, None).await.unwrap();
+    let rsi = ticker.rsi_df(ohlcv.clone(), 14, None).await.unwrap();
+    let column = |df: &polars::prelude::DataFrame, name: &str| -> Vec<f64> { df.column(name).unwrap().f64().unwrap().into_no_null_iter().collect() };
+    let (ema12, ema26, rsi) = (column(&ema12, "ema-12"), column(&ema26, "ema-26"), column(&rsi, "rsi-14"));
+
+    let spread: Expression = "EMA(close,12) - ema(close, 26)".parse().unwrap();
+    assert!(!spread.is_condition());
+    let series = spread.evaluate(&ohlcv).unwrap();
+    assert_eq!(series.name().as_str(), "EMA(close,12) - ema(close, 26)");
+    let values: Vec<f64> = series.f64().unwrap().into_no_null_iter().collect();
+    let expected: Vec<f64> = ema12.iter().zip(&ema26).map(|(a, b)| a - b).collect();
+    assert_eq!(values, expected);
+
+    let signal: Expression = "ema(close,12) - ema(close,26) > 0 and rsi(14) < 70".parse().unwrap();
+    assert!(signal.is_condition());
+    assert_eq!(signal.lookback(), 27);
+    let holds: Vec<bool> = signal.evaluate(&ohlcv).unwrap().bool().unwrap().into_no_null_iter().collect();
+    let expected: Vec<bool> = (0..120).map(|i| ema12[i] - ema26[i] > 0.0 && rsi[i] < 70.0).collect();
+    assert_eq!(holds, expected);
+    assert!(holds.contains(&true) && holds.contains(&false));
+
+    // the series left out is the default column of the batch method
+    let mut adjusted = ohlcv.clone();
+    let adjclose: Vec<f64> = column(&ohlcv, "close").iter().map(|c| c - 20.0).collect();
+    adjusted.with_column(polars::prelude::Series::new("adjclose".into(), adjclose)).unwrap();
+    for (source, df, name) in [
+        ("max(20)", ticker.max_df(adjusted.clone(), 20, None).await.unwrap(), "max-20"),
+        ("min(20)", ticker.min_df(adjusted.clone(), 20, None).await.unwrap(), "min-20"),
+        ("roc(10)", ticker.roc_df(adjusted.clone(), 10, None).await.unwrap(), "roc-10"),
+    ] {
+        let values: Vec<f64> = Expression::parse(source).unwrap().evaluate(&adjusted).unwrap().f64().unwrap().into_no_null_iter().collect();
+        assert_eq!(values, column(&df, name), "{}", source);
+    }
+
+    // the same expression over the stored bars
+    let bars = ohlcv_bars(&ohlcv);
+    assert_eq!(signal.evaluate_bars(&bars).unwrap(), Values::Conditions(expected.clone()));
+
+    // crossings hold on single bars only
+    let cross: Expression = "ema(12) crosses_above ema(26)".parse().unwrap();
+    let Values::Conditions(crossings) = cross.evaluate_bars(&bars).unwrap() else { panic!("no condition") };
+    for i in 1..120 {
+        assert_eq!(crossings[i], ema12[i] > ema26[i] && ema12[i - 1] <= ema26[i - 1], "bar {}", i);
+    }
+    let Values::Numbers(ratio) = Expression::parse("close / (high - high)").unwrap().evaluate_bars(&bars).unwrap() else { panic!("no number") };
+    assert!(ratio.iter().all(|v| v.is_nan()));
+
+    // as alert rule condition it fires on the bar the expression starts to hold
+    let condition = Condition::Expression { expression: "ema(12) crosses_above ema(26) or rsi(14) > 70".to_string() };
+    assert!(condition.validate().is_empty());
+    assert_eq!(condition.lookback(), 29);
+    let start = (27..120).find(|i| expected[*i] && !expected[*i - 1]).unwrap();
+    let condition = Condition::Expression { expression: "ema(close,12) - ema(close,26) > 0 and rsi(14) < 70".to_string() };
+    assert_eq!(
+        condition.evaluate(&bars[..=start]),
+        Some("ema(close,12) - ema(close,26) > 0 and rsi(14) < 70 started to hold".to_string())
+    );
+    assert_eq!(condition.evaluate(&bars[..start]), None);
+    assert_eq!(Condition::Expression { expression: "rsi(14)".to_string() }.validate(), vec!["expression rsi(14) is a number, not a condition"]);
+    assert_eq!(Condition::Expression { expression: "rsi(0) > 1".to_string() }.validate().len(), 1);
+}
+
+#[test]
+fn test_candlestick_patterns() {
+    use super::analytics::patterns::{self, Bias, Pattern, PATTERNS};
+    use super::data::sql::{self, events};
+    let bars = |candles: &[(f64, f64, f64, f64)]| daily_bars(candles.iter().map(|&(open, high, low, close)| (open, high, low, close, 1000.0)));
+    // the patterns ending on the last bar
+    let found = |candles: &[(f64, f64, f64, f64)]| -> Vec<Pattern> {
+        let bars = bars(candles);
+        let last = bars[bars.len() - 1].datetime;
+        patterns::detect_in_series("TEST", &bars, 0.0).into_iter().filter(|e| e.datetime == last).map(|e| e.pattern).collect()
+    };
+
+    assert_eq!(found(&[(10.0, 11.0, 9.0, 10.05)]), vec![Pattern::Doji]);
+    assert!(found(&[(10.0, 10.2, 8.8, 9.0), (8.9, 10.6, 8.7, 10.5)]).contains(&Pattern::BullishEngulfing));
+    assert!(found(&[(10.0, 10.2, 8.8, 9.0), (8.9, 10.6, 8.7, 10.5)]).contains(&Pattern::OutsideBar));
+    assert!(found(&[(9.0, 10.2, 8.8, 10.0), (10.1, 10.3, 8.7, 8.8)]).contains(&Pattern::BearishEngulfing));
+    assert_eq!(found(&[(10.0, 10.2, 7.8, 8.0), (8.5, 9.2, 8.3, 9.0)]), vec![Pattern::BullishHarami, Pattern::InsideBar]);
+    assert!(found(&[(10.0, 10.1, 7.9, 8.0), (7.8, 7.9, 7.4, 7.6), (7.7, 9.6, 7.6, 9.5)]).contains(&Pattern::MorningStar));
+    assert!(found(&[(8.0, 10.1, 7.9, 10.0), (10.2, 10.6, 10.1, 10.4), (10.3, 10.4, 8.4, 8.5)]).contains(&Pattern::EveningStar));
+    assert!(found(&[(10.0, 11.1, 9.9, 11.0), (10.5, 12.1, 10.4, 12.0), (11.5, 13.1, 11.4, 13.0)]).contains(&Pattern::ThreeWhiteSoldiers));
+    assert!(found(&[(13.0, 13.1, 11.9, 12.0), (12.5, 12.6, 10.9, 11.0), (11.5, 11.6, 9.9, 10.0)]).contains(&Pattern::ThreeBlackCrows));
+
+    // a hammer after falling prices shows the pattern clearly
+    let mut candles: Vec<(f64, f64, f64, f64)> = (0..6)
+        .map(|i| {
+            let close = 110.0 - 2.0 * i as f64;
+            (close + 1.0, close + 1.5, close - 0.5, close)
+        })
+        .collect();
+    candles.push((98.5, 99.1, 96.0, 99.0));
+    let hammer = patterns::detect_in_series("TEST", &bars(&candles), 0.0);
+    assert_eq!(hammer.len(), 1, "{:?}", hammer);
+    assert_eq!((hammer[0].pattern, hammer[0].confidence, hammer[0].datetime), (Pattern::Hammer, 1.0, 1_700_000_000 + 6 * 86400));
+    assert!(patterns::detect_in_series("TEST", &bars(&candles), 1.01).is_empty());
+
+    for pattern in PATTERNS {
+        assert_eq!(pattern.as_str().parse::<Pattern>(), Ok(*pattern));
+        assert!((1..=3).contains(&pattern.bars()));
+    }
+    assert_eq!(Pattern::MorningStar.bias(), Bias::Bullish);
+    assert!("tweezer_top".parse::<Pattern>().is_err());
+
+    // stored like the jump events, once per symbol, bar and pattern
+    let db = sql::Database::open(":memory:").unwrap();
+    assert_eq!(events::insert_pattern_events(db.clone(), &hammer), 1);
+    assert_eq!(events::insert_pattern_events(db.clone(), &hammer), 0);
+    assert_eq!(events::pattern_events(db.clone(), "TEST"), hammer);
+    assert!(events::pattern_events(db, "OTHER").is_empty());
+
+    let found = patterns::detect("TEST", &synthetic_ohlcv(120), 0.0).unwrap();
+    assert!(!found.is_empty());
+    assert!(found.iter().all(|e| (0.0..=1.0).contains(&e.confidence) && (e.datetime - 1_700_000_000) % 86400 == 0));
+}
+
+#[test]
+fn test_support_resistance() {
+    use super::analytics::levels::{self, Boundary, LevelKind, LevelSettings, SwingKind};
+    use super::analytics::rules::Direction;
+    // prices swinging between 100 and 110 every 20 bars, rising to 109 and closing at 118 on the last bar
+    let triangle = |i: usize| if i % 20 <= 10 { i % 20 } else { 20 - i % 20 };
+    let mut closes: Vec<f64> = (0..=80).map(|i| 100.0 + triangle(i) as f64).collect();
+    closes.extend((1..=9).map(|i| 100.0 + i as f64));
+    closes.push(118.0);
+    let series = |closes: &[f64]| daily_bars(closes.iter().map(|&close| (close, close + 0.5, close - 0.5, close, 1000.0)));
+    let bars = series(&closes);
+    let settings = LevelSettings::default();
+    let found = levels::detect_in_series(&bars, &settings);
+
+    let indices = |kind: SwingKind| found.swings.iter().filter(|s| s.kind == kind).map(|s| s.index).collect::<Vec<usize>>();
+    assert_eq!(indices(SwingKind::High), vec![10, 30, 50, 70]);
+    assert_eq!(indices(SwingKind::Low), vec![20, 40, 60, 80]);
+
+    // both levels are below the last close, the broken resistance became a support
+    assert_eq!(found.levels.len(), 2, "{:?}", found.levels);
+    assert_eq!(found.levels.iter().map(|l| (l.price, l.kind, l.touches)).collect::<Vec<_>>(), vec![(99.5, LevelKind::Support, 4), (110.5, LevelKind::Support, 4)]);
+    assert_eq!((found.levels[0].first, found.levels[0].last), (1_700_000_000 + 20 * 86400, 1_700_000_000 + 80 * 86400));
+    // the touches of the lows are more recent
+    assert_eq!(found.levels[0].strength, 1.0);
+    assert!(found.levels[1].strength > 0.5 && found.levels[1].strength < 1.0);
+
+    // a flat channel
+    let resistance = found.resistance_line.as_ref().unwrap();
+    let support = found.support_line.as_ref().unwrap();
+    assert!(resistance.slope.abs() < 1e-9 && (resistance.value_at(90) - 110.5).abs() < 1e-9);
+    assert!(support.slope.abs() < 1e-9 && (support.value_at(90) - 99.5).abs() < 1e-9);
+    assert_eq!((resistance.kind, resistance.start, resistance.touches), (LevelKind::Resistance, 10, 4));
+
+    // the last close broke out of the level and the channel
+    assert_eq!(found.breakouts.len(), 2, "{:?}", found.breakouts);
+    assert_eq!(found.breakouts.iter().map(|b| (b.direction, b.boundary)).collect::<Vec<_>>(), vec![(Direction::Above, Boundary::Level), (Direction::Above, Boundary::Trendline)]);
+    assert_eq!(found.breakouts[0].datetime, 1_700_000_000 + 90 * 86400);
+    assert_eq!(found.breakouts[0].key(), "above-110.50");
+    assert_eq!(found.breakouts[0].to_string(), "close 118.00 broke above the resistance at 110.50 touched 4 times");
+    assert_eq!(found.breakouts[1].to_string(), "close 118.00 broke above the resistance line at 110.50 touched 4 times");
+
+    // no breakout before the last bar, no level with more touches than there are
+    assert!(levels::detect_in_series(&bars[..90], &settings).breakouts.is_empty());
+    // a close crawling past the resistance in small steps breaks out once, on the bar leaving the tolerance
+    let mut crawling = closes[..90].to_vec();
+    crawling.extend([110.0, 110.6, 111.2, 111.8, 112.4]);
+    let crawling = series(&crawling);
+    let broken: Vec<usize> = (91..=crawling.len()).filter(|n| !levels::detect_in_series(&crawling[..*n], &settings).breakouts.is_empty()).collect();
+    assert_eq!(broken, vec![94]);
+    let found_crawling = levels::detect_in_series(&crawling[..94], &settings);
+    assert!(found_crawling.breakouts.iter().all(|b| b.direction == Direction::Above && (b.price - 110.5).abs() < 1e-9));
+    assert!(levels::detect_in_series(&bars, &LevelSettings { min_touches: 5, ..settings.clone() }).levels.is_empty());
+    assert_eq!(levels::detect_in_series(&[], &settings), levels::Levels::default());
+    assert_eq!(LevelSettings { window: 0, tolerance: 1.5, ..settings }.validate().len(), 2);
+
+    let found = levels::detect(&synthetic_ohlcv(200), &LevelSettings::default()).unwrap();
+    assert!(found.levels.iter().all(|l| l.touches >= 2 && (0.0..=1.0).contains(&l.strength)));
+    assert!(found.swings.windows(2).all(|w| w[0].index <= w[1].index));
+}
@@ -2,7 +2,7 @@
 
 mod allocator;
 mod logging;
-//mod prometheus;
+mod prometheus;
 mod shutdown;
 mod tokio_console;
 
@@ -10,7 +10,7 @@ use self::allocator::Allocator;
 use eyre::{Result as EyreResult, WrapErr as _};
 use structopt::StructOpt;
 use tokio::{runtime, sync::broadcast};
-use tracing::info;
+use tracing::{error, info};
 
 use notificationservice as lib;
 
@@ -44,8 +44,8 @@ pub static ALLOCATOR: Allocator<allocator::MiMalloc> = allocator::new_mimalloc()
 struct Options {
     #[structopt(flatten)]
     log: logging::Options,
-//    #[structopt(flatten)]
-//    pub prometheus: prometheus::Options,
+    #[structopt(flatten)]
+    pub prometheus: prometheus::Options,
     #[structopt(flatten)]
     app: lib::Options,
 }
@@ -82,7 +82,7 @@ fn main() -> EyreResult<()> {
             });
 
             // Start prometheus
-            //let prometheus = tokio::spawn(prometheus::main(options.prometheus, shutdown.clone()));
+            let prometheus = tokio::spawn(prometheus::main(options.prometheus, shutdown.clone()));
 
             // Start main
             let _ret = lib::main(options.app, shutdown.clone()).await;
@@ -92,7 +92,11 @@ fn main() -> EyreResult<()> {
 
             // Stop prometheus
             info!("Stopping metrics server");
-            //prometheus.await?
+            match prometheus.await {
+                Ok(Ok(())) => {}
+                Ok(Err(error)) => error!(?error, "Metrics server failed"),
+                Err(error) => error!(?error, "Metrics server panicked"),
+            }
         });
 
     // Terminate successfully
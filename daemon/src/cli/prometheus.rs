@@ -0,0 +1,116 @@
+#![warn(clippy::all, clippy::pedantic, clippy::cargo, clippy::nursery)]
+
+use eyre::{bail, ensure, Result as EyreResult, WrapErr as _};
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use once_cell::sync::Lazy;
+use prometheus::{register_histogram, Encoder as _, Histogram, TextEncoder};
+use std::{convert::Infallible, net::SocketAddr};
+use structopt::StructOpt;
+use tokio::sync::broadcast;
+use tracing::{error, info, trace};
+use url::Url;
+
+#[derive(Clone, Debug, PartialEq, Eq, StructOpt)]
+pub struct Options {
+    /// Prometheus scrape endpoint, the path serves the metrics
+    // See <https://github.com/prometheus/prometheus/wiki/Default-port-allocations>
+    #[structopt(long, env, default_value = "http://127.0.0.1:9998/metrics")]
+    pub prometheus: Url,
+
+    /// Do not serve the metrics
+    #[structopt(long)]
+    pub no_metrics: bool,
+}
+
+static LATENCY: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!("prometheus_exporter_latency_seconds", "HTTP request latency of the metrics endpoint.").unwrap()
+});
+
+fn metrics() -> Response<Body> {
+    let mut buffer = Vec::new();
+    let encoder = TextEncoder::new();
+    match encoder.encode(&prometheus::gather(), &mut buffer) {
+        Ok(()) => Response::builder()
+            .header(CONTENT_TYPE, encoder.format_type())
+            .body(Body::from(buffer))
+            .unwrap_or_default(),
+        Err(error) => {
+            error!(?error, "Failed to encode the metrics");
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from(error.to_string()))
+                .unwrap_or_default()
+        }
+    }
+}
+
+#[allow(clippy::unused_async)] // We are implementing an interface
+async fn route(path: String, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let _timer = LATENCY.start_timer();
+    trace!(url = %request.uri(), method = %request.method(), "Metrics request");
+    if request.method() == Method::GET && request.uri().path() == path {
+        Ok(metrics())
+    } else {
+        Ok(Response::builder().status(StatusCode::NOT_FOUND).body(Body::from("404")).unwrap_or_default())
+    }
+}
+
+/// the socket address of the scrape endpoint, only plain `http` URLs are served
+fn socket_address(url: &Url) -> EyreResult<SocketAddr> {
+    ensure!(url.scheme() == "http", "Only http:// is supported in {}", url);
+    let addresses = url.socket_addrs(|| Some(80)).wrap_err_with(|| format!("Cannot resolve the host of {url}"))?;
+    let Some(address) = addresses.into_iter().next() else {
+        bail!("No address for the host of {}", url);
+    };
+    Ok(address)
+}
+
+/// serve the metrics until `shutdown` is signalled
+pub async fn main(options: Options, shutdown: broadcast::Sender<()>) -> EyreResult<()> {
+    if options.no_metrics {
+        return Ok(());
+    }
+    // subscribe before anything can fail to not miss the signal
+    let mut shutdown = shutdown.subscribe();
+    let address = socket_address(&options.prometheus)?;
+    let path = options.prometheus.path().to_string();
+    let server = Server::try_bind(&address)
+        .wrap_err_with(|| format!("Could not bind the metrics server to {address}"))?
+        .serve(make_service_fn(move |_| {
+            let path = path.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |request| route(path.clone(), request))) }
+        }))
+        .with_graceful_shutdown(async move {
+            let _ = shutdown.recv().await;
+        });
+    info!(url = %options.prometheus, "Metrics server listening");
+    server.await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_socket_address() {
+        let url: Url = "http://127.0.0.1:9998/metrics".parse().unwrap();
+        assert_eq!(socket_address(&url).unwrap(), "127.0.0.1:9998".parse().unwrap());
+        assert!(socket_address(&"https://127.0.0.1/metrics".parse().unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_route() {
+        let request = Request::get("/metrics").body(Body::empty()).unwrap();
+        let response = route("/metrics".to_string(), request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let request = Request::get("/other").body(Body::empty()).unwrap();
+        let response = route("/metrics".to_string(), request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,828 @@
+//! Configuration file of the daemon
+//!
+//! The daemon reads a TOML file (`--config`, default `~/.config/stockanalysis/daemon.toml`)
+//! describing watchlists, portfolios, the schedule, thresholds, the output directory and the
+//! market data providers. Every section is optional and falls back to the defaults below.
+//! Options given on the command line or in the environment override the file.
+//! The file is read again on `SIGHUP`, an invalid file keeps the running configuration.
+//!
+//! ```toml
+//! exchange = "XFRA"
+//! database = "~/stock-data/time_series.sqlite"
+//!
+//! [output]
+//! reports = "~/stock-analysis-reports"
+//!
+//! [schedule]
+//! nightly = "0 23 * * *"
+//! live = "* 8-22 * * 1-5"
+//!
+//! [[watchlists]]
+//! name = "tech"
+//! symbols = ["AAPL", "MSFT"]
+//!
+//! [[portfolios]]
+//! name = "tech"
+//! symbols = ["AAPL", "MSFT"]
+//! benchmark = "^GSPC"
+//! start_date = "2025-01-01"
+//!
+//! [[providers]]
+//! kind = "yahoo"
+//!
+//! [[notifiers]]
+//! name = "phone"
+//! kind = "ntfy"
+//! url = "https://ntfy.sh"
+//! topic = "my-stocks"
+//!
+//! [charts]
+//! overlays = ["supertrend", "adx"]
+//!
+//! # without a token everybody reaching the address may change the active symbols and
+//! # generate the reports, listen on other addresses only with a token behind a TLS proxy
+//! [http]
+//! listen = "127.0.0.1:8080"
+//! token = "a long random secret"
+//! base_url = "https://stocks.example.org"
+//!
+//! [alerts]
+//! notify = ["phone"]
+//! cooldown_minutes = 60
+//! escalate_after_minutes = 30
+//!
+//! [[rules]]
+//! name = "overbought"
+//! symbol = "AAPL"
+//! cooldown_minutes = 120
+//! condition = { type = "all", conditions = [
+//!     { type = "rsi", level = 70, direction = "above" },
+//!     { type = "volume_spike", factor = 3 },
+//! ] }
+//!
+//! [levels]
+//! notify = true
+//!
+//! [[rules]]
+//! name = "golden cross"
+//! condition = { type = "expression", expression = "sma(50) crosses_above sma(200) and adx(14) > 20" }
+//! ```
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use api::data::livedata::ProviderSettings;
+use api::analytics::levels::LevelSettings;
+use api::analytics::rules::AlertRule;
+use api::charts::ticker::Overlay;
+use api::data::quality::{QualitySettings, Repair};
+use api::data::sql::{AlertPolicy, Database};
+use api::prelude::{Exchange, ObjectiveFunction};
+
+use crate::notify::{ChannelConfig, NotifierConfig, Notifiers};
+use crate::scheduler::CronSchedule;
+use crate::server::Options;
+
+/// Complete configuration of the daemon
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// exchange (MIC) of symbols without stored exchange, also the calendar of the reports
+    pub exchange: String,
+    /// location of the database, a path or `sqlite://` URL, `STOCK_DATABASE` or the default
+    /// file if not set. The database is opened once, changing it needs a restart
+    pub database: Option<String>,
+    pub output: OutputConfig,
+    pub schedule: ScheduleConfig,
+    pub thresholds: Thresholds,
+    pub quality: QualityConfig,
+    pub charts: ChartConfig,
+    pub screener: ScreenerConfig,
+    pub levels: LevelsConfig,
+    /// symbols added to the active symbols
+    pub watchlists: Vec<Watchlist>,
+    /// portfolios analyzed every night
+    pub portfolios: Vec<PortfolioConfig>,
+    /// market data providers, the providers of the environment are used if empty
+    pub providers: Vec<ProviderSettings>,
+    /// channels delivering the alerts, a desktop channel if not configured
+    pub notifiers: Vec<NotifierConfig>,
+    pub alerts: AlertConfig,
+    /// alert rules evaluated on every live update, stored in the `alert_rules` table
+    pub rules: Vec<AlertRule>,
+    pub http: HttpConfig,
+}
+
+/// Web server of the reports, events and active symbols
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    pub enabled: bool,
+    /// address and port the server listens on
+    pub listen: String,
+    /// address of the server as seen by the users, used for the links of the notifications
+    pub base_url: Option<String>,
+    /// bearer token required by the requests other than `GET`, they are open without one
+    pub token: Option<String>,
+}
+
+/// Delivery of the alerts
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertConfig {
+    /// names of the notifiers receiving the alerts
+    pub notify: Vec<String>,
+    /// attempts after a failed delivery
+    pub retries: u32,
+    /// seconds before the first retry, doubled for every further one
+    pub retry_delay_seconds: u64,
+    /// minutes between two notifications of the same alert, alert rules have their own
+    pub cooldown_minutes: u32,
+    /// minutes without the condition after which an alert ends
+    pub resolve_after_minutes: u32,
+    /// minutes after which an unacknowledged alert is raised to the next level, 0 never
+    pub escalate_after_minutes: u32,
+}
+
+/// Where the reports are written
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// directory of the reports, `~` is the home directory
+    pub reports: PathBuf,
+}
+
+/// When the jobs run, cron expressions in local time, see `scheduler::CronSchedule`
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScheduleConfig {
+    /// the nightly update and reports
+    pub nightly: String,
+    /// the live updates and event detection
+    pub live: String,
+    /// days a missed nightly run is caught up after downtime, 0 disables the catch-up
+    pub catch_up_days: u32,
+}
+
+/// Limits of the event detection
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Thresholds {
+    /// relative rise of a jump
+    pub jump_up: f64,
+    /// relative drop of a jump
+    pub jump_down: f64,
+    /// relative rise of the slope raising a notification
+    pub slope_up: f64,
+    /// relative drop of the slope raising a notification
+    pub slope_down: f64,
+    /// days of history analyzed every night
+    pub history_days: u64,
+    /// minimum confidence of the stored candlestick patterns, between 0 and 1
+    pub pattern_confidence: f64,
+}
+
+/// Nightly validation of the stored bars, see `api::data::quality`
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct QualityConfig {
+    /// `report`, `flag` or `quarantine`
+    pub repair: String,
+    pub interpolate: bool,
+    pub max_interpolated: usize,
+    pub spike_percent_daily: f64,
+    pub spike_percent_intraday: f64,
+    pub stale_bars_daily: usize,
+    pub stale_bars_intraday: usize,
+}
+
+/// Charts of the active symbols
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChartConfig {
+    pub benchmark: String,
+    /// days shown in the charts
+    pub days: u64,
+    /// the live charts subscribe to the updates of the web server and change in place
+    pub live_updates: bool,
+    /// indicators drawn on the daily candlestick charts, e.g. `["ichimoku", "adx"]`
+    pub overlays: Vec<Overlay>,
+}
+
+/// Nightly screener report
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScreenerConfig {
+    pub enabled: bool,
+    /// Yahoo exchange code, `NMS` is the NASDAQ
+    pub exchange: String,
+    /// number of symbols with the largest market cap
+    pub size: usize,
+    pub benchmark: String,
+    /// days of history of the screened symbols
+    pub days: u64,
+}
+
+/// Support and resistance levels of the active symbols, see `api::analytics::levels`
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LevelsConfig {
+    /// notify when the newest bar breaks out of a level or trendline
+    pub notify: bool,
+    /// days of daily bars the levels are detected in
+    pub days: u64,
+    /// bars on both sides a swing high or low stands out of
+    pub window: usize,
+    /// relative distance of prices belonging to the same level
+    pub tolerance: f64,
+    /// touches of a level, fewer are no level
+    pub min_touches: usize,
+}
+
+/// A named list of symbols
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Watchlist {
+    pub name: String,
+    pub symbols: Vec<String>,
+}
+
+/// A portfolio analyzed every night, written to a directory of its name
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PortfolioConfig {
+    pub name: String,
+    /// the active symbols if empty
+    pub symbols: Vec<String>,
+    pub benchmark: String,
+    /// `YYYY-MM-DD`, `days` before the end date if not set
+    pub start_date: Option<String>,
+    /// `YYYY-MM-DD`, yesterday if not set
+    pub end_date: Option<String>,
+    pub days: u64,
+    pub confidence_level: f64,
+    pub risk_free_rate: f64,
+    /// `max_sharpe`, `min_vol`, `max_return`, `min_drawdown`, `min_var` or `min_cvar`
+    pub objective: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            exchange: api::calendar::DEFAULT_EXCHANGE.to_string(),
+            database: None,
+            output: OutputConfig::default(),
+            schedule: ScheduleConfig::default(),
+            thresholds: Thresholds::default(),
+            quality: QualityConfig::default(),
+            charts: ChartConfig::default(),
+            screener: ScreenerConfig::default(),
+            levels: LevelsConfig::default(),
+            watchlists: Vec::new(),
+            portfolios: vec![PortfolioConfig::default()],
+            providers: Vec::new(),
+            notifiers: vec![NotifierConfig { name: "desktop".to_string(), channel: ChannelConfig::Desktop }],
+            alerts: AlertConfig::default(),
+            rules: Vec::new(),
+            http: HttpConfig::default(),
+        }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self { enabled: true, listen: "127.0.0.1:8080".to_string(), base_url: None, token: None }
+    }
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            notify: vec!["desktop".to_string()],
+            retries: 3,
+            retry_delay_seconds: 5,
+            cooldown_minutes: 60,
+            resolve_after_minutes: 30,
+            escalate_after_minutes: 0,
+        }
+    }
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self { reports: PathBuf::from("~/stock-analysis-reports") }
+    }
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self { nightly: "0 23 * * *".to_string(), live: "* * * * *".to_string(), catch_up_days: 7 }
+    }
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self { jump_up: 0.5, jump_down: 0.3, slope_up: 0.5, slope_down: 0.3, history_days: 90, pattern_confidence: 0.6 }
+    }
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        let settings = QualitySettings::default();
+        Self {
+            repair: "flag".to_string(),
+            interpolate: settings.interpolate,
+            max_interpolated: settings.max_interpolated,
+            spike_percent_daily: settings.spike_percent_daily,
+            spike_percent_intraday: settings.spike_percent_intraday,
+            stale_bars_daily: settings.stale_bars_daily,
+            stale_bars_intraday: settings.stale_bars_intraday,
+        }
+    }
+}
+
+impl Default for ChartConfig {
+    fn default() -> Self {
+        Self { benchmark: "0H1C".to_string(), days: 90, live_updates: true, overlays: Vec::new() }
+    }
+}
+
+impl Default for LevelsConfig {
+    fn default() -> Self {
+        let settings = LevelSettings::default();
+        Self {
+            notify: false,
+            days: 180,
+            window: settings.window,
+            tolerance: settings.tolerance,
+            min_touches: settings.min_touches,
+        }
+    }
+}
+
+impl Default for ScreenerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            exchange: Exchange::NASDAQ.as_ref().to_string(),
+            size: 10,
+            benchmark: "0H1C".to_string(),
+            days: 180,
+        }
+    }
+}
+
+impl Default for PortfolioConfig {
+    fn default() -> Self {
+        Self {
+            name: "portfolio".to_string(),
+            symbols: Vec::new(),
+            benchmark: "0H1C".to_string(),
+            start_date: None,
+            end_date: None,
+            days: 180,
+            confidence_level: 0.95,
+            risk_free_rate: 0.02,
+            objective: "max_sharpe".to_string(),
+        }
+    }
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").map_err(|e| format!("invalid date {value}: {e}"))
+}
+
+/// replace a leading `~` by the home directory
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+impl Config {
+    /// the default location of the configuration file
+    pub fn default_path() -> PathBuf {
+        dirs::config_dir().unwrap_or_default().join("stockanalysis").join("daemon.toml")
+    }
+
+    /// parse a configuration without validating it
+    ///
+    /// # Errors
+    /// if the text is not TOML or has unknown keys
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// read and validate the configuration file and apply the command line overrides.
+    /// A missing file at the default location gives the default configuration
+    ///
+    /// # Errors
+    /// if the file can not be read or the configuration is invalid
+    pub fn load(options: &Options) -> Result<Self, String> {
+        let path = options.config.clone().unwrap_or_else(Self::default_path);
+        let mut config = if path.exists() {
+            let text = std::fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+            Self::from_toml(&text).map_err(|e| format!("{}: {e}", path.display()))?
+        } else if options.config.is_some() {
+            return Err(format!("{} does not exist", path.display()));
+        } else {
+            Self::default()
+        };
+        config.apply_overrides(options);
+        config.validate().map_err(|errors| format!("{}: {}", path.display(), errors.join(", ")))?;
+        Ok(config)
+    }
+
+    /// options of the command line or the environment replace the values of the file
+    pub fn apply_overrides(&mut self, options: &Options) {
+        if let Some(exchange) = &options.exchange {
+            self.exchange = exchange.clone();
+        }
+        if let Some(database) = &options.database {
+            self.database = Some(database.clone());
+        }
+        if let Some(reports) = &options.reports_dir {
+            self.output.reports = reports.clone();
+        }
+        if let Some(nightly) = &options.nightly_schedule {
+            self.schedule.nightly = nightly.clone();
+        }
+        if let Some(repair) = &options.quality_repair {
+            self.quality.repair = repair.clone();
+        }
+        if options.interpolate_gaps {
+            self.quality.interpolate = true;
+        }
+        if let Some(listen) = &options.listen {
+            self.http.listen = listen.clone();
+        }
+    }
+
+    /// all problems of the configuration
+    ///
+    /// # Errors
+    /// a message for every invalid value
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if self.exchange.trim().is_empty() {
+            errors.push("exchange must not be empty".to_string());
+        }
+        if self.database.as_deref().is_some_and(|d| d.trim().is_empty()) {
+            errors.push("database must not be empty".to_string());
+        }
+        if let Err(e) = CronSchedule::from_str(&self.schedule.nightly) {
+            errors.push(format!("schedule.nightly: {e}"));
+        }
+        if let Err(e) = CronSchedule::from_str(&self.schedule.live) {
+            errors.push(format!("schedule.live: {e}"));
+        }
+        let t = &self.thresholds;
+        for (name, value) in [("jump_up", t.jump_up), ("jump_down", t.jump_down), ("slope_up", t.slope_up), ("slope_down", t.slope_down)] {
+            if !value.is_finite() || value <= 0.0 {
+                errors.push(format!("thresholds.{name} must be positive"));
+            }
+        }
+        if !(0.0..=1.0).contains(&t.pattern_confidence) {
+            errors.push("thresholds.pattern_confidence must be between 0 and 1".to_string());
+        }
+        if let Err(e) = Repair::from_str(&self.quality.repair) {
+            errors.push(format!("quality.repair: {e}"));
+        }
+        if self.charts.days == 0 {
+            errors.push("charts.days must be positive".to_string());
+        }
+        if self.screener.enabled {
+            if Exchange::from_str(&self.screener.exchange).is_err() {
+                errors.push(format!("screener.exchange {} is not a Yahoo exchange code", self.screener.exchange));
+            }
+            if self.screener.size == 0 {
+                errors.push("screener.size must be positive".to_string());
+            }
+        }
+        if self.levels.days == 0 {
+            errors.push("levels.days must be positive".to_string());
+        }
+        for e in self.level_settings().validate() {
+            errors.push(format!("levels: {e}"));
+        }
+        for (i, w) in self.watchlists.iter().enumerate() {
+            if w.symbols.iter().any(|s| s.trim().is_empty()) {
+                errors.push(format!("watchlists[{i}] {} has an empty symbol", w.name));
+            }
+        }
+        let mut names = Vec::new();
+        for (i, p) in self.portfolios.iter().enumerate() {
+            if p.name.trim().is_empty() || p.name.contains(['/', '\\']) {
+                errors.push(format!("portfolios[{i}] needs a name usable as directory"));
+            } else if names.contains(&p.name) {
+                errors.push(format!("portfolios[{i}] {} is defined twice", p.name));
+            }
+            names.push(p.name.clone());
+            let start = p.start_date.as_deref().map(parse_date);
+            let end = p.end_date.as_deref().map(parse_date);
+            for date in [&start, &end] {
+                if let Some(Err(e)) = date {
+                    errors.push(format!("portfolios[{i}] {}: {e}", p.name));
+                }
+            }
+            if let (Some(Ok(start)), Some(Ok(end))) = (start, end) {
+                if start >= end {
+                    errors.push(format!("portfolios[{i}] {}: start_date is not before end_date", p.name));
+                }
+            }
+            if !(0.0..1.0).contains(&p.confidence_level) {
+                errors.push(format!("portfolios[{i}] {}: confidence_level must be between 0 and 1", p.name));
+            }
+            if let Err(e) = ObjectiveFunction::from_str(&p.objective) {
+                errors.push(format!("portfolios[{i}] {}: {e}", p.name));
+            }
+        }
+        let mut channels = Vec::new();
+        for (i, n) in self.notifiers.iter().enumerate() {
+            if channels.contains(&n.name) {
+                errors.push(format!("notifiers[{i}] {} is defined twice", n.name));
+            }
+            channels.push(n.name.clone());
+            if let Err(e) = n.build() {
+                errors.push(format!("notifiers[{i}] {}: {e}", n.name));
+            }
+        }
+        for name in self.alerts.notify.iter().filter(|n| !channels.contains(n)) {
+            errors.push(format!("alerts.notify: unknown notifier {name}"));
+        }
+        if self.http.enabled && self.http.listen.parse::<std::net::SocketAddr>().is_err() {
+            errors.push(format!("http.listen {} is not an address with port", self.http.listen));
+        }
+        if self.http.token.as_deref().is_some_and(|t| t.trim().is_empty()) {
+            errors.push("http.token must not be empty".to_string());
+        }
+        if self.alerts.resolve_after_minutes == 0 {
+            errors.push("alerts.resolve_after_minutes must be positive".to_string());
+        }
+        let mut rules = Vec::new();
+        for (i, r) in self.rules.iter().enumerate() {
+            if rules.contains(&r.name) {
+                errors.push(format!("rules[{i}] {} is defined twice", r.name));
+            }
+            rules.push(r.name.clone());
+            errors.extend(r.validate().into_iter().map(|e| format!("rules[{i}]: {e}")));
+            for name in r.notify.iter().filter(|n| !channels.contains(n)) {
+                errors.push(format!("rules[{i}] {}: unknown notifier {name}", r.name));
+            }
+        }
+        if !self.providers.is_empty() && !self.providers.iter().any(|p| p.enabled) {
+            errors.push("providers: at least one provider must be enabled".to_string());
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// the directory of the reports, created if missing. Falls back to the home directory
+    /// the file of the database, see `database`
+    pub fn database_path(&self) -> PathBuf {
+        match &self.database {
+            Some(location) => expand_home(Path::new(location.strip_prefix("sqlite://").unwrap_or(location))),
+            None => api::data::sql::database_path(),
+        }
+    }
+
+    /// open the database, a new one is created and an old one migrated to the current schema
+    ///
+    /// # Errors
+    /// if the database cannot be opened
+    pub fn open_database(&self) -> Result<Database, String> {
+        let path = self.database_path();
+        Database::open(&path.to_string_lossy()).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    pub fn reports_dir(&self) -> PathBuf {
+        let path = expand_home(&self.output.reports);
+        if !path.is_dir() {
+            if let Err(e) = std::fs::create_dir_all(&path) {
+                log::error!("Failed to create directory {}: {}", path.display(), e);
+                return dirs::home_dir().unwrap_or_default();
+            }
+        }
+        path
+    }
+
+    /// schedule of the nightly jobs
+    pub fn nightly_schedule(&self) -> CronSchedule {
+        self.schedule.nightly.parse().unwrap_or_else(|_| ScheduleConfig::default().nightly.parse().expect("valid default schedule"))
+    }
+
+    /// schedule of the live updates
+    pub fn live_schedule(&self) -> CronSchedule {
+        self.schedule.live.parse().unwrap_or_else(|_| ScheduleConfig::default().live.parse().expect("valid default schedule"))
+    }
+
+    /// the settings of the nightly validation
+    pub fn quality_settings(&self) -> QualitySettings {
+        let q = &self.quality;
+        QualitySettings {
+            repair: q.repair.parse().unwrap_or(Repair::Flag),
+            interpolate: q.interpolate,
+            max_interpolated: q.max_interpolated,
+            spike_percent_daily: q.spike_percent_daily,
+            spike_percent_intraday: q.spike_percent_intraday,
+            stale_bars_daily: q.stale_bars_daily,
+            stale_bars_intraday: q.stale_bars_intraday,
+        }
+    }
+
+    /// the settings of the level detection
+    pub fn level_settings(&self) -> LevelSettings {
+        let l = &self.levels;
+        LevelSettings { window: l.window, tolerance: l.tolerance, min_touches: l.min_touches, ..LevelSettings::default() }
+    }
+
+    /// the configured notification channels
+    pub fn notifiers(&self) -> Notifiers {
+        Notifiers::from_config(&self.notifiers, self.alerts.retries, std::time::Duration::from_secs(self.alerts.retry_delay_seconds))
+    }
+
+    /// the address of a page of the web server, `None` without server
+    pub fn link(&self, path: &str) -> Option<String> {
+        if !self.http.enabled {
+            return None;
+        }
+        let base = self.http.base_url.clone().unwrap_or_else(|| format!("http://{}", self.http.listen));
+        Some(format!("{}{}", base.trim_end_matches('/'), path))
+    }
+
+    /// how often an alert notifies, see `api::data::sql::alerts`
+    pub fn alert_policy(&self) -> AlertPolicy {
+        AlertPolicy {
+            cooldown: i64::from(self.alerts.cooldown_minutes) * 60,
+            resolve_after: i64::from(self.alerts.resolve_after_minutes) * 60,
+            escalate_after: i64::from(self.alerts.escalate_after_minutes) * 60,
+        }
+    }
+
+    /// symbols of all watchlists without duplicates
+    pub fn watchlist_symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = self.watchlists.iter().flat_map(|w| w.symbols.iter().map(|s| s.trim().to_string())).collect();
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    }
+}
+
+impl PortfolioConfig {
+    /// the analyzed period as `YYYY-MM-DD`, relative to `today` unless configured
+    pub fn period(&self, today: NaiveDate) -> (String, String) {
+        let end = self
+            .end_date
+            .as_deref()
+            .and_then(|d| parse_date(d).ok())
+            .unwrap_or_else(|| today - chrono::Days::new(1));
+        let start = self
+            .start_date
+            .as_deref()
+            .and_then(|d| parse_date(d).ok())
+            .unwrap_or_else(|| end - chrono::Days::new(self.days));
+        (start.to_string(), end.to_string())
+    }
+
+    pub fn objective_function(&self) -> ObjectiveFunction {
+        self.objective.parse().unwrap_or(ObjectiveFunction::MaxSharpe)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use structopt::StructOpt;
+
+    const EXAMPLE: &str = r#"
+        exchange = "XETR"
+        database = "sqlite:///tmp/time_series.sqlite"
+
+        [output]
+        reports = "/tmp/reports"
+
+        [schedule]
+        nightly = "30 22 * * 1-5"
+
+        [thresholds]
+        jump_up = 0.4
+        pattern_confidence = 0.75
+
+        [charts]
+        overlays = ["ichimoku", "williams_r"]
+
+        [levels]
+        notify = true
+        window = 3
+
+        [[watchlists]]
+        name = "tech"
+        symbols = ["MSFT", "AAPL"]
+
+        [[watchlists]]
+        name = "cars"
+        symbols = ["VOW3.DE", "AAPL"]
+
+        [[portfolios]]
+        name = "tech"
+        symbols = ["AAPL", "MSFT"]
+        start_date = "2025-03-01"
+        end_date = "2025-08-31"
+        objective = "min_vol"
+
+        [[providers]]
+        kind = "yahoo"
+        priority = 1
+
+        [[rules]]
+        name = "breakout"
+        symbol = "AAPL"
+        level = "critical"
+        condition = { type = "any", conditions = [
+            { type = "price_crosses", level = 250.0, direction = "above" },
+            { type = "bollinger", direction = "above" },
+            { type = "expression", expression = "ema(close, 12) - ema(close, 26) > 0 and rsi(14) < 70" },
+        ] }
+    "#;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::from_toml(EXAMPLE).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.exchange, "XETR");
+        assert_eq!(config.database_path(), PathBuf::from("/tmp/time_series.sqlite"));
+        assert_eq!(config.output.reports, PathBuf::from("/tmp/reports"));
+        assert_eq!(config.nightly_schedule(), "30 22 * * 1-5".parse().unwrap());
+        assert_eq!(config.schedule.live, "* * * * *");
+        assert!((config.thresholds.jump_up - 0.4).abs() < f64::EPSILON);
+        assert!((config.thresholds.jump_down - 0.3).abs() < f64::EPSILON);
+        assert!((config.thresholds.pattern_confidence - 0.75).abs() < f64::EPSILON);
+        assert_eq!(config.watchlist_symbols(), vec!["AAPL", "MSFT", "VOW3.DE"]);
+        assert_eq!(config.charts.overlays, vec![Overlay::Ichimoku, Overlay::WilliamsR]);
+        assert!(config.levels.notify);
+        assert_eq!(config.level_settings(), LevelSettings { window: 3, ..LevelSettings::default() });
+        assert_eq!(config.portfolios.len(), 1);
+        let today = NaiveDate::from_ymd_opt(2025, 9, 15).unwrap();
+        assert_eq!(config.portfolios[0].period(today), ("2025-03-01".to_string(), "2025-08-31".to_string()));
+        assert!(matches!(config.portfolios[0].objective_function(), ObjectiveFunction::MinVol));
+        assert_eq!(config.providers.len(), 1);
+        assert_eq!(config.rules.len(), 1);
+        assert_eq!(config.rules[0].cooldown_minutes, 60);
+        assert!(config.rules[0].applies_to("aapl"));
+
+        let default = Config::from_toml("").unwrap();
+        assert_eq!(default, Config::default());
+        assert_eq!(default.validate(), Ok(()));
+        assert_eq!(default.portfolios[0].period(today), ("2025-03-18".to_string(), "2025-09-14".to_string()));
+        assert_eq!(default.quality_settings(), QualitySettings::default());
+        assert_eq!(default.level_settings(), LevelSettings::default());
+        assert_eq!(default.alert_policy(), AlertPolicy { cooldown: 3600, resolve_after: 1800, escalate_after: 0 });
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert!(Config::from_toml("[schedule]\nnightly_at = \"23:00\"").is_err());
+        let config = Config::from_toml(
+            r#"
+            [schedule]
+            nightly = "0 25 * * *"
+            [thresholds]
+            slope_down = -1.0
+            [quality]
+            repair = "delete"
+            [[portfolios]]
+            name = "a"
+            start_date = "2025-09-01"
+            end_date = "2025-03-01"
+            objective = "max_luck"
+            [[rules]]
+            name = "rsi"
+            notify = ["pager"]
+            condition = { type = "rsi", level = 170, direction = "above" }
+        "#,
+        )
+        .unwrap();
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 7, "{errors:?}");
+    }
+
+    #[test]
+    fn test_config_overrides() {
+        let options = Options::from_iter_safe(["test", "--exchange", "XNYS", "--nightly-schedule", "15 21 * * *", "--interpolate-gaps", "--database", "/tmp/other.sqlite"]).unwrap();
+        let mut config = Config::default();
+        config.apply_overrides(&options);
+        assert_eq!(config.exchange, "XNYS");
+        assert_eq!(config.schedule.nightly, "15 21 * * *");
+        assert_eq!(config.database_path(), PathBuf::from("/tmp/other.sqlite"));
+        assert_eq!(config.link("/events/AAPL").as_deref(), Some("http://127.0.0.1:8080/events/AAPL"));
+        assert!(config.quality.interpolate);
+        assert_eq!(config.quality.repair, "flag");
+    }
+}
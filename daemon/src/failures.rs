@@ -0,0 +1,116 @@
+//! Failures of the steps of a job run
+//!
+//! A job runs its steps per symbol, report or portfolio and records what failed instead of giving
+//! up, the summary ends up as the message of the run in the `job_runs` table.
+
+use std::fmt;
+
+/// failures listed in the summary, the rest are counted
+const LISTED: usize = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Failure {
+    /// the symbol, report or portfolio the step worked on
+    pub subject: String,
+    pub error: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Failures {
+    failures: Vec<Failure>,
+}
+
+impl Failures {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// record a failed step and log it
+    pub fn add(&mut self, subject: &str, error: impl fmt::Display) {
+        log::error!("{}: {}", subject, error);
+        self.failures.push(Failure { subject: subject.to_string(), error: error.to_string() });
+    }
+
+    /// the value of a successful step, a failure is recorded
+    pub fn check<T, E: fmt::Display>(&mut self, subject: &str, result: Result<T, E>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.add(subject, error);
+                None
+            }
+        }
+    }
+
+    /// record the failures of the provider updates, named by their symbol
+    pub fn add_errors(&mut self, subject: &str, errors: Vec<api::Error>) {
+        for error in errors {
+            let subject = error.symbol().unwrap_or(subject).to_string();
+            self.add(&subject, error);
+        }
+    }
+
+    pub fn extend(&mut self, other: Self) {
+        self.failures.extend(other.failures);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.failures.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Failure> {
+        self.failures.iter()
+    }
+}
+
+/// the summary of the failures, empty without failures
+impl fmt::Display for Failures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.failures.is_empty() {
+            return Ok(());
+        }
+        write!(f, "{} failed", self.failures.len())?;
+        for (i, failure) in self.failures.iter().take(LISTED).enumerate() {
+            let separator = if i == 0 { ": " } else { "; " };
+            write!(f, "{}{}: {}", separator, failure.subject, failure.error)?;
+        }
+        if self.failures.len() > LISTED {
+            write!(f, "; and {} more", self.failures.len() - LISTED)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_failures() {
+        let mut failures = Failures::new();
+        assert_eq!(failures.to_string(), "");
+        assert_eq!(failures.check::<_, String>("AAPL", Ok(1)), Some(1));
+        assert_eq!(failures.check("MSFT", Err::<(), _>("no data")), None);
+        failures.add_errors("nightly", vec![
+            api::Error::provider("SAP.DE", "rate limit reached"),
+            api::Error::storage("disk full"),
+        ]);
+        assert_eq!(failures.len(), 3);
+        assert_eq!(
+            failures.to_string(),
+            "3 failed: MSFT: no data; SAP.DE: provider error for SAP.DE: rate limit reached; nightly: storage error: disk full"
+        );
+
+        let mut many = Failures::new();
+        for i in 0..12 {
+            many.add(&format!("S{i}"), "failed");
+        }
+        failures.extend(many);
+        assert!(failures.to_string().ends_with("; and 5 more"));
+    }
+}
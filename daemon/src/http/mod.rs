@@ -0,0 +1,505 @@
+//! Web server of the daemon
+//!
+//! Serves the reports written to the reports directory, the detected events of the active
+//! symbols, a small REST interface to manage the active symbols and the JSON interface over
+//! the analytics library described in `rest`.
+//!
+//! | Route | |
+//! |---|---|
+//! | `GET /` | latest reports and archives |
+//! | `GET /reports/<path>` | a report or a directory listing |
+//! | `POST /reports` | generate the reports now, `202` with the job run, `409` while running |
+//! | `GET /events`, `GET /events/<symbol>` | jump, drop, recurring and pattern events |
+//! | `GET /symbols` | the active symbols as JSON |
+//! | `POST /symbols` | `{"symbols": ["AAPL"]}` adds active symbols |
+//! | `DELETE /symbols/<symbol>` | removes an active symbol |
+//! | `GET /api/...` | market data and analytics as JSON, see `/api/openapi.json` |
+//! | `GET /stream?symbols=AAPL,MSFT` | server-sent events with the live bars, indicators and alerts, all symbols without `symbols` |
+//! | `GET /live/<symbol>` | candlestick chart of the day, updated from `/stream` |
+//!
+//! With `http.token` configured the requests other than `GET` need it as bearer token,
+//! `Authorization: Bearer <token>`, and are answered with `401` otherwise.
+
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{delete, get};
+use axum::{Json, Router};
+use futures::{Stream, StreamExt};
+use serde::Deserialize;
+use std::fmt::Write as _;
+use std::path::{Component, PathBuf};
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+
+mod rest;
+
+use crate::config::Config;
+use crate::live::LiveEvent;
+use crate::server::{current_config, regenerate_reports, stream_url};
+
+/// Shared state of the handlers
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<RwLock<Arc<Config>>>,
+    /// the database of the daemon, opened once at the start
+    pub database: api::data::sql::Database,
+    /// ends the open event streams, the server waits for them before it stops
+    pub shutdown: broadcast::Sender<()>,
+}
+
+impl AppState {
+    fn config(&self) -> Arc<Config> {
+        current_config(&self.config)
+    }
+}
+
+/// all routes of the server
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/", get(index))
+        .route("/reports", get(index).post(regenerate))
+        .route("/reports/", get(index))
+        .route("/reports/*path", get(report))
+        .route("/events", get(all_events))
+        .route("/events/:symbol", get(symbol_events))
+        .route("/symbols", get(symbols).post(add_symbols))
+        .route("/symbols/:symbol", delete(remove_symbol))
+        .route("/stream", get(stream))
+        .route("/live/:symbol", get(live_chart))
+        .merge(rest::routes())
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state)
+}
+
+/// whether two tokens are equal, in a time independent of where they differ
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// the requests changing the daemon need the configured token
+async fn authorize(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if request.method() == Method::GET || request.method() == Method::HEAD {
+        return next.run(request).await;
+    }
+    let config = state.config();
+    let Some(token) = config.http.token.as_deref() else {
+        return next.run(request).await;
+    };
+    let given = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if given.is_some_and(|given| same_token(given.trim(), token)) {
+        return next.run(request).await;
+    }
+    (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer")], Json(serde_json::json!({ "error": "missing or wrong token" }))).into_response()
+}
+
+/// serve until the shutdown signal of the state
+///
+/// # Errors
+/// if the listener fails
+pub async fn serve(listener: tokio::net::TcpListener, state: AppState) -> eyre::Result<()> {
+    log::info!("Web server listening on http://{}", listener.local_addr()?);
+    let mut stop = state.shutdown.subscribe();
+    axum::serve(listener, router(state))
+        .with_graceful_shutdown(async move {
+            let _ = stop.recv().await;
+        })
+        .await?;
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn page(title: &str, body: &str) -> Html<String> {
+    Html(format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{0}</title>\
+         <style>body{{font-family:sans-serif;margin:2em}}td,th{{padding:0 1em;text-align:left}}</style></head>\
+         <body><p><a href=\"/\">reports</a> | <a href=\"/events\">events</a> | <a href=\"/symbols\">symbols</a> | <a href=\"/api/openapi.json\">api</a></p>\
+         <h1>{0}</h1>\n{1}</body></html>\n",
+        escape(title),
+        body
+    ))
+}
+
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, page("Not found", "")).into_response()
+}
+
+fn internal_error() -> Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, page("Internal error", "")).into_response()
+}
+
+/// run blocking database calls on a blocking thread, `None` if the call panicked
+async fn blocking<T, F>(call: F) -> Option<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(call).await {
+        Ok(value) => Some(value),
+        Err(error) => {
+            log::error!("A database call of the web server failed! {error}");
+            None
+        }
+    }
+}
+
+/// the path below the reports directory, `None` if it leaves the directory
+fn resolve(root: &std::path::Path, path: &str) -> Option<PathBuf> {
+    let mut resolved = root.to_path_buf();
+    for component in std::path::Path::new(path).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(resolved)
+}
+
+const fn content_type(extension: &str) -> &'static str {
+    match extension.as_bytes() {
+        b"html" | b"htm" => "text/html; charset=utf-8",
+        b"css" => "text/css",
+        b"js" => "text/javascript",
+        b"json" => "application/json",
+        b"csv" => "text/csv",
+        b"txt" | b"log" => "text/plain; charset=utf-8",
+        b"png" => "image/png",
+        b"svg" => "image/svg+xml",
+        b"pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+/// directories and files of a directory, sorted by name
+fn entries(dir: &std::path::Path) -> (Vec<String>, Vec<String>) {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    if let Ok(read) = std::fs::read_dir(dir) {
+        for entry in read.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                continue;
+            }
+            if entry.path().is_dir() {
+                dirs.push(name);
+            } else {
+                files.push(name);
+            }
+        }
+    }
+    dirs.sort();
+    files.sort();
+    (dirs, files)
+}
+
+fn listing(root: &std::path::Path, path: &str) -> String {
+    let dir = resolve(root, path).unwrap_or_else(|| root.to_path_buf());
+    let prefix = if path.is_empty() { "/reports/".to_string() } else { format!("/reports/{}/", path.trim_end_matches('/')) };
+    let (dirs, files) = entries(&dir);
+    let mut body = String::from("<ul>\n");
+    for name in dirs.iter().filter(|d| !d.starts_with("archive_")) {
+        let _ = writeln!(body, "<li><a href=\"{}{}/\">{}/</a></li>", prefix, escape(name), escape(name));
+    }
+    for name in &files {
+        let _ = writeln!(body, "<li><a href=\"{}{}\">{}</a></li>", prefix, escape(name), escape(name));
+    }
+    body.push_str("</ul>\n");
+    // archives are named by date, the newest first
+    let archives: Vec<&String> = dirs.iter().filter(|d| d.starts_with("archive_")).rev().collect();
+    if !archives.is_empty() {
+        body.push_str("<h2>Archive</h2>\n<ul>\n");
+        for name in archives {
+            let _ = writeln!(body, "<li><a href=\"{}{}/\">{}</a></li>", prefix, escape(name), escape(name.trim_start_matches("archive_")));
+        }
+        body.push_str("</ul>\n");
+    }
+    body
+}
+
+async fn index(State(state): State<AppState>) -> Html<String> {
+    let config = state.config();
+    page("Reports", &listing(&config.reports_dir(), ""))
+}
+
+async fn report(State(state): State<AppState>, Path(path): Path<String>) -> Response {
+    let root = state.config().reports_dir();
+    let Some(file) = resolve(&root, &path) else {
+        return not_found();
+    };
+    if file.is_dir() {
+        return page(&path, &listing(&root, &path)).into_response();
+    }
+    match tokio::fs::read(&file).await {
+        Ok(content) => {
+            let extension = file.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+            ([(header::CONTENT_TYPE, content_type(&extension))], content).into_response()
+        }
+        Err(_) => not_found(),
+    }
+}
+
+async fn regenerate(State(state): State<AppState>) -> Response {
+    match regenerate_reports(state.database.clone(), state.config()) {
+        Some(run_id) => (StatusCode::ACCEPTED, Json(serde_json::json!({ "run_id": run_id }))).into_response(),
+        None => (StatusCode::CONFLICT, Json(serde_json::json!({ "error": "the reports are being generated" }))).into_response(),
+    }
+}
+
+fn time(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+fn events_table(sql_connection: api::data::sql::Database, symbol: &str) -> String {
+    let mut body = String::new();
+    for (kind, events) in [
+        ("Jumps", api::data::sql::events::jump_events(sql_connection.clone(), symbol)),
+        ("Drops", api::data::sql::events::drop_events(sql_connection.clone(), symbol)),
+    ] {
+        if events.is_empty() {
+            continue;
+        }
+        let _ = writeln!(body, "<h3>{kind}</h3>\n<table><tr><th>time</th><th>percent</th></tr>");
+        for e in events.iter().rev() {
+            let _ = writeln!(body, "<tr><td>{}</td><td>{:.2}</td></tr>", time(e.datetime), e.percent * 100.0);
+        }
+        body.push_str("</table>\n");
+    }
+    let recurring = api::data::sql::events::recurring_events(sql_connection.clone(), symbol);
+    if !recurring.is_empty() {
+        body.push_str("<h3>Recurring</h3>\n<table><tr><th>period (minutes)</th><th>percent</th></tr>\n");
+        for e in &recurring {
+            let _ = writeln!(body, "<tr><td>{}</td><td>{:.2}</td></tr>", e.minutes_period, e.time_scale);
+        }
+        body.push_str("</table>\n");
+    }
+    let patterns = api::data::sql::events::pattern_events(sql_connection, symbol);
+    if !patterns.is_empty() {
+        body.push_str("<h3>Candlestick patterns</h3>\n<table><tr><th>time</th><th>pattern</th><th>confidence</th></tr>\n");
+        for e in patterns.iter().rev() {
+            let _ = writeln!(body, "<tr><td>{}</td><td>{}</td><td>{:.0}%</td></tr>", time(e.datetime), e.pattern.label(), e.confidence * 100.0);
+        }
+        body.push_str("</table>\n");
+    }
+    if body.is_empty() {
+        body.push_str("<p>no events</p>\n");
+    }
+    body
+}
+
+async fn all_events(State(state): State<AppState>) -> Response {
+    let sql_connection = state.database;
+    let body = blocking(move || {
+        let symbols = api::data::sql::symbols::active_symbols(sql_connection.clone());
+        let mut body = String::new();
+        for symbol in &symbols {
+            let _ = writeln!(body, "<h2><a href=\"/events/{0}\">{0}</a></h2>", escape(symbol));
+            body.push_str(&events_table(sql_connection.clone(), symbol));
+        }
+        body
+    });
+    match body.await {
+        Some(body) => page("Events", &body).into_response(),
+        None => internal_error(),
+    }
+}
+
+async fn symbol_events(State(state): State<AppState>, Path(symbol): Path<String>) -> Response {
+    let title = format!("Events of {symbol}");
+    match blocking(move || events_table(state.database, &symbol)).await {
+        Some(body) => page(&title, &body).into_response(),
+        None => internal_error(),
+    }
+}
+
+async fn symbols(State(state): State<AppState>) -> Response {
+    match blocking(move || api::data::sql::symbols::active_symbols(state.database)).await {
+        Some(symbols) => Json(symbols).into_response(),
+        None => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": "failed to read the symbols" }))).into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct NewSymbols {
+    symbols: Vec<String>,
+}
+
+async fn add_symbols(State(state): State<AppState>, Json(request): Json<NewSymbols>) -> Response {
+    let symbols: Vec<String> = request.symbols.iter().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+    if symbols.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": "no symbols" }))).into_response();
+    }
+    let sql_connection = state.database;
+    let stored = blocking(move || {
+        if api::data::sql::symbols::insert_active_symbols(sql_connection.clone(), &symbols) != 0 {
+            return None;
+        }
+        Some(api::data::sql::symbols::active_symbols(sql_connection))
+    });
+    match stored.await.flatten() {
+        Some(symbols) => (StatusCode::CREATED, Json(symbols)).into_response(),
+        None => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": "failed to store the symbols" }))).into_response(),
+    }
+}
+
+async fn remove_symbol(State(state): State<AppState>, Path(symbol): Path<String>) -> StatusCode {
+    match blocking(move || api::data::sql::symbols::delete_active_symbols(state.database, &vec![symbol])).await {
+        Some(0) => StatusCode::NOT_FOUND,
+        Some(_) => StatusCode::NO_CONTENT,
+        None => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamQuery {
+    symbols: Option<String>,
+}
+
+/// the live updates of the symbols as server-sent events named `bar`, `indicators` and `alert`
+async fn stream(State(state): State<AppState>, Query(query): Query<StreamQuery>) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let symbols: Vec<String> =
+        query.symbols.unwrap_or_default().split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect();
+    let mut stop = state.shutdown.subscribe();
+    let updates = futures::stream::unfold(crate::live::subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => return Some((event, receiver)),
+                Err(broadcast::error::RecvError::Lagged(missed)) => log::warn!("A live stream missed {missed} updates"),
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    let events = updates
+        .filter(move |event: &LiveEvent| {
+            futures::future::ready(symbols.is_empty() || symbols.iter().any(|s| s.eq_ignore_ascii_case(event.symbol())))
+        })
+        .map(|event| Event::default().event(event.name()).json_data(&event))
+        .take_until(async move {
+            let _ = stop.recv().await;
+        });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+async fn live_chart(Path(symbol): Path<String>) -> Response {
+    let stream = stream_url(&symbol);
+    let chart = tokio::task::spawn_blocking(move || {
+        let today = chrono::Utc::now().date_naive().to_string();
+        let ticker = api::prelude::Ticker::builder()
+            .ticker(&symbol)
+            .start_date(&today)
+            .end_date(&today)
+            .interval(api::prelude::Interval::OneMinute)
+            .build();
+        futures::executor::block_on(api::prelude::TickerCharts::candlestick_chart_live(&ticker, None, None))
+            .map(|plot| plot.to_html())
+            .map_err(|e| e.to_string())
+    })
+    .await;
+    match chart {
+        Ok(Ok(html)) => Html(api::charts::live::live_chart_html(&html, &stream)).into_response(),
+        Ok(Err(error)) => (StatusCode::NOT_FOUND, page("No chart", &format!("<p>{}</p>", escape(&error)))).into_response(),
+        Err(error) => {
+            log::error!("Failed to draw a live chart! {}", error);
+            (StatusCode::INTERNAL_SERVER_ERROR, page("No chart", "")).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    async fn start(reports: &std::path::Path) -> String {
+        start_with(Config { output: crate::config::OutputConfig { reports: reports.to_path_buf() }, ..Config::default() }).await
+    }
+
+    async fn start_with(config: Config) -> String {
+        let (shutdown, _) = broadcast::channel(1);
+        let database = api::data::sql::Database::open(":memory:").unwrap();
+        let state = AppState { config: Arc::new(RwLock::new(Arc::new(config))), database, shutdown };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { serve(listener, state).await });
+        format!("http://{address}")
+    }
+
+    #[tokio::test]
+    async fn test_reports() {
+        let dir = std::env::temp_dir().join(format!("stockanalysis-http-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("archive_2025-09-01")).unwrap();
+        std::fs::write(dir.join("AAPL_chart.html"), "<html>latest</html>").unwrap();
+        std::fs::write(dir.join("archive_2025-09-01").join("AAPL_chart.html"), "<html>old</html>").unwrap();
+        let base = start(&dir).await;
+
+        let index = reqwest::get(&base).await.unwrap().text().await.unwrap();
+        assert!(index.contains("/reports/AAPL_chart.html"));
+        assert!(index.contains("/reports/archive_2025-09-01/"));
+        let response = reqwest::get(format!("{base}/reports/AAPL_chart.html")).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/html; charset=utf-8");
+        assert_eq!(response.text().await.unwrap(), "<html>latest</html>");
+        let archived = reqwest::get(format!("{base}/reports/archive_2025-09-01/AAPL_chart.html")).await.unwrap();
+        assert_eq!(archived.text().await.unwrap(), "<html>old</html>");
+        let listing = reqwest::get(format!("{base}/reports/archive_2025-09-01/")).await.unwrap().text().await.unwrap();
+        assert!(listing.contains("/reports/archive_2025-09-01/AAPL_chart.html"));
+        assert_eq!(reqwest::get(format!("{base}/reports/missing.html")).await.unwrap().status(), 404);
+        assert_eq!(reqwest::get(format!("{base}/reports/..%2F..%2Fetc%2Fpasswd")).await.unwrap().status(), 404);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let base = start(&std::env::temp_dir()).await;
+        let mut response = reqwest::get(format!("{base}/stream?symbols=ssetest")).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/event-stream");
+        // the handler subscribed before it answered
+        crate::live::publish(LiveEvent::Bar { symbol: "OTHER".to_string(), bars: Vec::new() });
+        let bar = api::data::sql::TimeSeriesData { datetime: 1_700_000_000, close: 10.0, ..Default::default() };
+        crate::live::publish_bars("SSETEST", &[bar]);
+        let mut text = String::new();
+        while !text.contains("event: indicators") {
+            let chunk = response.chunk().await.unwrap().unwrap();
+            text.push_str(&String::from_utf8_lossy(&chunk));
+        }
+        assert!(text.starts_with("event: bar\ndata: {\"type\":\"bar\",\"symbol\":\"SSETEST\""));
+        assert!(!text.contains("OTHER"));
+    }
+
+    #[tokio::test]
+    async fn test_token() {
+        let http = crate::config::HttpConfig { token: Some("secret".to_string()), ..Default::default() };
+        let base = start_with(Config { http, ..Config::default() }).await;
+        let client = reqwest::Client::new();
+        let add = || client.post(format!("{base}/symbols")).json(&serde_json::json!({ "symbols": ["AAPL"] }));
+        assert_eq!(add().send().await.unwrap().status(), 401);
+        assert_eq!(add().bearer_auth("wrong").send().await.unwrap().status(), 401);
+        assert_eq!(add().bearer_auth("secret").send().await.unwrap().status(), 201);
+        assert_eq!(client.delete(format!("{base}/symbols/AAPL")).send().await.unwrap().status(), 401);
+        assert_eq!(client.delete(format!("{base}/symbols/AAPL")).bearer_auth("secret").send().await.unwrap().status(), 204);
+        // reading needs no token
+        assert_eq!(reqwest::get(format!("{base}/symbols")).await.unwrap().status(), 200);
+        assert!(same_token("secret", "secret"));
+        assert!(!same_token("secre", "secret"));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let root = std::path::Path::new("/reports");
+        assert_eq!(resolve(root, "a/b.html"), Some(PathBuf::from("/reports/a/b.html")));
+        assert_eq!(resolve(root, "../etc/passwd"), None);
+        assert_eq!(resolve(root, "/etc/passwd"), None);
+    }
+}
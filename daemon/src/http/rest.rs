@@ -0,0 +1,769 @@
+//! JSON interface over the analytics library
+//!
+//! The routes are declared once in [`endpoints`], the same declarations register the handlers
+//! and describe them in the OpenAPI document served at `/api/openapi.json`.
+//! Dates are `YYYY-MM-DD`, the range defaults to the last 90 days and the interval to `1d`.
+//! Errors are answered as `{"error": "..."}`.
+
+use axum::extract::rejection::QueryRejection;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, MethodRouter};
+use axum::{Json, Router};
+use chrono::NaiveDate;
+use polars::prelude::*;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::future::Future;
+
+use api::prelude::*;
+
+use super::AppState;
+use crate::config::Config;
+
+/// indicators of `/api/indicators/{name}`
+pub const INDICATORS: &[&str] = &[
+    "sma", "ema", "rsi", "macd", "ppo", "mfi", "bb", "fs", "ss", "sd", "mad", "max", "min", "atr", "roc", "obv",
+    "ichimoku", "adx", "psar", "vwap", "kc", "dc", "supertrend", "cci", "wr", "cmf",
+];
+
+/// intervals of the `interval` parameter
+pub const INTERVALS: &[&str] = &["1m", "2m", "5m", "15m", "30m", "60m", "90m", "1h", "1d", "5d", "1wk", "1mo", "3mo"];
+
+const COLUMNS: &[&str] = &["open", "high", "low", "close", "volume", "adjclose"];
+
+const OBJECTIVES: &[&str] = &["max_sharpe", "min_vol", "max_return", "min_drawdown", "min_var", "min_cvar"];
+
+const ALERT_STATES: &[&str] = &["open", "acknowledged", "snoozed", "resolved"];
+
+/// days of the range without `start`
+const DEFAULT_DAYS: u64 = 90;
+
+/// Where a parameter is passed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum In {
+    Path,
+    Query,
+}
+
+impl In {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Path => "path",
+            Self::Query => "query",
+        }
+    }
+}
+
+/// A parameter of an endpoint
+#[derive(Clone, Copy, Debug)]
+pub struct Param {
+    pub name: &'static str,
+    pub location: In,
+    pub required: bool,
+    /// OpenAPI type, `string`, `integer`, `number` or `boolean`
+    pub kind: &'static str,
+    pub description: &'static str,
+    /// allowed values, any if empty
+    pub values: &'static [&'static str],
+}
+
+impl Param {
+    const fn query(name: &'static str, kind: &'static str, description: &'static str) -> Self {
+        Self { name, location: In::Query, required: false, kind, description, values: &[] }
+    }
+
+    const fn path(name: &'static str, description: &'static str) -> Self {
+        Self { name, location: In::Path, required: true, kind: "string", description, values: &[] }
+    }
+
+    #[must_use]
+    const fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    #[must_use]
+    const fn one_of(mut self, values: &'static [&'static str]) -> Self {
+        self.values = values;
+        self
+    }
+}
+
+/// A route of the JSON interface
+pub struct Endpoint {
+    /// HTTP method in lower case
+    pub method: &'static str,
+    /// path in OpenAPI notation, `/api/indicators/{name}`
+    pub path: &'static str,
+    pub summary: &'static str,
+    pub params: Vec<Param>,
+    pub handler: fn() -> MethodRouter<AppState>,
+}
+
+fn range_params() -> Vec<Param> {
+    vec![
+        Param::query("symbol", "string", "ticker symbol").required(),
+        Param::query("start", "string", "first day, YYYY-MM-DD, 90 days before `end` by default"),
+        Param::query("end", "string", "last day, YYYY-MM-DD, today by default"),
+        Param::query("interval", "string", "bar interval").one_of(INTERVALS),
+    ]
+}
+
+/// all routes of the JSON interface
+pub fn endpoints() -> Vec<Endpoint> {
+    vec![
+        Endpoint {
+            method: "get",
+            path: "/api/ohlcv",
+            summary: "open, high, low, close and volume bars of a symbol",
+            params: range_params(),
+            handler: || get(ohlcv),
+        },
+        Endpoint {
+            method: "get",
+            path: "/api/indicators/{name}",
+            summary: "a technical indicator of a symbol",
+            params: [
+                vec![Param::path("name", "indicator").one_of(INDICATORS)],
+                range_params(),
+                vec![
+                    Param::query("period", "integer", "period of the indicator, 14 by default, 20 for kc, dc, cci and cmf, 10 for supertrend"),
+                    Param::query("fast", "integer", "fast period of macd and ppo, 12 by default, conversion period of ichimoku, 9 by default"),
+                    Param::query("slow", "integer", "slow period of macd and ppo, 26 by default, base period of ichimoku, 26 by default"),
+                    Param::query("signal", "integer", "signal period of macd and ppo, 9 by default, span B period of ichimoku, 52 by default"),
+                    Param::query("std_dev", "number", "standard deviations of bb, 2 by default"),
+                    Param::query("ema_period", "integer", "smoothing period of ss, 3 by default"),
+                    Param::query("atr_period", "integer", "period of the average true range of kc, 10 by default"),
+                    Param::query("multiplier", "number", "multiple of the average true range of kc and supertrend, 2 and 3 by default"),
+                    Param::query("step", "number", "acceleration step of psar, 0.02 by default"),
+                    Param::query("max_step", "number", "maximum acceleration of psar, 0.2 by default"),
+                    Param::query("column", "string", "column the indicator is computed on, close by default").one_of(COLUMNS),
+                ],
+            ]
+            .concat(),
+            handler: || get(indicator),
+        },
+        Endpoint {
+            method: "get",
+            path: "/api/performance",
+            summary: "performance statistics of a symbol against a benchmark",
+            params: [
+                range_params(),
+                vec![
+                    Param::query("benchmark", "string", "benchmark symbol, the one of the charts by default"),
+                    Param::query("confidence_level", "number", "confidence level of value at risk, 0.95 by default"),
+                    Param::query("risk_free_rate", "number", "annual risk free rate, 0.02 by default"),
+                ],
+            ]
+            .concat(),
+            handler: || get(performance),
+        },
+        Endpoint {
+            method: "get",
+            path: "/api/portfolio",
+            summary: "optimal weights and performance statistics of a portfolio",
+            params: vec![
+                Param::query("symbols", "string", "comma separated ticker symbols").required(),
+                Param::query("benchmark", "string", "benchmark symbol, the one of the charts by default"),
+                Param::query("start", "string", "first day, YYYY-MM-DD, 90 days before `end` by default"),
+                Param::query("end", "string", "last day, YYYY-MM-DD, today by default"),
+                Param::query("interval", "string", "bar interval").one_of(INTERVALS),
+                Param::query("objective", "string", "objective of the optimization, max_sharpe by default").one_of(OBJECTIVES),
+                Param::query("confidence_level", "number", "confidence level of value at risk, 0.95 by default"),
+                Param::query("risk_free_rate", "number", "annual risk free rate, 0.02 by default"),
+            ],
+            handler: || get(portfolio),
+        },
+        Endpoint {
+            method: "get",
+            path: "/api/screener",
+            summary: "the symbols of an exchange with the largest market cap",
+            params: vec![
+                Param::query("exchange", "string", "Yahoo exchange code, the one of the screener configuration by default"),
+                Param::query("size", "integer", "number of symbols, the one of the screener configuration by default"),
+            ],
+            handler: || get(screener),
+        },
+        Endpoint {
+            method: "get",
+            path: "/api/levels",
+            summary: "swing highs and lows, support and resistance levels, trendlines and breakouts of a symbol",
+            params: [
+                range_params(),
+                vec![
+                    Param::query("window", "integer", "bars on both sides of a swing high or low, the one of the levels configuration by default"),
+                    Param::query("tolerance", "number", "relative distance of prices of the same level, the one of the levels configuration by default"),
+                    Param::query("min_touches", "integer", "touches of a level, the one of the levels configuration by default"),
+                ],
+            ]
+            .concat(),
+            handler: || get(levels),
+        },
+        Endpoint {
+            method: "get",
+            path: "/api/events",
+            summary: "jump, drop, recurring and candlestick pattern events of a symbol",
+            params: vec![Param::query("symbol", "string", "ticker symbol").required()],
+            handler: || get(events),
+        },
+        Endpoint {
+            method: "get",
+            path: "/api/alerts",
+            summary: "alerts raised by the daemon, newest first",
+            params: vec![
+                Param::query("symbol", "string", "only the alerts of this symbol"),
+                Param::query("state", "string", "only the alerts in this state").one_of(ALERT_STATES),
+                Param::query("days", "integer", "alerts seen in the last days, 7 by default"),
+                Param::query("limit", "integer", "maximum number of alerts, 100 by default"),
+            ],
+            handler: || get(alerts),
+        },
+        Endpoint {
+            method: "get",
+            path: "/api/openapi.json",
+            summary: "this description",
+            params: Vec::new(),
+            handler: || get(openapi_json),
+        },
+    ]
+}
+
+/// the axum notation of an OpenAPI path, `/a/{b}` becomes `/a/:b`
+fn axum_path(path: &str) -> String {
+    path.split('/')
+        .map(|part| part.strip_prefix('{').and_then(|p| p.strip_suffix('}')).map_or_else(|| part.to_string(), |p| format!(":{p}")))
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// the routes of [`endpoints`]
+pub fn routes() -> Router<AppState> {
+    let mut router = Router::new();
+    for endpoint in endpoints() {
+        router = router.route(&axum_path(endpoint.path), (endpoint.handler)());
+    }
+    router
+}
+
+/// OpenAPI 3 description of the endpoints
+pub fn openapi(endpoints: &[Endpoint]) -> Value {
+    let mut paths = serde_json::Map::new();
+    for endpoint in endpoints {
+        let parameters: Vec<Value> = endpoint
+            .params
+            .iter()
+            .map(|p| {
+                let mut schema = json!({ "type": p.kind });
+                if !p.values.is_empty() {
+                    schema["enum"] = json!(p.values);
+                }
+                json!({
+                    "name": p.name,
+                    "in": p.location.as_str(),
+                    "required": p.required,
+                    "description": p.description,
+                    "schema": schema,
+                })
+            })
+            .collect();
+        let operation = json!({
+            "summary": endpoint.summary,
+            "parameters": parameters,
+            "responses": {
+                "200": { "description": "success", "content": { "application/json": {} } },
+                "400": { "description": "invalid parameters" },
+                "500": { "description": "the data could not be loaded" },
+            },
+        });
+        let path = paths.entry(endpoint.path).or_insert_with(|| json!({}));
+        path[endpoint.method] = operation;
+    }
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "stockanalysis daemon",
+            "description": "market data and analytics of the active symbols",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+    })
+}
+
+async fn openapi_json() -> Json<Value> {
+    Json(openapi(&endpoints()))
+}
+
+/// An error answered as `{"error": "..."}`
+#[derive(Debug)]
+pub struct ApiError(StatusCode, String);
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self(StatusCode::BAD_REQUEST, message.into())
+    }
+
+    fn internal(message: impl Into<String>) -> Self {
+        Self(StatusCode::INTERNAL_SERVER_ERROR, message.into())
+    }
+}
+
+/// missing or malformed query parameters
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self(rejection.status(), rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type ApiResult = Result<Json<Value>, ApiError>;
+
+/// run a computation of the analytics library on a blocking thread, its futures are not `Send`
+async fn analytics<F, Fut>(computation: F) -> ApiResult
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Value, Box<dyn Error>>>,
+{
+    tokio::task::spawn_blocking(move || futures::executor::block_on(computation()).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))?
+        .map(Json)
+        .map_err(|e| {
+            log::error!("Failed to answer an API request: {e}");
+            ApiError::internal(e)
+        })
+}
+
+/// the rows of a data frame as JSON objects
+fn frame_json(df: &mut DataFrame) -> Result<Value, Box<dyn Error>> {
+    let mut buffer = Vec::new();
+    JsonWriter::new(&mut buffer).with_json_format(JsonFormat::Json).finish(df)?;
+    Ok(serde_json::from_slice(&buffer)?)
+}
+
+/// the values of a series, missing values as `null`
+fn series_json(series: &Series) -> Value {
+    series
+        .cast(&DataType::Float64)
+        .ok()
+        .and_then(|s| s.f64().ok().map(|values| json!(values.into_iter().collect::<Vec<Option<f64>>>())))
+        .unwrap_or_else(|| json!([]))
+}
+
+/// a checked date range and interval
+#[derive(Debug)]
+struct Range {
+    start: NaiveDate,
+    end: NaiveDate,
+    interval: Interval,
+}
+
+fn range(start: Option<NaiveDate>, end: Option<NaiveDate>, interval: Option<&str>) -> Result<Range, ApiError> {
+    let end = end.unwrap_or_else(|| chrono::Local::now().date_naive());
+    let start = start.unwrap_or_else(|| end - chrono::Days::new(DEFAULT_DAYS));
+    if start > end {
+        return Err(ApiError::bad_request(format!("start {start} is after end {end}")));
+    }
+    let interval = interval.unwrap_or("1d");
+    let interval = interval
+        .parse()
+        .map_err(|()| ApiError::bad_request(format!("unknown interval {interval}, one of {}", INTERVALS.join(", "))))?;
+    Ok(Range { start, end, interval })
+}
+
+fn symbol(symbol: &str) -> Result<String, ApiError> {
+    let symbol = symbol.trim();
+    if symbol.is_empty() {
+        return Err(ApiError::bad_request("no symbol"));
+    }
+    Ok(symbol.to_string())
+}
+
+fn ticker(state: &AppState, symbol: &str, range: &Range, benchmark: &str, confidence_level: f64, risk_free_rate: f64) -> Ticker {
+    Ticker::builder()
+        .ticker(symbol)
+        .start_date(&range.start.to_string())
+        .end_date(&range.end.to_string())
+        .interval(range.interval)
+        .benchmark_symbol(benchmark)
+        .confidence_level(confidence_level)
+        .risk_free_rate(risk_free_rate)
+        .database(Some(state.database.clone()))
+        .build()
+}
+
+#[derive(Debug, Deserialize)]
+struct OhlcvQuery {
+    symbol: String,
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    interval: Option<String>,
+}
+
+async fn ohlcv(State(state): State<AppState>, query: Result<Query<OhlcvQuery>, QueryRejection>) -> ApiResult {
+    let Query(query) = query?;
+    let symbol = symbol(&query.symbol)?;
+    let range = range(query.start, query.end, query.interval.as_deref())?;
+    let ticker = ticker(&state, &symbol, &range, &state.config().charts.benchmark, 0.95, 0.02);
+    analytics(move || async move { frame_json(&mut ticker.get_chart().await?) }).await
+}
+
+#[derive(Debug, Deserialize)]
+struct IndicatorQuery {
+    symbol: String,
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    interval: Option<String>,
+    period: Option<usize>,
+    fast: Option<usize>,
+    slow: Option<usize>,
+    signal: Option<usize>,
+    std_dev: Option<f64>,
+    ema_period: Option<usize>,
+    atr_period: Option<usize>,
+    multiplier: Option<f64>,
+    step: Option<f64>,
+    max_step: Option<f64>,
+    column: Option<String>,
+}
+
+/// compute the indicator `name`, one of [`INDICATORS`]
+async fn compute_indicator(ticker: &Ticker, name: &str, query: &IndicatorQuery) -> Result<DataFrame, Box<dyn Error>> {
+    let period = query.period.unwrap_or(14);
+    let (fast, slow, signal) = (query.fast.unwrap_or(12), query.slow.unwrap_or(26), query.signal.unwrap_or(9));
+    let col = Some(Column::from_str(query.column.as_deref().unwrap_or("close")));
+    match name {
+        "sma" => ticker.sma(period, col).await,
+        "ema" => ticker.ema(period, col).await,
+        "rsi" => ticker.rsi(period, col).await,
+        "macd" => ticker.macd(fast, slow, signal, col).await,
+        "ppo" => ticker.ppo(fast, slow, signal, col).await,
+        "mfi" => ticker.mfi(period).await,
+        "bb" => ticker.bb(period, query.std_dev.unwrap_or(2.0), col).await,
+        "fs" => ticker.fs(period, col).await,
+        "ss" => ticker.ss(period, query.ema_period.unwrap_or(3), col).await,
+        "sd" => ticker.sd(period, col).await,
+        "mad" => ticker.mad(period, col).await,
+        "max" => ticker.max(period, col).await,
+        "min" => ticker.min(period, col).await,
+        "atr" => ticker.atr(period).await,
+        "roc" => ticker.roc(period, col).await,
+        "obv" => ticker.obv().await,
+        "ichimoku" => ticker.ichimoku(query.fast.unwrap_or(9), query.slow.unwrap_or(26), query.signal.unwrap_or(52)).await,
+        "adx" => ticker.adx(period).await,
+        "psar" => ticker.psar(query.step.unwrap_or(0.02), query.max_step.unwrap_or(0.2)).await,
+        "vwap" => ticker.vwap().await,
+        "kc" => ticker.kc(query.period.unwrap_or(20), query.atr_period.unwrap_or(10), query.multiplier.unwrap_or(2.0)).await,
+        "dc" => ticker.dc(query.period.unwrap_or(20)).await,
+        "supertrend" => ticker.supertrend(query.period.unwrap_or(10), query.multiplier.unwrap_or(3.0)).await,
+        "cci" => ticker.cci(query.period.unwrap_or(20)).await,
+        "wr" => ticker.wr(period).await,
+        "cmf" => ticker.cmf(query.period.unwrap_or(20)).await,
+        _ => Err(format!("unknown indicator {name}").into()),
+    }
+}
+
+async fn indicator(State(state): State<AppState>, Path(name): Path<String>, query: Result<Query<IndicatorQuery>, QueryRejection>) -> ApiResult {
+    let Query(query) = query?;
+    if !INDICATORS.contains(&name.as_str()) {
+        return Err(ApiError(StatusCode::NOT_FOUND, format!("unknown indicator {name}, one of {}", INDICATORS.join(", "))));
+    }
+    let symbol = symbol(&query.symbol)?;
+    let range = range(query.start, query.end, query.interval.as_deref())?;
+    if [query.period, query.fast, query.slow, query.signal, query.ema_period, query.atr_period].contains(&Some(0)) {
+        return Err(ApiError::bad_request("periods must be positive"));
+    }
+    if let Some(column) = query.column.as_deref().filter(|c| !COLUMNS.contains(c)) {
+        return Err(ApiError::bad_request(format!("unknown column {column}, one of {}", COLUMNS.join(", "))));
+    }
+    let ticker = ticker(&state, &symbol, &range, &state.config().charts.benchmark, 0.95, 0.02);
+    analytics(move || async move { frame_json(&mut compute_indicator(&ticker, &name, &query).await?) }).await
+}
+
+#[derive(Debug, Deserialize)]
+struct PerformanceQuery {
+    symbol: String,
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    interval: Option<String>,
+    benchmark: Option<String>,
+    confidence_level: Option<f64>,
+    risk_free_rate: Option<f64>,
+}
+
+fn benchmark(benchmark: Option<String>, config: &Config) -> String {
+    benchmark.filter(|b| !b.trim().is_empty()).unwrap_or_else(|| config.charts.benchmark.clone())
+}
+
+fn confidence_level(confidence_level: Option<f64>) -> Result<f64, ApiError> {
+    match confidence_level.unwrap_or(0.95) {
+        c if c > 0.0 && c < 1.0 => Ok(c),
+        c => Err(ApiError::bad_request(format!("confidence level {c} is not between 0 and 1"))),
+    }
+}
+
+async fn performance(State(state): State<AppState>, query: Result<Query<PerformanceQuery>, QueryRejection>) -> ApiResult {
+    let Query(query) = query?;
+    let symbol = symbol(&query.symbol)?;
+    let range = range(query.start, query.end, query.interval.as_deref())?;
+    let confidence_level = confidence_level(query.confidence_level)?;
+    let benchmark = benchmark(query.benchmark, &state.config());
+    let ticker = ticker(&state, &symbol, &range, &benchmark, confidence_level, query.risk_free_rate.unwrap_or(0.02));
+    analytics(move || async move {
+        let stats = ticker.performance_stats().await?;
+        Ok(json!({
+            "symbol": stats.ticker_symbol,
+            "benchmark": stats.benchmark_symbol,
+            "start": stats.start_date,
+            "end": stats.end_date,
+            "confidence_level": stats.confidence_level,
+            "risk_free_rate": stats.risk_free_rate,
+            "stats": stats.performance_stats,
+            "dates": stats.dates_array,
+            "prices": series_json(&stats.security_prices),
+            "returns": series_json(&stats.security_returns),
+            "benchmark_returns": series_json(&stats.benchmark_returns),
+        }))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+struct PortfolioQuery {
+    symbols: String,
+    benchmark: Option<String>,
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    interval: Option<String>,
+    objective: Option<String>,
+    confidence_level: Option<f64>,
+    risk_free_rate: Option<f64>,
+}
+
+async fn portfolio(State(state): State<AppState>, query: Result<Query<PortfolioQuery>, QueryRejection>) -> ApiResult {
+    let Query(query) = query?;
+    let symbols: Vec<String> = query.symbols.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect();
+    if symbols.len() < 2 {
+        return Err(ApiError::bad_request("a portfolio needs at least two symbols"));
+    }
+    let range = range(query.start, query.end, query.interval.as_deref())?;
+    let objective: ObjectiveFunction = query.objective.as_deref().unwrap_or("max_sharpe").parse().map_err(ApiError::bad_request)?;
+    let confidence_level = confidence_level(query.confidence_level)?;
+    let benchmark = benchmark(query.benchmark, &state.config());
+    let risk_free_rate = query.risk_free_rate.unwrap_or(0.02);
+    let database = state.database.clone();
+    analytics(move || async move {
+        let portfolio = Portfolio::builder()
+            .ticker_symbols(symbols.iter().map(String::as_str).collect())
+            .benchmark_symbol(&benchmark)
+            .start_date(&range.start.to_string())
+            .end_date(&range.end.to_string())
+            .interval(range.interval)
+            .confidence_level(confidence_level)
+            .risk_free_rate(risk_free_rate)
+            .objective_function(objective)
+            .database(Some(database))
+            .build()
+            .await?;
+        let stats = portfolio.performance_stats;
+        let weights: serde_json::Map<String, Value> =
+            stats.ticker_symbols.iter().cloned().zip(stats.optimal_weights.iter().map(|w| json!(w))).collect();
+        Ok(json!({
+            "symbols": stats.ticker_symbols,
+            "benchmark": stats.benchmark_symbol,
+            "start": stats.start_date,
+            "end": stats.end_date,
+            "optimization_method": stats.optimization_method,
+            "weights": weights,
+            "stats": stats.performance_stats,
+            "dates": stats.dates_array,
+            "returns": series_json(&stats.optimal_portfolio_returns),
+            "benchmark_returns": series_json(&stats.benchmark_returns),
+            "efficient_frontier": stats.efficient_frontier,
+        }))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+struct ScreenerQuery {
+    exchange: Option<String>,
+    size: Option<usize>,
+}
+
+async fn screener(State(state): State<AppState>, query: Result<Query<ScreenerQuery>, QueryRejection>) -> ApiResult {
+    let Query(query) = query?;
+    let settings = state.config().screener.clone();
+    let exchange = query.exchange.unwrap_or(settings.exchange);
+    let exchange: Exchange = exchange.parse().map_err(|_| ApiError::bad_request(format!("unknown exchange {exchange}")))?;
+    let size = query.size.unwrap_or(settings.size);
+    if size == 0 {
+        return Err(ApiError::bad_request("size must be positive"));
+    }
+    analytics(move || async move {
+        let screener = Screener::builder()
+            .quote_type(QuoteType::Equity)
+            .add_filter(ScreenerFilter::EqStr(ScreenerMetric::Equity(EquityScreener::Exchange), exchange.into()))
+            .sort_by(ScreenerMetric::Equity(EquityScreener::MarketCapIntraday), true)
+            .size(size)
+            .build()
+            .await?;
+        Ok(json!({
+            "symbols": screener.symbols,
+            "overview": frame_json(&mut screener.overview().data)?,
+        }))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+struct LevelsQuery {
+    symbol: String,
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    interval: Option<String>,
+    window: Option<usize>,
+    tolerance: Option<f64>,
+    min_touches: Option<usize>,
+}
+
+async fn levels(State(state): State<AppState>, query: Result<Query<LevelsQuery>, QueryRejection>) -> ApiResult {
+    let Query(query) = query?;
+    let symbol = symbol(&query.symbol)?;
+    let range = range(query.start, query.end, query.interval.as_deref())?;
+    let config = state.config();
+    let mut settings = config.level_settings();
+    settings.window = query.window.unwrap_or(settings.window);
+    settings.tolerance = query.tolerance.unwrap_or(settings.tolerance);
+    settings.min_touches = query.min_touches.unwrap_or(settings.min_touches);
+    let errors = settings.validate();
+    if !errors.is_empty() {
+        return Err(ApiError::bad_request(errors.join(", ")));
+    }
+    let ticker = ticker(&state, &symbol, &range, &config.charts.benchmark, 0.95, 0.02);
+    analytics(move || async move { Ok(serde_json::to_value(api::analytics::levels::detect(&ticker.get_chart().await?, &settings)?)?) }).await
+}
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    symbol: String,
+}
+
+async fn events(State(state): State<AppState>, query: Result<Query<EventsQuery>, QueryRejection>) -> ApiResult {
+    let Query(query) = query?;
+    let symbol = symbol(&query.symbol)?;
+    let sql_connection = state.database;
+    let events = super::blocking(move || {
+        json!({
+            "symbol": symbol,
+            "jumps": api::data::sql::events::jump_events(sql_connection.clone(), &symbol),
+            "drops": api::data::sql::events::drop_events(sql_connection.clone(), &symbol),
+            "recurring": api::data::sql::events::recurring_events(sql_connection.clone(), &symbol),
+            "patterns": api::data::sql::events::pattern_events(sql_connection, &symbol),
+        })
+    });
+    events.await.map(Json).ok_or_else(|| ApiError::internal("failed to read the events"))
+}
+
+#[derive(Debug, Deserialize)]
+struct AlertsQuery {
+    symbol: Option<String>,
+    state: Option<String>,
+    days: Option<i64>,
+    limit: Option<usize>,
+}
+
+async fn alerts(State(state): State<AppState>, query: Result<Query<AlertsQuery>, QueryRejection>) -> ApiResult {
+    let Query(query) = query?;
+    let state = query.state.as_deref().map(str::parse::<api::data::sql::AlertState>).transpose().map_err(ApiError::bad_request)?;
+    let since = chrono::Utc::now().timestamp() - query.days.unwrap_or(7).max(0) * 86400;
+    let alerts = super::blocking(move || {
+        json!(api::data::sql::alerts::alerts(state.database, query.symbol.as_deref(), state, since, query.limit.unwrap_or(100)))
+    });
+    alerts.await.map(Json).ok_or_else(|| ApiError::internal("failed to read the alerts"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::sync::{Arc, RwLock};
+
+    async fn start() -> String {
+        let (shutdown, _) = tokio::sync::broadcast::channel(1);
+        let database = api::data::sql::Database::open(":memory:").unwrap();
+        let state = AppState { config: Arc::new(RwLock::new(Arc::new(Config::default()))), database, shutdown };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { super::super::serve(listener, state).await });
+        format!("http://{address}")
+    }
+
+    #[test]
+    fn test_openapi() {
+        assert_eq!(axum_path("/api/indicators/{name}"), "/api/indicators/:name");
+        assert_eq!(axum_path("/api/ohlcv"), "/api/ohlcv");
+        let endpoints = endpoints();
+        let description = openapi(&endpoints);
+        for endpoint in &endpoints {
+            let operation = &description["paths"][endpoint.path][endpoint.method];
+            assert_eq!(operation["summary"], endpoint.summary);
+            assert_eq!(operation["parameters"].as_array().unwrap().len(), endpoint.params.len());
+            // path parameters are required and named in the path
+            for param in endpoint.params.iter().filter(|p| p.location == In::Path) {
+                assert!(param.required);
+                assert!(endpoint.path.contains(&format!("{{{}}}", param.name)));
+            }
+        }
+        let name = &description["paths"]["/api/indicators/{name}"]["get"]["parameters"][0];
+        assert_eq!(name["in"], "path");
+        assert_eq!(name["schema"]["enum"].as_array().unwrap().len(), INDICATORS.len());
+    }
+
+    #[test]
+    fn test_range() {
+        let day = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok();
+        let checked = range(None, day("2025-09-30"), Some("5m")).unwrap();
+        assert_eq!(checked.start, day("2025-07-02").unwrap());
+        assert!(matches!(checked.interval, Interval::FiveMinutes));
+        assert_eq!(range(day("2025-10-01"), day("2025-09-30"), None).unwrap_err().0, StatusCode::BAD_REQUEST);
+        assert_eq!(range(None, None, Some("7m")).unwrap_err().0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_api() {
+        let base = start().await;
+        let description: Value = reqwest::get(format!("{base}/api/openapi.json")).await.unwrap().json().await.unwrap();
+        assert_eq!(description["openapi"], "3.0.3");
+        assert!(description["paths"]["/api/ohlcv"]["get"].is_object());
+
+        let missing = reqwest::get(format!("{base}/api/ohlcv")).await.unwrap();
+        assert_eq!(missing.status(), 400);
+        let error: Value = missing.json().await.unwrap();
+        assert!(error["error"].as_str().unwrap().contains("symbol"));
+        let missing = reqwest::get(format!("{base}/api/events")).await.unwrap();
+        assert_eq!(missing.status(), 400);
+        assert!(missing.json::<Value>().await.unwrap()["error"].is_string());
+        let interval = reqwest::get(format!("{base}/api/ohlcv?symbol=AAPL&interval=7m")).await.unwrap();
+        assert_eq!(interval.status(), 400);
+        let error: Value = interval.json().await.unwrap();
+        assert!(error["error"].as_str().unwrap().starts_with("unknown interval 7m"));
+        let unknown = reqwest::get(format!("{base}/api/indicators/foo?symbol=AAPL")).await.unwrap();
+        assert_eq!(unknown.status(), 404);
+        let period = reqwest::get(format!("{base}/api/indicators/rsi?symbol=AAPL&period=0")).await.unwrap();
+        assert_eq!(period.status(), 400);
+        let window = reqwest::get(format!("{base}/api/levels?symbol=AAPL&window=0")).await.unwrap();
+        assert_eq!(window.status(), 400);
+        let portfolio = reqwest::get(format!("{base}/api/portfolio?symbols=AAPL")).await.unwrap();
+        assert_eq!(portfolio.status(), 400);
+        let objective = reqwest::get(format!("{base}/api/portfolio?symbols=AAPL,MSFT&objective=max_fun")).await.unwrap();
+        assert_eq!(objective.status(), 400);
+    }
+}
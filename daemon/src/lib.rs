@@ -1,6 +1,14 @@
 #![doc = include_str!("../Readme.md")]
 #![warn(clippy::all, clippy::pedantic, clippy::cargo, clippy::nursery)]
 
+mod config;
+mod failures;
+#[cfg(feature = "http")]
+mod http;
+mod live;
+mod metrics;
+mod notify;
+mod scheduler;
 mod server;
 mod utils;
 
@@ -14,10 +22,113 @@ use tracing::info;
 pub struct Options {
     #[structopt(flatten)]
     server: server::Options,
+
+    /// Report (`status`) or apply (`apply`) pending database migrations and exit
+    #[structopt(long, possible_values = &["status", "apply"])]
+    migrate: Option<String>,
+
+    /// Store the splits and dividends of a CSV file (`symbol,date,kind,value`) and exit
+    #[structopt(long, parse(from_os_str))]
+    import_corporate_actions: Option<std::path::PathBuf>,
+
+    /// Print the alerts of the last days and exit
+    #[structopt(long)]
+    list_alerts: Option<u32>,
+
+    /// Acknowledge an alert by id, it stops notifying, and exit
+    #[structopt(long)]
+    acknowledge_alert: Option<i64>,
+
+    /// Silence an alert by id for `--snooze-minutes` and exit
+    #[structopt(long)]
+    snooze_alert: Option<i64>,
+
+    /// Minutes a snoozed alert stays silent
+    #[structopt(long, default_value = "60")]
+    snooze_minutes: u32,
+}
+
+/// the configuration of the options, the commands use its database like the server
+fn load_config(options: &Options) -> EyreResult<config::Config> {
+    config::Config::load(&options.server).map_err(|e| eyre::eyre!("Invalid configuration {}", e))
+}
+
+/// report or apply the pending migrations of the database
+fn migrate_database(config: &config::Config, command: &str) -> EyreResult<()> {
+    let path = config.database_path();
+    if !path.exists() {
+        if command == "apply" {
+            // opening creates a new database with the current schema
+            config.open_database().map_err(|e| eyre::eyre!("Failed to create the database {}", e))?;
+            println!("created database {}", path.display());
+        } else {
+            println!("database {} does not exist yet", path.display());
+        }
+        return Ok(());
+    }
+    let mut connection = rusqlite::Connection::open(&path)?;
+    if command == "apply" {
+        let applied = api::data::sql::migrations::migrate(&mut connection)
+            .map_err(|e| eyre::eyre!("Failed to migrate {}: {}", path.display(), e))?;
+        println!("applied {} migrations to {}", applied.len(), path.display());
+    }
+    let status = api::data::sql::migrations::status(&connection)?;
+    println!("{}: {}", path.display(), status);
+    Ok(())
+}
+
+/// print, acknowledge or snooze the alerts
+fn manage_alerts(config: &config::Config, options: &Options) -> EyreResult<()> {
+    let sql_connection = config.open_database().map_err(|e| eyre::eyre!("Failed to open the database {}", e))?;
+    let now = chrono::Utc::now().timestamp();
+    if let Some(alert_id) = options.acknowledge_alert {
+        if api::data::sql::alerts::acknowledge_alert(sql_connection.clone(), alert_id, now) == 0 {
+            return Err(eyre::eyre!("There is no active alert {}", alert_id));
+        }
+        println!("acknowledged alert {alert_id}");
+    }
+    if let Some(alert_id) = options.snooze_alert {
+        let until = now + i64::from(options.snooze_minutes) * 60;
+        if api::data::sql::alerts::snooze_alert(sql_connection.clone(), alert_id, until) == 0 {
+            return Err(eyre::eyre!("There is no active alert {}", alert_id));
+        }
+        println!("snoozed alert {alert_id} for {} minutes", options.snooze_minutes);
+    }
+    if let Some(days) = options.list_alerts {
+        let since = now - i64::from(days) * 86400;
+        for alert in api::data::sql::alerts::alerts(sql_connection, None, None, since, 1000) {
+            let last_seen = chrono::DateTime::from_timestamp(alert.last_seen, 0).unwrap_or_default();
+            println!(
+                "{:>6} {:<12} {:<8} {:<10} {:>4}x {} {}",
+                alert.alert_id,
+                alert.state.as_str(),
+                alert.level,
+                alert.symbol,
+                alert.occurrences,
+                last_seen.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                alert.body
+            );
+        }
+    }
+    Ok(())
 }
 
 #[allow(clippy::missing_errors_doc, clippy::missing_panics_doc)]
 pub async fn main(options: Options, shutdown: broadcast::Sender<()>) -> EyreResult<()> {
+    if let Some(command) = &options.migrate {
+        return migrate_database(&load_config(&options)?, command);
+    }
+    if let Some(path) = &options.import_corporate_actions {
+        let sql_connection = load_config(&options)?.open_database().map_err(|e| eyre::eyre!("Failed to open the database {}", e))?;
+        let count = api::data::sql::corporate_actions::import_corporate_actions_csv(sql_connection, path)
+            .map_err(|e| eyre::eyre!("Failed to import {}: {}", path.display(), e))?;
+        println!("stored {} corporate actions of {}", count, path.display());
+        return Ok(());
+    }
+    if options.list_alerts.is_some() || options.acknowledge_alert.is_some() || options.snooze_alert.is_some() {
+        return manage_alerts(&load_config(&options)?, &options);
+    }
+
     // Start server
     let server = spawn_or_abort({
         let shutdown = shutdown.clone();
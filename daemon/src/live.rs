@@ -0,0 +1,127 @@
+//! Live updates of the active symbols
+//!
+//! The live job publishes the new minute bars, the indicators over the bars of the day and the
+//! sent notifications here, the web server streams them to its subscribers at `/stream`.
+//! Bars and indicators are only read while somebody is subscribed.
+
+use api::data::sql::TimeSeriesData;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+use crate::notify::Notification;
+
+/// updates buffered for slow subscribers
+const CAPACITY: usize = 1024;
+
+static CHANNEL: Lazy<broadcast::Sender<LiveEvent>> = Lazy::new(|| broadcast::channel(CAPACITY).0);
+
+/// time of the newest published bar of every symbol
+static PUBLISHED: Lazy<Mutex<HashMap<String, i64>>> = Lazy::new(Mutex::default);
+
+/// An update of a symbol
+#[derive(Clone, Debug, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    /// new minute bars, the newest bar is sent again while the provider updates it
+    Bar { symbol: String, bars: Vec<TimeSeriesData> },
+    /// the indicators at the newest bar, keyed like the indicator columns
+    Indicators { symbol: String, timestamp: i64, values: BTreeMap<String, f64> },
+    /// a notification sent about the symbol
+    Alert { notification: Notification },
+}
+
+impl LiveEvent {
+    pub fn symbol(&self) -> &str {
+        match self {
+            Self::Bar { symbol, .. } | Self::Indicators { symbol, .. } => symbol,
+            Self::Alert { notification } => &notification.symbol,
+        }
+    }
+
+    /// name of the server-sent event
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Bar { .. } => "bar",
+            Self::Indicators { .. } => "indicators",
+            Self::Alert { .. } => "alert",
+        }
+    }
+}
+
+/// receive the updates published from now on
+#[cfg_attr(not(feature = "http"), allow(dead_code))]
+pub fn subscribe() -> broadcast::Receiver<LiveEvent> {
+    CHANNEL.subscribe()
+}
+
+/// whether anybody receives the updates
+pub fn has_subscribers() -> bool {
+    CHANNEL.receiver_count() > 0
+}
+
+pub fn publish(event: LiveEvent) {
+    // fails only without subscribers
+    let _ = CHANNEL.send(event);
+}
+
+/// publish the bars of a symbol newer than the ones published before and the indicators over all
+/// of them, `bars` are the bars of the day ordered by time
+pub fn publish_bars(symbol: &str, bars: &[TimeSeriesData]) {
+    let Some(last) = bars.last() else {
+        return;
+    };
+    let previous = match PUBLISHED.lock() {
+        Ok(mut published) => published.insert(symbol.to_string(), last.datetime).unwrap_or(i64::MIN),
+        Err(error) => {
+            log::error!("Failed to lock the published bars! {}", error);
+            return;
+        }
+    };
+    let new: Vec<TimeSeriesData> = bars.iter().filter(|b| b.datetime >= previous).cloned().collect();
+    publish(LiveEvent::Bar { symbol: symbol.to_string(), bars: new });
+    publish(LiveEvent::Indicators {
+        symbol: symbol.to_string(),
+        timestamp: last.datetime,
+        values: api::analytics::technicals::live_indicators(bars),
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn bar(minute: i64, close: f64) -> TimeSeriesData {
+        TimeSeriesData { datetime: 1_700_000_000 + minute * 60, open: close, high: close, low: close, close, volume: 100.0 }
+    }
+
+    #[test]
+    fn test_publish_bars() {
+        let mut receiver = subscribe();
+        assert!(has_subscribers());
+        let mut bars: Vec<TimeSeriesData> = (0..3).map(|i| bar(i, 10.0)).collect();
+        publish_bars("LIVETEST", &bars);
+        bars.push(bar(3, 11.0));
+        publish_bars("LIVETEST", &bars);
+        publish_bars("LIVETEST", &[]);
+
+        let mut received = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            // other tests may publish at the same time
+            if event.symbol() == "LIVETEST" {
+                received.push(event);
+            }
+        }
+        assert_eq!(received.len(), 4);
+        assert_eq!(received[0], LiveEvent::Bar { symbol: "LIVETEST".to_string(), bars: bars[..3].to_vec() });
+        assert_eq!(received[1].name(), "indicators");
+        // the last bar of the first update is sent again with the new one
+        assert_eq!(received[2], LiveEvent::Bar { symbol: "LIVETEST".to_string(), bars: bars[2..].to_vec() });
+        let json = serde_json::to_value(&received[3]).unwrap();
+        assert_eq!(json["type"], "indicators");
+        assert_eq!(json["timestamp"], 1_700_000_180);
+    }
+}
@@ -0,0 +1,111 @@
+//! Metrics of the jobs, the alerts and the reports
+//!
+//! They go to the default `prometheus` registry next to the provider and database metrics of the
+//! `api` crate, the binary serves all of them on its metrics endpoint.
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    exponential_buckets, register_histogram, register_histogram_vec, register_int_counter_vec, register_int_gauge,
+    Histogram, HistogramVec, IntCounterVec, IntGauge,
+};
+use std::time::Duration;
+
+use crate::notify::Level;
+
+static JOB_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "job_duration_seconds",
+        "Duration of the scheduled job runs.",
+        &["job"],
+        exponential_buckets(0.1, 2.0, 16).unwrap()
+    )
+    .unwrap()
+});
+static JOB_RUNS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("job_runs_total", "Scheduled job runs by status.", &["job", "status"]).unwrap()
+});
+static ALERTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("alerts_total", "Notified alerts by source and level.", &["source", "level"]).unwrap()
+});
+static REPORT_SECONDS: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "report_generation_seconds",
+        "Duration of the generation of the screener, chart and portfolio reports.",
+        exponential_buckets(1.0, 2.0, 12).unwrap()
+    )
+    .unwrap()
+});
+static DATABASE_SIZE: Lazy<IntGauge> =
+    Lazy::new(|| register_int_gauge!("database_size_bytes", "Size of the database file.").unwrap());
+
+/// a finished run of a scheduled job, `status` as stored in `job_runs`
+pub fn job_finished(job: &str, status: api::data::sql::JobStatus, duration: Duration) {
+    JOB_RUNS.with_label_values(&[job, status.as_str()]).inc();
+    JOB_SECONDS.with_label_values(&[job]).observe(duration.as_secs_f64());
+}
+
+/// a run of a scheduled job skipped because the previous one was busy
+pub fn job_skipped(job: &str) {
+    JOB_RUNS.with_label_values(&[job, api::data::sql::JobStatus::Skipped.as_str()]).inc();
+}
+
+/// a notified alert, `source` is `events` for the price events and `rules` for the alert rules
+pub fn alert(source: &str, level: Level) {
+    ALERTS.with_label_values(&[source, level.as_str()]).inc();
+}
+
+pub fn reports_generated(duration: Duration) {
+    REPORT_SECONDS.observe(duration.as_secs_f64());
+}
+
+/// read the size of the database, updated after every job run
+pub fn update_database_size(sql_connection: api::data::sql::Database) {
+    if let Some(size) = api::data::sql::database_size(sql_connection) {
+        DATABASE_SIZE.set(i64::try_from(size).unwrap_or(i64::MAX));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn value(name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+        prometheus::gather()
+            .into_iter()
+            .filter(|family| family.get_name() == name)
+            .flat_map(|family| family.get_metric().to_vec())
+            .find(|metric| {
+                labels.iter().all(|(label, value)| {
+                    metric.get_label().iter().any(|l| l.get_name() == *label && l.get_value() == *value)
+                })
+            })
+            .map(|metric| {
+                if metric.has_histogram() {
+                    metric.get_histogram().get_sample_count() as f64
+                } else if metric.has_gauge() {
+                    metric.get_gauge().get_value()
+                } else {
+                    metric.get_counter().get_value()
+                }
+            })
+    }
+
+    #[test]
+    fn test_metrics() {
+        job_finished("metrics-test", api::data::sql::JobStatus::Failed, Duration::from_millis(250));
+        job_finished("metrics-test", api::data::sql::JobStatus::Ok, Duration::from_millis(50));
+        job_skipped("metrics-test");
+        assert_eq!(value("job_runs_total", &[("job", "metrics-test"), ("status", "failed")]), Some(1.0));
+        assert_eq!(value("job_runs_total", &[("job", "metrics-test"), ("status", "skipped")]), Some(1.0));
+        assert_eq!(value("job_duration_seconds", &[("job", "metrics-test")]), Some(2.0));
+
+        let sql_connection = api::data::sql::Database::open(":memory:").unwrap();
+        update_database_size(sql_connection);
+        assert!(value("database_size_bytes", &[]).is_some());
+
+        // the api crate records into the same registry
+        api::metrics::rows_inserted("metrics_test", 3);
+        assert_eq!(value("db_rows_inserted_total", &[("table", "metrics_test")]), Some(3.0));
+    }
+}
@@ -0,0 +1,29 @@
+//! Popups on the desktop of the user running the daemon
+
+use super::{Level, Notification, Notifier, NotifyError, NotifyFuture};
+
+/// Shows notifications with the desktop notification service
+pub struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+    fn kind(&self) -> &'static str {
+        "desktop"
+    }
+
+    fn send<'a>(&'a self, notification: &'a Notification) -> NotifyFuture<'a> {
+        Box::pin(async move {
+            let urgency = match notification.level {
+                Level::Info => notify_rust::Urgency::Low,
+                Level::Warning => notify_rust::Urgency::Normal,
+                Level::Critical => notify_rust::Urgency::Critical,
+            };
+            let mut popup = notify_rust::Notification::new();
+            popup.summary(&notification.title).body(&notification.text()).icon("alarm");
+            #[cfg(all(unix, not(target_os = "macos")))]
+            popup.urgency(urgency);
+            #[cfg(not(all(unix, not(target_os = "macos"))))]
+            let _ = urgency;
+            popup.show().map(|_| ()).map_err(|e| NotifyError::Desktop(e.to_string()))
+        })
+    }
+}
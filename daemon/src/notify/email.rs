@@ -0,0 +1,78 @@
+//! Email via SMTP
+
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Deserialize;
+
+use super::{Notification, Notifier, NotifyError, NotifyFuture};
+
+/// Encryption of the SMTP connection
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// plain text, only for local relays
+    None,
+    /// upgrade the connection, port 587 by default
+    #[default]
+    StartTls,
+    /// TLS from the start, port 465 by default
+    Tls,
+}
+
+/// Mails the notification to the recipients
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl EmailNotifier {
+    /// # Errors
+    /// if an address is invalid or there are no recipients
+    pub fn new(
+        host: &str,
+        port: Option<u16>,
+        security: SmtpSecurity,
+        credentials: Option<(String, String)>,
+        from: &str,
+        to: &[String],
+    ) -> Result<Self, NotifyError> {
+        let address = |a: &str| a.parse::<Mailbox>().map_err(|e| NotifyError::Config(format!("address {a}: {e}")));
+        if to.is_empty() {
+            return Err(NotifyError::Config("smtp needs at least one recipient".to_string()));
+        }
+        let smtp_error = |e: lettre::transport::smtp::Error| NotifyError::Smtp(e.to_string());
+        let mut builder = match security {
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host).port(port.unwrap_or(25)),
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host).map_err(smtp_error)?.port(port.unwrap_or(587)),
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host).map_err(smtp_error)?.port(port.unwrap_or(465)),
+        };
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+        Ok(Self {
+            transport: builder.build(),
+            from: address(from)?,
+            to: to.iter().map(|a| address(a)).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn kind(&self) -> &'static str {
+        "smtp"
+    }
+
+    fn send<'a>(&'a self, notification: &'a Notification) -> NotifyFuture<'a> {
+        Box::pin(async move {
+            let mut message = Message::builder().from(self.from.clone()).subject(&notification.title);
+            for to in &self.to {
+                message = message.to(to.clone());
+            }
+            let message = message.body(notification.text()).map_err(|e| NotifyError::Smtp(e.to_string()))?;
+            self.transport.send(message).await.map_err(|e| NotifyError::Smtp(e.to_string()))?;
+            Ok(())
+        })
+    }
+}
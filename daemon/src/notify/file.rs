@@ -0,0 +1,50 @@
+//! Notifications written as lines to a file or stdout
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use super::{Notification, Notifier, NotifyFuture};
+
+/// Appends one line per notification, prints to stdout without a path
+pub struct FileNotifier {
+    path: Option<PathBuf>,
+}
+
+impl FileNotifier {
+    pub const fn new(path: Option<PathBuf>) -> Self {
+        Self { path }
+    }
+}
+
+/// `2025-09-01T12:00:00+00:00 warning AAPL: title - body`
+fn line(notification: &Notification) -> String {
+    let time = chrono::DateTime::from_timestamp(notification.timestamp, 0).unwrap_or_default();
+    format!(
+        "{} {} {}: {} - {}\n",
+        time.to_rfc3339(),
+        notification.level.as_str(),
+        notification.symbol,
+        notification.title,
+        notification.text().replace('\n', " ")
+    )
+}
+
+impl Notifier for FileNotifier {
+    fn kind(&self) -> &'static str {
+        "file"
+    }
+
+    fn send<'a>(&'a self, notification: &'a Notification) -> NotifyFuture<'a> {
+        Box::pin(async move {
+            let text = line(notification);
+            match &self.path {
+                Some(path) => {
+                    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+                    file.write_all(text.as_bytes())?;
+                }
+                None => std::io::stdout().write_all(text.as_bytes())?,
+            }
+            Ok(())
+        })
+    }
+}
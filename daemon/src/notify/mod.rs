@@ -0,0 +1,489 @@
+//! Delivery of alerts to the user
+//!
+//! Every channel implements `Notifier`. The channels are configured by name in the
+//! `[[notifiers]]` sections of the configuration and selected by the alerts, `Notifiers`
+//! delivers a notification to the selected channels with retries and records every delivery
+//! in the `notifications` table.
+
+mod desktop;
+mod email;
+mod file;
+mod push;
+mod webhook;
+
+pub use desktop::DesktopNotifier;
+pub use email::{EmailNotifier, SmtpSecurity};
+pub use file::FileNotifier;
+pub use push::{GotifyNotifier, NtfyNotifier};
+pub use webhook::WebhookNotifier;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use api::data::sql::notifications::{insert_notification, NotificationRecord};
+use api::data::sql::Database;
+
+/// Severity of a notification
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+impl Level {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+impl std::str::FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "info" => Ok(Self::Info),
+            "warning" => Ok(Self::Warning),
+            "critical" => Ok(Self::Critical),
+            _ => Err(format!("Unknown level {s}")),
+        }
+    }
+}
+
+/// A message to the user
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+    /// the symbol the notification is about, empty for general messages
+    pub symbol: String,
+    pub level: Level,
+    /// seconds since 1.1.1970 the notification was raised
+    pub timestamp: i64,
+    /// page of the daemon's web server with the details
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+}
+
+impl Notification {
+    pub fn new(symbol: &str, level: Level, title: &str, body: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            body: body.to_string(),
+            symbol: symbol.to_string(),
+            level,
+            timestamp: chrono::Utc::now().timestamp(),
+            link: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_link(mut self, link: Option<String>) -> Self {
+        self.link = link;
+        self
+    }
+
+    /// the body followed by the link, for channels showing plain text
+    pub fn text(&self) -> String {
+        match &self.link {
+            Some(link) => format!("{}\n{}", self.body, link),
+            None => self.body.clone(),
+        }
+    }
+}
+
+/// Failure of a delivery
+#[derive(Debug, thiserror::Error)]
+pub enum NotifyError {
+    #[error("invalid notifier configuration: {0}")]
+    Config(String),
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("server answered with status {0}")]
+    Status(u16),
+    #[error("SMTP delivery failed: {0}")]
+    Smtp(String),
+    #[error("desktop notification failed: {0}")]
+    Desktop(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Future of one delivery attempt
+pub type NotifyFuture<'a> = Pin<Box<dyn Future<Output = Result<(), NotifyError>> + Send + 'a>>;
+
+/// A channel delivering notifications
+pub trait Notifier: Send + Sync {
+    /// kind of the channel used in log messages
+    fn kind(&self) -> &'static str;
+
+    /// deliver one notification
+    fn send<'a>(&'a self, notification: &'a Notification) -> NotifyFuture<'a>;
+}
+
+/// A named channel of the configuration, selected by alerts with its name
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct NotifierConfig {
+    pub name: String,
+    #[serde(flatten)]
+    pub channel: ChannelConfig,
+}
+
+/// The settings of the channel kinds
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ChannelConfig {
+    /// popup on the desktop of the user running the daemon
+    Desktop,
+    /// email via SMTP
+    Smtp {
+        host: String,
+        port: Option<u16>,
+        /// `none`, `starttls` or `tls`
+        #[serde(default)]
+        security: SmtpSecurity,
+        username: Option<String>,
+        password: Option<String>,
+        from: String,
+        to: Vec<String>,
+    },
+    /// JSON POST of the notification
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    /// ntfy.sh or a self-hosted ntfy server
+    Ntfy {
+        url: String,
+        topic: String,
+        token: Option<String>,
+    },
+    /// Gotify server
+    Gotify { url: String, token: String },
+    /// line per notification appended to a file, stdout if no path is given
+    File { path: Option<PathBuf> },
+}
+
+impl NotifierConfig {
+    /// the channel of the configuration
+    ///
+    /// # Errors
+    /// if the configuration is incomplete
+    pub fn build(&self) -> Result<Arc<dyn Notifier>, NotifyError> {
+        let notifier: Arc<dyn Notifier> = match &self.channel {
+            ChannelConfig::Desktop => Arc::new(DesktopNotifier),
+            ChannelConfig::Smtp { host, port, security, username, password, from, to } => Arc::new(EmailNotifier::new(
+                host,
+                *port,
+                *security,
+                username.clone().zip(password.clone()),
+                from,
+                to,
+            )?),
+            ChannelConfig::Webhook { url, headers } => Arc::new(WebhookNotifier::new(url, headers)?),
+            ChannelConfig::Ntfy { url, topic, token } => Arc::new(NtfyNotifier::new(url, topic, token.clone())?),
+            ChannelConfig::Gotify { url, token } => Arc::new(GotifyNotifier::new(url, token)?),
+            ChannelConfig::File { path } => Arc::new(FileNotifier::new(path.clone())),
+        };
+        Ok(notifier)
+    }
+}
+
+/// The configured channels with the retry policy
+pub struct Notifiers {
+    channels: HashMap<String, Arc<dyn Notifier>>,
+    /// attempts after the first failed one
+    retries: u32,
+    /// wait before the first retry, doubled for every further one
+    retry_delay: Duration,
+}
+
+/// result of the delivery to one channel
+#[derive(Debug)]
+pub struct Delivery {
+    pub channel: String,
+    pub attempts: u32,
+    pub result: Result<(), String>,
+}
+
+impl Notifiers {
+    pub fn new(retries: u32, retry_delay: Duration) -> Self {
+        Self { channels: HashMap::new(), retries, retry_delay }
+    }
+
+    /// the channels of the configuration, channels with invalid settings are left out
+    pub fn from_config(configs: &[NotifierConfig], retries: u32, retry_delay: Duration) -> Self {
+        let mut notifiers = Self::new(retries, retry_delay);
+        for config in configs {
+            match config.build() {
+                Ok(notifier) => notifiers.register(&config.name, notifier),
+                Err(error) => log::error!("Failed to set up notifier {}: {}", config.name, error),
+            }
+        }
+        notifiers
+    }
+
+    pub fn register(&mut self, name: &str, notifier: Arc<dyn Notifier>) {
+        self.channels.insert(name.to_string(), notifier);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.channels.contains_key(name)
+    }
+
+    /// deliver to one channel, retrying with growing delays
+    async fn deliver(&self, name: &str, notifier: &dyn Notifier, notification: &Notification) -> Delivery {
+        let mut delay = self.retry_delay;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match notifier.send(notification).await {
+                Ok(()) => return Delivery { channel: name.to_string(), attempts, result: Ok(()) },
+                Err(error) if attempts > self.retries => {
+                    log::error!("Failed to deliver {} notification via {} after {} attempts: {}", notifier.kind(), name, attempts, error);
+                    return Delivery { channel: name.to_string(), attempts, result: Err(error.to_string()) };
+                }
+                Err(error) => {
+                    log::warn!("Failed to deliver notification via {}, retrying in {:?}: {}", name, delay, error);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+            }
+        }
+    }
+
+    /// deliver the notification to the named channels and record the outcome in the database
+    pub async fn notify(&self, sql_connection: &Database, channels: &[String], notification: &Notification) -> Vec<Delivery> {
+        let mut deliveries = Vec::new();
+        for name in channels {
+            let delivery = match self.channels.get(name) {
+                Some(notifier) => self.deliver(name, notifier.as_ref(), notification).await,
+                None => Delivery { channel: name.clone(), attempts: 0, result: Err(format!("unknown notifier {name}")) },
+            };
+            let record = NotificationRecord {
+                channel: delivery.channel.clone(),
+                symbol: notification.symbol.clone(),
+                level: notification.level.as_str().to_string(),
+                title: notification.title.clone(),
+                body: notification.body.clone(),
+                created_at: notification.timestamp,
+                sent_at: delivery.result.is_ok().then(|| chrono::Utc::now().timestamp()),
+                attempts: delivery.attempts,
+                status: if delivery.result.is_ok() { "sent" } else { "failed" }.to_string(),
+                error: delivery.result.clone().err().unwrap_or_default(),
+                ..Default::default()
+            };
+            insert_notification(sql_connection.clone(), &record);
+            deliveries.push(delivery);
+        }
+        deliveries
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// fails the first `failures` attempts
+    struct Flaky {
+        failures: u32,
+        attempts: AtomicU32,
+    }
+
+    impl Notifier for Flaky {
+        fn kind(&self) -> &'static str {
+            "flaky"
+        }
+
+        fn send<'a>(&'a self, _notification: &'a Notification) -> NotifyFuture<'a> {
+            Box::pin(async move {
+                if self.attempts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                    Err(NotifyError::Status(503))
+                } else {
+                    Ok(())
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_and_records() {
+        let sql_connection = Database::open(":memory:").unwrap();
+        let mut notifiers = Notifiers::new(2, Duration::from_millis(1));
+        notifiers.register("recovers", Arc::new(Flaky { failures: 2, attempts: AtomicU32::new(0) }));
+        notifiers.register("broken", Arc::new(Flaky { failures: 10, attempts: AtomicU32::new(0) }));
+        let notification = Notification::new("AAPL", Level::Warning, "AAPL", "dropped by 3%");
+        let channels = vec!["recovers".to_string(), "broken".to_string(), "missing".to_string()];
+        let deliveries = notifiers.notify(&sql_connection, &channels, &notification).await;
+        assert_eq!(deliveries.iter().map(|d| (d.attempts, d.result.is_ok())).collect::<Vec<_>>(), vec![(3, true), (3, false), (0, false)]);
+
+        let records = api::data::sql::notifications::notifications(sql_connection, 0, 10);
+        assert_eq!(records.len(), 3);
+        let sent = records.iter().find(|r| r.channel == "recovers").unwrap();
+        assert_eq!(sent.status, "sent");
+        assert_eq!(sent.symbol, "AAPL");
+        assert!(sent.sent_at.is_some());
+        let failed = records.iter().find(|r| r.channel == "broken").unwrap();
+        assert_eq!(failed.status, "failed");
+        assert!(failed.error.contains("503"));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_and_push() {
+        // a stand-in server answering every request with 200 and handing out the request
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, mut requests) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buffer = vec![0; 8192];
+                let mut request = String::new();
+                loop {
+                    let n = stream.read(&mut buffer).await.unwrap();
+                    request.push_str(&String::from_utf8_lossy(&buffer[..n]));
+                    let complete = request.split_once("\r\n\r\n").is_some_and(|(head, body)| {
+                        let length = head
+                            .lines()
+                            .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap_or(0)))
+                            .unwrap_or(0);
+                        body.len() >= length
+                    });
+                    if n == 0 || complete {
+                        break;
+                    }
+                }
+                stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n").await.unwrap();
+                sender.send(request).unwrap();
+            }
+        });
+        let notification = Notification::new("MSFT", Level::Critical, "MSFT jumped", "MSFT increased by 5%");
+
+        let headers = HashMap::from([("X-Token".to_string(), "secret".to_string())]);
+        let webhook = WebhookNotifier::new(&format!("http://{address}/hook"), &headers).unwrap();
+        webhook.send(&notification).await.unwrap();
+        let request = requests.recv().await.unwrap();
+        assert!(request.starts_with("POST /hook"));
+        assert!(request.to_lowercase().contains("x-token: secret"));
+        let body: serde_json::Value = serde_json::from_str(request.split_once("\r\n\r\n").unwrap().1).unwrap();
+        assert_eq!(body["symbol"], "MSFT");
+        assert_eq!(body["level"], "critical");
+
+        let ntfy = NtfyNotifier::new(&format!("http://{address}"), "stocks", Some("tk".to_string())).unwrap();
+        ntfy.send(&notification).await.unwrap();
+        let request = requests.recv().await.unwrap();
+        assert!(request.starts_with("POST /stocks"));
+        assert!(request.to_lowercase().contains("authorization: bearer tk"));
+        assert!(request.ends_with("MSFT increased by 5%"));
+
+        let gotify = GotifyNotifier::new(&format!("http://{address}"), "a&b=c").unwrap();
+        gotify.send(&notification).await.unwrap();
+        let request = requests.recv().await.unwrap();
+        assert!(request.starts_with("POST /message HTTP"));
+        assert!(request.to_lowercase().contains("x-gotify-key: a&b=c"));
+    }
+
+    #[tokio::test]
+    async fn test_smtp() {
+        // a stand-in SMTP server accepting one mail without authentication
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut reader = BufReader::new(reader);
+            writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+            let mut mail = String::new();
+            let mut data = false;
+            let mut line = String::new();
+            while reader.read_line(&mut line).await.unwrap() > 0 {
+                if data {
+                    if line == ".\r\n" {
+                        data = false;
+                        writer.write_all(b"250 queued\r\n").await.unwrap();
+                    } else {
+                        mail.push_str(&line);
+                    }
+                } else {
+                    let command = line.to_uppercase();
+                    if command.starts_with("EHLO") {
+                        writer.write_all(b"250-localhost\r\n250 8BITMIME\r\n").await.unwrap();
+                    } else if command.starts_with("DATA") {
+                        data = true;
+                        writer.write_all(b"354 go ahead\r\n").await.unwrap();
+                    } else if command.starts_with("QUIT") {
+                        writer.write_all(b"221 bye\r\n").await.unwrap();
+                        break;
+                    } else {
+                        writer.write_all(b"250 ok\r\n").await.unwrap();
+                    }
+                }
+                line.clear();
+            }
+            mail
+        });
+        let email = EmailNotifier::new("127.0.0.1", Some(port), SmtpSecurity::None, None, "daemon@localhost", &["user@localhost".to_string()]).unwrap();
+        let notification = Notification::new("SAP", Level::Warning, "SAP dropped", "SAP dropped by 4%");
+        email.send(&notification).await.unwrap();
+        let mail = server.await.unwrap();
+        assert!(mail.contains("Subject: SAP dropped"));
+        assert!(mail.contains("SAP dropped by 4%"));
+    }
+
+    #[tokio::test]
+    async fn test_file_notifier() {
+        let path = std::env::temp_dir().join(format!("notifications-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let file = FileNotifier::new(Some(path.clone()));
+        file.send(&Notification::new("SAP", Level::Info, "first", "one")).await.unwrap();
+        file.send(&Notification::new("SAP", Level::Info, "second", "two")).await.unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(content.lines().nth(1).unwrap().contains("second"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_notifier_config() {
+        #[derive(Deserialize)]
+        struct Channels {
+            notifiers: Vec<NotifierConfig>,
+        }
+        let channels: Channels = toml::from_str(
+            r#"
+            [[notifiers]]
+            name = "phone"
+            kind = "ntfy"
+            url = "https://ntfy.sh"
+            topic = "stocks"
+
+            [[notifiers]]
+            name = "mail"
+            kind = "smtp"
+            host = "mail.example.com"
+            security = "starttls"
+            from = "daemon@example.com"
+            to = ["me@example.com"]
+        "#,
+        )
+        .unwrap();
+        assert_eq!(channels.notifiers.len(), 2);
+        assert!(matches!(channels.notifiers[1].channel, ChannelConfig::Smtp { security: SmtpSecurity::StartTls, .. }));
+        assert!(channels.notifiers.iter().all(|n| n.build().is_ok()));
+    }
+}
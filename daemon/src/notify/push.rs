@@ -0,0 +1,109 @@
+//! Push notifications via ntfy or Gotify servers
+
+use std::time::Duration;
+
+use super::{Level, Notification, Notifier, NotifyError, NotifyFuture};
+
+fn client() -> Result<reqwest::Client, NotifyError> {
+    Ok(reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?)
+}
+
+fn check(response: &reqwest::Response) -> Result<(), NotifyError> {
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(NotifyError::Status(response.status().as_u16()))
+    }
+}
+
+/// Publishes to a topic of a ntfy server
+pub struct NtfyNotifier {
+    url: String,
+    token: Option<String>,
+    client: reqwest::Client,
+}
+
+impl NtfyNotifier {
+    /// # Errors
+    /// if the HTTP client can not be created
+    pub fn new(server: &str, topic: &str, token: Option<String>) -> Result<Self, NotifyError> {
+        if topic.is_empty() {
+            return Err(NotifyError::Config("ntfy needs a topic".to_string()));
+        }
+        Ok(Self { url: format!("{}/{}", server.trim_end_matches('/'), topic), token, client: client()? })
+    }
+}
+
+impl Notifier for NtfyNotifier {
+    fn kind(&self) -> &'static str {
+        "ntfy"
+    }
+
+    fn send<'a>(&'a self, notification: &'a Notification) -> NotifyFuture<'a> {
+        Box::pin(async move {
+            let priority = match notification.level {
+                Level::Info => "low",
+                Level::Warning => "default",
+                Level::Critical => "urgent",
+            };
+            let mut request = self
+                .client
+                .post(&self.url)
+                .header("Title", &notification.title)
+                .header("Priority", priority)
+                .header("Tags", notification.level.as_str())
+                .body(notification.body.clone());
+            if let Some(link) = &notification.link {
+                request = request.header("Click", link);
+            }
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+            check(&request.send().await?)
+        })
+    }
+}
+
+/// Sends messages to a Gotify application
+pub struct GotifyNotifier {
+    url: String,
+    /// application token, sent in the `X-Gotify-Key` header and kept out of the URL
+    token: String,
+    client: reqwest::Client,
+}
+
+impl GotifyNotifier {
+    /// # Errors
+    /// if the HTTP client can not be created
+    pub fn new(server: &str, token: &str) -> Result<Self, NotifyError> {
+        if token.is_empty() {
+            return Err(NotifyError::Config("gotify needs an application token".to_string()));
+        }
+        Ok(Self { url: format!("{}/message", server.trim_end_matches('/')), token: token.to_string(), client: client()? })
+    }
+}
+
+impl Notifier for GotifyNotifier {
+    fn kind(&self) -> &'static str {
+        "gotify"
+    }
+
+    fn send<'a>(&'a self, notification: &'a Notification) -> NotifyFuture<'a> {
+        Box::pin(async move {
+            let priority = match notification.level {
+                Level::Info => 2,
+                Level::Warning => 5,
+                Level::Critical => 8,
+            };
+            let mut message = serde_json::json!({
+                "title": notification.title,
+                "message": notification.body,
+                "priority": priority,
+            });
+            if let Some(link) = &notification.link {
+                message["extras"] = serde_json::json!({ "client::notification": { "click": { "url": link } } });
+            }
+            check(&self.client.post(&self.url).header("X-Gotify-Key", &self.token).json(&message).send().await?)
+        })
+    }
+}
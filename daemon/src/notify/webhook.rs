@@ -0,0 +1,44 @@
+//! JSON POST of notifications to a URL
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::{Notification, Notifier, NotifyError, NotifyFuture};
+
+/// Posts the notification as JSON object with title, body, symbol, level and timestamp
+pub struct WebhookNotifier {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl WebhookNotifier {
+    /// # Errors
+    /// if a header name or value is invalid
+    pub fn new(url: &str, headers: &HashMap<String, String>) -> Result<Self, NotifyError> {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| NotifyError::Config(format!("header {name}: {e}")))?;
+            let value = HeaderValue::from_str(value).map_err(|e| NotifyError::Config(format!("header {name}: {e}")))?;
+            map.insert(name, value);
+        }
+        let client = reqwest::Client::builder().default_headers(map).timeout(Duration::from_secs(30)).build()?;
+        Ok(Self { url: url.to_string(), client })
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn kind(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn send<'a>(&'a self, notification: &'a Notification) -> NotifyFuture<'a> {
+        Box::pin(async move {
+            let response = self.client.post(&self.url).json(notification).send().await?;
+            if !response.status().is_success() {
+                return Err(NotifyError::Status(response.status().as_u16()));
+            }
+            Ok(())
+        })
+    }
+}
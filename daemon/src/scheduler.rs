@@ -0,0 +1,379 @@
+//! Cron-style scheduler of the daemon jobs
+//!
+//! Every job has a name, a cron expression in local time and a lock, a run that is due while
+//! the previous one is still busy is skipped. Runs are recorded in the `job_runs` table.
+//! Jobs marked for catch-up run once at startup when their schedule was due since the last
+//! recorded run, so a nightly update missed during downtime is done after the restart.
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, TimeZone, Timelike};
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use api::data::sql::{job_runs, Database, JobStatus};
+
+/// A cron expression `minute hour day-of-month month day-of-week`.
+///
+/// Fields take `*`, numbers, ranges `1-5`, lists `1,15` and steps `*/15` or `8-18/2`.
+/// Day of week 0 and 7 are Sunday. If both day fields are restricted a day matching either
+/// is due, as in cron. `@hourly`, `@daily`, `@weekly` and `@monthly` are shortcuts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// bit mask of the values of one field
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut mask = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| format!("invalid step in {part}"))?;
+                if step == 0 {
+                    return Err(format!("invalid step in {part}"));
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (first, last) = if range == "*" {
+            (min, max)
+        } else if let Some((first, last)) = range.split_once('-') {
+            let first = first.parse().map_err(|_| format!("invalid value in {part}"))?;
+            let last = last.parse().map_err(|_| format!("invalid value in {part}"))?;
+            (first, last)
+        } else {
+            let value = range.parse().map_err(|_| format!("invalid value in {part}"))?;
+            // `5/10` starts at 5 and runs to the end of the field
+            (value, if step > 1 { max } else { value })
+        };
+        if first < min || last > max || first > last {
+            return Err(format!("{part} is out of range {min}-{max}"));
+        }
+        for value in (first..=last).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("cron expression {s} needs 5 fields: minute hour day month weekday"));
+        }
+        let mut weekdays = parse_field(fields[4], 0, 7).map_err(|e| format!("{s}: {e}"))?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            expression: s.trim().to_string(),
+            minutes: parse_field(fields[0], 0, 59).map_err(|e| format!("{s}: {e}"))?,
+            hours: parse_field(fields[1], 0, 23).map_err(|e| format!("{s}: {e}"))?,
+            days: parse_field(fields[2], 1, 31).map_err(|e| format!("{s}: {e}"))?,
+            months: parse_field(fields[3], 1, 12).map_err(|e| format!("{s}: {e}"))?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+}
+
+impl std::fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl CronSchedule {
+    fn matches_day(&self, date: chrono::NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        }
+    }
+
+    /// whether the schedule is due in the minute of `time`
+    pub fn matches(&self, time: NaiveDateTime) -> bool {
+        self.matches_day(time.date()) && self.hours & (1 << time.hour()) != 0 && self.minutes & (1 << time.minute()) != 0
+    }
+
+    /// the latest minute the schedule was due in `(after, until]`
+    pub fn latest_due(&self, after: NaiveDateTime, until: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut date = until.date();
+        while date >= after.date() {
+            if self.matches_day(date) {
+                for hour in (0..24).rev().filter(|h| self.hours & (1 << h) != 0) {
+                    for minute in (0..60).rev().filter(|m| self.minutes & (1 << m) != 0) {
+                        let time = date.and_hms_opt(hour, minute, 0)?;
+                        if time <= after {
+                            return None;
+                        }
+                        if time <= until {
+                            return Some(time);
+                        }
+                    }
+                }
+            }
+            date = date.pred_opt()?;
+        }
+        None
+    }
+}
+
+/// Result of a job run, the message is stored with the run
+pub type JobResult = Result<String, String>;
+
+/// Future of one job run
+pub type JobFuture = Pin<Box<dyn Future<Output = JobResult> + Send>>;
+
+/// A named job running on a schedule
+pub struct Job {
+    pub name: String,
+    pub schedule: CronSchedule,
+    /// run once at startup if the schedule was due since the last recorded run
+    pub catch_up: bool,
+    run: Arc<dyn Fn(DateTime<Local>) -> JobFuture + Send + Sync>,
+    lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Job {
+    /// a job calling `run` with the scheduled time
+    pub fn new<F>(name: &str, schedule: CronSchedule, catch_up: bool, run: F) -> Self
+    where
+        F: Fn(DateTime<Local>) -> JobFuture + Send + Sync + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            schedule,
+            catch_up,
+            run: Arc::new(run),
+            lock: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+}
+
+/// Runs the due jobs on every tick
+pub struct Scheduler {
+    sql_connection: Database,
+    jobs: Vec<Job>,
+    /// the minute of the last tick, jobs due after it run on the next one
+    last_tick: Option<NaiveDateTime>,
+    /// how far back missed runs are caught up
+    catch_up_limit: Duration,
+}
+
+/// the local time of a minute, the earlier one if it is ambiguous
+fn local(time: NaiveDateTime) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&time).earliest()
+}
+
+/// `time` without seconds
+fn minute_of(time: DateTime<Local>) -> NaiveDateTime {
+    let naive = time.naive_local();
+    naive.with_second(0).and_then(|t| t.with_nanosecond(0)).unwrap_or(naive)
+}
+
+impl Scheduler {
+    pub fn new(sql_connection: Database, catch_up_days: u32) -> Self {
+        Self { sql_connection, jobs: Vec::new(), last_tick: None, catch_up_limit: Duration::days(i64::from(catch_up_days)) }
+    }
+
+    pub fn add(&mut self, job: Job) {
+        self.jobs.push(job);
+    }
+
+    /// replace the schedule of a job, e.g. after the configuration was reloaded
+    pub fn reschedule(&mut self, name: &str, schedule: CronSchedule) {
+        if let Some(job) = self.jobs.iter_mut().find(|j| j.name == name) {
+            if job.schedule != schedule {
+                log::info!("Job {} now runs at {}", name, schedule);
+                job.schedule = schedule;
+            }
+        }
+    }
+
+    /// start the jobs due since the last tick, the first tick catches up missed runs
+    pub fn tick(&mut self, now: DateTime<Local>) {
+        let minute = minute_of(now);
+        let last_tick = self.last_tick.replace(minute);
+        if last_tick.is_none() {
+            let interrupted = job_runs::interrupt_running_jobs(self.sql_connection.clone());
+            if interrupted > 0 {
+                log::warn!("{} job runs were interrupted by the last shutdown", interrupted);
+            }
+        }
+        for job in self.jobs.iter() {
+            let after = match last_tick {
+                Some(t) => t,
+                None if job.catch_up => {
+                    let limit = minute - self.catch_up_limit;
+                    match job_runs::last_scheduled_run(self.sql_connection.clone(), &job.name)
+                        .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                    {
+                        Some(last) => last.with_timezone(&Local).naive_local().max(limit),
+                        // never ran before, nothing was missed
+                        None => minute - Duration::minutes(1),
+                    }
+                }
+                None => minute - Duration::minutes(1),
+            };
+            let Some(due) = job.schedule.latest_due(after, minute).and_then(local) else {
+                continue;
+            };
+            if due.naive_local() < minute {
+                log::info!("Catching up job {} due at {}", job.name, due);
+            }
+            self.launch(job, due);
+        }
+    }
+
+    /// run a job unless its previous run is still busy
+    fn launch(&self, job: &Job, scheduled: DateTime<Local>) {
+        let Ok(guard) = job.lock.clone().try_lock_owned() else {
+            log::warn!("Job {} due at {} skipped, the previous run is still busy", job.name, scheduled);
+            job_runs::skip_job_run(self.sql_connection.clone(), &job.name, scheduled.timestamp(), "previous run still busy");
+            crate::metrics::job_skipped(&job.name);
+            return;
+        };
+        let sql_connection = self.sql_connection.clone();
+        let name = job.name.clone();
+        let run = (job.run)(scheduled);
+        tokio::spawn(async move {
+            let started_at = chrono::Utc::now().timestamp();
+            let timer = std::time::Instant::now();
+            let run_id = job_runs::start_job_run(sql_connection.clone(), &name, scheduled.timestamp(), started_at);
+            let (status, message) = match run.await {
+                Ok(message) => (JobStatus::Ok, message),
+                Err(message) => {
+                    log::error!("Job {} failed: {}", name, message);
+                    (JobStatus::Failed, message)
+                }
+            };
+            crate::metrics::job_finished(&name, status, timer.elapsed());
+            if let Some(run_id) = run_id {
+                job_runs::finish_job_run(sql_connection.clone(), run_id, status, &message);
+            }
+            crate::metrics::update_database_size(sql_connection);
+            drop(guard);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::NaiveDate;
+    use pretty_assertions::assert_eq;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // 2025-09-01 is a Monday
+        NaiveDate::from_ymd_opt(2025, 9, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_cron_schedule() {
+        let nightly: CronSchedule = "0 23 * * *".parse().unwrap();
+        assert!(nightly.matches(at(1, 23, 0)));
+        assert!(!nightly.matches(at(1, 23, 1)));
+        assert_eq!(nightly.latest_due(at(1, 22, 0), at(1, 23, 30)), Some(at(1, 23, 0)));
+        assert_eq!(nightly.latest_due(at(1, 23, 0), at(2, 22, 59)), None);
+        // three days of downtime run the job only for the latest missed night
+        assert_eq!(nightly.latest_due(at(1, 23, 0), at(4, 8, 0)), Some(at(3, 23, 0)));
+
+        let weekdays: CronSchedule = "*/15 8-17 * * 1-5".parse().unwrap();
+        assert!(weekdays.matches(at(5, 8, 45)));
+        assert!(!weekdays.matches(at(6, 8, 45)));
+        assert!(!weekdays.matches(at(5, 18, 0)));
+        assert_eq!(weekdays.latest_due(at(5, 17, 50), at(8, 9, 0)), Some(at(8, 9, 0)));
+
+        let sunday: CronSchedule = "30 6 * * 7".parse().unwrap();
+        assert!(sunday.matches(at(7, 6, 30)));
+        // either day field matches if both are restricted
+        let either: CronSchedule = "0 0 1 * 3".parse().unwrap();
+        assert!(either.matches(at(1, 0, 0)));
+        assert!(either.matches(at(3, 0, 0)));
+        assert!(!either.matches(at(2, 0, 0)));
+        assert_eq!("@daily".parse::<CronSchedule>().unwrap().latest_due(at(1, 12, 0), at(2, 12, 0)), Some(at(2, 0, 0)));
+
+        assert!("0 24 * * *".parse::<CronSchedule>().is_err());
+        assert!("0 23 * *".parse::<CronSchedule>().is_err());
+        assert!("*/0 * * * *".parse::<CronSchedule>().is_err());
+        assert!("5-1 * * * *".parse::<CronSchedule>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_catch_up() {
+        let sql_connection = Database::open(":memory:").unwrap();
+        let runs = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut scheduler = Scheduler::new(sql_connection.clone(), 7);
+        let recorded = runs.clone();
+        scheduler.add(Job::new("nightly", "0 23 * * *".parse().unwrap(), true, move |scheduled| {
+            recorded.lock().unwrap().push(scheduled.naive_local());
+            Box::pin(async { Ok(String::new()) })
+        }));
+        // the last complete nightly run was three days ago, the next one was interrupted
+        let last = local(at(1, 23, 0)).unwrap().timestamp();
+        let run_id = job_runs::start_job_run(sql_connection.clone(), "nightly", last, last).unwrap();
+        job_runs::finish_job_run(sql_connection.clone(), run_id, JobStatus::Ok, "");
+        let interrupted = local(at(2, 23, 0)).unwrap().timestamp();
+        job_runs::start_job_run(sql_connection.clone(), "nightly", interrupted, interrupted);
+        scheduler.tick(local(at(4, 8, 0)).unwrap());
+        assert_eq!(*runs.lock().unwrap(), vec![at(3, 23, 0)]);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let recorded = job_runs::job_runs(sql_connection.clone(), "nightly", 10);
+        assert_eq!(recorded.iter().map(|r| r.status).collect::<Vec<_>>(), vec![JobStatus::Ok, JobStatus::Interrupted, JobStatus::Ok]);
+
+        // a slow tick still runs the job of the skipped minute once
+        scheduler.tick(local(at(4, 22, 59)).unwrap());
+        scheduler.tick(local(at(4, 23, 2)).unwrap());
+        scheduler.tick(local(at(4, 23, 3)).unwrap());
+        assert_eq!(*runs.lock().unwrap(), vec![at(3, 23, 0), at(4, 23, 0)]);
+
+        // a run due while the previous one is busy is skipped
+        let mut scheduler = Scheduler::new(sql_connection.clone(), 7);
+        let (release, wait) = tokio::sync::oneshot::channel::<()>();
+        let wait = Arc::new(std::sync::Mutex::new(Some(wait)));
+        scheduler.add(Job::new("live", "* * * * *".parse().unwrap(), false, move |_| {
+            let wait = wait.lock().unwrap().take();
+            Box::pin(async move {
+                if let Some(wait) = wait {
+                    let _ = wait.await;
+                }
+                Ok(String::new())
+            })
+        }));
+        scheduler.tick(local(at(5, 10, 0)).unwrap());
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        scheduler.tick(local(at(5, 10, 1)).unwrap());
+        let _ = release.send(());
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let recorded = job_runs::job_runs(sql_connection.clone(), "live", 10);
+        assert_eq!(recorded.iter().map(|r| r.status).collect::<Vec<_>>(), vec![JobStatus::Skipped, JobStatus::Ok]);
+    }
+}
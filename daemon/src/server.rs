@@ -1,7 +1,8 @@
 use polars::prelude::*;
-use chrono::{Datelike, NaiveTime, offset::Local, Timelike};
+use chrono::{NaiveTime, offset::Local};
 use eyre::Result as EyreResult;
 use std::error::Error;
+use std::sync::{Arc, RwLock};
 use structopt::StructOpt;
 use tokio::{
     sync::broadcast,
@@ -9,6 +10,23 @@ use tokio::{
 };
 
 use api::prelude::*;
+use api::analytics::levels::LevelSettings;
+use api::charts::ticker::Overlay;
+
+use crate::config::{ChartConfig, Config, PortfolioConfig, ScreenerConfig, Thresholds};
+use crate::failures::Failures;
+use crate::notify::{Level, Notification};
+use crate::scheduler::{Job, Scheduler};
+
+/// name of the nightly update in the `job_runs` table
+pub const NIGHTLY_JOB: &str = "nightly";
+/// name of the live updates in the `job_runs` table
+pub const LIVE_JOB: &str = "live";
+/// name of the reports generated on request in the `job_runs` table
+pub const REPORTS_JOB: &str = "reports";
+
+/// held while the reports are written, the nightly job and a request must not write them both
+static REPORT_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
 
 /// convert an OsString (from PathBuf) to a usable String
 pub fn osstr_to_string(osstr: std::ffi::OsString) -> String {
@@ -19,11 +37,47 @@ pub fn osstr_to_string(osstr: std::ffi::OsString) -> String {
     String::new()
 }
 
-#[derive(Debug, PartialEq, StructOpt)]
+#[derive(Clone, Debug, PartialEq, StructOpt)]
 pub struct Options {
-    /// API Server url
-    #[structopt(long, env = "HELP", default_value = "no")]
-    pub help: String,
+    /// Configuration file, default `~/.config/stockanalysis/daemon.toml`
+    #[structopt(long, env = "STOCK_CONFIG", parse(from_os_str))]
+    pub config: Option<std::path::PathBuf>,
+
+    /// Exchange of symbols without stored exchange, overrides `exchange` of the configuration
+    #[structopt(long, env = "STOCK_EXCHANGE")]
+    pub exchange: Option<String>,
+
+    /// Database, a path or `sqlite://` URL, overrides `database` of the configuration
+    #[structopt(long, env = "STOCK_DATABASE")]
+    pub database: Option<String>,
+
+    /// Directory of the reports, overrides `output.reports` of the configuration
+    #[structopt(long, env = "REPORTS_DIR", parse(from_os_str))]
+    pub reports_dir: Option<std::path::PathBuf>,
+
+    /// Cron expression of the nightly jobs, overrides `schedule.nightly` of the configuration
+    #[structopt(long, env = "NIGHTLY_SCHEDULE")]
+    pub nightly_schedule: Option<String>,
+
+    /// Directory with recorded CSV/JSON bars replayed instead of asking the data providers
+    #[structopt(long, env = "REPLAY")]
+    pub replay: Option<std::path::PathBuf>,
+
+    /// Replayed seconds per second, 0 delivers all recorded bars at once
+    #[structopt(long, env = "REPLAY_SPEED", default_value = "60")]
+    pub replay_speed: f64,
+
+    /// What the nightly validation does with bad bars: report, flag or quarantine
+    #[structopt(long, env = "QUALITY_REPAIR")]
+    pub quality_repair: Option<String>,
+
+    /// Fill short gaps of the trading sessions with interpolated bars during the nightly validation
+    #[structopt(long)]
+    pub interpolate_gaps: bool,
+
+    /// Address of the web server, overrides `http.listen` of the configuration
+    #[structopt(long, env = "HTTP_LISTEN")]
+    pub listen: Option<String>,
 }
 
 fn move_file_to_archive(filepath: &std::path::PathBuf, archivepath: &std::path::PathBuf, file: &std::path::PathBuf) {
@@ -66,144 +120,97 @@ fn build_portfolio(portfolio: api::models::portfolio::PortfolioBuilder) -> Resul
     )
 }
 
+/// write a report file, the previous one is moved to the archive
+fn write_report(
+    filepath: &std::path::PathBuf,
+    archivepath: &std::path::PathBuf,
+    file_name: &str,
+    contents: &str,
+) -> api::error::Result<()> {
+    let path = filepath.join(file_name);
+    move_file_to_archive(filepath, archivepath, &path);
+    std::fs::write(&path, contents)
+        .map_err(|e| api::Error::storage(format!("failed to write {}: {}", path.display(), e)))
+}
+
+/// write a chart as `<name>.jpg` and `<name>.html`, the previous ones are moved to the archive
+fn write_chart(
+    filepath: &std::path::PathBuf,
+    archivepath: &std::path::PathBuf,
+    name: &str,
+    chart: &plotly::Plot,
+) -> api::error::Result<()> {
+    let path = filepath.join(format!("{name}.jpg"));
+    move_file_to_archive(filepath, archivepath, &path);
+    chart.to_jpeg(&osstr_to_string(path.into_os_string()), 1200, 800, 1.0);
+    write_report(filepath, archivepath, &format!("{name}.html"), &chart.to_html())
+}
+
+/// analyze a configured portfolio, `symbolsstrings` are used if it has no symbols.
+/// Returns the charts and tables that failed, the others are written anyway
 pub fn run_portfolio_analysis(
+    sql_connection: api::data::sql::Database,
+    settings: &PortfolioConfig,
     symbolsstrings: &Vec<String>,
+    exchange: &str,
     filepath: &std::path::PathBuf
-) -> Result<(), Box<dyn Error>> {
+) -> Result<Failures, Box<dyn Error>> {
     // 
+    let symbolsstrings = if settings.symbols.is_empty() { symbolsstrings } else { &settings.symbols };
     let symbols: Vec<&str> = symbolsstrings.iter().map(|s| &**s).collect();
-    let days = chrono::Local::now().weekday().num_days_from_monday();
-    let three_months_ago = chrono::Local::now().date_naive().checked_sub_days(chrono::Days::new(90)).unwrap();
-    let yesterday = chrono::Local::now().date_naive().checked_sub_days(chrono::Days::new(1)).unwrap();
-    let date_based_name = if days < 5 {
+    let mut failures = Failures::new();
+    let trading_day = api::calendar::calendar(exchange).is_trading_day(api::data::livedata::now().with_timezone(&Local).date_naive());
+    let (start_date, end_date) = settings.period(api::data::livedata::now().with_timezone(&Local).date_naive());
+    let yesterday = api::data::livedata::now().with_timezone(&Local).date_naive().checked_sub_days(chrono::Days::new(1)).unwrap();
+    let date_based_name = if trading_day {
         format!("archive_{}", yesterday.to_string())
     } else {
-        return Ok(());
+        return Ok(failures);
     };
+    let filepath = &filepath.join(&settings.name);
+    if !filepath.is_dir() {
+        std::fs::create_dir_all(filepath)?;
+    }
     let archivepath = filepath.clone().join(date_based_name);
 
     let portfolio = Portfolio::builder()
             .ticker_symbols(symbols.clone())
-            .benchmark_symbol("0H1C")
-            .start_date("2025-03-01")
-            .end_date("2025-08-31")
+            .benchmark_symbol(&settings.benchmark)
+            .start_date(&start_date)
+            .end_date(&end_date)
             .interval(Interval::OneDay)
-            .confidence_level(0.95)
-            .risk_free_rate(0.02)
-            .objective_function(ObjectiveFunction::MaxSharpe);
+            .confidence_level(settings.confidence_level)
+            .risk_free_rate(settings.risk_free_rate)
+            .objective_function(settings.objective_function())
+            .database(Some(sql_connection));
     let portfolio = build_portfolio(portfolio)?;
-    let testportfolio = portfolio.clone();
-    let portfolio_clone;
-    let opt_chart = portfolio.optimization_chart(None, None)
-            .map_err(|e| format!("Optimization Chart error: {e}"));
-    match opt_chart {
-        Ok(chart) => {
-            let file_name = "opt_chart.jpg".to_string();
-            let path = filepath.clone().join(file_name);
-            move_file_to_archive(filepath, &archivepath, &path);
-            chart.to_jpeg(&osstr_to_string(path.into_os_string()), 1200, 800, 1.0);
-
-            let file_name = "opt_chart.html";
-            let path = filepath.clone().join(file_name);
-            move_file_to_archive(filepath, &archivepath, &path);
-            std::fs::write(&osstr_to_string(path.into_os_string()), &chart.to_html()).expect("Should be able to write to file")
-        },
-        Err(e) => {
-            log::error!("Failed to get chart for portfolio: {e}");
-            return Ok(());
-        }
-    }
-    let testportfolio = portfolio.clone();
-    let perf_chart = portfolio.performance_chart(None, None)
-            .map_err(|e| format!("Performance Chart error: {e}"));
-    match perf_chart {
-        Ok(chart) => {
-            let file_name = "perf_chart.jpg".to_string();
-            let path = filepath.clone().join(file_name);
-            move_file_to_archive(filepath, &archivepath, &path);
-            chart.to_jpeg(&osstr_to_string(path.into_os_string()), 1200, 800, 1.0);
-
-            let file_name = "perf_chart.html";
-            let path = filepath.clone().join(file_name);
-            move_file_to_archive(filepath, &archivepath, &path);
-            std::fs::write(&osstr_to_string(path.into_os_string()), &chart.to_html()).expect("Should be able to write to file")
-        },
-        Err(e) => {
-            log::error!("Failed to get chart for portfolio: {e}");
-            return Ok(());
-        }
-    }
-    let testportfolio = portfolio.clone();
-    let perf_stats_chart = portfolio.performance_stats_table()
-            .map_err(|e| format!("Performance Stats Table error: {e}")).unwrap().to_html();
-    match perf_stats_chart {
-        Ok(chart) => {
-            let file_name = "performance_stats_table.html";
-            let path = filepath.clone().join(file_name);
-            move_file_to_archive(filepath, &archivepath, &path);
-            std::fs::write(&osstr_to_string(path.into_os_string()), &chart).expect("Should be able to write to file")
-        },
-        Err(e) => {
-            log::error!("Failed to get chart for portfolio: {e}");
-            return Ok(());
-        }
-    }
-    let testportfolio = portfolio.clone();
-    let returns_table = portfolio.returns_table().
-            map_err(|e| format!("Returns Table error: {e}")).unwrap().to_html();
-    let file_name = "returns_table.html";
-    let path = filepath.clone().join(file_name);
-    match returns_table {
-        Ok(chart) => {
-            move_file_to_archive(filepath, &archivepath, &path);
-            std::fs::write(&osstr_to_string(path.into_os_string()), &chart).expect("Should be able to write to file");
-        },
-        Err(e) => {
-            log::error!("Failed to get chart for portfolio: {e}");
-            return Ok(());
-        }
-    }
-    let testportfolio = portfolio.clone();
-    let returns_chart = portfolio.returns_chart(None, None)
-            .map_err(|e| format!("Returns Chart error: {e}"));
-    match returns_chart {
-        Ok(chart) => {
-            let file_name = "returns_chart.jpg".to_string();
-            let path = filepath.clone().join(file_name);
-            move_file_to_archive(filepath, &archivepath, &path);
-            chart.to_jpeg(&osstr_to_string(path.into_os_string()), 1200, 800, 1.0);
-
-            let file_name = "returns_chart.html";
-            let path = filepath.clone().join(file_name);
-            move_file_to_archive(filepath, &archivepath, &path);
-            std::fs::write(&osstr_to_string(path.into_os_string()), &chart.to_html()).expect("Should be able to write to file")
-        },
-        Err(e) => {
-            log::error!("Failed to get chart for portfolio: {e}");
-            return Ok(());
-        }
-    }
-    let testportfolio = portfolio.clone();
-    let returns_matrix = portfolio.returns_matrix(None, None)
-            .map_err(|e| format!("Returns Matrix error: {e}"));
-    match returns_matrix {
-        Ok(chart) => {
-            let file_name = "returns_matrix.jpg".to_string();
-            let path = filepath.clone().join(file_name);
-            move_file_to_archive(filepath, &archivepath, &path);
-            chart.to_jpeg(&osstr_to_string(path.into_os_string()), 1200, 800, 1.0);
-
-            let file_name = "returns_matrix.html";
-            let path = filepath.clone().join(file_name);
-            move_file_to_archive(filepath, &archivepath, &path);
-            std::fs::write(&osstr_to_string(path.into_os_string()), &chart.to_html()).expect("Should be able to write to file")
-        },
-        Err(e) => {
-            log::error!("Failed to get chart for portfolio: {e}");
-            return Ok(());
+
+    let subject = |what: &str| format!("portfolio {} {}", settings.name, what);
+    let charts = [
+        ("opt_chart", portfolio.optimization_chart(None, None)),
+        ("perf_chart", portfolio.performance_chart(None, None)),
+        ("returns_chart", portfolio.returns_chart(None, None)),
+        ("returns_matrix", portfolio.returns_matrix(None, None)),
+    ];
+    for (name, chart) in charts {
+        let chart = chart.map_err(api::Error::rendering);
+        if let Some(chart) = failures.check(&subject(name), chart) {
+            let written = write_chart(filepath, &archivepath, name, &chart);
+            failures.check(&subject(name), written);
         }
     }
-    Ok(())
+    let tables = [
+        ("performance_stats_table", portfolio.performance_stats_table()),
+        ("returns_table", portfolio.returns_table()),
+    ];
+    for (name, table) in tables {
+        let html = table.and_then(|t| t.to_html()).map_err(api::Error::rendering);
+        if let Some(html) = failures.check(&subject(name), html) {
+            let written = write_report(filepath, &archivepath, &format!("{name}.html"), &html);
+            failures.check(&subject(name), written);
+        }
+    }
+    Ok(failures)
 }
 
 fn get_chart_daily(ticker: &Ticker) -> Result<DataFrame, Box<dyn Error>> {
@@ -214,11 +221,11 @@ fn get_chart_daily(ticker: &Ticker) -> Result<DataFrame, Box<dyn Error>> {
     )
 }
 
-fn candlestick_chart_async(ticker: &Ticker) -> Result<plotly::plot::Plot, Box<dyn Error>> {
+fn candlestick_chart_async(ticker: &Ticker, overlays: &[Overlay], levels: &LevelSettings) -> Result<plotly::plot::Plot, Box<dyn Error>> {
     let handle = tokio::runtime::Handle::current();
     let _ = handle.enter();
     futures::executor::block_on(
-        ticker.candlestick_chart(None, None)
+        ticker.candlestick_chart_overlays(overlays, levels, None, None)
     )
 }
 
@@ -230,94 +237,87 @@ fn candlestick_chart_live_async(ticker: &Ticker) -> Result<plotly::plot::Plot, B
     )
 }
 
+/// URL of the live updates of a symbol, relative to the pages of the web server
+pub fn stream_url(symbol: &str) -> String {
+    format!("/stream?symbols={}", url::form_urlencoded::byte_serialize(symbol.as_bytes()).collect::<String>())
+}
+
+/// the charts and the table of the daily bars of the active symbols, returns the symbols that
+/// failed, the charts of the others are written anyway
 fn run_ticker_charts(
+    sql_connection: api::data::sql::Database,
     symbolsstrings: &Vec<String>,
+    settings: &ChartConfig,
+    levels: &LevelSettings,
+    exchange: &str,
+    stream: bool,
+    filepath: &std::path::PathBuf
+) -> Failures {
+    let mut failures = Failures::new();
+    let trading_day = api::calendar::calendar(exchange).is_trading_day(api::data::livedata::now().with_timezone(&Local).date_naive());
+    if !trading_day {
+        return failures;
+    }
+    for stock_symbol in symbolsstrings.iter() {
+        let charts = ticker_charts(sql_connection.clone(), stock_symbol, settings, levels, stream, filepath);
+        failures.check(stock_symbol, charts);
+    }
+    failures
+}
+
+/// the charts of one symbol for `run_ticker_charts`
+fn ticker_charts(
+    sql_connection: api::data::sql::Database,
+    stock_symbol: &str,
+    settings: &ChartConfig,
+    levels: &LevelSettings,
+    stream: bool,
     filepath: &std::path::PathBuf
 ) -> Result<(), Box<dyn Error>> {
-    // 
-    let symbols: Vec<&str> = symbolsstrings.iter().map(|s| &**s).collect();
-    let days = chrono::Local::now().weekday().num_days_from_monday();
-    let three_months_ago = chrono::Local::now().date_naive().checked_sub_days(chrono::Days::new(90)).unwrap();
-    let yesterday = chrono::Local::now().date_naive().checked_sub_days(chrono::Days::new(1)).unwrap();
-    let date_based_name = if days < 5 {
-        format!("archive_{}", yesterday.to_string())
-    } else {
-        return Ok(());
+    let today = api::data::livedata::now().with_timezone(&Local).date_naive();
+    let (Some(three_months_ago), Some(yesterday)) = (
+        today.checked_sub_days(chrono::Days::new(settings.days)),
+        today.checked_sub_days(chrono::Days::new(1)),
+    ) else {
+        return Err(api::Error::analytics(format!("no chart period of {} days before {}", settings.days, today)).into());
     };
-    let archivepath = filepath.clone().join(date_based_name);
+    let archivepath = filepath.clone().join(format!("archive_{}", yesterday.to_string()));
+    let end_of_day = NaiveTime::from_hms_opt(23, 59, 0).unwrap_or(NaiveTime::MIN);
+    let start_date = three_months_ago.and_time(NaiveTime::MIN).and_utc();
+    let end_date = yesterday.and_time(end_of_day).and_utc();
 
-    let mut tickers = Vec::new();
-    let start_date = three_months_ago.and_time(chrono::NaiveTime::from_num_seconds_from_midnight_opt(0, 0).unwrap()).and_utc();
-    let end_date = yesterday.and_time(chrono::NaiveTime::from_num_seconds_from_midnight_opt(23 * 3600 + 59 * 60, 0).unwrap()).and_utc();
-
-    for i in 0..symbols.len() {
-        let stock_symbol = symbols[i].to_string();
-        let mut ticker: Ticker = api::models::ticker::TickerBuilder::new()
-            .ticker(&stock_symbol)
-            .start_date(&start_date.naive_utc().to_string())
-            .end_date(&end_date.naive_utc().to_string())
-            .benchmark_symbol("0H1C")
-            .interval(Interval::OneDay)
-            .build();
-
-        let df = get_chart_daily(&ticker).unwrap();
-        let table = df.to_datatable("ohlcv", true, DataTableFormat::Number);
-        let html = table.to_html()?;
-        let mut file_name = stock_symbol.clone();
-        file_name.extend(".html".chars());
-        let path = filepath.clone().join(file_name);
-        std::fs::write(&path, &html).expect("Should be able to write to file");
-        match candlestick_chart_async(&ticker) {
-            Ok(pl) => {
-                let mut file_name = stock_symbol.clone();
-                file_name.extend("_chart.jpg".chars());
-                let path = filepath.clone().join(file_name);
-                move_file_to_archive(filepath, &archivepath, &path);
-                pl.to_jpeg(&osstr_to_string(path.into_os_string()), 1200, 800, 1.0);
-                let html = pl.to_html();
-                let mut file_name = stock_symbol.clone();
-                file_name.extend("_chart.html".chars());
-                let path = filepath.clone().join(file_name);
-                move_file_to_archive(filepath, &archivepath, &path);
-                std::fs::write(&path, &html).expect("Should be able to write to file");
-            },
-            Err(error) => {
-                log::error!("Failed to crate chart for ticker {}!: {}", stock_symbol, error);
-                continue;
-            },
-        }
-        // get only the last stock day.
-        // TODO: Replace by live data
-        let start_date = yesterday.and_time(chrono::NaiveTime::from_num_seconds_from_midnight_opt(0, 0).unwrap()).and_utc();
-        let end_date = yesterday.and_time(chrono::NaiveTime::from_num_seconds_from_midnight_opt(23 * 3600 + 59 * 60, 0).unwrap()).and_utc();
-        ticker.start_date = start_date.naive_utc().to_string();
-        ticker.end_date = end_date.naive_utc().to_string();
-        if end_date.timestamp_millis() <= start_date.timestamp_millis() {
-            log::error!("timestamps are do not span a time span!");
-        }
-        match candlestick_chart_live_async(&ticker) {
-            Ok(pl) => {
-                let mut file_name = stock_symbol.clone();
-                file_name.extend("_chart_live.jpg".chars());
-                let path = filepath.clone().join(file_name);
-                move_file_to_archive(filepath, &archivepath, &path);
-                pl.to_jpeg(&osstr_to_string(path.into_os_string()), 1200, 800, 1.0);
-                let html = pl.to_html();
-                let mut file_name = stock_symbol.clone();
-                file_name.extend("_chart_live.html".chars());
-                let path = filepath.clone().join(file_name);
-                move_file_to_archive(filepath, &archivepath, &path);
-                std::fs::write(&path, &html).expect("Should be able to write to file");
-            },
-            Err(error) => {
-                log::error!("Failed to crate chart for ticker {}!: {}", stock_symbol, error);
-                continue;
-            },
-        }
-        //println!("{}", html);
-        tickers.push(ticker);
-        //table.show()?;
-    }
+    let mut ticker: Ticker = api::models::ticker::TickerBuilder::new()
+        .ticker(stock_symbol)
+        .start_date(&start_date.naive_utc().to_string())
+        .end_date(&end_date.naive_utc().to_string())
+        .benchmark_symbol(&settings.benchmark)
+        .interval(Interval::OneDay)
+        .database(Some(sql_connection))
+        .build();
+
+    let df = get_chart_daily(&ticker)?;
+    let table = df.to_datatable("ohlcv", true, DataTableFormat::Number);
+    let html = table.to_html().map_err(api::Error::rendering)?;
+    let path = filepath.join(format!("{stock_symbol}.html"));
+    std::fs::write(&path, &html).map_err(|e| api::Error::storage(format!("failed to write {}: {}", path.display(), e)))?;
+    let chart = candlestick_chart_async(&ticker, &settings.overlays, levels).map_err(api::Error::rendering)?;
+    write_chart(filepath, &archivepath, &format!("{stock_symbol}_chart"), &chart)?;
+
+    // get only the last stock day.
+    // TODO: Replace by live data
+    ticker.start_date = yesterday.and_time(NaiveTime::MIN).and_utc().naive_utc().to_string();
+    ticker.end_date = end_date.naive_utc().to_string();
+    let chart = candlestick_chart_live_async(&ticker).map_err(api::Error::rendering)?;
+    let name = format!("{stock_symbol}_chart_live");
+    let path = filepath.join(format!("{name}.jpg"));
+    move_file_to_archive(filepath, &archivepath, &path);
+    chart.to_jpeg(&osstr_to_string(path.into_os_string()), 1200, 800, 1.0);
+    let html = if stream {
+        api::charts::live::live_chart_html(&chart.to_html(), &stream_url(stock_symbol))
+    } else {
+        chart.to_html()
+    };
+    write_report(filepath, &archivepath, &format!("{name}.html"), &html)?;
     Ok(())
 }
 
@@ -369,55 +369,62 @@ fn report_portfolio(ticker: api::prelude::Portfolio, reporttype: Option<ReportTy
     )
 }
 
-pub fn run_screener_process(filepath: &std::path::PathBuf) -> Result<(), Box<dyn Error>> {
-    let days = chrono::Local::now().weekday().num_days_from_monday();
-    let yesterday = chrono::Local::now().date_naive().checked_sub_days(chrono::Days::new(1)).unwrap();
-    let date_based_name = if days < 5 {
+/// validate the stored bars and write the issues to `data_quality.txt`
+pub fn run_data_validation(
+    sql_connection: api::data::sql::Database,
+    symbols: &Vec<String>,
+    settings: &api::data::quality::QualitySettings,
+    filepath: &std::path::PathBuf,
+) -> Result<(), Box<dyn Error>> {
+    let reports = api::data::quality::validate_symbols(sql_connection, symbols, settings);
+    let mut text = String::new();
+    for report in reports.iter().filter(|r| !r.is_clean() || r.interpolated > 0) {
+        log::warn!("Data quality: {}", report.to_string().lines().next().unwrap_or_default());
+        text.push_str(&format!("{}\n", report));
+    }
+    if text.is_empty() {
+        text.push_str("no issues found\n");
+    }
+    std::fs::write(filepath.join("data_quality.txt"), text)?;
+    Ok(())
+}
+
+pub fn run_screener_process(
+    settings: &ScreenerConfig,
+    exchange: &str,
+    filepath: &std::path::PathBuf
+) -> Result<(), Box<dyn Error>> {
+    let trading_day = api::calendar::calendar(exchange).is_trading_day(api::data::livedata::now().with_timezone(&Local).date_naive());
+    let yesterday = api::data::livedata::now().with_timezone(&Local).date_naive().checked_sub_days(chrono::Days::new(1)).unwrap();
+    let date_based_name = if trading_day {
         format!("archive_{}", yesterday.to_string())
     } else {
         return Ok(());
     };
     let archivepath = filepath.clone().join(date_based_name);
-    // Screen for Large-Cap NASDAQ Stocks
+    let Some(start_date) = yesterday.checked_sub_days(chrono::Days::new(settings.days)) else {
+        return Err(api::Error::analytics(format!("no screener period of {} days before {}", settings.days, yesterday)).into());
+    };
+    let screened_exchange: Exchange = settings.exchange.parse().unwrap_or(Exchange::NASDAQ);
+    // Screen for Large-Cap Stocks of the exchange
     let screener = Screener::builder()
         .quote_type(QuoteType::Equity)
         .add_filter(ScreenerFilter::EqStr(
             ScreenerMetric::Equity(EquityScreener::Exchange),
-            Exchange::NASDAQ.as_ref()
+            screened_exchange.into()
         ))
         .sort_by(
             ScreenerMetric::Equity(EquityScreener::MarketCapIntraday),
             true
         )
-        .size(10);
+        .size(settings.size);
     let screener = build_screener(screener)?;
-    let file_name = "screener_overview.html";
-    let path = filepath.clone().join(file_name);
-    let overview = screener.clone().overview().to_html();
-    match overview {
-        Ok(chart) => {
-            move_file_to_archive(filepath, &archivepath, &path);
-            std::fs::write(&osstr_to_string(path.into_os_string()), &chart).expect("Should be able to write to file")
-        },
-        Err(e) => {
-            log::error!("Failed to get overview for screener: {e}");
-            return Ok(());
-        }
-    }
-
-    let file_name = "screener_metrics.html";
-    let path = filepath.clone().join(file_name);
-    let metrics = metrics(screener.clone())?.to_html();
-    match metrics {
-        Ok(chart) => {
-            move_file_to_archive(filepath, &archivepath, &path);
-            std::fs::write(&osstr_to_string(path.into_os_string()), &chart).expect("Should be able to write to file")
-        },
-        Err(e) => {
-            log::error!("Failed to get metrics for screener: {e}");
-            return Ok(());
-        }
-    }
+    let overview = screener.clone().overview().to_html()
+        .map_err(|e| api::Error::rendering(format!("screener overview: {e}")))?;
+    write_report(filepath, &archivepath, "screener_overview.html", &overview)?;
+    let metrics = metrics(screener.clone())?.to_html()
+        .map_err(|e| api::Error::rendering(format!("screener metrics: {e}")))?;
+    write_report(filepath, &archivepath, "screener_metrics.html", &metrics)?;
 
     // Instantiate a Multiple Ticker Object
     let ticker_symbols = screener.symbols.iter()
@@ -425,54 +432,38 @@ pub fn run_screener_process(filepath: &std::path::PathBuf) -> Result<(), Box<dyn
 
     let tickers = api::models::tickers::TickersBuilder::new()
         .tickers(ticker_symbols.clone())
-        .start_date("2025-03-01")
-        .end_date("2025-09-15")
+        .start_date(&start_date.to_string())
+        .end_date(&yesterday.to_string())
         .interval(Interval::OneDay)
-        .benchmark_symbol("0H1C")
+        .benchmark_symbol(&settings.benchmark)
         .confidence_level(0.95)
         .risk_free_rate(0.02)
         .build();
 
     // Generate a Single Ticker Report
-    let symbol = ticker_symbols.first().unwrap();
+    let Some(symbol) = ticker_symbols.first() else {
+        return Err(api::Error::analytics("the screener found no symbols").into());
+    };
     let ticker = get_ticker(tickers.clone(), symbol)?;
     let performance = report(ticker.clone(), Some(ReportType::Performance))?.to_html();
-    let file_name = "screener_top_performance.html";
-    let path = filepath.clone().join(file_name);
-    move_file_to_archive(filepath, &archivepath, &path);
-    std::fs::write(&osstr_to_string(path.into_os_string()), &performance).expect("Should be able to write to file");
+    write_report(filepath, &archivepath, "screener_top_performance.html", &performance)?;
     let financials = report(ticker.clone(), Some(ReportType::Financials))?.to_html();
-    let file_name = "screener_financials.html";
-    let path = filepath.clone().join(file_name);
-    move_file_to_archive(filepath, &archivepath, &path);
-    std::fs::write(&osstr_to_string(path.into_os_string()), &financials).expect("Should be able to write to file");
+    write_report(filepath, &archivepath, "screener_financials.html", &financials)?;
     let options = report(ticker.clone(), Some(ReportType::Options))?.to_html();
-    let file_name = "screener_options.html";
-    let path = filepath.clone().join(file_name);
-    move_file_to_archive(filepath, &archivepath, &path);
-    std::fs::write(&osstr_to_string(path.into_os_string()), &options).expect("Should be able to write to file");
+    write_report(filepath, &archivepath, "screener_options.html", &options)?;
     let news = report(ticker.clone(), Some(ReportType::News))?.to_html();
-    let file_name = "screescreener_newsner_overview.html";
-    let path = filepath.clone().join(file_name);
-    move_file_to_archive(filepath, &archivepath, &path);
-    std::fs::write(&osstr_to_string(path.into_os_string()), &news).expect("Should be able to write to file");
+    write_report(filepath, &archivepath, "screescreener_newsner_overview.html", &news)?;
 
     // Generate a Multiple Ticker Report
     let report = report(ticker.clone(), Some(ReportType::Performance))?.to_html();
-    let file_name = "screener_report.html";
-    let path = filepath.clone().join(file_name);
-    move_file_to_archive(filepath, &archivepath, &path);
-    std::fs::write(&osstr_to_string(path.into_os_string()), &report).expect("Should be able to write to file");
+    write_report(filepath, &archivepath, "screener_report.html", &report)?;
 
     // Perform a Portfolio Optimization
     let portfolio = optimize(tickers.clone(), Some(ObjectiveFunction::MaxSharpe))?;
 
     // Generate a Portfolio Report
     let portfolioreport = report_portfolio(portfolio.clone(), Some(ReportType::Performance))?.to_html();
-    let file_name = "screener_portfolioreport.html";
-    let path = filepath.clone().join(file_name);
-    move_file_to_archive(filepath, &archivepath, &path);
-    std::fs::write(&osstr_to_string(path.into_os_string()), &portfolioreport).expect("Should be able to write to file");
+    write_report(filepath, &archivepath, "screener_portfolioreport.html", &portfolioreport)?;
 
     // TODO write a HTML file with links to the written HTML files
 
@@ -481,11 +472,15 @@ pub fn run_screener_process(filepath: &std::path::PathBuf) -> Result<(), Box<dyn
     Ok(())
 }
 
+/// detect the events of today's data, returns the notifications to send
 pub fn run_analysis_on_updated_dataframe(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>, 
-    symbols: &Vec<String>
-) {
-    let now = Local::now();
+    sql_connection: api::data::sql::Database, 
+    symbols: &Vec<String>,
+    thresholds: &Thresholds,
+    policy: &api::data::sql::AlertPolicy,
+) -> Vec<Notification> {
+    let now = api::data::livedata::now().with_timezone(&Local);
+    let mut notifications = Vec::new();
     
     for symbol in symbols.iter() {
         let mut vt = Vec::new();
@@ -536,7 +531,13 @@ pub fn run_analysis_on_updated_dataframe(
                                 continue;
                             }
                         };
-                        df = concat([df.lazy(), vec[i].clone().lazy()], UnionArgs::default()).unwrap().collect().unwrap();
+                        df = match concat([df.lazy(), vec[i].clone().lazy()], UnionArgs::default()).and_then(|df| df.collect()) {
+                            Ok(df) => df,
+                            Err(error) => {
+                                log::error!("Unable to join the data of symbol {}! {}", symbol, error);
+                                break;
+                            }
+                        };
                     }
                 }
                 if df.height() > 0 {
@@ -574,45 +575,65 @@ pub fn run_analysis_on_updated_dataframe(
             }
         };
         
-        let jumps = api::analytics::detectors::jumps_in_series(symbol, &timestamps, &adjclose, 0.5, 0.3);
-        api::data::sql::events::insert_jump_events(sql_connection.clone(), &jumps);
+        let jumps = api::analytics::detectors::jumps_in_series(symbol, &timestamps, &adjclose, thresholds.jump_up, thresholds.jump_down);
+        let new_jumps = api::data::sql::events::insert_jump_events(sql_connection.clone(), &jumps);
+        if new_jumps > 0 {
+            log::debug!("Stored {} new jumps of symbol {}", new_jumps, symbol);
+        }
         
-        // detect a increasing or decreasing slope and raise a notification
-        let slope = api::analytics::detectors::increasing_slope(&vv[vv.len()-1], 0.5, 0.3);
-        if slope != 0.0 {
-            // send alarm
-            let text;
-            if slope > 0.0 {
-                text = format!("Symbol {} increased by {} at {}!", symbol, slope, datetimes[datetimes.len()-1].to_string());
-            } else {
-                text = format!("Symbol {} dropped by {} at {}!", symbol, slope, datetimes[datetimes.len()-1].to_string());
-            }
-            log::warn!("{}", &text);
-            match notify_rust::Notification::new()
-                .summary("stock-analysis")
-                .body(&text)
-                .icon("alarm")
-                .show()
-            {
-                Ok(_h) => {},
-                Err(e) => log::error!("Failed to notify the desktop user: {}", e),
+        // detect a increasing or decreasing slope and raise a notification once per slope
+        let slope = api::analytics::detectors::increasing_slope(&vv[vv.len()-1], thresholds.slope_up, thresholds.slope_down);
+        let up = api::data::sql::alerts::fingerprint("slope", symbol, "up");
+        let down = api::data::sql::alerts::fingerprint("slope", symbol, "down");
+        if slope == 0.0 {
+            api::data::sql::alerts::resolve_alert(sql_connection.clone(), &up, now.timestamp());
+            api::data::sql::alerts::resolve_alert(sql_connection.clone(), &down, now.timestamp());
+            continue;
+        }
+        let (text, fingerprint, ended) = if slope > 0.0 {
+            (format!("Symbol {} increased by {} at {}!", symbol, slope, datetimes[datetimes.len()-1].to_string()), up, down)
+        } else {
+            (format!("Symbol {} dropped by {} at {}!", symbol, slope, datetimes[datetimes.len()-1].to_string()), down, up)
+        };
+        api::data::sql::alerts::resolve_alert(sql_connection.clone(), &ended, now.timestamp());
+        let alert = api::data::sql::alerts::NewAlert {
+            fingerprint,
+            source: "slope".to_string(),
+            symbol: symbol.clone(),
+            level: Level::Warning.as_str().to_string(),
+            title: "stock-analysis".to_string(),
+            body: text.clone(),
+        };
+        match api::data::sql::alerts::raise_alert(sql_connection.clone(), &alert, policy, now.timestamp()) {
+            Some(raised) if raised.notify => {
+                log::warn!("{}", &text);
+                let level = raised.level.parse().unwrap_or(Level::Warning);
+                notifications.push(Notification::new(symbol, level, "stock-analysis", &text));
             }
+            Some(_) => log::debug!("{}", &text),
+            None => log::error!("Failed to record the slope alert of symbol {}", symbol),
         }
     }
+    notifications
 }
 
 pub fn run_analysis_on_historical_data(
-    sql_connection: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>, 
-    symbols: &Vec<String>
+    sql_connection: api::data::sql::Database, 
+    symbols: &Vec<String>,
+    thresholds: &Thresholds,
 ) {
-    let now = Local::now();
+    let now = api::data::livedata::now().with_timezone(&Local);
+    let Some(first_day) = now.date_naive().checked_sub_days(chrono::Days::new(thresholds.history_days)) else {
+        log::error!("No history of {} days before {}", thresholds.history_days, now);
+        return;
+    };
     
     for symbol in symbols.iter() {
         let mut vt = Vec::new();
         let mut vv = Vec::new();
         let start_time = NaiveTime::from_num_seconds_from_midnight_opt(0, 0).expect("That should never fail!");
         let end_time = NaiveTime::from_num_seconds_from_midnight_opt(23*3600 + 59*60, 0).expect("That should never fail!");
-        let start_date = now.clone().date_naive().checked_sub_days(chrono::Days::new(90)).unwrap().and_time(start_time);
+        let start_date = first_day.and_time(start_time);
         let end_date = now.clone().date_naive().and_time(end_time);
         let ohlcv: polars::prelude::DataFrame = match api::data::sql::to_dataframe::ohlcv_to_dataframe(
             sql_connection.clone(),
@@ -656,7 +677,13 @@ pub fn run_analysis_on_historical_data(
                                 continue;
                             }
                         };
-                        df = concat([df.lazy(), vec[i].clone().lazy()], UnionArgs::default()).unwrap().collect().unwrap();
+                        df = match concat([df.lazy(), vec[i].clone().lazy()], UnionArgs::default()).and_then(|df| df.collect()) {
+                            Ok(df) => df,
+                            Err(error) => {
+                                log::error!("Unable to join the data of symbol {}! {}", symbol, error);
+                                break;
+                            }
+                        };
                     }
                 }
                 if df.height() > 0 {
@@ -711,86 +738,452 @@ pub fn run_analysis_on_historical_data(
             // analyze changepoints
         }
 
-        let jumps = api::analytics::detectors::jumps_in_series(symbol, &timestamps, &adjclose, 0.5, 0.3);
+        let jumps = api::analytics::detectors::jumps_in_series(symbol, &timestamps, &adjclose, thresholds.jump_up, thresholds.jump_down);
         api::data::sql::events::insert_jump_events(sql_connection.clone(), &jumps);
+    }
+}
+
+/// detect the candlestick patterns in the daily bars of the last `history_days` days of the
+/// symbols and store the new ones, the candles of minute bars are too small to tell a pattern
+fn store_patterns(sql_connection: api::data::sql::Database, symbols: &[String], config: &Config, now: chrono::DateTime<Local>) {
+    for symbol in symbols.iter() {
+        let bars = daily_bars(sql_connection.clone(), &config.exchange, symbol, config.thresholds.history_days, now);
+        let patterns = api::analytics::patterns::detect_in_series(symbol, &bars, config.thresholds.pattern_confidence);
+        let new_patterns = api::data::sql::events::insert_pattern_events(sql_connection.clone(), &patterns);
+        if new_patterns > 0 {
+            log::debug!("Stored {} new candlestick patterns of symbol {}", new_patterns, symbol);
+        }
+    }
+}
+
+/// the screener, the charts and the portfolio reports of the active symbols
+/// returns the reports that failed
+fn generate_reports(sql_connection: api::data::sql::Database, config: &Config, symbols: &Vec<String>, filepath: &std::path::PathBuf) -> Failures {
+    let started = std::time::Instant::now();
+    let mut failures = Failures::new();
+    if config.screener.enabled {
+        failures.check("screener", run_screener_process(&config.screener, &config.exchange, filepath));
+    }
+
+    // the live charts are served by the web server, which streams their updates
+    let stream = cfg!(feature = "http") && config.http.enabled && config.charts.live_updates;
+    failures.extend(run_ticker_charts(sql_connection.clone(), symbols, &config.charts, &config.level_settings(), &config.exchange, stream, filepath));
+
+    for portfolio in config.portfolios.iter() {
+        let subject = format!("portfolio {}", portfolio.name);
+        if let Some(failed) = failures.check(&subject, run_portfolio_analysis(sql_connection.clone(), portfolio, symbols, &config.exchange, filepath)) {
+            failures.extend(failed);
+        }
+    }
+    crate::metrics::reports_generated(started.elapsed());
+    failures
+}
+
+/// start the generation of the reports outside of the nightly job, returns the id of the job run
+/// or `None` if the reports are being generated already
+pub fn regenerate_reports(sql_connection: api::data::sql::Database, config: Arc<Config>) -> Option<i64> {
+    let guard = REPORT_LOCK.try_lock().ok()?;
+    let now = chrono::Utc::now().timestamp();
+    let run_id = api::data::sql::job_runs::start_job_run(sql_connection.clone(), REPORTS_JOB, now, now).unwrap_or_default();
+    // the reports are drawn and written synchronously, off the workers of the runtime
+    tokio::task::spawn_blocking(move || {
+        let _guard = guard;
+        let timer = std::time::Instant::now();
+        let symbols = api::data::sql::symbols::active_symbols(sql_connection.clone());
+        let failures = generate_reports(sql_connection.clone(), &config, &symbols, &config.reports_dir());
+        // the reports that were written are kept, the run failed if any of them failed
+        let status = if failures.is_empty() {
+            api::data::sql::JobStatus::Ok
+        } else {
+            log::warn!("Reports generated with failures, {}", failures);
+            api::data::sql::JobStatus::Failed
+        };
+        crate::metrics::job_finished(REPORTS_JOB, status, timer.elapsed());
+        if run_id != 0 {
+            api::data::sql::job_runs::finish_job_run(sql_connection, run_id, status, &failures.to_string());
+        }
+    });
+    Some(run_id)
+}
+
+/// the nightly update of the stored data and the reports for the day of `scheduled`,
+/// returns the symbols and reports that failed, the others are updated anyway
+pub async fn run_nightly_jobs(sql_connection: api::data::sql::Database, config: Arc<Config>, scheduled: chrono::DateTime<Local>) -> EyreResult<Failures> {
+    let symbols = api::data::sql::symbols::active_symbols(sql_connection.clone());
+    let filepath = config.reports_dir();
+    let mut failures = Failures::new();
+    // the providers block on their requests, the other tasks of the runtime move to the other workers
+    let updated = tokio::task::block_in_place(|| api::data::livedata::update_nightly(sql_connection.clone(), &config.exchange, &symbols));
+    failures.add_errors("daily data", updated);
+    api::data::sql::corporate_actions::update_corporate_actions(sql_connection.clone(), &symbols);
+    failures.check("data validation", run_data_validation(sql_connection.clone(), &symbols, &config.quality_settings(), &filepath));
+    
+    // temporarily get the minutely data also once per day until there is a subscription with live-data access
+    for symbol in symbols.iter() {
+        let mut metadata: api::data::sql::MetaData = api::data::sql::metadata(sql_connection.clone(), &config.exchange, symbol);
+        let calendar = api::calendar::calendar(&metadata.exchange_code);
+        let Some((open, close)) = calendar.session(calendar.local_date(scheduled.with_timezone(&chrono::Utc))) else {
+            continue;
+        };
+        let start_date = open.naive_utc();
+        let end_date = close.naive_utc();
+        metadata.start_date = open;
+        metadata.end_date = close;
         
+        let stored = tokio::task::block_in_place(|| api::data::livedata::update_live_data(sql_connection.clone(), &metadata, start_date, end_date))
+            .map_err(|e| api::Error::from_provider(symbol, e));
+        if let Some(stored) = failures.check(symbol, stored) {
+            log::debug!("Stored {} new values for symbol {}", stored.len(), symbol);
+        }
     }
+    run_analysis_on_historical_data(sql_connection.clone(), &symbols, &config.thresholds);
+    store_patterns(sql_connection.clone(), &symbols, &config, scheduled);
+
+    {
+        let _reports = REPORT_LOCK.lock().await;
+        failures.extend(generate_reports(sql_connection.clone(), &config, &symbols, &filepath));
+    }
+
+    // keep the history of the job runs for three months
+    let expired = scheduled - chrono::Duration::days(90);
+    api::data::sql::job_runs::delete_job_runs_before(sql_connection.clone(), expired.timestamp());
+
+    if !failures.is_empty() {
+        log::warn!("Nightly jobs of {} finished with failures, {}", scheduled, failures);
+    }
+    Ok(failures)
 }
 
-pub async fn run_jobs() -> EyreResult<()> {
-    let now = Local::now();
-    let sql_connection = api::data::sql::connect();
+/// the live updates of the symbols whose exchanges are trading, `live_updates` fetches
+/// the minutely data before the live analysis. Returns the symbols whose update failed
+pub async fn run_live_jobs(sql_connection: api::data::sql::Database, live_updates: bool, config: Arc<Config>) -> EyreResult<Failures> {
+    let now = api::data::livedata::now().with_timezone(&Local);
     let symbols = api::data::sql::symbols::active_symbols(sql_connection.clone());
-    let mut filepath = dirs::home_dir().unwrap().join("stock-analysis-reports");
-    if !filepath.is_dir() {
-        match std::fs::create_dir_all(filepath.clone()) {
-            Ok(()) => (),
-            Err(e) => {
-                log::error!("Failed to create directory: {}", e);
-            },
+    let mut failures = Failures::new();
+    let mut open_symbols = Vec::new();
+    for symbol in symbols.iter() {
+        let metadata = api::data::sql::metadata(sql_connection.clone(), &config.exchange, symbol);
+        if !api::calendar::is_open(&metadata.exchange_code, now.with_timezone(&chrono::Utc)) {
+            continue;
         }
-        filepath = dirs::home_dir().unwrap();
+        if live_updates {
+            // from the open of the session or the newest stored bar, which may have been
+            // incomplete, up to now, all in UTC
+            let utc_now = now.with_timezone(&chrono::Utc);
+            let calendar = api::calendar::calendar(&metadata.exchange_code);
+            let open = calendar.session(calendar.local_date(utc_now)).map_or(utc_now, |(open, _)| open);
+            let start = api::data::sql::live_data::last_live_timestamp(sql_connection.clone(), &metadata)
+                .and_then(|last| chrono::DateTime::from_timestamp(last, 0))
+                .map_or(open, |last| last.max(open));
+            let start_date = start.naive_utc();
+            let end_date = utc_now.naive_utc();
+            let stored = tokio::task::block_in_place(|| api::data::livedata::update_live_data(sql_connection.clone(), &metadata, start_date, end_date))
+                .map_err(|e| api::Error::from_provider(symbol, e));
+            if let Some(stored) = failures.check(symbol, stored) {
+                log::debug!("Stored {} new values for symbol {}", stored.len(), symbol);
+            }
+        }
+        open_symbols.push(symbol.clone());
     }
-    if now.hour() == 23 && now.minute() == 0 {
-        // run daily jobs.
-        api::data::livedata::update_nightly(sql_connection.clone(), &symbols);
-        
-        // temporarily get the minutely data also once per day until there is a subscription with live-data access
-        let start_time = NaiveTime::from_num_seconds_from_midnight_opt(7*3600, 0).expect("That should never fail!");
-        let end_time = NaiveTime::from_num_seconds_from_midnight_opt(22*3600, 0).expect("That should never fail!");
-        for symbol in symbols.iter() {
-            let mut metadata: api::data::sql::MetaData = api::data::sql::metadata(sql_connection.clone(), "XFRA", symbol);
-            let start_date = now.clone().date_naive().and_time(start_time);
-            let end_date = now.clone().date_naive().and_time(end_time);
-            metadata.start_date = start_date.clone().and_utc();
-            metadata.end_date = end_date.clone().and_utc();
-            
-            match api::data::livedata::live_data(symbol, start_date, end_date) {
-                Ok(enhanced_data) => {
-                    // store the data
-                    for data in enhanced_data.iter() {
-                        let _ret = api::data::sql::insert_live_data(sql_connection.clone(), &metadata, data);
-                    }
-                },
-                Err(e) => {
-                    log::error!("Failed to retrieve data for symbol {} from provider! {}", symbol, e);
-                    continue;
-                },
+    if !open_symbols.is_empty() {
+        // triger the live analysis and event detection
+        let policy = config.alert_policy();
+        let mut notifications = run_analysis_on_updated_dataframe(sql_connection.clone(), &open_symbols, &config.thresholds, &policy);
+        store_patterns(sql_connection.clone(), &open_symbols, &config, now);
+        if config.levels.notify {
+            notifications.extend(run_level_alerts(sql_connection.clone(), &open_symbols, &config, &policy, now));
+        }
+        let rule_alerts = run_alert_rules(sql_connection.clone(), &open_symbols, &config.exchange, &policy, now);
+        if crate::live::has_subscribers() {
+            for symbol in open_symbols.iter() {
+                crate::live::publish_bars(symbol, &todays_bars(sql_connection.clone(), &config.exchange, symbol, now));
             }
         }
-        run_analysis_on_historical_data(sql_connection.clone(), &symbols);
+        if !notifications.is_empty() || !rule_alerts.is_empty() {
+            let notifiers = config.notifiers();
+            let with_link = |n: &Notification| n.clone().with_link(config.link(&format!("/events/{}", n.symbol)));
+            for notification in notifications.iter() {
+                let notification = with_link(notification);
+                notifiers.notify(&sql_connection, &config.alerts.notify, &notification).await;
+                crate::metrics::alert("events", notification.level);
+                crate::live::publish(crate::live::LiveEvent::Alert { notification });
+            }
+            for (notification, channels) in rule_alerts.iter() {
+                let channels = if channels.is_empty() { &config.alerts.notify } else { channels };
+                let notification = with_link(notification);
+                notifiers.notify(&sql_connection, channels, &notification).await;
+                crate::metrics::alert("rules", notification.level);
+                crate::live::publish(crate::live::LiveEvent::Alert { notification });
+            }
+        }
+    }
 
-        let _ret = run_screener_process(&filepath);
+    Ok(failures)
+}
 
-        let _ret = run_ticker_charts(&symbols, &filepath);
+/// the minute bars of the day of `now`, ordered by time
+fn todays_bars(
+    sql_connection: api::data::sql::Database,
+    exchange: &str,
+    symbol: &str,
+    now: chrono::DateTime<Local>,
+) -> Vec<api::data::sql::TimeSeriesData> {
+    let mut metadata = api::data::sql::metadata(sql_connection.clone(), exchange, symbol);
+    metadata.start_date = now.with_timezone(&chrono::Utc);
+    metadata.end_date = metadata.start_date;
+    api::data::sql::live_data::live_data(sql_connection, &metadata).into_iter().flatten().collect()
+}
 
-        let _ret = run_portfolio_analysis(&symbols, &filepath);
+/// the daily bars of the last `days` days, today's minute bars joined into the last one
+fn daily_bars(
+    sql_connection: api::data::sql::Database,
+    exchange: &str,
+    symbol: &str,
+    days: u64,
+    now: chrono::DateTime<Local>,
+) -> Vec<api::data::sql::TimeSeriesData> {
+    let today = todays_bars(sql_connection.clone(), exchange, symbol, now);
+    let mut metadata = api::data::sql::metadata(sql_connection.clone(), exchange, symbol);
+    metadata.end_date = now.with_timezone(&chrono::Utc);
+    metadata.start_date = metadata.end_date.checked_sub_days(chrono::Days::new(days)).unwrap_or(chrono::DateTime::<chrono::Utc>::MIN_UTC);
+    let mut bars = api::data::sql::time_series::timeseries(sql_connection, &metadata);
+    if let (Some(first), Some(last)) = (today.first(), today.last()) {
+        bars.retain(|bar| bar.datetime < first.datetime);
+        bars.push(api::data::sql::TimeSeriesData {
+            datetime: first.datetime,
+            open: first.open,
+            high: today.iter().map(|bar| bar.high).fold(f64::MIN, f64::max),
+            low: today.iter().map(|bar| bar.low).fold(f64::MAX, f64::min),
+            close: last.close,
+            volume: today.iter().map(|bar| bar.volume).sum(),
+        });
+    }
+    bars
+}
 
-    } else {
-        // run live updates every minute on Weekdays
-        if now.weekday().num_days_from_monday() < 5 {
-            if now.hour() > 6 || now.hour() < 22 {
-                //get_livedata_active_symbols(sql_connection.clone(), &symbols);
+/// detect the support and resistance levels and trendlines of the symbols in their daily bars,
+/// returns the notifications of the breakouts of today
+pub fn run_level_alerts(
+    sql_connection: api::data::sql::Database,
+    symbols: &[String],
+    config: &Config,
+    policy: &api::data::sql::AlertPolicy,
+    now: chrono::DateTime<Local>,
+) -> Vec<Notification> {
+    let settings = config.level_settings();
+    let mut notifications = Vec::new();
+    for symbol in symbols.iter() {
+        let bars = daily_bars(sql_connection.clone(), &config.exchange, symbol, config.levels.days, now);
+        let levels = api::analytics::levels::detect_in_series(&bars, &settings);
+        for breakout in levels.breakouts.iter() {
+            let text = format!("Symbol {} {}!", symbol, breakout);
+            let alert = api::data::sql::alerts::NewAlert {
+                fingerprint: api::data::sql::alerts::fingerprint("levels", symbol, &breakout.key()),
+                source: "levels".to_string(),
+                symbol: symbol.clone(),
+                level: Level::Warning.as_str().to_string(),
+                title: "stock-analysis".to_string(),
+                body: text.clone(),
+            };
+            match api::data::sql::alerts::raise_alert(sql_connection.clone(), &alert, policy, now.timestamp()) {
+                Some(raised) if raised.notify => {
+                    log::warn!("{}", &text);
+                    let level = raised.level.parse().unwrap_or(Level::Warning);
+                    notifications.push(Notification::new(symbol, level, "stock-analysis", &text));
+                }
+                Some(_) => log::debug!("{}", &text),
+                None => log::error!("Failed to record the breakout alert of symbol {}", symbol),
+            }
+        }
+    }
+    notifications
+}
 
-                // triger the live analysis and event detection
-                run_analysis_on_updated_dataframe(sql_connection.clone(), &symbols);
+/// evaluate the stored alert rules on the live data of today, returns the notifications of the
+/// new alerts with the channels they go to, empty channels are the default ones
+pub fn run_alert_rules(
+    sql_connection: api::data::sql::Database,
+    symbols: &[String],
+    exchange: &str,
+    policy: &api::data::sql::AlertPolicy,
+    now: chrono::DateTime<Local>,
+) -> Vec<(Notification, Vec<String>)> {
+    let rules = api::data::sql::alert_rules::alert_rules(sql_connection.clone());
+    let mut alerts = Vec::new();
+    if !rules.iter().any(|r| r.enabled) {
+        return alerts;
+    }
+    for symbol in symbols.iter() {
+        if !rules.iter().any(|r| r.applies_to(symbol)) {
+            continue;
+        }
+        let bars = todays_bars(sql_connection.clone(), exchange, symbol, now);
+        if bars.is_empty() {
+            continue;
+        }
+        let events = api::data::sql::alert_rules::evaluate_alert_rules(sql_connection.clone(), &rules, symbol, &bars, policy, now.timestamp());
+        for event in events {
+            log::info!("Alert rule {} fired: {}", event.rule, event.message);
+            let level = event.level.parse().unwrap_or(Level::Warning);
+            let mut notification = Notification::new(symbol, level, &event.rule, &event.message);
+            notification.timestamp = event.timestamp;
+            alerts.push((notification, event.notify));
+        }
+    }
+    alerts
+}
 
-                
-            }
+/// the current configuration, the jobs read it when they start
+pub(crate) fn current_config(config: &RwLock<Arc<Config>>) -> Arc<Config> {
+    match config.read() {
+        Ok(c) => c.clone(),
+        Err(error) => {
+            log::error!("Failed to read the configuration! {}", error);
+            error.into_inner().clone()
         }
     }
-    
+}
 
+/// the scheduler with the nightly and the live job
+fn build_scheduler(sql_connection: api::data::sql::Database, config: &Arc<RwLock<Arc<Config>>>, live_updates: bool) -> Scheduler {
+    let initial = current_config(config);
+    let mut scheduler = Scheduler::new(sql_connection.clone(), initial.schedule.catch_up_days);
+    let nightly_config = config.clone();
+    let nightly_connection = sql_connection.clone();
+    scheduler.add(Job::new(NIGHTLY_JOB, initial.nightly_schedule(), initial.schedule.catch_up_days > 0, move |scheduled| {
+        let config = current_config(&nightly_config);
+        let sql_connection = nightly_connection.clone();
+        Box::pin(async move { run_nightly_jobs(sql_connection, config, scheduled).await.map(|failures| failures.to_string()).map_err(|e| e.to_string()) })
+    }));
+    let live_config = config.clone();
+    scheduler.add(Job::new(LIVE_JOB, initial.live_schedule(), false, move |_| {
+        let config = current_config(&live_config);
+        let sql_connection = sql_connection.clone();
+        Box::pin(async move { run_live_jobs(sql_connection, live_updates, config).await.map(|failures| failures.to_string()).map_err(|e| e.to_string()) })
+    }));
+    scheduler
+}
+
+/// make the symbols of the watchlists active, store the alert rules and register the configured providers
+fn apply_config(sql_connection: api::data::sql::Database, config: &Config, replay: bool) -> EyreResult<()> {
+    let watched = config.watchlist_symbols();
+    if !watched.is_empty() && api::data::sql::symbols::insert_active_symbols(sql_connection.clone(), &watched) != 0 {
+        log::error!("Failed to activate the symbols of the watchlists");
+    }
+    for rule in config.rules.iter() {
+        if api::data::sql::alert_rules::insert_alert_rule(sql_connection.clone(), rule).is_none() {
+            log::error!("Failed to store the alert rule {}", rule.name);
+        }
+    }
+    if !replay && !config.providers.is_empty() {
+        let providers = api::data::livedata::MarketDataProviders::from_settings(&config.providers)
+            .map_err(|e| eyre::eyre!("Failed to set up the market data providers: {}", e))?;
+        api::data::livedata::set_providers(providers);
+    }
     Ok(())
 }
 
-pub async fn main(_options: Options, shutdown: broadcast::Sender<()>) -> EyreResult<()> {
+/// read the configuration again on every SIGHUP, an invalid file keeps the running one
+#[cfg(unix)]
+async fn reload_on_hangup(
+    options: Options,
+    sql_connection: api::data::sql::Database,
+    config: Arc<RwLock<Arc<Config>>>,
+    replay: bool,
+) -> EyreResult<()> {
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    while hangup.recv().await.is_some() {
+        log::info!("SIGHUP received, reloading the configuration");
+        let reloaded = match Config::load(&options) {
+            Ok(c) => c,
+            Err(e) => {
+                log::error!("Keeping the running configuration, {}", e);
+                continue;
+            }
+        };
+        let database = current_config(&config).database_path();
+        if reloaded.database_path() != database {
+            log::warn!("The database {} is used until the restart", database.display());
+        }
+        if let Err(e) = apply_config(sql_connection.clone(), &reloaded, replay) {
+            log::error!("Keeping the running configuration, {}", e);
+            continue;
+        }
+        match config.write() {
+            Ok(mut c) => *c = Arc::new(reloaded),
+            Err(error) => log::error!("Failed to replace the configuration! {}", error),
+        }
+    }
+    Ok(())
+}
+
+/// the scheduler ticks once per minute of the clock it follows, a replay at `replay_speed`
+/// ticks once per replayed minute so the live job runs for every minute of the recording
+fn tick_period(options: &Options) -> Duration {
+    match options.replay {
+        Some(_) if options.replay_speed > 0.0 && options.replay_speed.is_finite() => {
+            Duration::from_secs_f64(60.0 / options.replay_speed)
+        }
+        _ => Duration::from_secs(60),
+    }
+}
+
+pub async fn main(options: Options, shutdown: broadcast::Sender<()>) -> EyreResult<()> {
+    let initial = Config::load(&options).map_err(|e| eyre::eyre!("Invalid configuration {}", e))?;
+    let live_updates = match &options.replay {
+        Some(dir) => {
+            let replay = match api::data::livedata::ReplayProvider::from_dir(dir, options.replay_speed) {
+                Ok(r) => r,
+                Err(e) => return Err(eyre::eyre!("Failed to load replay data from {}: {}", dir.display(), e)),
+            };
+            log::info!("Replaying recorded data of {:?} at {}x speed", replay.symbols(), options.replay_speed);
+            let mut providers = api::data::livedata::MarketDataProviders::new();
+            providers.register(Box::new(replay), 0);
+            api::data::livedata::set_providers(providers);
+            true
+        },
+        None => false,
+    };
+    // the database is opened once, changing it needs a restart
+    let sql_connection = initial.open_database().map_err(|e| eyre::eyre!("Failed to open the database {}", e))?;
+    apply_config(sql_connection.clone(), &initial, live_updates)?;
+    let config = Arc::new(RwLock::new(Arc::new(initial)));
+
+    #[cfg(unix)]
+    tokio::spawn(reload_on_hangup(options.clone(), sql_connection.clone(), config.clone(), live_updates));
 
+    #[cfg(feature = "http")]
+    {
+        let http = current_config(&config).http.clone();
+        if http.enabled {
+            // the address is read once, changing it needs a restart
+            let listener = tokio::net::TcpListener::bind(&http.listen)
+                .await
+                .map_err(|e| eyre::eyre!("Failed to listen on {}: {}", http.listen, e))?;
+            let loopback = http.listen.parse::<std::net::SocketAddr>().is_ok_and(|a| a.ip().is_loopback());
+            if !loopback && http.token.is_none() {
+                log::warn!("The web server listens on {} without http.token, everybody reaching it may change the daemon", http.listen);
+            }
+            let state = crate::http::AppState { config: config.clone(), database: sql_connection.clone(), shutdown: shutdown.clone() };
+            tokio::spawn(async move {
+                if let Err(e) = crate::http::serve(listener, state).await {
+                    log::error!("The web server failed! {}", e);
+                }
+            });
+        }
+    }
+
+    let mut scheduler = build_scheduler(sql_connection, &config, live_updates);
+    let period = tick_period(&options);
     tokio::spawn(async move {
-        let mut interval = time::interval(Duration::from_secs(60));
+        let mut interval = time::interval(period);
         loop {
             interval.tick().await; // This should go first.
-            tokio::spawn(run_jobs());
+            // a reloaded configuration may change the schedules
+            let current = current_config(&config);
+            scheduler.reschedule(NIGHTLY_JOB, current.nightly_schedule());
+            scheduler.reschedule(LIVE_JOB, current.live_schedule());
+            scheduler.tick(api::data::livedata::now().with_timezone(&Local));
         }
     });
     // Wait for shutdown
@@ -808,21 +1201,28 @@ mod test {
 
     #[tokio::test]
     async fn test_analysis_on_updated_frames() {
-        let sql_connection = api::data::sql::connect();
+        let sql_connection = api::data::sql::connect().unwrap();
         let symbols = api::data::sql::symbols::active_symbols(sql_connection.clone());
-        run_analysis_on_updated_dataframe(sql_connection.clone(), &symbols);
+        run_analysis_on_updated_dataframe(sql_connection.clone(), &symbols, &Thresholds::default(), &api::data::sql::AlertPolicy::default());
+    }
+
+    #[tokio::test]
+    async fn test_patterns_of_daily_bars() {
+        let sql_connection = api::data::sql::connect().unwrap();
+        let symbols = api::data::sql::symbols::active_symbols(sql_connection.clone());
+        store_patterns(sql_connection.clone(), &symbols, &Config::default(), api::data::livedata::now().with_timezone(&Local));
     }
 
     #[tokio::test]
     async fn test_analysis_on_historical_data() {
-        let sql_connection = api::data::sql::connect();
+        let sql_connection = api::data::sql::connect().unwrap();
         let symbols = api::data::sql::symbols::active_symbols(sql_connection.clone());
-        run_analysis_on_historical_data(sql_connection.clone(), &symbols);
+        run_analysis_on_historical_data(sql_connection.clone(), &symbols, &Thresholds::default());
     }
 
     #[tokio::test]
     async fn test_charts() {
-        let sql_connection = api::data::sql::connect();
+        let sql_connection = api::data::sql::connect().unwrap();
         let symbols = api::data::sql::symbols::active_symbols(sql_connection.clone());
         let mut filepath = dirs::home_dir().unwrap().join("stock-analysis-reports");
         if !filepath.is_dir() {
@@ -834,15 +1234,24 @@ mod test {
             }
             filepath = dirs::home_dir().unwrap();
         }
-        match run_ticker_charts(&symbols, &filepath) {
-            Ok(()) => {},
-            Err(e) => log::error!("screener process threw error: {}", e),
+        let failures = run_ticker_charts(sql_connection, &symbols, &ChartConfig::default(), &LevelSettings::default(), api::calendar::DEFAULT_EXCHANGE, false, &filepath);
+        if !failures.is_empty() {
+            log::error!("charts failed: {}", failures);
         }
     }
 
+    #[test]
+    fn test_tick_period() {
+        let options = |args: &str| Options::from_iter_safe(args.split(' ')).unwrap();
+        assert_eq!(tick_period(&options("server")), Duration::from_secs(60));
+        assert_eq!(tick_period(&options("server --replay data")), Duration::from_secs(1));
+        assert_eq!(tick_period(&options("server --replay data --replay-speed 600")), Duration::from_millis(100));
+        assert_eq!(tick_period(&options("server --replay data --replay-speed 0")), Duration::from_secs(60));
+    }
+
     #[tokio::test]
     async fn test_portfolio() {
-        let sql_connection = api::data::sql::connect();
+        let sql_connection = api::data::sql::connect().unwrap();
         let symbols = api::data::sql::symbols::active_symbols(sql_connection.clone());
         let mut filepath = dirs::home_dir().unwrap().join("stock-analysis-reports");
         if !filepath.is_dir() {
@@ -854,8 +1263,8 @@ mod test {
             }
             filepath = dirs::home_dir().unwrap();
         }
-        match run_portfolio_analysis(&symbols, &filepath) {
-            Ok(()) => {},
+        match run_portfolio_analysis(sql_connection, &PortfolioConfig::default(), &symbols, api::calendar::DEFAULT_EXCHANGE, &filepath) {
+            Ok(_) => {},
             Err(e) => log::error!("screener process threw error: {}", e),
         }
     }
@@ -873,7 +1282,7 @@ mod test {
             }
             filepath = dirs::home_dir().unwrap();
         }
-        match run_screener_process(&filepath) {
+        match run_screener_process(&ScreenerConfig::default(), api::calendar::DEFAULT_EXCHANGE, &filepath) {
             Ok(()) => {},
             Err(e) => log::error!("screener process threw error: {}", e),
         }
@@ -881,9 +1290,10 @@ mod test {
 
     #[tokio::test]
     async fn test_run_jobs() {
-        match run_jobs().await {
-            Ok(()) => {},
-            Err(e) => log::error!("screener process threw error: {}", e),
+        let sql_connection = api::data::sql::Database::open(":memory:").unwrap();
+        match run_live_jobs(sql_connection, false, Arc::new(Config::default())).await {
+            Ok(_) => {},
+            Err(e) => log::error!("live jobs threw error: {}", e),
         }
     }
 
@@ -187,7 +187,7 @@ pub async fn test_portfolio(portfolio: Result<Portfolio, String>, filepath: &std
 
 async fn test_ticker_data(filepath: &std::path::PathBuf) -> Result<(), Box<dyn Error>> {
     // get a list of symbols from the database
-    let sql_connection = api::data::sql::connect();
+    let sql_connection = api::data::sql::connect()?;
     let symbolsstrings = api::data::sql::active_symbols(sql_connection.clone());
 
     // 
@@ -409,7 +409,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     
 
     // get a list of symbols from the database
-    let sql_connection = api::data::sql::connect();
+    let sql_connection = api::data::sql::connect()?;
     let symbolsstrings = api::data::sql::active_symbols(sql_connection.clone());
 
     // 